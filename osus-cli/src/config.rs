@@ -0,0 +1,181 @@
+//! Defaults read from `osus.toml` files.
+//!
+//! The user's file (`~/.config/osus/osus.toml`, or `%APPDATA%\osus\osus.toml` on Windows) is read first,
+//! then the nearest `osus.toml` in the current folder or its parents, whose settings win.
+//! Arguments passed on the command line always win over both.
+//!
+//! ```toml
+//! backup = false
+//! output-dir = "out"
+//! time-decimals = 0
+//! tolerance = 2
+//! sample-bank = "soft"
+//!
+//! [presets.splat-hitsounds]
+//! hold-ends = "move"
+//! modes = [0, 3]
+//! ```
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::Command;
+use osus::ErrorCategory;
+use serde::Deserialize;
+
+use crate::CliError;
+
+const CONFIG_FILENAME: &str = "osus.toml";
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+	/// Whether beatmaps are backed up before being changed.
+	pub backup: Option<bool>,
+	/// Folder where changed beatmaps are written instead of replacing them,
+	/// relative to the folder of the file it was read from.
+	pub output_dir: Option<PathBuf>,
+	/// Amount of decimals times are written with.
+	pub time_decimals: Option<u8>,
+	/// Default `--tolerance` of `splat-hitsounds`.
+	pub tolerance: Option<f64>,
+	/// Default `--sample` of `reset-sample-sets`.
+	pub sample_bank: Option<String>,
+	/// Default arguments of each command, by command and argument name.
+	pub presets: HashMap<String, toml::Table>,
+}
+
+impl Config {
+	/// Reads the user's file and the nearest file of the current folder, if there are any.
+	pub fn load() -> Result<Self, CliError> {
+		let mut config = Self::default();
+
+		let directory_path = env::current_dir().ok().and_then(|dir| find_config(&dir));
+		for path in user_config_path().into_iter().chain(directory_path) {
+			if path.is_file() {
+				config.merge(Self::read(&path)?);
+			}
+		}
+
+		Ok(config)
+	}
+
+	fn read(path: &Path) -> Result<Self, CliError> {
+		let text = fs::read_to_string(path)
+			.map_err(|e| CliError::new(ErrorCategory::Io, format!("Could not read {}: {e}", path.display())))?;
+
+		let mut config: Self = toml::from_str(&text)
+			.map_err(|e| CliError::new(ErrorCategory::Parse, format!("Invalid config {}: {e}", path.display())))?;
+
+		if let (Some(output_dir), Some(folder)) = (&config.output_dir, path.parent()) {
+			config.output_dir = Some(folder.join(output_dir));
+		}
+
+		Ok(config)
+	}
+
+	/// Overrides the settings of this config with the ones set in `other`.
+	fn merge(&mut self, other: Self) {
+		self.backup = other.backup.or(self.backup);
+		self.output_dir = other.output_dir.or(self.output_dir.take());
+		self.time_decimals = other.time_decimals.or(self.time_decimals);
+		self.tolerance = other.tolerance.or(self.tolerance);
+		self.sample_bank = other.sample_bank.or(self.sample_bank.take());
+
+		for (command, preset) in other.presets {
+			self.presets.entry(command).or_default().extend(preset);
+		}
+	}
+
+	/// Sets the default values of the command's arguments from this config.
+	///
+	/// Presets use the names of the commands and of their long arguments, like `hold-ends` for `--hold-ends`.
+	pub fn apply_defaults(&self, mut command: Command) -> Result<Command, CliError> {
+		if let Some(tolerance) = self.tolerance {
+			command = command.mut_subcommand("splat-hitsounds", |subcommand| {
+				subcommand.mut_arg("tolerance", |arg| arg.default_value(tolerance.to_string()))
+			});
+		}
+
+		if let Some(sample_bank) = &self.sample_bank {
+			command = command.mut_subcommand("reset-sample-sets", |subcommand| {
+				subcommand.mut_arg("sample", |arg| arg.default_value(sample_bank))
+			});
+		}
+
+		for (name, preset) in &self.presets {
+			if command.find_subcommand(name).is_none() {
+				return Err(CliError::new(
+					ErrorCategory::Validation,
+					format!("Preset for unknown command {name}"),
+				));
+			}
+
+			for (key, value) in preset {
+				let id = key.replace('-', "_");
+				let has_arg = (command.find_subcommand(name))
+					.is_some_and(|subcommand| subcommand.get_arguments().any(|arg| arg.get_id() == id.as_str()));
+				if !has_arg {
+					return Err(CliError::new(
+						ErrorCategory::Validation,
+						format!("Preset for {name} sets unknown argument {key}"),
+					));
+				}
+
+				let values = preset_values(value).ok_or_else(|| {
+					CliError::new(
+						ErrorCategory::Validation,
+						format!("Preset for {name} sets {key} to a table, expected a value or an array"),
+					)
+				})?;
+
+				command = command.mut_subcommand(name, |subcommand| {
+					subcommand.mut_arg(id, |arg| arg.default_values(values))
+				});
+			}
+		}
+
+		Ok(command)
+	}
+}
+
+/// Values of an argument as they would be typed on the command line, or `None` for tables.
+fn preset_values(value: &toml::Value) -> Option<Vec<String>> {
+	match value {
+		toml::Value::String(s) => Some(vec![s.clone()]),
+		toml::Value::Array(values) => values.iter().map(preset_value).collect(),
+		value => preset_value(value).map(|value| vec![value]),
+	}
+}
+
+fn preset_value(value: &toml::Value) -> Option<String> {
+	match value {
+		toml::Value::String(s) => Some(s.clone()),
+		toml::Value::Integer(i) => Some(i.to_string()),
+		toml::Value::Float(f) => Some(f.to_string()),
+		toml::Value::Boolean(b) => Some(b.to_string()),
+		toml::Value::Datetime(datetime) => Some(datetime.to_string()),
+		toml::Value::Array(_) | toml::Value::Table(_) => None,
+	}
+}
+
+/// Path of the user's config file, in the platform's config folder.
+fn user_config_path() -> Option<PathBuf> {
+	let config_dir = if cfg!(windows) {
+		env::var_os("APPDATA").map(PathBuf::from)
+	} else {
+		(env::var_os("XDG_CONFIG_HOME").map(PathBuf::from))
+			.or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+	};
+
+	config_dir.map(|dir| dir.join("osus").join(CONFIG_FILENAME))
+}
+
+/// Nearest config file in a folder or its parents.
+fn find_config(dir: &Path) -> Option<PathBuf> {
+	(dir.ancestors())
+		.map(|ancestor| ancestor.join(CONFIG_FILENAME))
+		.find(|path| path.is_file())
+}
@@ -2,39 +2,199 @@ use std::env::current_dir;
 use std::error::Error;
 use std::fmt;
 use std::fs::{self, File};
-use std::io::{self, BufRead, BufReader};
-use std::ops::Deref;
+use std::io;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
 use std::str::FromStr;
 
-use clap::{Parser, Subcommand};
+use clap::builder::PossibleValue;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum, ValueHint};
+use clap_complete::Shell;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use miette::GraphicalReportHandler;
+use osus::algos::catch::{convert_to_catch, movements};
+use osus::algos::lazer::{to_stable, ConversionReport};
+use osus::algos::mania::{
+	avoid_jacks, convert_to_mania, mirror, resolve_conflicts, shuffle_columns, to_long_notes, to_rice, ConflictPolicy,
+	ManiaConversionOptions, SpinnerPolicy,
+};
+use osus::algos::simplify::{simplify, SimplifyOptions};
+use osus::algos::slider_path::SliderPath;
 use osus::algos::{
-	convert_slider_points_to_legacy, mix_volume, offset_map, remove_duplicates, remove_useless_speed_changes,
-	reset_hitsounds,
+	concat, copy_timing, cut_with, isolate_timing_points, mix_volume, nudge_into_bounds, offset_by_beats,
+	offset_map_checked, refit_slider_lengths, repair_timing, reset_hitsounds_deep, set_slider_velocity,
+	suggest_preview_time, volume_ramp, CopyTimingOptions, CutOptions, NudgeOptions, OffsetOptions, RatePreset,
+	RefitPolicy, ResetScope, StraddlingPolicy, VolumeCurve,
 };
-use osus::close_range;
-use osus::file::beatmap::{
-	BeatmapFile, HitObject, HitObjectParams, HitSample, HitSampleSet, HitSound, SampleBank, SliderPoint, TimingPoint,
+use osus::analysis::{measure_difficulty, slider_duration, slider_tick_times, suggest_difficulty_settings};
+use osus::clipboard::{format_selection, parse_selection, selection_range};
+use osus::edit::{ChangeSet, ElementChanges};
+use osus::file::beatmap::deserializing::DeserializeOptions;
+use osus::file::beatmap::errors::BeatmapFileParseError;
+use osus::file::beatmap::parsing::{parse_hit_object, ParseOptions, SectionRegistry};
+use osus::file::beatmap::{BeatmapFile, HitObjectParams, MetadataSection, SampleBank};
+use osus::file::sniff_osu_file;
+use osus::generate::{new_beatmap, GenerateError, GenerateOptions};
+use osus::hitsounds::{
+	apply_pattern, copy_hitsounds, CopyHitsoundsOptions, CopyHitsoundsReport, HitsoundPattern, HitsoundPatternError,
+	HoldEndHitsounds, HoldEndMode,
 };
-use osus::{ExtTimestamped, Timestamped, TimestampedSlice};
+use osus::index::{BeatmapIndex, IndexError, IndexQuery};
+use osus::lint::{format_report, lint, ReportStyle};
+use osus::mapset::assets::{list_assets, replace_background, strip_videos, unused_files};
+use osus::mapset::rank::{rank_check, RankIssue};
+use osus::mapset::samples::{missing_samples, remap_sample_indices, used_sample_indices};
+use osus::mapset::{standard_filename, BeatmapSet, BeatmapSetError};
+use osus::ops::{cleanup_timing_points, rate_changed_copy, rate_preset_difficulties, splat_hitsounds, SplatReport};
+use osus::point::{Point, Rounding};
+use osus::progress::ProgressSink;
+use osus::render::render_timeline_svg;
+use osus::timestamp::{format_timestamp, parse_timestamp, InvalidTimestampError};
+use osus::{ErrorCategory, Timestamped, Tolerance};
 use tracing::Level;
 use walkdir::WalkDir;
 
+use crate::config::Config;
+use crate::pipeline::Pipeline;
+
+mod config;
+mod pipeline;
+#[cfg(feature = "tui")]
+mod tui;
+mod watch;
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
+#[command(after_help = EXIT_CODES_HELP, before_long_help = CONFIG_HELP)]
 struct Cli {
+	#[arg(
+		long,
+		global = true,
+		default_value_t = OutputFormat::Text,
+		help = "Output format of the lint, search, remap-sample-indices and rank-check commands (text or json)."
+	)]
+	format: OutputFormat,
+
+	#[arg(
+		long,
+		global = true,
+		help = "Save what changed in each written beatmap as JSON, next to its backup (like map.osu.changes.json)."
+	)]
+	save_changes: bool,
+
+	#[arg(
+		long,
+		global = true,
+		help = "Keep the comments and blank lines of parsed beatmaps when writing them back."
+	)]
+	preserve_comments: bool,
+
+	#[arg(long, short, global = true, help = "Only log errors.")]
+	quiet: bool,
+
+	#[arg(
+		long,
+		short,
+		global = true,
+		conflicts_with = "quiet",
+		help = "Also log details, like what each command changed in the beatmaps it wrote."
+	)]
+	verbose: bool,
+
+	#[arg(
+		long,
+		global = true,
+		help = "Whether to back up beatmaps before changing them (true or false, defaults to true)."
+	)]
+	backup: Option<bool>,
+
+	#[arg(
+		long,
+		global = true,
+		value_hint = ValueHint::DirPath,
+		help = "Write changed beatmaps in this folder instead of replacing them."
+	)]
+	out_dir: Option<PathBuf>,
+
+	#[arg(
+		long,
+		global = true,
+		help = "Round times to this many decimals when writing beatmaps (0 for whole milliseconds)."
+	)]
+	time_decimals: Option<u8>,
+
+	#[arg(
+		long,
+		global = true,
+		help = "Make positions whole when writing beatmaps, with \"exact\", \"round\" or \"floor\" \
+(by default, positions are only rounded for osu!stable beatmaps)."
+	)]
+	position_rounding: Option<RoundingOption>,
+
 	#[command(subcommand)]
 	command: Commands,
 }
 
+/// How beatmaps are read and written by every command, from the global options and the config.
+#[derive(Clone, Debug)]
+struct Settings {
+	/// Save the changes made to each beatmap next to its backup (`--save-changes`).
+	save_changes: bool,
+	/// Keep the comments and blank lines of parsed beatmaps (`--preserve-comments`).
+	preserve_comments: bool,
+	/// Back up beatmaps before changing them.
+	backup: bool,
+	/// Folder where changed beatmaps are written instead of replacing them.
+	out_dir: Option<PathBuf>,
+	/// Amount of decimals of written times.
+	time_decimals: Option<u8>,
+	/// How written positions are made whole.
+	position_rounding: Option<Rounding>,
+}
+
+impl Settings {
+	/// Options used to parse every beatmap.
+	fn parse_options(&self) -> ParseOptions {
+		// sections the library doesn't know are kept, since the beatmap may be written back
+		ParseOptions {
+			preserve_trivia: self.preserve_comments,
+			custom_sections: SectionRegistry::all(),
+		}
+	}
+
+	/// Options used to write every beatmap.
+	fn deserialize_options(&self) -> DeserializeOptions {
+		DeserializeOptions {
+			normalize_events: true,
+			time_decimals: self.time_decimals,
+			position_rounding: self.position_rounding,
+		}
+	}
+}
+
+const CONFIG_HELP: &str =
+	"Defaults of these options and of the arguments of each command can be set in osus.toml files, \
+in ~/.config/osus/ and in the current folder or its parents.";
+
+const EXIT_CODES_HELP: &str = "Exit codes: 0 on success, 1 for other errors, 2 for invalid arguments, \
+3 for IO errors, 4 for files or values that couldn't be parsed, 5 for inputs that can't be used by the command \
+and 6 for game modes the command doesn't support.";
+
+const DEFAULT_INDEX_FILENAME: &str = "osus-index.tsv";
+
 const PATH_HELP: &str = "Path to beatmap file or folder containing beatmap files.";
+const FROM_HELP: &str = "Only change the beatmap from this time (milliseconds or mm:ss.mmm).";
+const TO_HELP: &str = "Only change the beatmap until this time (milliseconds or mm:ss.mmm).";
+const OBJECTS_HELP: &str =
+	"Only change the beatmap from the first to the last of these objects, as copied from the osu! editor (like \"00:45:123 (1,2,3) -\").";
 
 #[derive(Subcommand)]
 enum Commands {
 	/// Extract every .osu file from hashed osu!lazer files.
 	ExtractOsuLazerFiles {
-		#[arg(short, long, help = "Output path where to copy the beatmaps (defaults to ./maps/).")]
+		#[arg(short, long, value_hint = ValueHint::DirPath, help = "Output path where to copy the beatmaps (defaults to ./maps/).")]
 		out_path: Option<PathBuf>,
 
 		#[arg(
@@ -44,6 +204,14 @@ enum Commands {
 		)]
 		recursive: bool,
 
+		#[cfg(feature = "parallel")]
+		#[arg(
+			short,
+			long,
+			help = "Number of files to process in parallel (defaults to the number of CPUs)."
+		)]
+		jobs: Option<usize>,
+
 		#[arg(help = PATH_HELP)]
 		path: PathBuf,
 	},
@@ -53,15 +221,393 @@ enum Commands {
 		#[arg(help = "Amount of milliseconds to offset the beatmap (can be a decimal number).")]
 		millis: f64,
 
+		#[arg(
+			long,
+			help = "Increase the audio lead-in if the first object ends up too close to the start of the audio for its approach or the countdown."
+		)]
+		adjust_lead_in: bool,
+
+		#[arg(help = PATH_HELP)]
+		path: PathBuf,
+	},
+
+	/// Move the objects of the beatmap by some amount of beats along its timing, keeping the red lines in place.
+	OffsetBeats {
+		#[arg(
+			allow_negative_numbers = true,
+			help = "Amount of beats to move the objects by, negative to move them earlier (can be a decimal number, like 0.5 for half a beat)."
+		)]
+		beats: f64,
+
+		#[arg(help = PATH_HELP)]
+		path: PathBuf,
+	},
+
+	/// Replace the timing of a beatmap with the one of another beatmap, keeping slider velocities and hitsounds.
+	CopyTiming {
+		#[arg(
+			short,
+			long,
+			default_value_t = 2.0,
+			help = "Hit objects further than this many milliseconds from the new timing get snapped."
+		)]
+		tolerance: f64,
+
+		#[arg(value_hint = ValueHint::FilePath, help = "Path to the beatmap to copy the timing from.")]
+		source: PathBuf,
+
+		#[arg(value_hint = ValueHint::FilePath, help = "Path to the beatmap to copy the timing to.")]
+		path: PathBuf,
+	},
+
+	/// Make a sped up or slowed down copy of a beatmap, like with the DT and HT mods, to practice on it.
+	ChangeRate {
+		#[arg(help = "Playback rate (1.5 for DT, 0.75 for HT).")]
+		rate: f64,
+
+		#[arg(
+			long,
+			help = "Keep the original AR and OD instead of matching the ones with the rate applied."
+		)]
+		keep_difficulty: bool,
+
+		#[arg(long, help = "Also time-stretch the audio file with ffmpeg, keeping its pitch.")]
+		audio: bool,
+
+		#[arg(value_hint = ValueHint::FilePath, help = "Path to the beatmap file.")]
+		path: PathBuf,
+	},
+
+	/// Make a nightcore (or daycore) version of a beatmap set in a new folder, with its title, tags and preview time.
+	Nightcore {
+		#[arg(long, help = "Make a daycore version instead (0.75x, lower pitch).")]
+		daycore: bool,
+
+		#[arg(long, help = "Also resample the audio file with ffmpeg, changing its pitch.")]
+		audio: bool,
+
+		#[arg(value_hint = ValueHint::DirPath, help = "Path to the beatmap set folder.")]
+		path: PathBuf,
+	},
+
+	/// Make a copy of a difficulty with only what happens in a time range, like a practice difficulty.
+	Cut {
+		#[arg(long, value_parser = parse_timestamp, value_name = "TIME", help = "Start of the range to keep (milliseconds or mm:ss.mmm).")]
+		from: Option<f64>,
+
+		#[arg(long, value_parser = parse_timestamp, value_name = "TIME", help = "End of the range to keep (milliseconds or mm:ss.mmm).")]
+		to: Option<f64>,
+
+		#[arg(
+			long,
+			default_value_t = StraddlingOption::Drop,
+			help = "What to do with objects crossing the boundaries of the range: \"drop\" them, \"keep\" them, or \"clip\" the ones ending after it."
+		)]
+		straddling: StraddlingOption,
+
+		#[arg(long, default_value = "Cut", help = "Difficulty name of the new difficulty.")]
+		name: String,
+
+		#[arg(value_hint = ValueHint::FilePath, help = "Path to the beatmap file.")]
+		path: PathBuf,
+	},
+
+	/// Make sliders fit the timing again after BPM or slider velocity changes, reporting the ones ending off the timing.
+	RefitSliders {
+		#[arg(
+			long,
+			default_value_t = RefitOption::Duration,
+			help = "How to refit sliders: keep their \"duration\" by changing their length so that they end on a tick, or keep their \"geometry\" and only report the ones ending off the timing."
+		)]
+		policy: RefitOption,
+
+		#[arg(
+			long,
+			value_hint = ValueHint::FilePath,
+			help = "Beatmap with the timing the sliders were made for, like a backup from before the timing changed. Required to keep their duration."
+		)]
+		old_timing: Option<PathBuf>,
+
 		#[arg(help = PATH_HELP)]
 		path: PathBuf,
 	},
 
+	/// Create an empty beatmap with one timing point and a background, as a starting point for mapping.
+	New {
+		#[arg(long, help = "Audio file of the beatmap, relative to its folder.")]
+		audio: String,
+
+		#[arg(long, help = "BPM of the song.")]
+		bpm: f64,
+
+		#[arg(long, default_value_t = 0.0, help = "Time of the first beat, in milliseconds.")]
+		offset: f64,
+
+		#[arg(
+			long,
+			default_value_t = 0,
+			help = "Game mode (0 = osu!, 1 = osu!taiko, 2 = osu!catch, 3 = osu!mania)."
+		)]
+		mode: u8,
+
+		#[arg(long, help = "Romanised artist of the song.")]
+		artist: String,
+
+		#[arg(long, help = "Romanised title of the song.")]
+		title: String,
+
+		#[arg(long, default_value = "", help = "Creator of the beatmap.")]
+		creator: String,
+
+		#[arg(long, default_value = "Normal", help = "Difficulty name of the beatmap.")]
+		name: String,
+
+		#[arg(
+			long,
+			default_value = "bg.jpg",
+			help = "Background image, relative to the beatmap folder."
+		)]
+		background: String,
+
+		#[arg(value_hint = ValueHint::DirPath, help = "Folder where to write the beatmap, created if it doesn't exist.")]
+		folder: PathBuf,
+	},
+
+	/// Join several beatmaps (like parts made with cut) into one, in order.
+	Concat {
+		#[arg(short, long, value_hint = ValueHint::FilePath, help = "Path of the joined beatmap.")]
+		out_path: PathBuf,
+
+		#[arg(required = true, num_args = 2.., value_hint = ValueHint::FilePath, help = "Paths to the beatmap files to join, in order.")]
+		parts: Vec<PathBuf>,
+	},
+
+	/// Make an easier copy of a difficulty by removing objects in dense sections and shortening sliders.
+	Simplify {
+		#[arg(long, default_value = "Normal", help = "Difficulty name of the new difficulty.")]
+		name: String,
+
+		#[arg(
+			long,
+			default_value_t = 1.0,
+			help = "Minimum gap between the end of an object and the start of the next one, in beats."
+		)]
+		min_gap: f64,
+
+		#[arg(long, help = "Keep slider repeats instead of removing them.")]
+		keep_repeats: bool,
+
+		#[arg(long, default_value_t = 6.0, help = "Maximum approach rate.")]
+		ar: f32,
+
+		#[arg(long, default_value_t = 5.0, help = "Maximum overall difficulty.")]
+		od: f32,
+
+		#[arg(long, default_value_t = 3.5, help = "Maximum circle size.")]
+		cs: f32,
+
+		#[arg(long, default_value_t = 4.0, help = "Maximum HP drain rate.")]
+		hp: f32,
+
+		#[arg(value_hint = ValueHint::FilePath, help = "Path to the beatmap file.")]
+		path: PathBuf,
+	},
+
+	/// Change the columns of an osu!mania beatmap's notes while keeping their times.
+	ManiaTransform {
+		#[arg(long, help = "Mirror the columns, like the Mirror mod.")]
+		mirror: bool,
+
+		#[arg(long, help = "Shuffle the columns with this seed, like the Random mod.")]
+		shuffle: Option<u64>,
+
+		#[arg(long, help = "Move notes to break up jacks longer than this amount of notes.")]
+		max_jack: Option<usize>,
+
+		#[arg(
+			long,
+			help = "Turn rice notes into holds ending this many milliseconds before the next note of their column."
+		)]
+		ln_gap: Option<f64>,
+
+		#[arg(
+			long,
+			default_value_t = 0.0,
+			help = "Minimum length of holds created with --ln-gap, in milliseconds."
+		)]
+		ln_min_length: f64,
+
+		#[arg(long, help = "Turn every hold into a rice note.")]
+		rice: bool,
+
+		#[arg(
+			long,
+			help = "Fix notes starting in a busy column at the end: \"move\" them to a free column, \"merge\" them into the note already there, or \"drop\" them."
+		)]
+		resolve_conflicts: Option<ConflictOption>,
+
+		#[arg(value_hint = ValueHint::FilePath, help = "Path to the beatmap file.")]
+		path: PathBuf,
+	},
+
+	/// Convert an osu! beatmap to osu!mania, putting every object in the column under it.
+	ConvertToMania {
+		#[arg(long, default_value_t = 4, help = "Amount of columns of the converted beatmap.")]
+		keys: u8,
+
+		#[arg(
+			long,
+			default_value_t = SpinnerOption::Hold,
+			help = "What spinners become: a \"roll\" between the middle columns, a \"hold\" in the middle column, \"stairs\" going through every column, or nothing with \"skip\"."
+		)]
+		spinner: SpinnerOption,
+
+		#[arg(
+			long,
+			default_value_t = 4,
+			help = "Notes per beat of spinners converted to rolls or stairs."
+		)]
+		spinner_divisor: u32,
+
+		#[arg(
+			long,
+			default_value_t = ConflictOption::Move,
+			help = "Fix notes ending up in a busy column: \"move\" them to a free column, \"merge\" them into the note already there, or \"drop\" them."
+		)]
+		resolve_conflicts: ConflictOption,
+
+		#[arg(value_hint = ValueHint::FilePath, help = "Path to the beatmap file.")]
+		path: PathBuf,
+	},
+
+	/// Convert an osu! beatmap to osu!catch, keeping every object inside the playfield.
+	ConvertToCatch {
+		#[arg(long, help = "Move objects closer to each other so that no hyperdash is needed.")]
+		no_hyperdashes: bool,
+
+		#[arg(value_hint = ValueHint::FilePath, help = "Path to the beatmap file.")]
+		path: PathBuf,
+	},
+
+	/// Move the objects and slider paths that go out of the playfield back into it.
+	NudgeIntoBounds {
+		#[arg(
+			long,
+			help = "Shrink sliders that are too big to fit in the playfield. This also makes them shorter."
+		)]
+		rescale: bool,
+
+		#[arg(value_hint = ValueHint::FilePath, help = "Path to the beatmap file.")]
+		path: PathBuf,
+	},
+
+	/// Print the geometry and timing of a slider, either from a hit object line or from a beatmap.
+	DebugSlider {
+		#[arg(
+			long,
+			help = "Hit object line of the slider, as written in the [HitObjects] section."
+		)]
+		line: Option<String>,
+
+		#[arg(long, value_hint = ValueHint::FilePath, help = "Path to the beatmap file to pick the slider from.")]
+		map: Option<PathBuf>,
+
+		#[arg(long, value_parser = parse_timestamp, value_name = "TIME", help = "Time of the slider to pick from the beatmap.")]
+		at: Option<f64>,
+
+		#[arg(long, help = "Also draw the slider path on the playfield with ASCII characters.")]
+		render: bool,
+	},
+
+	/// Render the hit objects of a time window as an SVG image of the playfield.
+	RenderPreview {
+		#[arg(long, value_parser = parse_timestamp, value_name = "TIME", help = FROM_HELP)]
+		from: Option<f64>,
+
+		#[arg(long, value_parser = parse_timestamp, value_name = "TIME", help = TO_HELP)]
+		to: Option<f64>,
+
+		#[arg(
+			short,
+			long,
+			value_hint = ValueHint::FilePath,
+			help = "Where to write the SVG file (defaults to the beatmap's path with an .svg extension)."
+		)]
+		out_path: Option<PathBuf>,
+
+		#[arg(value_hint = ValueHint::FilePath, help = "Path to the beatmap file.")]
+		path: PathBuf,
+	},
+
+	/// Browse the timing points and hit objects of a beatmap interactively.
+	#[cfg(feature = "tui")]
+	Tui {
+		#[arg(value_hint = ValueHint::FilePath, help = "Path to the beatmap file.")]
+		path: PathBuf,
+	},
+
 	/// Raise or lower the beatmap's volume.
 	MixVolume {
 		#[arg(long, help = "Amount of volume to add. Can be positive or negative.")]
 		val: i8,
 
+		#[arg(long, value_parser = parse_timestamp, value_name = "TIME", help = FROM_HELP)]
+		from: Option<f64>,
+
+		#[arg(long, value_parser = parse_timestamp, value_name = "TIME", help = TO_HELP)]
+		to: Option<f64>,
+
+		#[arg(long, conflicts_with_all = ["from", "to"], help = OBJECTS_HELP)]
+		objects: Option<String>,
+
+		#[arg(help = PATH_HELP)]
+		path: PathBuf,
+	},
+
+	/// Set the slider velocity of every slider in a time range, restoring the previous one right after each slider.
+	SetSliderVelocity {
+		#[arg(
+			long,
+			help = "Slider velocity multiplier to give to the sliders (like 1.5 for 1.5x)."
+		)]
+		multiplier: f64,
+
+		#[arg(long, value_parser = parse_timestamp, value_name = "TIME", help = FROM_HELP)]
+		from: Option<f64>,
+
+		#[arg(long, value_parser = parse_timestamp, value_name = "TIME", help = TO_HELP)]
+		to: Option<f64>,
+
+		#[arg(long, conflicts_with_all = ["from", "to"], help = OBJECTS_HELP)]
+		objects: Option<String>,
+
+		#[arg(help = PATH_HELP)]
+		path: PathBuf,
+	},
+
+	/// Fade the volume in or out over a time range.
+	FadeVolume {
+		#[arg(long, value_parser = parse_timestamp, value_name = "TIME", help = "Time where the fade starts (milliseconds or mm:ss.mmm).")]
+		from: f64,
+
+		#[arg(long, value_parser = parse_timestamp, value_name = "TIME", help = "Time where the fade ends (milliseconds or mm:ss.mmm).")]
+		to: f64,
+
+		#[arg(long, help = "Volume at the start of the fade (0-100).")]
+		start_volume: u8,
+
+		#[arg(long, help = "Volume at the end of the fade (0-100).")]
+		end_volume: u8,
+
+		#[arg(long, default_value_t = 1.0, help = "Amount of beats between each volume change.")]
+		step: f64,
+
+		#[arg(
+			long,
+			help = "Change the volume by the same ratio at each step instead of the same amount."
+		)]
+		exponential: bool,
+
 		#[arg(help = PATH_HELP)]
 		path: PathBuf,
 	},
@@ -71,6 +617,7 @@ enum Commands {
 		#[arg(
 			long,
 			default_value_t = SampleBankOption::Auto,
+			ignore_case = true,
 			help = "Which sample set to use as the overwriting value."
 		)]
 		sample: SampleBankOption,
@@ -82,6 +629,42 @@ enum Commands {
 		)]
 		cleanup: bool,
 
+		#[arg(
+			long,
+			help = "Whether to also reset the sample sets of hit objects and slider edges."
+		)]
+		deep: bool,
+
+		#[arg(long, value_parser = parse_timestamp, value_name = "TIME", help = FROM_HELP)]
+		from: Option<f64>,
+
+		#[arg(long, value_parser = parse_timestamp, value_name = "TIME", help = TO_HELP)]
+		to: Option<f64>,
+
+		#[arg(long, conflicts_with_all = ["from", "to"], help = OBJECTS_HELP)]
+		objects: Option<String>,
+
+		#[arg(help = PATH_HELP)]
+		path: PathBuf,
+	},
+
+	/// Put hitsounds on the beat grid following a pattern, like claps on 2 and 4.
+	///
+	/// The pattern is a JSON file like `{ "divisor": 1, "steps": ["", "C", "", "C"], "overwrite": false }`,
+	/// where each step has the additions (W, F, C) of one tick of the divisor, repeating from every red line.
+	ApplyHitsoundPattern {
+		#[arg(long, value_hint = ValueHint::FilePath, help = "Path to the JSON file describing the pattern.")]
+		pattern: PathBuf,
+
+		#[arg(long, value_parser = parse_timestamp, value_name = "TIME", help = FROM_HELP)]
+		from: Option<f64>,
+
+		#[arg(long, value_parser = parse_timestamp, value_name = "TIME", help = TO_HELP)]
+		to: Option<f64>,
+
+		#[arg(long, conflicts_with_all = ["from", "to"], help = OBJECTS_HELP)]
+		objects: Option<String>,
+
 		#[arg(help = PATH_HELP)]
 		path: PathBuf,
 	},
@@ -92,533 +675,2722 @@ enum Commands {
 		path: PathBuf,
 	},
 
-	/// Take hitsounds from a map and splat them on another.
+	/// Fix broken timing points, like unsorted ones or inherited ones with a positive slider velocity.
+	RepairTiming {
+		#[arg(value_hint = ValueHint::FilePath, help = "Path to the beatmap file.")]
+		path: PathBuf,
+	},
+
+	/// Take hitsounds from a map and splat them on another, or on every difficulty of a beatmap set.
 	SplatHitsounds {
-		#[arg(short, long, help = "Path to hitsound map file.")]
+		#[arg(short, long, value_hint = ValueHint::FilePath, help = "Path to hitsound map file.")]
 		sound_map: PathBuf,
 
-		#[arg(help = PATH_HELP)]
+		#[arg(help = "Path to the beatmap file, or to a beatmap set folder to hitsound all its difficulties.")]
 		path: PathBuf,
 
+		#[arg(
+			long,
+			value_delimiter = ',',
+			help = "Only hitsound the difficulties of a beatmap set of these modes (0 = osu!, 1 = taiko, 2 = catch, 3 = mania), comma-separated."
+		)]
+		modes: Vec<u8>,
+
 		#[arg(
 			short,
 			long,
 			help = "Whether we're hitsounding for mania. In that case, an extra transformation happens to spread out hitsounds on all notes in each row as much as possible."
 		)]
 		mania: bool,
+
+		#[arg(
+			long,
+			help = "Whether to also use the soundmap's storyboard samples as custom samples for hit objects at the same time."
+		)]
+		samples: bool,
+
+		#[arg(
+			long,
+			default_value_t = HoldEndOption::Ignore,
+			help = "What to do with hitsounds at the end of holds, which osu!mania can't play: \"ignore\", \"adjacent\" to move them to the closest note, or \"discard\" to report them."
+		)]
+		hold_ends: HoldEndOption,
+
+		#[arg(
+			long,
+			default_value_t = 50.0,
+			help = "Maximum distance in milliseconds between a hold's end and the note its hitsounds are moved to."
+		)]
+		hold_end_window: f64,
+
+		#[arg(
+			long,
+			default_value_t = Tolerance::DEFAULT.same_time,
+			help = "Maximum distance in milliseconds between a soundmap's object and the object it gives its hitsounds to. Lower it for osu!lazer maps with sub-millisecond times."
+		)]
+		tolerance: f64,
 	},
 
-	/// Convert a Lazer map (v128) to a Stable map (v14).
-	LazerToStable {
-		#[arg(help = PATH_HELP)]
+	/// Run the steps of a pipeline file on a beatmap, parsing, backing it up and writing it only once.
+	///
+	/// The pipeline is a TOML file with a list of steps like `[[steps]] op = "offset" millis = 25`,
+	/// where `op` is offset, reset-sample-sets, splat-hitsounds, cleanup or serialize-as-v14
+	/// and the other keys are the options of the matching command.
+	Run {
+		#[arg(value_hint = ValueHint::FilePath, help = "Path to the pipeline file.")]
+		pipeline: PathBuf,
+
+		#[arg(value_hint = ValueHint::FilePath, help = "Path to the beatmap file.")]
 		path: PathBuf,
 	},
+
+	/// Splat hitsounds on a beatmap again each time it or the soundmap is saved, writing the result to another difficulty.
+	///
+	/// This gives a live hitsounding workflow: hitsound the soundmap in the editor and play the output difficulty.
+	Watch {
+		#[arg(
+			short,
+			long,
+			value_hint = ValueHint::FilePath,
+			help = "Path to hitsound map file, whose changes are watched too."
+		)]
+		sound_map: Option<PathBuf>,
+
+		#[arg(short, long, help = "Whether we're hitsounding for mania, see splat-hitsounds.")]
+		mania: bool,
+
+		#[arg(long, help = "Clean up the timing points of the output difficulty.")]
+		cleanup: bool,
+
+		#[arg(
+			long,
+			default_value = "Hitsounded",
+			help = "Added to the difficulty name of the beatmap to name the output difficulty, like \"Hard (Hitsounded)\"."
+		)]
+		name: String,
+
+		#[arg(value_hint = ValueHint::FilePath, help = "Path to the beatmap file.")]
+		path: PathBuf,
+	},
+
+	/// Convert a Lazer map (v128) to a Stable map (v14).
+	LazerToStable {
+		#[arg(help = PATH_HELP)]
+		path: PathBuf,
+	},
+
+	/// Set the time at which the audio preview starts.
+	SetPreview {
+		#[arg(
+			long,
+			help = "Suggest a preview time automatically, from kiai sections and note density."
+		)]
+		auto: bool,
+
+		#[arg(
+			long,
+			required_unless_present = "auto",
+			conflicts_with = "auto",
+			value_parser = parse_timestamp, value_name = "TIME",
+			help = "Preview time (milliseconds or mm:ss.mmm)."
+		)]
+		time: Option<f64>,
+
+		#[arg(help = PATH_HELP)]
+		path: PathBuf,
+	},
+
+	/// Fill the romanised title and artist by transliterating the unicode ones, when they aren't ASCII.
+	#[cfg(feature = "transliteration")]
+	RomaniseMetadata {
+		#[arg(help = PATH_HELP)]
+		path: PathBuf,
+	},
+
+	/// Suggest AR, OD and HP from the note density, BPM and spacing of a beatmap.
+	SuggestDifficulty {
+		#[arg(long, help = "Write the suggested settings to the beatmap.")]
+		apply: bool,
+
+		#[arg(help = PATH_HELP)]
+		path: PathBuf,
+	},
+
+	/// List, replace or clean up the files used by a beatmap set.
+	ManageAssets {
+		#[arg(long, help = "List the files referenced by the difficulties.")]
+		list: bool,
+
+		#[arg(long, help = "Replace the background image of every difficulty.")]
+		set_background: Option<String>,
+
+		#[arg(long, help = "Remove the video from every difficulty.")]
+		strip_videos: bool,
+
+		#[arg(long, help = "Report files in the folder that no difficulty uses.")]
+		unused: bool,
+
+		#[arg(help = "Path to the beatmap set folder (or .osz archive when only reading).")]
+		path: PathBuf,
+	},
+
+	/// List, remap and check the custom sample indices used by the difficulties of a beatmap set.
+	RemapSampleIndices {
+		#[arg(long, help = "List the sample indices used by each difficulty.")]
+		list: bool,
+
+		#[arg(
+			long = "map",
+			value_parser = parse_index_mapping,
+			help = "Replace a sample index with another, written as FROM=TO (e.g. 3=2). Can be given several times."
+		)]
+		mappings: Vec<(u32, u32)>,
+
+		#[arg(
+			long,
+			help = "Report custom sample files played by a difficulty that don't exist in the folder."
+		)]
+		missing: bool,
+
+		#[arg(value_hint = ValueHint::DirPath, help = "Path to the beatmap set folder.")]
+		path: PathBuf,
+	},
+
+	/// Check a beatmap set against the ranking criteria, with a pass/fail summary per difficulty.
+	///
+	/// Fails with the validation exit code if a difficulty or the set itself has a problem.
+	RankCheck {
+		#[arg(help = "Path to the beatmap set folder (or .osz archive, without the audio check).")]
+		path: PathBuf,
+	},
+
+	/// Index the metadata of every beatmap in a songs folder, to search them later.
+	BuildIndex {
+		#[arg(short, long, value_hint = ValueHint::FilePath, help = "Where to write the index (defaults to ./osus-index.tsv).")]
+		out_path: Option<PathBuf>,
+
+		#[arg(value_hint = ValueHint::DirPath, help = "Path to the songs folder (osu!stable Songs folder or extracted osu!lazer files).")]
+		path: PathBuf,
+	},
+
+	/// Search beatmaps in an index built with build-index.
+	Search {
+		#[arg(short, long, value_hint = ValueHint::FilePath, help = "Path to the index (defaults to ./osus-index.tsv).")]
+		index: Option<PathBuf>,
+
+		#[arg(long, help = "Only show beatmaps whose artist contains this text.")]
+		artist: Option<String>,
+
+		#[arg(long, help = "Only show beatmaps whose title contains this text.")]
+		title: Option<String>,
+
+		#[arg(long, help = "Only show beatmaps whose creator contains this text.")]
+		creator: Option<String>,
+
+		#[arg(
+			long,
+			help = "Only show beatmaps of this mode (0 = osu!, 1 = taiko, 2 = catch, 3 = mania)."
+		)]
+		mode: Option<u8>,
+
+		#[arg(help = "Text to find in the title, artist, creator, difficulty name or tags.")]
+		text: Option<String>,
+	},
+
+	/// Check the beatmap for issues, like spinners that are too short.
+	Lint {
+		#[arg(help = PATH_HELP)]
+		path: PathBuf,
+	},
+
+	/// Write the issues of a beatmap as a modding post, ready to be pasted on the osu! website.
+	ModReport {
+		#[arg(help = PATH_HELP)]
+		path: PathBuf,
+	},
+
+	/// Print the completion script of a shell, like `osus-cli completions bash > ~/.local/share/bash-completion/completions/osus-cli`.
+	Completions {
+		#[arg(help = "Shell to complete the commands in (bash, zsh, fish, powershell or elvish).")]
+		shell: Shell,
+	},
+}
+
+#[derive(Clone, Copy, Debug)]
+#[repr(u8)]
+pub enum SampleBankOption {
+	Auto = 0,
+	Normal = 1,
+	Soft = 2,
+	Drum = 3,
+}
+
+impl fmt::Display for SampleBankOption {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			SampleBankOption::Auto => "auto",
+			SampleBankOption::Normal => "normal",
+			SampleBankOption::Soft => "soft",
+			SampleBankOption::Drum => "drum",
+		})
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct InvalidSampleBankOptionError(String);
+
+impl std::error::Error for InvalidSampleBankOptionError {}
+
+impl fmt::Display for InvalidSampleBankOptionError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"Invalid sample bank: expected \"auto\", \"normal\", \"soft\" or \"drum\", got {:?}",
+			self.0
+		)
+	}
+}
+
+/// Lists the sample banks in the help and in shell completions.
+impl ValueEnum for SampleBankOption {
+	fn value_variants<'a>() -> &'a [Self] {
+		&[Self::Auto, Self::Normal, Self::Soft, Self::Drum]
+	}
+
+	fn to_possible_value(&self) -> Option<PossibleValue> {
+		Some(PossibleValue::new(self.to_string()))
+	}
+}
+
+impl FromStr for SampleBankOption {
+	type Err = InvalidSampleBankOptionError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.to_ascii_lowercase();
+		match s.as_str() {
+			"auto" => Ok(SampleBankOption::Auto),
+			"normal" => Ok(SampleBankOption::Normal),
+			"soft" => Ok(SampleBankOption::Soft),
+			"drum" => Ok(SampleBankOption::Drum),
+			_ => Err(InvalidSampleBankOptionError(s)),
+		}
+	}
+}
+
+/// How commands reporting results print them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+	/// Human-readable text.
+	Text,
+	/// A single JSON value, to be consumed by scripts.
+	Json,
+}
+
+impl fmt::Display for OutputFormat {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			OutputFormat::Text => "text",
+			OutputFormat::Json => "json",
+		})
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct InvalidOutputFormatError(String);
+
+impl std::error::Error for InvalidOutputFormatError {}
+
+impl fmt::Display for InvalidOutputFormatError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"Invalid output format: expected \"text\" or \"json\", got {:?}",
+			self.0
+		)
+	}
+}
+
+impl FromStr for OutputFormat {
+	type Err = InvalidOutputFormatError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.to_ascii_lowercase();
+		match s.as_str() {
+			"text" => Ok(OutputFormat::Text),
+			"json" => Ok(OutputFormat::Json),
+			_ => Err(InvalidOutputFormatError(s)),
+		}
+	}
+}
+
+/// What to do with the soundmap's hitsounds that fall on the end of a hold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HoldEndOption {
+	Ignore,
+	Adjacent,
+	Discard,
+}
+
+impl fmt::Display for HoldEndOption {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			HoldEndOption::Ignore => "ignore",
+			HoldEndOption::Adjacent => "adjacent",
+			HoldEndOption::Discard => "discard",
+		})
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct InvalidHoldEndOptionError(String);
+
+impl std::error::Error for InvalidHoldEndOptionError {}
+
+impl fmt::Display for InvalidHoldEndOptionError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"Invalid hold end option: expected \"ignore\", \"adjacent\" or \"discard\", got {:?}",
+			self.0
+		)
+	}
+}
+
+impl FromStr for HoldEndOption {
+	type Err = InvalidHoldEndOptionError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.to_ascii_lowercase();
+		match s.as_str() {
+			"ignore" => Ok(HoldEndOption::Ignore),
+			"adjacent" => Ok(HoldEndOption::Adjacent),
+			"discard" => Ok(HoldEndOption::Discard),
+			_ => Err(InvalidHoldEndOptionError(s)),
+		}
+	}
+}
+
+/// What to do with objects crossing the boundaries of a cut.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StraddlingOption {
+	Drop,
+	Keep,
+	Clip,
+}
+
+impl fmt::Display for StraddlingOption {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			StraddlingOption::Drop => "drop",
+			StraddlingOption::Keep => "keep",
+			StraddlingOption::Clip => "clip",
+		})
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct InvalidStraddlingOptionError(String);
+
+impl std::error::Error for InvalidStraddlingOptionError {}
+
+impl fmt::Display for InvalidStraddlingOptionError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"Invalid straddling option: expected \"drop\", \"keep\" or \"clip\", got {:?}",
+			self.0
+		)
+	}
+}
+
+impl FromStr for StraddlingOption {
+	type Err = InvalidStraddlingOptionError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.to_ascii_lowercase();
+		match s.as_str() {
+			"drop" => Ok(StraddlingOption::Drop),
+			"keep" => Ok(StraddlingOption::Keep),
+			"clip" => Ok(StraddlingOption::Clip),
+			_ => Err(InvalidStraddlingOptionError(s)),
+		}
+	}
+}
+
+impl StraddlingOption {
+	fn to_straddling_policy(self) -> StraddlingPolicy {
+		match self {
+			StraddlingOption::Drop => StraddlingPolicy::Drop,
+			StraddlingOption::Keep => StraddlingPolicy::Keep,
+			StraddlingOption::Clip => StraddlingPolicy::Clip,
+		}
+	}
+}
+
+/// How to make positions whole when writing beatmaps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoundingOption {
+	Exact,
+	Round,
+	Floor,
+}
+
+impl fmt::Display for RoundingOption {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			RoundingOption::Exact => "exact",
+			RoundingOption::Round => "round",
+			RoundingOption::Floor => "floor",
+		})
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct InvalidRoundingOptionError(String);
+
+impl std::error::Error for InvalidRoundingOptionError {}
+
+impl fmt::Display for InvalidRoundingOptionError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"Invalid rounding option: expected \"exact\", \"round\" or \"floor\", got {:?}",
+			self.0
+		)
+	}
+}
+
+impl FromStr for RoundingOption {
+	type Err = InvalidRoundingOptionError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.to_ascii_lowercase();
+		match s.as_str() {
+			"exact" => Ok(RoundingOption::Exact),
+			"round" => Ok(RoundingOption::Round),
+			"floor" => Ok(RoundingOption::Floor),
+			_ => Err(InvalidRoundingOptionError(s)),
+		}
+	}
+}
+
+impl RoundingOption {
+	fn to_rounding(self) -> Rounding {
+		match self {
+			RoundingOption::Exact => Rounding::Exact,
+			RoundingOption::Round => Rounding::Round,
+			RoundingOption::Floor => Rounding::Floor,
+		}
+	}
+}
+
+/// How to make sliders fit the timing of a beatmap again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RefitOption {
+	Duration,
+	Geometry,
+}
+
+impl fmt::Display for RefitOption {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			RefitOption::Duration => "duration",
+			RefitOption::Geometry => "geometry",
+		})
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct InvalidRefitOptionError(String);
+
+impl std::error::Error for InvalidRefitOptionError {}
+
+impl fmt::Display for InvalidRefitOptionError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"Invalid refit policy: expected \"duration\" or \"geometry\", got {:?}",
+			self.0
+		)
+	}
+}
+
+impl FromStr for RefitOption {
+	type Err = InvalidRefitOptionError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.to_ascii_lowercase();
+		match s.as_str() {
+			"duration" => Ok(RefitOption::Duration),
+			"geometry" => Ok(RefitOption::Geometry),
+			_ => Err(InvalidRefitOptionError(s)),
+		}
+	}
+}
+
+impl RefitOption {
+	fn to_refit_policy(self) -> RefitPolicy {
+		match self {
+			RefitOption::Duration => RefitPolicy::PreserveDuration,
+			RefitOption::Geometry => RefitPolicy::PreserveGeometry,
+		}
+	}
+}
+
+/// What to do with osu!mania notes starting in a busy column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictOption {
+	Move,
+	Merge,
+	Drop,
+}
+
+impl fmt::Display for ConflictOption {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			ConflictOption::Move => "move",
+			ConflictOption::Merge => "merge",
+			ConflictOption::Drop => "drop",
+		})
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct InvalidConflictOptionError(String);
+
+impl std::error::Error for InvalidConflictOptionError {}
+
+impl fmt::Display for InvalidConflictOptionError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"Invalid conflict policy: expected \"move\", \"merge\" or \"drop\", got {:?}",
+			self.0
+		)
+	}
+}
+
+impl FromStr for ConflictOption {
+	type Err = InvalidConflictOptionError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.to_ascii_lowercase();
+		match s.as_str() {
+			"move" => Ok(ConflictOption::Move),
+			"merge" => Ok(ConflictOption::Merge),
+			"drop" => Ok(ConflictOption::Drop),
+			_ => Err(InvalidConflictOptionError(s)),
+		}
+	}
+}
+
+/// What osu!mania conversions turn spinners into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpinnerOption {
+	Roll,
+	Hold,
+	Stairs,
+	Skip,
+}
+
+impl fmt::Display for SpinnerOption {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			SpinnerOption::Roll => "roll",
+			SpinnerOption::Hold => "hold",
+			SpinnerOption::Stairs => "stairs",
+			SpinnerOption::Skip => "skip",
+		})
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct InvalidSpinnerOptionError(String);
+
+impl std::error::Error for InvalidSpinnerOptionError {}
+
+impl fmt::Display for InvalidSpinnerOptionError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"Invalid spinner policy: expected \"roll\", \"hold\", \"stairs\" or \"skip\", got {:?}",
+			self.0
+		)
+	}
+}
+
+impl FromStr for SpinnerOption {
+	type Err = InvalidSpinnerOptionError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.to_ascii_lowercase();
+		match s.as_str() {
+			"roll" => Ok(SpinnerOption::Roll),
+			"hold" => Ok(SpinnerOption::Hold),
+			"stairs" => Ok(SpinnerOption::Stairs),
+			"skip" => Ok(SpinnerOption::Skip),
+			_ => Err(InvalidSpinnerOptionError(s)),
+		}
+	}
+}
+
+impl SpinnerOption {
+	const fn to_spinner_policy(self, divisor: u32) -> SpinnerPolicy {
+		match self {
+			SpinnerOption::Roll => SpinnerPolicy::Roll { divisor },
+			SpinnerOption::Hold => SpinnerPolicy::Hold,
+			SpinnerOption::Stairs => SpinnerPolicy::Stairs { divisor },
+			SpinnerOption::Skip => SpinnerPolicy::Skip,
+		}
+	}
+}
+
+impl ConflictOption {
+	fn to_conflict_policy(self) -> ConflictPolicy {
+		match self {
+			ConflictOption::Move => ConflictPolicy::MoveToFreeColumn,
+			ConflictOption::Merge => ConflictPolicy::Merge,
+			ConflictOption::Drop => ConflictPolicy::Drop,
+		}
+	}
+}
+
+impl HoldEndOption {
+	fn to_hold_end_mode(self) -> HoldEndMode {
+		match self {
+			HoldEndOption::Ignore => HoldEndMode::Ignore,
+			HoldEndOption::Adjacent => HoldEndMode::Adjacent,
+			HoldEndOption::Discard => HoldEndMode::Discard,
+		}
+	}
+}
+
+impl SampleBankOption {
+	fn to_sample_bank(self) -> SampleBank {
+		match self {
+			SampleBankOption::Auto => SampleBank::Auto,
+			SampleBankOption::Normal => SampleBank::Normal,
+			SampleBankOption::Soft => SampleBank::Soft,
+			SampleBankOption::Drum => SampleBank::Drum,
+		}
+	}
+}
+
+fn main() -> ExitCode {
+	let config = match Config::load() {
+		Ok(config) => config,
+		Err(err) => {
+			print_error(&err, true);
+			return ExitCode::from(exit_code(Some(err.category)));
+		}
+	};
+
+	// the config gives the default values, which arguments override
+	let command = match config.apply_defaults(Cli::command()) {
+		Ok(command) => command,
+		Err(err) => {
+			print_error(&err, true);
+			return ExitCode::from(exit_code(Some(err.category)));
+		}
+	};
+
+	let Cli {
+		format,
+		save_changes,
+		preserve_comments,
+		quiet,
+		verbose,
+		backup,
+		out_dir,
+		time_decimals,
+		position_rounding,
+		command,
+	} = Cli::from_arg_matches(&command.get_matches()).unwrap_or_else(|err| err.exit());
+	let settings = Settings {
+		save_changes,
+		preserve_comments,
+		backup: backup.or(config.backup).unwrap_or(true),
+		out_dir: out_dir.or(config.output_dir),
+		time_decimals: time_decimals.or(config.time_decimals),
+		position_rounding: position_rounding.map(RoundingOption::to_rounding),
+	};
+
+	// keep stdout clean for scripts reading the JSON output
+	let level = if quiet {
+		Level::ERROR
+	} else if verbose {
+		Level::DEBUG
+	} else {
+		Level::INFO
+	};
+	let subscriber = tracing_subscriber::fmt().with_max_level(level);
+	match format {
+		OutputFormat::Text => subscriber.init(),
+		OutputFormat::Json => subscriber.with_writer(io::stderr).init(),
+	}
+
+	let result = match command {
+		Commands::ExtractOsuLazerFiles {
+			out_path,
+			recursive,
+			#[cfg(feature = "parallel")]
+			jobs,
+			path,
+		} => {
+			let out_path = out_path.unwrap_or(current_dir().unwrap().join("maps"));
+
+			#[cfg(feature = "parallel")]
+			if let Some(jobs) = jobs {
+				rayon::ThreadPoolBuilder::new()
+					.num_threads(jobs)
+					.build_global()
+					.expect("the global thread pool is only built once");
+			}
+
+			cli_extract_osu_lazer_files(&out_path, recursive, &path)
+		}
+
+		Commands::Offset {
+			millis,
+			adjust_lead_in,
+			path,
+		} => cli_offset(millis, OffsetOptions { adjust_lead_in }, &path, &settings),
+
+		Commands::OffsetBeats { beats, path } => cli_offset_beats(beats, &path, &settings),
+
+		Commands::RefitSliders {
+			policy,
+			old_timing,
+			path,
+		} => cli_refit_sliders(policy.to_refit_policy(), old_timing.as_deref(), &path, &settings),
+
+		Commands::CopyTiming {
+			tolerance,
+			source,
+			path,
+		} => cli_copy_timing(tolerance, &source, &path, &settings),
+
+		Commands::ChangeRate {
+			rate,
+			keep_difficulty,
+			audio,
+			path,
+		} => cli_change_rate(rate, !keep_difficulty, audio, &path, &settings),
+
+		Commands::Nightcore { daycore, audio, path } => {
+			let preset = if daycore {
+				RatePreset::Daycore
+			} else {
+				RatePreset::Nightcore
+			};
+			cli_nightcore(preset, audio, &path, &settings)
+		}
+
+		Commands::Cut {
+			from,
+			to,
+			straddling,
+			name,
+			path,
+		} => {
+			let options = CutOptions {
+				straddling: straddling.to_straddling_policy(),
+			};
+			cli_cut(time_range(from, to), options, &name, &path, &settings)
+		}
+
+		Commands::New {
+			audio,
+			bpm,
+			offset,
+			mode,
+			artist,
+			title,
+			creator,
+			name,
+			background,
+			folder,
+		} => {
+			let metadata = MetadataSection {
+				title_unicode: title.clone(),
+				title,
+				artist_unicode: artist.clone(),
+				artist,
+				creator,
+				version: name,
+				..MetadataSection::default()
+			};
+			let options = GenerateOptions {
+				audio,
+				bpm,
+				offset,
+				mode,
+				metadata,
+				background,
+			};
+			cli_new(options, &folder, &settings)
+		}
+
+		Commands::Concat { out_path, parts } => cli_concat(&parts, &out_path, &settings),
+
+		Commands::Simplify {
+			name,
+			min_gap,
+			keep_repeats,
+			ar,
+			od,
+			cs,
+			hp,
+			path,
+		} => {
+			let options = SimplifyOptions {
+				min_gap_beats: min_gap,
+				remove_repeats: !keep_repeats,
+				approach_rate: Some(ar),
+				overall_difficulty: Some(od),
+				circle_size: Some(cs),
+				hp_drain_rate: Some(hp),
+				version: name,
+				..SimplifyOptions::default()
+			};
+			cli_simplify(&options, &path, &settings)
+		}
+
+		Commands::ManiaTransform {
+			mirror,
+			shuffle,
+			max_jack,
+			ln_gap,
+			ln_min_length,
+			rice,
+			resolve_conflicts,
+			path,
+		} => {
+			let long_notes = ln_gap.map(|gap| (gap, ln_min_length));
+			let conflict_policy = resolve_conflicts.map(ConflictOption::to_conflict_policy);
+			cli_mania_transform(
+				mirror,
+				shuffle,
+				max_jack,
+				long_notes,
+				rice,
+				conflict_policy,
+				&path,
+				&settings,
+			)
+		}
+
+		Commands::ConvertToMania {
+			keys,
+			spinner,
+			spinner_divisor,
+			resolve_conflicts,
+			path,
+		} => {
+			let options = ManiaConversionOptions {
+				key_count: keys,
+				spinners: spinner.to_spinner_policy(spinner_divisor),
+				conflicts: resolve_conflicts.to_conflict_policy(),
+			};
+			cli_convert_to_mania(options, &path, &settings)
+		}
+		Commands::ConvertToCatch { no_hyperdashes, path } => cli_convert_to_catch(!no_hyperdashes, &path, &settings),
+
+		Commands::NudgeIntoBounds { rescale, path } => cli_nudge_into_bounds(rescale, &path, &settings),
+
+		Commands::RenderPreview {
+			from,
+			to,
+			out_path,
+			path,
+		} => {
+			let out_path = out_path.unwrap_or_else(|| path.with_extension("svg"));
+			cli_render_preview(time_range(from, to), &out_path, &path, &settings)
+		}
+
+		#[cfg(feature = "tui")]
+		Commands::Tui { path } => parse_beatmap(&path, false, &settings).and_then(tui::run),
+
+		Commands::DebugSlider { line, map, at, render } => {
+			cli_debug_slider(line.as_deref(), map.as_deref(), at, render, &settings)
+		}
+
+		Commands::MixVolume {
+			val,
+			from,
+			to,
+			objects,
+			path,
+		} => cli_mix_volume(val, &TimeScope::new(from, to, objects), &path, &settings),
+
+		Commands::SetSliderVelocity {
+			multiplier,
+			from,
+			to,
+			objects,
+			path,
+		} => cli_set_slider_velocity(multiplier, &TimeScope::new(from, to, objects), &path, &settings),
+
+		Commands::FadeVolume {
+			from,
+			to,
+			start_volume,
+			end_volume,
+			step,
+			exponential,
+			path,
+		} => {
+			let curve = if exponential {
+				VolumeCurve::Exponential
+			} else {
+				VolumeCurve::Linear
+			};
+			cli_fade_volume(from..to, start_volume, end_volume, step, curve, &path, &settings)
+		}
+
+		Commands::ResetSampleSets {
+			sample,
+			cleanup,
+			deep,
+			from,
+			to,
+			objects,
+			path,
+		} => {
+			let scope = if deep {
+				ResetScope::ALL
+			} else {
+				ResetScope::TIMING_POINTS
+			};
+			cli_reset_sample_sets(
+				sample.to_sample_bank(),
+				cleanup,
+				scope,
+				&TimeScope::new(from, to, objects),
+				&path,
+				&settings,
+			)
+		}
+
+		Commands::ApplyHitsoundPattern {
+			pattern,
+			from,
+			to,
+			objects,
+			path,
+		} => cli_apply_hitsound_pattern(&pattern, &TimeScope::new(from, to, objects), &path, &settings),
+
+		Commands::CleanupTimingPoints { path } => cli_cleanup_timing_points(&path, &settings),
+
+		Commands::RepairTiming { path } => cli_repair_timing(&path, &settings),
+
+		Commands::SplatHitsounds {
+			sound_map,
+			path,
+			modes,
+			mania,
+			samples,
+			hold_ends,
+			hold_end_window,
+			tolerance,
+		} => {
+			let options = CopyHitsoundsOptions {
+				mania,
+				samples,
+				hold_ends: hold_ends.to_hold_end_mode(),
+				hold_end_window,
+				tolerance: Tolerance {
+					same_time: tolerance,
+					..Tolerance::DEFAULT
+				},
+			};
+
+			cli_splat_hitsounds(&sound_map, &path, &modes, &options, &settings)
+		}
+
+		Commands::Run { pipeline, path } => cli_run(&pipeline, &path, &settings),
+
+		Commands::Watch {
+			sound_map,
+			mania,
+			cleanup,
+			name,
+			path,
+		} => {
+			let pipeline = watch::Pipeline {
+				sound_map,
+				hitsounds: CopyHitsoundsOptions {
+					mania,
+					..CopyHitsoundsOptions::default()
+				},
+				cleanup,
+				name,
+			};
+
+			watch::run(&path, &pipeline, &settings)
+		}
+
+		Commands::LazerToStable { path } => cli_lazer_to_stable(&path, &settings),
+
+		Commands::SetPreview { auto, time, path } => cli_set_preview(auto, time, &path, &settings),
+
+		#[cfg(feature = "transliteration")]
+		Commands::RomaniseMetadata { path } => cli_romanise_metadata(&path, &settings),
+
+		Commands::SuggestDifficulty { apply, path } => cli_suggest_difficulty(apply, &path, &settings),
+
+		Commands::ManageAssets {
+			list,
+			set_background,
+			strip_videos,
+			unused,
+			path,
+		} => cli_manage_assets(list, set_background.as_deref(), strip_videos, unused, &path, &settings),
+
+		Commands::RemapSampleIndices {
+			list,
+			mappings,
+			missing,
+			path,
+		} => cli_remap_sample_indices(list, &mappings, missing, &path, format, &settings),
+
+		Commands::RankCheck { path } => cli_rank_check(&path, format),
+
+		Commands::BuildIndex { out_path, path } => {
+			let out_path = out_path.unwrap_or(current_dir().unwrap().join(DEFAULT_INDEX_FILENAME));
+			cli_build_index(&out_path, &path)
+		}
+
+		Commands::Search {
+			index,
+			artist,
+			title,
+			creator,
+			mode,
+			text,
+		} => {
+			let index = index.unwrap_or(current_dir().unwrap().join(DEFAULT_INDEX_FILENAME));
+			let query = IndexQuery {
+				text,
+				artist,
+				title,
+				creator,
+				mode,
+			};
+
+			cli_search(&index, &query, format)
+		}
+
+		Commands::Lint { path } => cli_lint(&path, format, &settings),
+
+		Commands::ModReport { path } => cli_mod_report(&path, &settings),
+
+		Commands::Completions { shell } => {
+			cli_completions(shell);
+			Ok(())
+		}
+	};
+
+	match result {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(err) => {
+			print_error(err.as_ref(), quiet);
+			ExitCode::from(exit_code(error_category(err.as_ref())))
+		}
+	}
+}
+
+/// An error found by the CLI itself, like arguments that can't be used together.
+#[derive(Debug)]
+struct CliError {
+	category: ErrorCategory,
+	message: String,
+}
+
+impl CliError {
+	fn new(category: ErrorCategory, message: impl Into<String>) -> Self {
+		Self {
+			category,
+			message: message.into(),
+		}
+	}
+}
+
+impl fmt::Display for CliError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.message)
+	}
+}
+
+impl Error for CliError {}
+
+/// Category of an error returned by a command, if it is one of the CLI's or the library's errors.
+fn error_category(err: &(dyn Error + 'static)) -> Option<ErrorCategory> {
+	if let Some(e) = err.downcast_ref::<CliError>() {
+		Some(e.category)
+	} else if let Some(e) = err.downcast_ref::<BeatmapFileParseError>() {
+		Some(e.category())
+	} else if let Some(e) = err.downcast_ref::<BeatmapSetError>() {
+		Some(e.category())
+	} else if let Some(e) = err.downcast_ref::<IndexError>() {
+		Some(e.category())
+	} else if let Some(e) = err.downcast_ref::<GenerateError>() {
+		Some(e.category())
+	} else if let Some(e) = err.downcast_ref::<HitsoundPatternError>() {
+		Some(e.category())
+	} else if let Some(e) = err.downcast_ref::<InvalidTimestampError>() {
+		Some(e.category())
+	} else if let Some(e) = err.downcast_ref::<serde_json::Error>() {
+		Some(if e.is_io() {
+			ErrorCategory::Io
+		} else {
+			ErrorCategory::Parse
+		})
+	} else if err.is::<io::Error>() || err.is::<walkdir::Error>() || err.is::<notify::Error>() {
+		Some(ErrorCategory::Io)
+	} else {
+		None
+	}
+}
+
+/// Exit code of a failed command, as listed in [`EXIT_CODES_HELP`].
+const fn exit_code(category: Option<ErrorCategory>) -> u8 {
+	match category {
+		None => 1,
+		Some(ErrorCategory::Io) => 3,
+		Some(ErrorCategory::Parse) => 4,
+		Some(ErrorCategory::Validation) => 5,
+		Some(ErrorCategory::UnsupportedMode) => 6,
+	}
+}
+
+/// Prints an error and its sources on stderr, with the whole error in debug format unless `quiet` is set.
+fn print_error(err: &(dyn Error + 'static), quiet: bool) {
+	if let Some(parse_err) = err.downcast_ref::<BeatmapFileParseError>() {
+		// pretty-print parse errors, underlining the broken part of the line
+		let mut report = String::new();
+		if GraphicalReportHandler::new()
+			.render_report(&mut report, parse_err)
+			.is_ok()
+		{
+			eprintln!("{report}");
+			return;
+		}
+	}
+
+	eprintln!("Error: {err}");
+
+	let mut e = err;
+	while let Some(sauce) = e.source() {
+		eprintln!("-> {sauce}");
+		e = sauce;
+	}
+
+	if !quiet {
+		eprintln!("\n{err:#?}");
+	}
+}
+
+/// Time range between two optional bounds, unbounded if they're not specified.
+fn time_range(from: Option<f64>, to: Option<f64>) -> Range<f64> {
+	from.unwrap_or(f64::NEG_INFINITY)..to.unwrap_or(f64::INFINITY)
+}
+
+/// Part of a beatmap a command changes, given by `--from` and `--to` or by the objects of `--objects`.
+enum TimeScope {
+	Range(Range<f64>),
+	Objects(String),
+}
+
+impl TimeScope {
+	fn new(from: Option<f64>, to: Option<f64>, objects: Option<String>) -> Self {
+		objects.map_or_else(|| Self::Range(time_range(from, to)), Self::Objects)
+	}
+
+	/// Time range of the scope in a beatmap, going from the first to the last selected object for `--objects`.
+	fn range(&self, beatmap: &BeatmapFile) -> Result<Range<f64>, Box<dyn Error>> {
+		match self {
+			Self::Range(range) => Ok(range.clone()),
+			Self::Objects(selection) => {
+				let indices = parse_selection(selection, beatmap)?;
+				let range = selection_range(beatmap, &indices).ok_or_else(|| {
+					CliError::new(
+						ErrorCategory::Validation,
+						"None of the selected objects are in the beatmap",
+					)
+				})?;
+				tracing::info!("Selected {}", format_selection(beatmap, &indices));
+				Ok(range)
+			}
+		}
+	}
+}
+
+/// Copies a beatmap next to itself as `map.osu.backup` (or `map.osu.1.backup` and so on),
+/// unless backups were disabled.
+fn backup(path: &Path, settings: &Settings) -> io::Result<u64> {
+	if !settings.backup {
+		return Ok(0);
+	}
+
+	tracing::info!("Backing up {}...", path.display());
+	let mut out_path = path.with_extension("osu.backup");
+
+	let mut n: u32 = 1;
+	while out_path.exists() {
+		out_path = path.with_extension(format!("osu.{n}.backup"));
+		n += 1;
+	}
+
+	fs::copy(path, out_path)
+}
+
+fn parse_beatmap(path: &Path, do_backup: bool, settings: &Settings) -> Result<BeatmapFile, Box<dyn Error>> {
+	if do_backup {
+		backup(path, settings)?;
+	}
+
+	tracing::info!("Parsing {}...", path.display());
+	let beatmap = BeatmapFile::parse_with_options(path, &settings.parse_options())?;
+
+	Ok(beatmap)
+}
+
+/// Most recent backup of a beatmap made by [`backup`], if any.
+fn latest_backup(path: &Path) -> Option<PathBuf> {
+	let mut latest = None;
+	let mut backup_path = path.with_extension("osu.backup");
+
+	let mut n: u32 = 1;
+	while backup_path.exists() {
+		latest = Some(backup_path);
+		backup_path = path.with_extension(format!("osu.{n}.backup"));
+		n += 1;
+	}
+
+	latest
+}
+
+fn element_changes_json<T: fmt::Display>(changes: &ElementChanges<T>) -> serde_json::Value {
+	let lines = |elements: &[T]| elements.iter().map(ToString::to_string).collect::<Vec<_>>();
+	let modified: Vec<serde_json::Value> = (changes.modified.iter())
+		.map(|(old, new)| serde_json::json!({ "old": old.to_string(), "new": new.to_string() }))
+		.collect();
+
+	serde_json::json!({
+		"added": lines(&changes.added),
+		"removed": lines(&changes.removed),
+		"modified": modified,
+	})
+}
+
+fn change_set_json(changes: &ChangeSet) -> serde_json::Value {
+	let settings: Vec<serde_json::Value> = (changes.settings.iter())
+		.map(|setting| {
+			serde_json::json!({
+				"section": setting.section,
+				"key": setting.key,
+				"old": setting.old,
+				"new": setting.new,
+			})
+		})
+		.collect();
+
+	serde_json::json!({
+		"summary": changes.to_string(),
+		"time_range": changes.time_range().map(|range| [range.start, range.end]),
+		"settings": settings,
+		"events": element_changes_json(&changes.events),
+		"timing_points": element_changes_json(&changes.timing_points),
+		"hit_objects": element_changes_json(&changes.hit_objects),
+	})
+}
+
+/// Reports what changed compared to the beatmap currently at `path`, saving it if `--save-changes` was passed.
+///
+/// Without the changes returned by the operation, they are found by comparing with the file,
+/// which is only done if they are saved or logged.
+fn report_changes(
+	beatmap: &BeatmapFile,
+	path: &Path,
+	changes: Option<&ChangeSet>,
+	settings: &Settings,
+) -> Result<(), Box<dyn Error>> {
+	let diffed;
+	let changes = match changes {
+		Some(changes) => changes,
+		None if settings.save_changes || tracing::enabled!(tracing::Level::DEBUG) => {
+			let before = BeatmapFile::parse_with_options(path, &settings.parse_options())?;
+			let mut after = beatmap.clone();
+			after.normalize_events();
+			diffed = ChangeSet::diff(&before, &after);
+			&diffed
+		}
+		None => return Ok(()),
+	};
+
+	tracing::debug!("Changes: {changes}");
+
+	if settings.save_changes {
+		let changes_path = match latest_backup(path) {
+			Some(backup_path) => backup_path.with_extension("changes.json"),
+			None => path.with_extension("osu.changes.json"),
+		};
+
+		tracing::info!("Saving changes to {}...", changes_path.display());
+		fs::write(changes_path, serde_json::to_string_pretty(&change_set_json(changes))?)?;
+	}
+
+	Ok(())
+}
+
+/// Writes a beatmap, in `--out-dir` instead of replacing an existing file if it was set.
+fn write_beatmap_out(beatmap: &BeatmapFile, path: &Path, settings: &Settings) -> io::Result<()> {
+	write_beatmap(beatmap, path, None, settings)
+}
+
+/// Writes a beatmap like [`write_beatmap_out`], with the changes the operation returned.
+fn write_beatmap_with_changes(
+	beatmap: &BeatmapFile,
+	path: &Path,
+	changes: &ChangeSet,
+	settings: &Settings,
+) -> io::Result<()> {
+	write_beatmap(beatmap, path, Some(changes), settings)
+}
+
+fn write_beatmap(
+	beatmap: &BeatmapFile,
+	path: &Path,
+	changes: Option<&ChangeSet>,
+	settings: &Settings,
+) -> io::Result<()> {
+	let mut out_path = path.to_owned();
+	if path.exists() {
+		if let Err(e) = report_changes(beatmap, path, changes, settings) {
+			tracing::warn!("Could not report the changes made to {}: {e}", path.display());
+		}
+
+		if let (Some(out_dir), Some(filename)) = (&settings.out_dir, path.file_name()) {
+			fs::create_dir_all(out_dir)?;
+			out_path = out_dir.join(filename);
+		}
+	}
+
+	tracing::info!("Writing beatmap to {}...", out_path.display());
+	let mut out_file = File::create(out_path)?;
+	beatmap.deserialize_with_options(&mut out_file, &settings.deserialize_options())?;
+
+	Ok(())
+}
+
+/// Backs up and writes every difficulty of a beatmap set like [`write_beatmap_out`].
+fn write_beatmap_set(set: &BeatmapSet, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	if !set.path.is_dir() {
+		return Err(Box::new(BeatmapSetError::SaveArchive));
+	}
+
+	tracing::info!("Writing beatmap set to {}...", set.path.display());
+	for difficulty in &set.difficulties {
+		let difficulty_path = set.path.join(&difficulty.filename);
+		backup(&difficulty_path, settings)?;
+		write_beatmap_out(&difficulty.beatmap, &difficulty_path, settings)?;
+	}
+
+	Ok(())
+}
+
+/// Shows the progress of a long operation as a progress bar on stderr.
+///
+/// The bar is hidden when stderr isn't a terminal, and the final message is logged in its place.
+struct ProgressBarSink(ProgressBar);
+
+impl ProgressBarSink {
+	fn new() -> Self {
+		// only drawn once the operation starts
+		Self(ProgressBar::with_draw_target(None, ProgressDrawTarget::hidden()))
+	}
+
+	/// Prints a line above the progress bar without breaking it.
+	fn println(&self, line: impl AsRef<str>) {
+		if self.0.is_hidden() {
+			println!("{}", line.as_ref());
+		} else {
+			self.0.println(line);
+		}
+	}
+}
+
+impl ProgressSink for ProgressBarSink {
+	fn start(&self, message: &str, total: Option<u64>) {
+		let style = match total {
+			Some(total) => {
+				self.0.set_length(total);
+				ProgressStyle::with_template("{msg} [{bar:40}] {pos}/{len} ({eta})")
+			}
+			None => ProgressStyle::with_template("{spinner} {msg} ({pos})"),
+		};
+
+		self.0
+			.set_style(style.expect("progress templates are valid").progress_chars("=> "));
+		self.0.set_message(message.to_owned());
+		self.0.set_draw_target(ProgressDrawTarget::stderr());
+	}
+
+	fn advance(&self, amount: u64) {
+		self.0.inc(amount);
+	}
+
+	fn finish(&self, message: &str) {
+		self.0.finish_and_clear();
+		tracing::info!("{message}");
+	}
+}
+
+/// Copies the file at `path` into `out_path` if it is a beatmap, returning whether it was one.
+fn extract_osu_lazer_file(out_path: &Path, path: &Path, progress: &ProgressBarSink) -> io::Result<bool> {
+	if !sniff_osu_file(path)? {
+		return Ok(false);
+	}
+
+	progress.println(format!("Map in {:?}", path));
+	let file_name = path.file_name().unwrap_or(path.as_os_str());
+	fs::copy(path, out_path.join(Path::new(file_name).with_extension("osu")))?;
+	Ok(true)
+}
+
+fn cli_extract_osu_lazer_files(out_path: &Path, recursive: bool, path: &Path) -> Result<(), Box<dyn Error>> {
+	fs::create_dir_all(out_path)?;
+
+	let files: Vec<PathBuf> = WalkDir::new(path)
+		.max_depth(if recursive { usize::MAX } else { 0 })
+		.follow_links(true)
+		.into_iter()
+		.filter_map(|e| e.ok())
+		.filter(|e| !e.path().is_dir())
+		.map(|e| e.into_path())
+		.collect();
+
+	let total = files.len();
+	let progress = ProgressBarSink::new();
+	progress.start("Scanning files", Some(total as u64));
+	let extract = |file: &PathBuf| {
+		let result = extract_osu_lazer_file(out_path, file, &progress);
+		progress.advance(1);
+		result
+	};
+
+	#[cfg(feature = "parallel")]
+	let results: Vec<io::Result<bool>> = {
+		use rayon::prelude::*;
+		files.par_iter().map(extract).collect()
+	};
+
+	#[cfg(not(feature = "parallel"))]
+	let results: Vec<io::Result<bool>> = files.iter().map(extract).collect();
+
+	let mut extracted = 0;
+	for result in results {
+		if result? {
+			extracted += 1;
+		}
+	}
+
+	progress.finish(&format!("Extracted {extracted} maps out of {total} files"));
+	Ok(())
+}
+
+fn cli_offset(millis: f64, options: OffsetOptions, path: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	let mut beatmap = parse_beatmap(path, true, settings)?;
+
+	tracing::info!("Offsetting beatmap...");
+	let report = offset_map_checked(&mut beatmap, millis, options);
+	for warning in &report.warnings {
+		tracing::warn!("{warning}");
+	}
+
+	write_beatmap_with_changes(&beatmap, path, &report.changes, settings)?;
+	Ok(())
+}
+
+fn cli_offset_beats(beats: f64, path: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	let mut beatmap = parse_beatmap(path, true, settings)?;
+
+	tracing::info!("Offsetting beatmap by {beats} beats...");
+	offset_by_beats(&mut beatmap, beats);
+
+	write_beatmap_out(&beatmap, path, settings)?;
+	Ok(())
+}
+
+fn cli_refit_sliders(
+	policy: RefitPolicy,
+	old_timing: Option<&Path>,
+	path: &Path,
+	settings: &Settings,
+) -> Result<(), Box<dyn Error>> {
+	let old_timing_points = match (old_timing, policy) {
+		(Some(old_timing), _) => parse_beatmap(old_timing, false, settings)?.timing_points,
+		(None, RefitPolicy::PreserveDuration) => {
+			return Err(CliError::new(
+				ErrorCategory::Validation,
+				"keeping the duration of sliders needs the beatmap with the old timing (--old-timing)",
+			)
+			.into());
+		}
+		(None, RefitPolicy::PreserveGeometry) => Vec::new(),
+	};
+	let mut beatmap = parse_beatmap(path, true, settings)?;
+
+	tracing::info!("Refitting sliders...");
+	let report = refit_slider_lengths(&mut beatmap, &old_timing_points, policy);
+	println!("Changed the length of {} sliders", report.resized);
+	for unsnapped in &report.unsnapped {
+		println!(
+			"{}: slider ends unsnapped by {}ms at {}",
+			format_timestamp(unsnapped.time),
+			unsnapped.offset,
+			format_timestamp(unsnapped.end_time)
+		);
+	}
+
+	write_beatmap_out(&beatmap, path, settings)?;
+	Ok(())
+}
+
+fn cli_copy_timing(tolerance: f64, source: &Path, path: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	let source = parse_beatmap(source, false, settings)?;
+	let mut beatmap = parse_beatmap(path, true, settings)?;
+
+	tracing::info!("Copying timing...");
+	let options = CopyTimingOptions {
+		drift_tolerance: tolerance,
+		..CopyTimingOptions::default()
+	};
+	let moved = copy_timing(&source, &mut beatmap, &options);
+	println!("Snapped {moved} hit objects to the new timing");
+
+	write_beatmap_out(&beatmap, path, settings)?;
+	Ok(())
+}
+
+/// Time-stretches an audio file with ffmpeg's `atempo` filter, which keeps the pitch.
+fn stretch_audio(rate: f64, input: &Path, output: &Path) -> Result<(), Box<dyn Error>> {
+	// atempo only accepts factors between 0.5 and 2, so bigger changes are chained
+	let mut factors = Vec::new();
+	let mut remaining = rate;
+	while remaining > 2.0 {
+		factors.push("atempo=2.0".to_string());
+		remaining /= 2.0;
+	}
+	while remaining < 0.5 {
+		factors.push("atempo=0.5".to_string());
+		remaining /= 0.5;
+	}
+	factors.push(format!("atempo={remaining}"));
+
+	let status = Command::new("ffmpeg")
+		.arg("-y")
+		.arg("-i")
+		.arg(input)
+		.arg("-filter:a")
+		.arg(factors.join(","))
+		.arg(output)
+		.status()
+		.map_err(|e| format!("could not run ffmpeg: {e}"))?;
+
+	if !status.success() {
+		return Err(format!("ffmpeg failed with {status}").into());
+	}
+
+	Ok(())
 }
 
-#[derive(Clone, Copy, Debug)]
-#[repr(u8)]
-pub enum SampleBankOption {
-	Auto = 0,
-	Normal = 1,
-	Soft = 2,
-	Drum = 3,
+/// Name of a new difficulty made from the beatmap at `path`, the one osu! would give it (see [`standard_filename`]).
+///
+/// Beatmaps without metadata are named from the file stem of `path` instead, with `from_stem`.
+fn derived_filename(beatmap: &BeatmapFile, path: &Path, from_stem: impl FnOnce(&str) -> String) -> String {
+	(beatmap.metadata.as_ref()).map_or_else(
+		|| from_stem(&path.file_stem().unwrap_or_default().to_string_lossy()),
+		standard_filename,
+	)
 }
 
-impl fmt::Display for SampleBankOption {
-	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		f.write_str(match self {
-			SampleBankOption::Auto => "auto",
-			SampleBankOption::Normal => "normal",
-			SampleBankOption::Soft => "soft",
-			SampleBankOption::Drum => "drum",
-		})
+fn cli_change_rate(
+	rate: f64,
+	adjust_difficulty: bool,
+	do_audio: bool,
+	path: &Path,
+	settings: &Settings,
+) -> Result<(), Box<dyn Error>> {
+	if rate <= 0.0 {
+		return Err(CliError::new(ErrorCategory::Validation, "the rate must be positive").into());
+	}
+
+	let beatmap = parse_beatmap(path, false, settings)?;
+
+	tracing::info!("Changing rate to {rate}x...");
+	let mut beatmap = rate_changed_copy(&beatmap, rate, adjust_difficulty);
+
+	let folder = path.parent().unwrap_or(Path::new("."));
+	if do_audio {
+		if let Some(general) = &mut beatmap.general {
+			let audio_path = Path::new(&general.audio_filename);
+			let stem = audio_path.file_stem().unwrap_or_default().to_string_lossy();
+			let extension = audio_path.extension().unwrap_or_default().to_string_lossy();
+			let new_audio_filename = format!("{stem} {rate}x.{extension}");
+
+			tracing::info!("Stretching audio into {new_audio_filename}...");
+			stretch_audio(
+				rate,
+				&folder.join(&general.audio_filename),
+				&folder.join(&new_audio_filename),
+			)?;
+			general.audio_filename = new_audio_filename;
+		}
 	}
+
+	let out_filename = derived_filename(&beatmap, path, |stem| match stem.strip_suffix(']') {
+		Some(stem) => format!("{stem} ({rate}x)].osu"),
+		None => format!("{stem} ({rate}x).osu"),
+	});
+
+	write_beatmap_out(&beatmap, &folder.join(out_filename), settings)?;
+	Ok(())
 }
 
-#[derive(Clone, Debug)]
-pub struct InvalidSampleBankOptionError(String);
+/// Resamples an audio file with ffmpeg, which changes its speed and pitch together like the NC mod.
+fn resample_audio(rate: f64, input: &Path, output: &Path) -> Result<(), Box<dyn Error>> {
+	let probe = Command::new("ffprobe")
+		.args(["-v", "error", "-select_streams", "a:0"])
+		.args(["-show_entries", "stream=sample_rate", "-of", "csv=p=0"])
+		.arg(input)
+		.output()
+		.map_err(|e| format!("could not run ffprobe: {e}"))?;
+
+	let sample_rate: f64 = (String::from_utf8_lossy(&probe.stdout).trim().parse())
+		.map_err(|_| format!("could not find the sample rate of {}", input.display()))?;
+
+	let status = Command::new("ffmpeg")
+		.arg("-y")
+		.arg("-i")
+		.arg(input)
+		.arg("-filter:a")
+		.arg(format!("asetrate={},aresample={sample_rate}", sample_rate * rate))
+		.arg(output)
+		.status()
+		.map_err(|e| format!("could not run ffmpeg: {e}"))?;
+
+	if !status.success() {
+		return Err(format!("ffmpeg failed with {status}").into());
+	}
 
-impl std::error::Error for InvalidSampleBankOptionError {}
+	Ok(())
+}
 
-impl fmt::Display for InvalidSampleBankOptionError {
-	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		write!(
-			f,
-			"Invalid sample bank: expected \"auto\", \"normal\", \"soft\" or \"drum\", got {:?}",
-			self.0
+fn cli_nightcore(preset: RatePreset, do_audio: bool, path: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	let folder_name = (path.file_name())
+		.ok_or_else(|| CliError::new(ErrorCategory::Validation, "the path must be a beatmap set folder"))?;
+	let out_folder = path.with_file_name(format!("{} ({})", folder_name.to_string_lossy(), preset.name()));
+	if out_folder.exists() {
+		return Err(CliError::new(
+			ErrorCategory::Validation,
+			format!("{} already exists", out_folder.display()),
 		)
+		.into());
 	}
-}
 
-impl FromStr for SampleBankOption {
-	type Err = InvalidSampleBankOptionError;
+	tracing::info!("Loading beatmap set {}...", path.display());
+	let set = BeatmapSet::load_dir(path)?;
+	fs::create_dir_all(&out_folder)?;
 
-	fn from_str(s: &str) -> Result<Self, Self::Err> {
-		let s = s.to_ascii_lowercase();
-		match s.as_str() {
-			"auto" => Ok(SampleBankOption::Auto),
-			"normal" => Ok(SampleBankOption::Normal),
-			"soft" => Ok(SampleBankOption::Soft),
-			"drum" => Ok(SampleBankOption::Drum),
-			_ => Err(InvalidSampleBankOptionError(s)),
+	let mut audio_files = Vec::new();
+	for difficulty in rate_preset_difficulties(&set, preset) {
+		if let Some(general) = &difficulty.beatmap.general {
+			if !audio_files.contains(&general.audio_filename) {
+				audio_files.push(general.audio_filename.clone());
+			}
 		}
+
+		write_beatmap_out(&difficulty.beatmap, &out_folder.join(&difficulty.filename), settings)?;
 	}
-}
 
-impl SampleBankOption {
-	fn to_sample_bank(self) -> SampleBank {
-		match self {
-			SampleBankOption::Auto => SampleBank::Auto,
-			SampleBankOption::Normal => SampleBank::Normal,
-			SampleBankOption::Soft => SampleBank::Soft,
-			SampleBankOption::Drum => SampleBank::Drum,
+	for asset in &set.shared_assets {
+		let out_path = out_folder.join(asset);
+		if let Some(parent) = out_path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+
+		let is_audio = audio_files.iter().any(|audio| Path::new(audio) == asset);
+		if is_audio && do_audio {
+			tracing::info!("Resampling audio into {}...", out_path.display());
+			resample_audio(preset.rate(), &path.join(asset), &out_path)?;
+		} else {
+			if is_audio {
+				tracing::warn!("Copying {} as is, use --audio to resample it", asset.display());
+			}
+			fs::copy(path.join(asset), out_path)?;
 		}
 	}
+
+	println!("Created {}", out_folder.display());
+	Ok(())
 }
 
-fn main() {
-	tracing_subscriber::fmt().with_max_level(Level::INFO).init();
+fn cli_cut(
+	range: Range<f64>,
+	options: CutOptions,
+	name: &str,
+	path: &Path,
+	settings: &Settings,
+) -> Result<(), Box<dyn Error>> {
+	let beatmap = parse_beatmap(path, false, settings)?;
+
+	tracing::info!("Cutting beatmap...");
+	let mut part = cut_with(&beatmap, range, options);
+	println!(
+		"Kept {} of {} hit objects",
+		part.hit_objects.len(),
+		beatmap.hit_objects.len()
+	);
+
+	if let Some(metadata) = &mut part.metadata {
+		name.clone_into(&mut metadata.version);
+	}
 
-	let Cli { command } = Cli::parse();
+	let out_filename = derived_filename(&part, path, |stem| match stem.rsplit_once('[') {
+		Some((prefix, _)) => format!("{prefix}[{name}].osu"),
+		None => format!("{stem} [{name}].osu"),
+	});
 
-	let result = match command {
-		Commands::ExtractOsuLazerFiles {
-			out_path,
-			recursive,
-			path,
-		} => {
-			let out_path = out_path.unwrap_or(current_dir().unwrap().join("maps"));
-			cli_extract_osu_lazer_files(&out_path, recursive, &path)
-		}
+	let folder = path.parent().unwrap_or(Path::new("."));
+	write_beatmap_out(&part, &folder.join(out_filename), settings)?;
+	Ok(())
+}
 
-		Commands::Offset { millis, path } => cli_offset(millis, &path),
+fn cli_new(options: GenerateOptions, folder: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	let out_path = folder.join(standard_filename(&options.metadata));
+	if out_path.exists() {
+		return Err(CliError::new(
+			ErrorCategory::Validation,
+			format!("{} already exists", out_path.display()),
+		)
+		.into());
+	}
 
-		Commands::MixVolume { val, path } => cli_mix_volume(val, &path),
+	tracing::info!("Creating beatmap...");
+	let beatmap = new_beatmap(options)?;
 
-		Commands::ResetSampleSets { sample, cleanup, path } => {
-			cli_reset_sample_sets(sample.to_sample_bank(), cleanup, &path)
-		}
+	fs::create_dir_all(folder)?;
+	write_beatmap_out(&beatmap, &out_path, settings)?;
+	println!("Created {}", out_path.display());
+	Ok(())
+}
 
-		Commands::CleanupTimingPoints { path } => cli_cleanup_timing_points(&path),
+fn cli_concat(part_paths: &[PathBuf], out_path: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	let parts = (part_paths.iter())
+		.map(|path| parse_beatmap(path, false, settings))
+		.collect::<Result<Vec<_>, _>>()?;
 
-		Commands::SplatHitsounds { sound_map, path, mania } => cli_splat_hitsounds(&sound_map, &path, mania),
+	tracing::info!("Joining {} beatmaps...", parts.len());
+	let beatmap = concat(&parts);
+	println!("Joined {} hit objects", beatmap.hit_objects.len());
 
-		Commands::LazerToStable { path } => cli_lazer_to_stable(&path),
-	};
+	write_beatmap_out(&beatmap, out_path, settings)?;
+	Ok(())
+}
 
-	if let Err(err) = result {
-		println!("Error: {}", err);
+fn cli_simplify(options: &SimplifyOptions, path: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	let mut beatmap = parse_beatmap(path, false, settings)?;
 
-		let mut e = err.deref();
-		while let Some(sauce) = e.source() {
-			println!("-> {}", sauce);
-			e = sauce;
-		}
+	tracing::info!("Simplifying beatmap...");
+	let removed = simplify(&mut beatmap, options);
+	println!("Removed {removed} hit objects");
 
-		println!("\n{:#?}", err);
-	}
+	let out_filename = derived_filename(&beatmap, path, |stem| match stem.rsplit_once('[') {
+		Some((prefix, _)) => format!("{prefix}[{}].osu", options.version),
+		None => format!("{stem} [{}].osu", options.version),
+	});
+
+	let folder = path.parent().unwrap_or(Path::new("."));
+	write_beatmap_out(&beatmap, &folder.join(out_filename), settings)?;
+	Ok(())
 }
 
-fn backup(path: &Path) -> io::Result<u64> {
-	let mut out_path = path.with_extension("osu.backup");
+#[allow(clippy::too_many_arguments)]
+fn cli_mania_transform(
+	do_mirror: bool,
+	seed: Option<u64>,
+	max_jack: Option<usize>,
+	long_notes: Option<(f64, f64)>,
+	do_rice: bool,
+	conflict_policy: Option<ConflictPolicy>,
+	path: &Path,
+	settings: &Settings,
+) -> Result<(), Box<dyn Error>> {
+	let mut beatmap = parse_beatmap(path, true, settings)?;
+	if beatmap.general.as_ref().map_or(0, |general| general.mode) != 3 {
+		return Err(CliError::new(ErrorCategory::UnsupportedMode, "not an osu!mania beatmap").into());
+	}
 
-	let mut n: u32 = 1;
-	while out_path.exists() {
-		out_path = path.with_extension(format!("osu.{n}.backup"));
-		n += 1;
+	if do_mirror {
+		tracing::info!("Mirroring columns...");
+		mirror(&mut beatmap);
 	}
 
-	fs::copy(path, out_path)
-}
+	if let Some(seed) = seed {
+		tracing::info!("Shuffling columns with seed {seed}...");
+		let permutation = shuffle_columns(&mut beatmap, seed);
+		println!("New columns: {permutation:?}");
+	}
 
-fn parse_beatmap(path: &Path, do_backup: bool) -> Result<BeatmapFile, Box<dyn Error>> {
-	if do_backup {
-		tracing::warn!("Backing up {}...", path.display());
-		backup(path)?;
+	if let Some(max_jack) = max_jack {
+		tracing::info!("Breaking up jacks longer than {max_jack} notes...");
+		let moved = avoid_jacks(&mut beatmap, max_jack);
+		println!("Moved {moved} notes");
 	}
 
-	tracing::warn!("Parsing {}...", path.display());
-	let beatmap = BeatmapFile::parse(path)?;
+	if do_rice {
+		tracing::info!("Converting holds to rice...");
+		let converted = to_rice(&mut beatmap);
+		println!("Converted {converted} holds");
+	}
 
-	Ok(beatmap)
+	if let Some((gap, min_length)) = long_notes {
+		tracing::info!("Converting rice to holds...");
+		let converted = to_long_notes(&mut beatmap, gap, min_length);
+		println!("Converted {converted} notes");
+	}
+
+	if let Some(policy) = conflict_policy {
+		tracing::info!("Resolving conflicting notes...");
+		let key_count = beatmap.key_count();
+		let resolved = resolve_conflicts(&mut beatmap.hit_objects, key_count, policy);
+		println!("Resolved {resolved} conflicting notes");
+	}
+
+	write_beatmap_out(&beatmap, path, settings)?;
+	Ok(())
+}
+
+fn cli_nudge_into_bounds(rescale: bool, path: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	let mut beatmap = parse_beatmap(path, true, settings)?;
+
+	tracing::info!("Moving objects into the playfield...");
+	let moved = nudge_into_bounds(&mut beatmap, NudgeOptions { rescale });
+	println!("Moved {moved} objects");
+	if moved == 0 {
+		return Ok(());
+	}
+
+	write_beatmap_out(&beatmap, path, settings)?;
+	Ok(())
 }
 
-fn write_beatmap_out(beatmap: &BeatmapFile, path: &Path) -> io::Result<()> {
-	tracing::warn!("Write beatmap to {}...", path.display());
-	let mut out_file = File::create(path)?;
-	beatmap.deserialize(&mut out_file)?;
+fn cli_convert_to_mania(
+	options: ManiaConversionOptions,
+	path: &Path,
+	settings: &Settings,
+) -> Result<(), Box<dyn Error>> {
+	let mut beatmap = parse_beatmap(path, true, settings)?;
+	if beatmap.general.as_ref().map_or(0, |general| general.mode) == 3 {
+		return Err(CliError::new(ErrorCategory::UnsupportedMode, "already an osu!mania beatmap").into());
+	}
+
+	tracing::info!("Converting to osu!mania...");
+	let report = convert_to_mania(&mut beatmap, options);
+	println!("Converted {} spinners", report.spinners);
+	println!("Resolved {} conflicting notes", report.conflicts);
 
+	write_beatmap_out(&beatmap, path, settings)?;
 	Ok(())
 }
 
-fn cleanup_timing_points(beatmap: &mut BeatmapFile) {
-	tracing::warn!("Removing duplicates...");
-	beatmap.timing_points = remove_duplicates(&beatmap.timing_points);
+fn cli_convert_to_catch(allow_hyperdashes: bool, path: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	let mut beatmap = parse_beatmap(path, true, settings)?;
 
-	let mode = beatmap.general.as_ref().unwrap().mode;
+	tracing::info!("Converting to osu!catch...");
+	convert_to_catch(&mut beatmap, allow_hyperdashes);
 
-	tracing::warn!("Removing useless speed changes...");
-	beatmap.timing_points = remove_useless_speed_changes(mode, &beatmap.timing_points, &beatmap.hit_objects);
+	let hyperdashes = movements(&beatmap).iter().filter(|m| m.hyperdash).count();
+	println!("{hyperdashes} hyperdashes");
 
-	tracing::warn!("Removing duplicates again...");
-	beatmap.timing_points = remove_duplicates(&beatmap.timing_points);
+	write_beatmap_out(&beatmap, path, settings)?;
+	Ok(())
 }
 
-/// Combine and merge the hitsound information of a bunch of hitobjects into another one.
-fn hitsound_hit_object(ho: &mut HitObject, ho_sounds: &[HitObject]) {
-	for so in ho_sounds {
-		tracing::info!("affecting {} at {}", ho.object_type, ho.timestamp());
+/// Width and height of the ASCII render of the playfield.
+const RENDER_SIZE: (usize, usize) = (64, 24);
 
-		if so.hit_sample.normal_set != SampleBank::Auto {
-			ho.hit_sample.normal_set = so.hit_sample.normal_set;
+/// Draws a slider path on the playfield with ASCII characters.
+fn render_slider_ascii(path: &SliderPath) -> String {
+	let (width, height) = RENDER_SIZE;
+	let mut grid = vec![vec![' '; width]; height];
+
+	let mut plot = |point: Point, c: char| {
+		#[allow(
+			clippy::cast_possible_truncation,
+			clippy::cast_sign_loss,
+			clippy::cast_precision_loss
+		)]
+		let (col, row) = (
+			(point.x / 512.0 * width as f64).floor().max(0.0) as usize,
+			(point.y / 384.0 * height as f64).floor().max(0.0) as usize,
+		);
+		if let Some(cell) = grid.get_mut(row).and_then(|row| row.get_mut(col)) {
+			*cell = c;
 		}
+	};
+
+	let steps = (path.length() / 2.0).ceil().max(1.0);
+	let mut step = 0.0;
+	while step <= steps {
+		plot(path.position_at(step / steps), '.');
+		step += 1.0;
+	}
 
-		if so.hit_sample.addition_set != SampleBank::Auto {
-			ho.hit_sample.addition_set = so.hit_sample.addition_set;
+	for segment in path.segments() {
+		for &point in &segment.control_points {
+			plot(point, 'o');
 		}
+	}
 
-		ho.hit_sample.index = so.hit_sample.index;
-		ho.hit_sample.volume = so.hit_sample.volume;
+	plot(path.position_at(1.0), 'E');
+	plot(path.position_at(0.0), 'H');
+
+	let border = format!("+{}+", "-".repeat(width));
+	let rows = grid
+		.into_iter()
+		.map(|row| format!("|{}|", row.into_iter().collect::<String>()));
+	std::iter::once(border.clone())
+		.chain(rows)
+		.chain(std::iter::once(border))
+		.collect::<Vec<_>>()
+		.join("\n")
+}
 
-		if so.hit_sample.filename.is_some() {
-			ho.hit_sample.filename = so.hit_sample.filename.clone();
+fn cli_debug_slider(
+	line: Option<&str>,
+	map: Option<&Path>,
+	at: Option<f64>,
+	render: bool,
+	settings: &Settings,
+) -> Result<(), Box<dyn Error>> {
+	let beatmap = map.map(|map| parse_beatmap(map, false, settings)).transpose()?;
+
+	let hit_object = match (line, &beatmap, at) {
+		(Some(line), _, _) => parse_hit_object(line)?,
+		(None, Some(beatmap), Some(at)) => (beatmap.hit_objects.iter())
+			.filter(|hit_object| hit_object.is_slider())
+			.min_by(|a, b| (a.time - at).abs().total_cmp(&(b.time - at).abs()))
+			.filter(|hit_object| hit_object.basically_at(at))
+			.cloned()
+			.ok_or_else(|| {
+				CliError::new(
+					ErrorCategory::Validation,
+					format!("no slider at {}", format_timestamp(at)),
+				)
+			})?,
+		_ => {
+			return Err(CliError::new(
+				ErrorCategory::Validation,
+				"either --line or both --map and --at are required",
+			)
+			.into())
 		}
+	};
+
+	let Some(path) = SliderPath::from_hit_object(&hit_object) else {
+		return Err(CliError::new(ErrorCategory::Validation, "the hit object is not a slider").into());
+	};
 
-		ho.hit_sound |= so.hit_sound;
+	let HitObjectParams::Slider { length, slides, .. } = hit_object.object_params else {
+		unreachable!("sliders have slider parameters");
+	};
+
+	println!(
+		"Slider at {} ({}, {})",
+		format_timestamp(hit_object.time),
+		hit_object.x,
+		hit_object.y
+	);
+
+	println!("Segments:");
+	for (i, segment) in path.segments().iter().enumerate() {
+		println!(
+			"  {}. {:?} with {} control points",
+			i + 1,
+			segment.curve_type,
+			segment.control_points.len()
+		);
 	}
-}
 
-fn cli_extract_osu_lazer_files(out_path: &Path, recursive: bool, path: &Path) -> Result<(), Box<dyn Error>> {
-	fs::create_dir_all(out_path)?;
+	let calculated_length = path.calculated_length();
+	println!("Declared length: {length}");
+	if calculated_length > length {
+		println!(
+			"Flattened length: {calculated_length:.2} (cut by {:.2})",
+			calculated_length - length
+		);
+	} else {
+		println!(
+			"Flattened length: {calculated_length:.2} (extended by {:.2})",
+			length - calculated_length
+		);
+	}
 
-	for entry in WalkDir::new(path)
-		.max_depth(if recursive { usize::MAX } else { 0 })
-		.follow_links(true)
-		.into_iter()
-		.filter_map(|e| e.ok())
-		.filter(|e| !e.path().is_dir())
-	{
-		let file = File::open(entry.path())?;
+	let end = path.position_at(1.0);
+	println!("End position: ({:.2}, {:.2})", end.x, end.y);
+	println!("Slides: {slides}");
 
-		let mut buffer = BufReader::new(file);
-		let mut first_line = String::new();
-		let _ = buffer.read_line(&mut first_line);
+	if let Some(beatmap) = &beatmap {
+		if let Some(duration) = slider_duration(beatmap, &hit_object) {
+			println!(
+				"End time: {} ({duration:.2} ms)",
+				format_timestamp(hit_object.time + duration)
+			);
+		}
 
-		if first_line.starts_with("osu file format v") {
-			println!("Map in {:?}", entry.path());
-			let entry_out_path = Path::new(entry.file_name()).with_extension("osu");
-			fs::copy(entry.path(), out_path.join(entry_out_path))?;
+		let ticks: Vec<String> = (slider_tick_times(beatmap, &hit_object).into_iter())
+			.map(format_timestamp)
+			.collect();
+		if ticks.is_empty() {
+			println!("Ticks: none");
+		} else {
+			println!("Ticks ({}): {}", ticks.len(), ticks.join(", "));
 		}
 	}
 
+	if render {
+		println!("{}", render_slider_ascii(&path));
+	}
+
+	Ok(())
+}
+
+fn cli_render_preview(
+	range: Range<f64>,
+	out_path: &Path,
+	path: &Path,
+	settings: &Settings,
+) -> Result<(), Box<dyn Error>> {
+	let beatmap = parse_beatmap(path, false, settings)?;
+
+	tracing::info!("Rendering preview to {}...", out_path.display());
+	fs::write(out_path, render_timeline_svg(&beatmap, range))?;
 	Ok(())
 }
 
-fn cli_offset(millis: f64, path: &Path) -> Result<(), Box<dyn Error>> {
-	let mut beatmap = parse_beatmap(path, true)?;
+fn cli_mix_volume(val: i8, scope: &TimeScope, path: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	let mut beatmap = parse_beatmap(path, true, settings)?;
+	let range = scope.range(&beatmap)?;
 
-	tracing::warn!("Offsetting beatmap...");
-	offset_map(&mut beatmap, millis);
+	tracing::info!("Mixing volume...");
+	beatmap.timing_points.sort_by(|a, b| a.time.total_cmp(&b.time));
+	mix_volume(isolate_timing_points(&mut beatmap.timing_points, range), val);
 
-	write_beatmap_out(&beatmap, path)?;
+	write_beatmap_out(&beatmap, path, settings)?;
 	Ok(())
 }
 
-fn cli_mix_volume(val: i8, path: &Path) -> Result<(), Box<dyn Error>> {
-	let mut beatmap = parse_beatmap(path, true)?;
+fn cli_set_slider_velocity(
+	multiplier: f64,
+	scope: &TimeScope,
+	path: &Path,
+	settings: &Settings,
+) -> Result<(), Box<dyn Error>> {
+	let mut beatmap = parse_beatmap(path, true, settings)?;
+	let range = scope.range(&beatmap)?;
+
+	tracing::info!("Setting slider velocity to {multiplier}x...");
+	let changed = set_slider_velocity(&mut beatmap, range, multiplier);
+	println!("Changed the slider velocity of {changed} sliders");
 
-	tracing::warn!("Mixing volume...");
-	mix_volume(&mut beatmap.timing_points, val);
+	write_beatmap_out(&beatmap, path, settings)?;
+	Ok(())
+}
 
-	write_beatmap_out(&beatmap, path)?;
+fn cli_fade_volume(
+	range: Range<f64>,
+	start_volume: u8,
+	end_volume: u8,
+	step: f64,
+	curve: VolumeCurve,
+	path: &Path,
+	settings: &Settings,
+) -> Result<(), Box<dyn Error>> {
+	let mut beatmap = parse_beatmap(path, true, settings)?;
+
+	tracing::info!("Fading volume...");
+	beatmap.timing_points.sort_by(|a, b| a.time.total_cmp(&b.time));
+	volume_ramp(&mut beatmap.timing_points, range, start_volume, end_volume, step, curve);
+
+	write_beatmap_out(&beatmap, path, settings)?;
 	Ok(())
 }
 
-fn cli_reset_sample_sets(sample_bank: SampleBank, cleanup: bool, path: &Path) -> Result<(), Box<dyn Error>> {
-	let mut beatmap = parse_beatmap(path, true)?;
+fn cli_reset_sample_sets(
+	sample_bank: SampleBank,
+	cleanup: bool,
+	scope: ResetScope,
+	time_scope: &TimeScope,
+	path: &Path,
+	settings: &Settings,
+) -> Result<(), Box<dyn Error>> {
+	let mut beatmap = parse_beatmap(path, true, settings)?;
+	let range = time_scope.range(&beatmap)?;
 
-	tracing::warn!("Resetting hitsounds...");
-	reset_hitsounds(&mut beatmap.timing_points, sample_bank);
+	tracing::info!("Resetting hitsounds...");
+	reset_hitsounds_deep(&mut beatmap, sample_bank, scope, range);
 
 	if cleanup {
-		cleanup_timing_points(&mut beatmap);
+		let report = cleanup_timing_points(&mut beatmap);
+		println!("Removed {} useless speed changes", report.removed_speed_changes.len());
 	}
 
-	write_beatmap_out(&beatmap, path)?;
+	write_beatmap_out(&beatmap, path, settings)?;
 	Ok(())
 }
 
-fn cli_cleanup_timing_points(path: &Path) -> Result<(), Box<dyn Error>> {
-	let mut beatmap = parse_beatmap(path, true)?;
+fn read_hitsound_pattern(path: &Path) -> Result<HitsoundPattern, Box<dyn Error>> {
+	let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+
+	let divisor = json["divisor"].as_u64().unwrap_or(1);
+	let steps = (json["steps"].as_array())
+		.ok_or_else(|| CliError::new(ErrorCategory::Parse, "The pattern needs a \"steps\" array"))?
+		.iter()
+		.map(|step| {
+			(step.as_str())
+				.ok_or_else(|| CliError::new(ErrorCategory::Parse, "Every step of the pattern must be a string"))
+		})
+		.collect::<Result<Vec<_>, _>>()?;
+	let overwrite = json["overwrite"].as_bool().unwrap_or(false);
 
-	cleanup_timing_points(&mut beatmap);
+	Ok(HitsoundPattern::new(u32::try_from(divisor)?, &steps, overwrite)?)
+}
 
-	write_beatmap_out(&beatmap, path)?;
+fn cli_apply_hitsound_pattern(
+	pattern_path: &Path,
+	scope: &TimeScope,
+	path: &Path,
+	settings: &Settings,
+) -> Result<(), Box<dyn Error>> {
+	let pattern = read_hitsound_pattern(pattern_path)?;
+	let mut beatmap = parse_beatmap(path, true, settings)?;
+	let range = scope.range(&beatmap)?;
+
+	tracing::info!("Applying hitsound pattern...");
+	let changed = apply_pattern(&mut beatmap, &pattern, range);
+	println!("Changed {changed} hitsounds");
+
+	write_beatmap_out(&beatmap, path, settings)?;
 	Ok(())
 }
 
-fn cli_splat_hitsounds(soundmap_path: &Path, beatmap_path: &Path, is_mania: bool) -> Result<(), Box<dyn Error>> {
-	let mut beatmap = parse_beatmap(beatmap_path, true)?;
-	let soundmap = parse_beatmap(soundmap_path, false)?;
+fn cli_cleanup_timing_points(path: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	let mut beatmap = parse_beatmap(path, true, settings)?;
 
-	// reset beatmap's hitsounds
-	tracing::warn!("Resetting beatmap's hitsounds...");
-	for hit_object in &mut beatmap.hit_objects {
-		hit_object.hit_sample = HitSample::default();
-		hit_object.hit_sound = HitSound::NONE;
+	let report = cleanup_timing_points(&mut beatmap);
+	println!("Removed {} useless speed changes", report.removed_speed_changes.len());
 
-		if let HitObjectParams::Slider {
-			edge_hitsounds,
-			edge_samplesets,
-			..
-		} = &mut hit_object.object_params
-		{
-			for eh in edge_hitsounds {
-				*eh = HitSound::NONE;
-			}
+	write_beatmap_with_changes(&beatmap, path, &report.changes, settings)?;
+	Ok(())
+}
 
-			for es in edge_samplesets {
-				*es = HitSampleSet::default();
-			}
-		}
+fn cli_repair_timing(path: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	let mut beatmap = parse_beatmap(path, true, settings)?;
+
+	tracing::info!("Repairing timing points...");
+	let repairs = repair_timing(&mut beatmap);
+	if repairs.is_empty() {
+		println!("Nothing to repair");
+		return Ok(());
 	}
 
-	// insert soundmap's hitsound information from timing points
-	tracing::warn!("Inserting soundmap's timing points...");
-	let mut new_timing_points: Vec<TimingPoint> = Vec::new();
-	let mut last_sound_point = &soundmap.timing_points[0];
-	for smtp_bmtp in (soundmap.timing_points).interleave_timestamped(&beatmap.timing_points) {
-		match smtp_bmtp {
-			Ok(soundmap_tp) => {
-				last_sound_point = soundmap_tp;
-
-				if let Some(new_tp) = new_timing_points.last_mut() {
-					if soundmap_tp.basically_eq(new_tp) {
-						new_tp.sample_set = soundmap_tp.sample_set;
-						new_tp.sample_index = soundmap_tp.sample_index;
-						new_tp.volume = soundmap_tp.volume;
-					} else {
-						let mut new_tp = new_tp.clone();
-						new_tp.time = soundmap_tp.time;
-						new_tp.uninherited = false;
-						new_tp.sample_set = soundmap_tp.sample_set;
-						new_tp.sample_index = soundmap_tp.sample_index;
-						new_tp.volume = soundmap_tp.volume;
-						new_timing_points.push(new_tp.clone());
-					}
-				}
-			}
-			Err(beatmap_tp) => {
-				let mut new_tp = beatmap_tp.clone();
-				new_tp.sample_set = last_sound_point.sample_set;
-				new_tp.sample_index = last_sound_point.sample_index;
-				new_tp.volume = last_sound_point.volume;
-				new_timing_points.push(new_tp);
-			}
-		}
+	for repair in &repairs {
+		println!("{repair}");
 	}
-	beatmap.timing_points = new_timing_points;
 
-	tracing::warn!("Inserting soundmap's hitsounds...");
-	let slider_multiplier = beatmap.difficulty.as_ref().unwrap().slider_multiplier as f64;
+	write_beatmap_out(&beatmap, path, settings)?;
+	Ok(())
+}
 
-	let mut modified_hit_objects = Vec::new();
+fn cli_run(pipeline_path: &Path, path: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	let pipeline = Pipeline::read(pipeline_path)?;
+	let mut beatmap = parse_beatmap(path, true, settings)?;
 
-	// TODO: improve performance by somehow walking along both maps
-	//       (instead of binary-searching the soundmap every time)
+	pipeline.run(&mut beatmap, settings)?;
 
-	let mut beat_length = 0.0;
-	let mut slider_velocity = 1.0;
-	for ho_tp in beatmap.iter_hit_objects_and_timing_points() {
-		match ho_tp {
-			Ok(hit_object) => {
-				let new_hit_object = match &hit_object.object_params {
-					HitObjectParams::HitCircle => {
-						// affect hitsound properties of the hitcircle
+	write_beatmap_out(&beatmap, path, settings)?;
+	Ok(())
+}
 
-						let mut hit_object = hit_object.clone();
+fn print_copy_hitsounds_report(report: &CopyHitsoundsReport) {
+	for hold_end in &report.hold_ends {
+		let HoldEndHitsounds {
+			hold_time, end_time, ..
+		} = hold_end;
+		match hold_end.moved_to {
+			Some(target) => {
+				println!("Hold at {hold_time}: moved hitsounds at its end ({end_time}) to the note at {target}")
+			}
+			None => tracing::warn!("Hold at {hold_time}: discarding hitsounds at its end ({end_time})"),
+		}
+	}
 
-						let start_hitsounds = (soundmap.hit_objects).between(close_range(hit_object.timestamp(), 2.0));
+	println!(
+		"Hitsounded {} hit objects, sampled {}",
+		report.hitsounded, report.sampled
+	);
+}
 
-						hitsound_hit_object(&mut hit_object, start_hitsounds);
-						hit_object
-					}
-					HitObjectParams::Slider { length, .. } => {
-						// affect all edge hitsound properties of the slider
+fn cli_splat_hitsounds(
+	soundmap_path: &Path,
+	path: &Path,
+	modes: &[u8],
+	options: &CopyHitsoundsOptions,
+	settings: &Settings,
+) -> Result<(), Box<dyn Error>> {
+	let soundmap = parse_beatmap(soundmap_path, false, settings)?;
+
+	if !path.is_dir() {
+		let mut beatmap = parse_beatmap(path, true, settings)?;
+		let report = copy_hitsounds(&mut beatmap, &soundmap, options);
+		print_copy_hitsounds_report(&report);
+
+		write_beatmap_out(&beatmap, path, settings)?;
+		return Ok(());
+	}
 
-						let mut hit_object = hit_object.clone();
+	tracing::info!("Loading beatmap set {}...", path.display());
+	let mut set = BeatmapSet::load_dir(path)?;
+	let soundmap_path = soundmap_path.canonicalize()?;
+	let soundmap_filename = (soundmap_path.strip_prefix(set.path.canonicalize()?))
+		.ok()
+		.map(|filename| filename.to_string_lossy().into_owned());
+
+	let reports = splat_hitsounds(&mut set, &soundmap, soundmap_filename.as_deref(), modes, options);
+	for SplatReport { difficulty, report } in reports {
+		let difficulty = &set.difficulties[difficulty];
+		println!("{}:", difficulty.filename);
+		print_copy_hitsounds_report(&report);
+
+		let difficulty_path = set.path.join(&difficulty.filename);
+		backup(&difficulty_path, settings)?;
+		write_beatmap_out(&difficulty.beatmap, &difficulty_path, settings)?;
+	}
 
-						let start_hitsounds = (soundmap.hit_objects).between(close_range(hit_object.timestamp(), 2.0));
+	Ok(())
+}
 
-						hitsound_hit_object(&mut hit_object, start_hitsounds);
+fn cli_lazer_to_stable(path: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	let mut beatmap = parse_beatmap(path, true, settings)?;
 
-						let timestamp = hit_object.timestamp();
-						let dur = *length * beat_length / (slider_multiplier * 100.0 * slider_velocity);
+	tracing::info!("Converting beatmap to osu!stable...");
+	let report = to_stable(&mut beatmap);
+	print_conversion_report(&report);
 
-						if let HitObjectParams::Slider {
-							edge_hitsounds,
-							edge_samplesets,
-							..
-						} = &mut hit_object.object_params
-						{
-							for (i, (edge_hs, edge_ss)) in
-								(edge_hitsounds.iter_mut()).zip(edge_samplesets.iter_mut()).enumerate()
-							{
-								let local_timestamp = timestamp + i as f64 * dur;
+	write_beatmap_with_changes(&beatmap, path, &report.changes, settings)?;
+	Ok(())
+}
 
-								let start_hitsounds = (soundmap.hit_objects).between(close_range(local_timestamp, 2.0));
+fn print_conversion_report(report: &ConversionReport) {
+	for warning in &report.warnings {
+		tracing::warn!("{warning}");
+	}
 
-								for so in start_hitsounds {
-									tracing::info!("affecting slider edge at {}", local_timestamp);
+	println!(
+		"Floored {} times, rounded {} positions, converted {} sliders, removed {} events",
+		report.floored_times, report.rounded_positions, report.converted_sliders, report.removed_events
+	);
+}
 
-									if so.hit_sample.normal_set != SampleBank::Auto {
-										edge_ss.normal_set = so.hit_sample.normal_set;
-									}
+fn cli_set_preview(auto: bool, time: Option<f64>, path: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	let mut beatmap = parse_beatmap(path, true, settings)?;
 
-									if so.hit_sample.addition_set != SampleBank::Auto {
-										edge_ss.addition_set = so.hit_sample.addition_set;
-									}
+	let preview_time = match time {
+		Some(time) if !auto => time,
+		_ => {
+			tracing::info!("Suggesting preview time...");
+			suggest_preview_time(&beatmap)
+		}
+	};
 
-									*edge_hs |= so.hit_sound;
-								}
-							}
-						}
+	println!("Preview time: {preview_time}");
+	beatmap.general.get_or_insert_with(Default::default).preview_time = preview_time;
 
-						hit_object
-					}
-					HitObjectParams::Spinner { end_time } => {
-						// affect hitsound properties of the spinner
+	write_beatmap_out(&beatmap, path, settings)?;
+	Ok(())
+}
 
-						let mut hit_object = hit_object.clone();
+#[cfg(feature = "transliteration")]
+fn cli_romanise_metadata(path: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	let mut beatmap = parse_beatmap(path, true, settings)?;
+	let metadata = beatmap.metadata.get_or_insert_with(Default::default);
 
-						let end_hitsounds = (soundmap.hit_objects).between(close_range(*end_time, 2.0));
+	if !metadata.fill_romanised() {
+		println!("The romanised metadata is already ASCII");
+		return Ok(());
+	}
 
-						hitsound_hit_object(&mut hit_object, end_hitsounds);
-						hit_object
-					}
-					HitObjectParams::Hold { .. } => {
-						// affect hitsound properties of the mania hold
+	println!("Title: {}", metadata.title);
+	println!("Artist: {}", metadata.artist);
+	tracing::warn!("Transliterations can be wrong, make sure to review them");
 
-						let mut hit_object = hit_object.clone();
+	write_beatmap_out(&beatmap, path, settings)?;
+	Ok(())
+}
 
-						let start_hitsounds = (soundmap.hit_objects).between(close_range(hit_object.timestamp(), 2.0));
+fn cli_suggest_difficulty(apply: bool, path: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	let mut beatmap = parse_beatmap(path, apply, settings)?;
+
+	tracing::info!("Measuring beatmap...");
+	let measures = measure_difficulty(&beatmap);
+	println!(
+		"Density: {:.2} objects/s, peak rate: {:.2} objects/s, main BPM: {}, spacing: {:.2} px/ms",
+		measures.density,
+		measures.peak_rate,
+		measures
+			.main_bpm
+			.map_or_else(|| "none".to_owned(), |bpm| format!("{bpm:.2}")),
+		measures.spacing_velocity,
+	);
+
+	let current = beatmap.difficulty.clone().unwrap_or_default();
+	let suggested = suggest_difficulty_settings(&beatmap);
+	println!("AR: {} -> {}", current.approach_rate, suggested.approach_rate);
+	println!("OD: {} -> {}", current.overall_difficulty, suggested.overall_difficulty);
+	println!("HP: {} -> {}", current.hp_drain_rate, suggested.hp_drain_rate);
+
+	if apply {
+		beatmap.difficulty = Some(suggested);
+		write_beatmap_out(&beatmap, path, settings)?;
+	}
 
-						hitsound_hit_object(&mut hit_object, start_hitsounds);
-						hit_object
-					}
-				};
+	Ok(())
+}
 
-				modified_hit_objects.push(new_hit_object);
-			}
-			Err(timing_point) if timing_point.uninherited => {
-				beat_length = timing_point.beat_length;
-			}
-			Err(timing_point) => {
-				slider_velocity = -100.0 / timing_point.beat_length;
-			}
+fn cli_manage_assets(
+	list: bool,
+	background: Option<&str>,
+	do_strip_videos: bool,
+	unused: bool,
+	path: &Path,
+	settings: &Settings,
+) -> Result<(), Box<dyn Error>> {
+	tracing::info!("Loading beatmap set {}...", path.display());
+	let mut set = BeatmapSet::load(path)?;
+
+	if list {
+		for asset in list_assets(&set) {
+			let status = if set.has_asset(&asset.path) { "" } else { " (missing)" };
+			println!("{:?}: {}{status}", asset.kind, asset.path);
 		}
 	}
 
-	if is_mania {
-		tracing::warn!("Applying mania hitsound spread-out transformation...");
+	if unused {
+		for file in unused_files(&set) {
+			println!("Unused: {}", file.display());
+		}
+	}
 
-		for group in modified_hit_objects.group_timestamped_mut() {
-			// Note: due to how the algorithm works, hitobjects in a group all have the same hitsound information.
+	if background.is_none() && !do_strip_videos {
+		return Ok(());
+	}
 
-			match group {
-				[] => break,
-				[_] => continue,
-				[ref mut first, ref mut remains @ ..] => {
-					let normal_set = first.hit_sample.normal_set;
-					let addition_set = first.hit_sample.addition_set;
+	if let Some(background) = background {
+		tracing::info!("Replacing backgrounds with {background}...");
+		replace_background(&mut set, background);
+	}
 
-					if normal_set != SampleBank::Auto {
-						// Only have the first hitobject on a non-auto normal set
-						for other in remains.iter_mut() {
-							other.hit_sample.normal_set = SampleBank::Auto;
-						}
-					}
+	if do_strip_videos {
+		tracing::info!("Stripping videos...");
+		let removed = strip_videos(&mut set);
+		println!("Removed {removed} video events");
+	}
 
-					if addition_set != SampleBank::Auto {
-						// Only have the non-first hitobjects on a non-auto addition set
-						first.hit_sample.addition_set = SampleBank::Auto;
-					}
+	write_beatmap_set(&set, settings)
+}
+
+/// Parses a sample index mapping written as `FROM=TO`.
+fn parse_index_mapping(s: &str) -> Result<(u32, u32), String> {
+	let (from, to) = (s.split_once('=')).ok_or_else(|| format!("expected FROM=TO, got {s:?}"))?;
+	let parse = |index: &str| (index.trim().parse::<u32>()).map_err(|e| format!("invalid sample index {index:?}: {e}"));
+	Ok((parse(from)?, parse(to)?))
+}
 
-					let hit_sound = first.hit_sound;
+fn cli_remap_sample_indices(
+	list: bool,
+	mappings: &[(u32, u32)],
+	missing: bool,
+	path: &Path,
+	format: OutputFormat,
+	settings: &Settings,
+) -> Result<(), Box<dyn Error>> {
+	tracing::info!("Loading beatmap set {}...", path.display());
+	let mut set = BeatmapSet::load_dir(path)?;
+	let mut json = serde_json::Map::new();
+
+	if !mappings.is_empty() {
+		tracing::info!("Remapping sample indices...");
+		let map = |index: u32| {
+			(mappings.iter())
+				.find(|(from, _)| *from == index)
+				.map_or(index, |(_, to)| *to)
+		};
+
+		let mut remapped = serde_json::Map::new();
+		for difficulty in &mut set.difficulties {
+			let changed = remap_sample_indices(&mut difficulty.beatmap, map);
+			match format {
+				OutputFormat::Text => println!("{}: remapped {changed} sample indices", difficulty.filename),
+				OutputFormat::Json => {
+					remapped.insert(difficulty.filename.clone(), changed.into());
+				}
+			}
+		}
+		json.insert("remapped".to_owned(), remapped.into());
+	}
 
-					// reset hitsounds for all hitobjects in the group
-					first.hit_sound = HitSound::NONE;
-					for other in remains.iter_mut() {
-						other.hit_sound = HitSound::NONE;
+	if list {
+		let mut indices = serde_json::Map::new();
+		for difficulty in &set.difficulties {
+			let usages = used_sample_indices(&difficulty.beatmap);
+			match format {
+				OutputFormat::Text => {
+					println!("{}:", difficulty.filename);
+					for usage in usages {
+						println!(
+							"  index {}: {} timing points, {} hit objects",
+							usage.index, usage.timing_points, usage.hit_objects
+						);
 					}
+				}
+				OutputFormat::Json => {
+					let usages = (usages.iter())
+						.map(|usage| {
+							serde_json::json!({
+								"index": usage.index,
+								"timing_points": usage.timing_points,
+								"hit_objects": usage.hit_objects,
+							})
+						})
+						.collect();
+					indices.insert(difficulty.filename.clone(), serde_json::Value::Array(usages));
+				}
+			}
+		}
+		json.insert("indices".to_owned(), indices.into());
+	}
 
-					// cycle through remaining hitobjects to give them a separate hitsound each
-					let mut cycle_idx = 0;
+	if missing {
+		let samples = missing_samples(&set);
+		match format {
+			OutputFormat::Text => {
+				for sample in samples {
+					println!(
+						"{}: {} is missing (first played at {})",
+						sample.difficulty,
+						sample.stem,
+						format_timestamp(sample.first_time)
+					);
+				}
+			}
+			OutputFormat::Json => {
+				let samples = (samples.iter())
+					.map(|sample| {
+						serde_json::json!({
+							"difficulty": sample.difficulty,
+							"stem": sample.stem,
+							"first_time": sample.first_time,
+						})
+					})
+					.collect();
+				json.insert("missing".to_owned(), serde_json::Value::Array(samples));
+			}
+		}
+	}
 
-					if hit_sound.has_whistle() {
-						remains[cycle_idx].hit_sound |= HitSound::WHISTLE;
-						cycle_idx = (cycle_idx + 1) % remains.len();
-					}
+	if format == OutputFormat::Json {
+		println!("{}", serde_json::Value::Object(json));
+	}
 
-					if hit_sound.has_finish() {
-						remains[cycle_idx].hit_sound |= HitSound::FINISH;
-						cycle_idx = (cycle_idx + 1) % remains.len();
-					}
+	if mappings.is_empty() {
+		return Ok(());
+	}
 
-					if hit_sound.has_clap() {
-						remains[cycle_idx].hit_sound |= HitSound::CLAP;
-					}
+	write_beatmap_set(&set, settings)
+}
+
+fn rank_issues_json(issues: &[RankIssue]) -> serde_json::Value {
+	(issues.iter())
+		.map(|issue| {
+			serde_json::json!({
+				"check": issue.check.to_string(),
+				"severity": issue.severity.to_string(),
+				"message": issue.message,
+			})
+		})
+		.collect()
+}
+
+fn cli_rank_check(path: &Path, format: OutputFormat) -> Result<(), Box<dyn Error>> {
+	tracing::info!("Loading beatmap set {}...", path.display());
+	let set = BeatmapSet::load(path)?;
+	let report = rank_check(&set);
+
+	match format {
+		OutputFormat::Text => {
+			for issue in &report.set_issues {
+				println!("[{}] {}: {}", issue.severity, issue.check, issue.message);
+			}
+
+			for difficulty in &report.difficulties {
+				let status = if difficulty.passed() { "PASS" } else { "FAIL" };
+				println!("{status} {}", difficulty.filename);
+				for issue in &difficulty.issues {
+					println!("  [{}] {}: {}", issue.severity, issue.check, issue.message);
 				}
 			}
 		}
+		OutputFormat::Json => {
+			let difficulties: Vec<serde_json::Value> = (report.difficulties.iter())
+				.map(|difficulty| {
+					serde_json::json!({
+						"filename": difficulty.filename,
+						"passed": difficulty.passed(),
+						"issues": rank_issues_json(&difficulty.issues),
+					})
+				})
+				.collect();
+
+			let json = serde_json::json!({
+				"passed": report.passed(),
+				"set_issues": rank_issues_json(&report.set_issues),
+				"difficulties": difficulties,
+			});
+			println!("{json}");
+		}
 	}
 
-	beatmap.hit_objects = modified_hit_objects;
+	if !report.passed() {
+		return Err(CliError::new(
+			ErrorCategory::Validation,
+			"The beatmap set doesn't pass the ranking checks",
+		)
+		.into());
+	}
 
-	write_beatmap_out(&beatmap, beatmap_path)?;
 	Ok(())
 }
 
-fn cli_lazer_to_stable(path: &Path) -> Result<(), Box<dyn Error>> {
-	let mut beatmap = parse_beatmap(path, true)?;
+fn cli_build_index(out_path: &Path, path: &Path) -> Result<(), Box<dyn Error>> {
+	tracing::info!("Indexing beatmaps in {}...", path.display());
+	let (index, failures) = BeatmapIndex::build_with_progress(path, &ProgressBarSink::new())?;
 
-	for timing_point in &mut beatmap.timing_points {
-		timing_point.time = timing_point.time.floor();
+	for (failed_path, e) in &failures {
+		tracing::error!("Could not index {}: {e}", failed_path.display());
 	}
 
-	for hit_object in &mut beatmap.hit_objects {
-		hit_object.time = hit_object.time.floor();
+	tracing::info!(
+		"Writing index of {} beatmaps to {}...",
+		index.entries.len(),
+		out_path.display()
+	);
+	index.save(out_path)?;
+	Ok(())
+}
 
-		if let HitObjectParams::Slider {
-			first_curve_type,
-			curve_points,
-			..
-		} = &mut hit_object.object_params
-		{
-			curve_points.insert(
-				0,
-				SliderPoint {
-					curve_type: *first_curve_type,
-					x: hit_object.x,
-					y: hit_object.y,
-				},
-			);
+fn cli_search(index_path: &Path, query: &IndexQuery, format: OutputFormat) -> Result<(), Box<dyn Error>> {
+	let index = BeatmapIndex::load(index_path)?;
+
+	if format == OutputFormat::Json {
+		let entries: Vec<serde_json::Value> = (index.search(query))
+			.map(|entry| {
+				serde_json::json!({
+					"path": entry.path,
+					"title": entry.title,
+					"title_unicode": entry.title_unicode,
+					"artist": entry.artist,
+					"artist_unicode": entry.artist_unicode,
+					"creator": entry.creator,
+					"version": entry.version,
+					"tags": entry.tags,
+					"mode": entry.mode,
+					"beatmap_id": entry.beatmap_id,
+					"beatmap_set_id": entry.beatmap_set_id,
+					"hp_drain_rate": entry.hp_drain_rate,
+					"circle_size": entry.circle_size,
+					"overall_difficulty": entry.overall_difficulty,
+					"approach_rate": entry.approach_rate,
+				})
+			})
+			.collect();
+
+		println!("{}", serde_json::Value::Array(entries));
+		return Ok(());
+	}
 
-			*curve_points = match convert_slider_points_to_legacy(curve_points) {
-				Ok(curve_points) => curve_points,
-				Err(err) => {
-					tracing::error!("\n{err:?}");
-					return Ok(());
-				}
-			};
+	let mut found = 0;
+	for entry in index.search(query) {
+		println!(
+			"{} - {} ({}) [{}]\n  {}",
+			entry.artist,
+			entry.title,
+			entry.creator,
+			entry.version,
+			entry.path.display()
+		);
+		found += 1;
+	}
 
-			let first_curve_point = curve_points.remove(0);
-			*first_curve_type = first_curve_point.curve_type;
-		}
+	println!("{found} beatmaps found");
+	Ok(())
+}
+
+fn cli_lint(path: &Path, format: OutputFormat, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	let beatmap = parse_beatmap(path, false, settings)?;
+
+	let issues = lint(&beatmap);
+	if format == OutputFormat::Json {
+		let issues: Vec<serde_json::Value> = (issues.iter())
+			.map(|issue| {
+				serde_json::json!({
+					"time": issue.time,
+					"severity": issue.severity.to_string(),
+					"objects": issue.objects.iter().map(ToString::to_string).collect::<Vec<_>>(),
+					"timestamp": format_timestamp(issue.time),
+					"message": issue.message,
+				})
+			})
+			.collect();
+
+		println!("{}", serde_json::Value::Array(issues));
+		return Ok(());
+	}
+
+	if issues.is_empty() {
+		println!("No issues found");
 	}
 
-	beatmap.osu_file_format = 14;
+	print!("{}", format_report(&issues, ReportStyle::Detailed));
+	Ok(())
+}
+
+fn cli_mod_report(path: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	let beatmap = parse_beatmap(path, false, settings)?;
 
-	write_beatmap_out(&beatmap, path)?;
+	let issues = lint(&beatmap);
+	if let Some(metadata) = &beatmap.metadata {
+		println!("[{}]", metadata.version);
+	}
+
+	print!("{}", format_report(&issues, ReportStyle::Forum));
 	Ok(())
 }
+
+fn cli_completions(shell: Shell) {
+	let mut command = Cli::command();
+	let name = command.get_name().to_owned();
+	clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+}
@@ -0,0 +1,176 @@
+//! Pipelines of operations run on a beatmap in one go, see the `run` command.
+//!
+//! A pipeline is a TOML file listing the steps in order, each with the options of its command:
+//!
+//! ```toml
+//! [[steps]]
+//! op = "offset"
+//! millis = 25
+//!
+//! [[steps]]
+//! op = "reset-sample-sets"
+//! sample = "soft"
+//!
+//! [[steps]]
+//! op = "splat-hitsounds"
+//! sound-map = "soundmap.osu"
+//!
+//! [[steps]]
+//! op = "cleanup"
+//!
+//! [[steps]]
+//! op = "serialize-as-v14"
+//! ```
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use osus::algos::lazer::to_stable;
+use osus::algos::{offset_map_checked, reset_hitsounds_deep, OffsetOptions, ResetScope};
+use osus::file::beatmap::BeatmapFile;
+use osus::hitsounds::{copy_hitsounds, CopyHitsoundsOptions};
+use osus::ops::cleanup_timing_points;
+use osus::ErrorCategory;
+use serde::{de, Deserialize, Deserializer};
+
+use crate::{
+	parse_beatmap, print_conversion_report, print_copy_hitsounds_report, CliError, SampleBankOption, Settings,
+};
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pipeline {
+	pub steps: Vec<Step>,
+}
+
+/// An operation of a pipeline, named after the command doing the same thing.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(
+	tag = "op",
+	rename_all = "kebab-case",
+	rename_all_fields = "kebab-case",
+	deny_unknown_fields
+)]
+pub enum Step {
+	Offset {
+		millis: f64,
+		#[serde(default)]
+		adjust_lead_in: bool,
+	},
+	ResetSampleSets {
+		#[serde(default = "default_sample", deserialize_with = "deserialize_sample")]
+		sample: SampleBankOption,
+		/// Also reset the sample sets of hit objects.
+		#[serde(default)]
+		deep: bool,
+	},
+	SplatHitsounds {
+		/// Relative to the folder of the pipeline file.
+		sound_map: PathBuf,
+		#[serde(default)]
+		mania: bool,
+		#[serde(default)]
+		samples: bool,
+	},
+	/// Cleans up the timing points, like `cleanup-timing-points`.
+	Cleanup,
+	/// Makes the beatmap readable by osu!stable, like `lazer-to-stable`.
+	SerializeAsV14,
+}
+
+const fn default_sample() -> SampleBankOption {
+	SampleBankOption::Auto
+}
+
+fn deserialize_sample<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SampleBankOption, D::Error> {
+	String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+}
+
+impl Pipeline {
+	/// Reads a pipeline file, making the paths it contains relative to its folder.
+	pub fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+		let mut pipeline: Self = toml::from_str(&fs::read_to_string(path)?).map_err(|e| {
+			CliError::new(
+				ErrorCategory::Parse,
+				format!("Invalid pipeline {}: {e}", path.display()),
+			)
+		})?;
+
+		let folder = path.parent().unwrap_or(Path::new(""));
+		for step in &mut pipeline.steps {
+			if let Step::SplatHitsounds { sound_map, .. } = step {
+				*sound_map = folder.join(&*sound_map);
+			}
+		}
+
+		Ok(pipeline)
+	}
+
+	/// Runs the steps in order on a beatmap.
+	pub fn run(&self, beatmap: &mut BeatmapFile, settings: &Settings) -> Result<(), Box<dyn Error>> {
+		for step in &self.steps {
+			step.run(beatmap, settings)?;
+		}
+
+		Ok(())
+	}
+}
+
+impl Step {
+	fn run(&self, beatmap: &mut BeatmapFile, settings: &Settings) -> Result<(), Box<dyn Error>> {
+		match self {
+			Self::Offset { millis, adjust_lead_in } => {
+				tracing::info!("Offsetting beatmap...");
+				let options = OffsetOptions {
+					adjust_lead_in: *adjust_lead_in,
+				};
+				for warning in offset_map_checked(beatmap, *millis, options).warnings {
+					tracing::warn!("{warning}");
+				}
+			}
+
+			Self::ResetSampleSets { sample, deep } => {
+				let scope = if *deep {
+					ResetScope::ALL
+				} else {
+					ResetScope::TIMING_POINTS
+				};
+
+				tracing::info!("Resetting hitsounds...");
+				reset_hitsounds_deep(
+					beatmap,
+					sample.to_sample_bank(),
+					scope,
+					f64::NEG_INFINITY..f64::INFINITY,
+				);
+			}
+
+			Self::SplatHitsounds {
+				sound_map,
+				mania,
+				samples,
+			} => {
+				let soundmap = parse_beatmap(sound_map, false, settings)?;
+				let options = CopyHitsoundsOptions {
+					mania: *mania,
+					samples: *samples,
+					..CopyHitsoundsOptions::default()
+				};
+				print_copy_hitsounds_report(&copy_hitsounds(beatmap, &soundmap, &options));
+			}
+
+			Self::Cleanup => {
+				let report = cleanup_timing_points(beatmap);
+				println!("Removed {} useless speed changes", report.removed_speed_changes.len());
+			}
+
+			Self::SerializeAsV14 => {
+				tracing::info!("Converting beatmap to osu!stable...");
+				print_conversion_report(&to_stable(beatmap));
+			}
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,313 @@
+//! Interactive browser for the timing points and hit objects of a beatmap.
+
+use std::error::Error;
+use std::io;
+
+use osus::file::beatmap::{BeatmapFile, HitObject, HitObjectType, TimingPoint};
+use osus::timestamp::{format_timestamp, parse_timestamp};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListState, Paragraph, Tabs, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+/// Amount of rows skipped with page up and page down.
+const PAGE_SIZE: usize = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Tab {
+	TimingPoints,
+	HitObjects,
+}
+
+/// Which hit objects are listed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Filter {
+	All,
+	Only(HitObjectType),
+}
+
+impl Filter {
+	const fn next(self) -> Self {
+		match self {
+			Self::All => Self::Only(HitObjectType::HitCircle),
+			Self::Only(HitObjectType::HitCircle) => Self::Only(HitObjectType::Slider),
+			Self::Only(HitObjectType::Slider) => Self::Only(HitObjectType::Spinner),
+			Self::Only(HitObjectType::Spinner) => Self::Only(HitObjectType::Hold),
+			Self::Only(HitObjectType::Hold) => Self::All,
+		}
+	}
+
+	fn matches(self, hit_object: &HitObject) -> bool {
+		match self {
+			Self::All => true,
+			Self::Only(object_type) => hit_object.object_type == object_type,
+		}
+	}
+
+	fn name(self) -> String {
+		match self {
+			Self::All => "all".to_owned(),
+			Self::Only(object_type) => object_type.to_string(),
+		}
+	}
+}
+
+struct App {
+	beatmap: BeatmapFile,
+	tab: Tab,
+	filter: Filter,
+	/// Indices of the hit objects matching the filter.
+	visible_hit_objects: Vec<usize>,
+	timing_point_state: ListState,
+	hit_object_state: ListState,
+	/// Timestamp being typed after pressing `g`.
+	goto: Option<String>,
+	status: String,
+}
+
+impl App {
+	fn new(beatmap: BeatmapFile) -> Self {
+		let mut app = Self {
+			beatmap,
+			tab: Tab::HitObjects,
+			filter: Filter::All,
+			visible_hit_objects: Vec::new(),
+			timing_point_state: ListState::default().with_selected(Some(0)),
+			hit_object_state: ListState::default().with_selected(Some(0)),
+			goto: None,
+			status: String::new(),
+		};
+		app.apply_filter();
+		app
+	}
+
+	fn apply_filter(&mut self) {
+		self.visible_hit_objects = (0..self.beatmap.hit_objects.len())
+			.filter(|&i| self.filter.matches(&self.beatmap.hit_objects[i]))
+			.collect();
+		self.hit_object_state.select(Some(0));
+	}
+
+	fn len(&self) -> usize {
+		match self.tab {
+			Tab::TimingPoints => self.beatmap.timing_points.len(),
+			Tab::HitObjects => self.visible_hit_objects.len(),
+		}
+	}
+
+	fn state(&mut self) -> &mut ListState {
+		match self.tab {
+			Tab::TimingPoints => &mut self.timing_point_state,
+			Tab::HitObjects => &mut self.hit_object_state,
+		}
+	}
+
+	fn move_selection(&mut self, delta: isize) {
+		let last = self.len().saturating_sub(1);
+		let state = self.state();
+		let selected = state.selected().unwrap_or_default();
+		state.select(Some(selected.saturating_add_signed(delta).min(last)));
+	}
+
+	fn time_of(&self, row: usize) -> f64 {
+		match self.tab {
+			Tab::TimingPoints => self.beatmap.timing_points[row].time,
+			Tab::HitObjects => self.beatmap.hit_objects[self.visible_hit_objects[row]].time,
+		}
+	}
+
+	/// Selects the first row at or after the given time.
+	fn jump_to(&mut self, time: f64) {
+		let row = (0..self.len()).find(|&row| self.time_of(row) >= time);
+		let row = row.unwrap_or_else(|| self.len().saturating_sub(1));
+		self.state().select(Some(row));
+		self.status = format!("Jumped to {}", format_timestamp(time));
+	}
+
+	/// Handles a key press, returning false when the app should quit.
+	fn handle_key(&mut self, key: KeyCode) -> bool {
+		if let Some(goto) = &mut self.goto {
+			match key {
+				KeyCode::Char(c) => goto.push(c),
+				KeyCode::Backspace => {
+					goto.pop();
+				}
+				KeyCode::Enter => {
+					let input = self.goto.take().unwrap_or_default();
+					match parse_timestamp(&input) {
+						Ok(time) => self.jump_to(time),
+						Err(e) => self.status = e.to_string(),
+					}
+				}
+				KeyCode::Esc => self.goto = None,
+				_ => (),
+			}
+			return true;
+		}
+
+		match key {
+			KeyCode::Char('q') | KeyCode::Esc => return false,
+			KeyCode::Tab => {
+				self.tab = match self.tab {
+					Tab::TimingPoints => Tab::HitObjects,
+					Tab::HitObjects => Tab::TimingPoints,
+				};
+			}
+			KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+			KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+			KeyCode::PageDown => self.move_selection(PAGE_SIZE as isize),
+			KeyCode::PageUp => self.move_selection(-(PAGE_SIZE as isize)),
+			KeyCode::Home => self.state().select(Some(0)),
+			KeyCode::End => {
+				let last = self.len().saturating_sub(1);
+				self.state().select(Some(last));
+			}
+			KeyCode::Char('f') => {
+				self.filter = self.filter.next();
+				self.apply_filter();
+				self.status = format!("Showing {} hit objects", self.filter.name());
+			}
+			KeyCode::Char('g') => self.goto = Some(String::new()),
+			_ => (),
+		}
+
+		true
+	}
+
+	fn timing_point_details(timing_point: &TimingPoint) -> Vec<Line<'static>> {
+		let kind = if timing_point.uninherited {
+			format!("Uninherited, {:.2} BPM", timing_point.bpm().unwrap_or_default())
+		} else {
+			format!("Inherited, {:.2}x slider velocity", timing_point.sv_multiplier())
+		};
+
+		vec![
+			Line::from(format!("Time: {}", format_timestamp(timing_point.time))),
+			Line::from(kind),
+			Line::from(format!("Meter: {}", timing_point.meter)),
+			Line::from(format!(
+				"Samples: {:?} set, index {}, volume {}",
+				timing_point.sample_set, timing_point.sample_index, timing_point.volume
+			)),
+			Line::from(format!("Kiai: {}", timing_point.is_kiai())),
+			Line::from(""),
+			Line::from(timing_point.to_string()).italic(),
+		]
+	}
+
+	fn hit_object_details(hit_object: &HitObject) -> Vec<Line<'static>> {
+		let sample = &hit_object.hit_sample;
+		let mut lines = vec![
+			Line::from(format!("Time: {}", format_timestamp(hit_object.time))),
+			Line::from(format!("Type: {}", hit_object.object_type)),
+			Line::from(format!("Position: ({}, {})", hit_object.x, hit_object.y)),
+			Line::from(format!("New combo: {}", hit_object.is_new_combo())),
+			Line::from(format!("Hitsound: {}", hit_object.hit_sound.flags_string_verbose())),
+			Line::from(format!(
+				"Sample sets: normal {:?}, addition {:?}, index {}, volume {}",
+				sample.normal_set, sample.addition_set, sample.index, sample.volume
+			)),
+		];
+
+		if let Some(filename) = &sample.filename {
+			lines.push(Line::from(format!("Custom sample: {filename}")));
+		}
+
+		lines.push(Line::from(""));
+		lines.push(Line::from(hit_object.to_string()).italic());
+		lines
+	}
+
+	fn draw(&mut self, frame: &mut Frame) {
+		let [tabs_area, main_area, status_area] =
+			Layout::vertical([Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+		let [list_area, details_area] =
+			Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(main_area);
+
+		let selected_tab = match self.tab {
+			Tab::TimingPoints => 0,
+			Tab::HitObjects => 1,
+		};
+		let tabs = Tabs::new(["Timing points", "Hit objects"])
+			.select(selected_tab)
+			.highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+		frame.render_widget(tabs, tabs_area);
+
+		let highlight = Style::new().add_modifier(Modifier::REVERSED);
+		let (items, details, title): (Vec<String>, Vec<Line>, String) = match self.tab {
+			Tab::TimingPoints => {
+				let items = (self.beatmap.timing_points.iter())
+					.map(|tp| {
+						let kind = if tp.uninherited { "red" } else { "green" };
+						format!("{} {kind:<5} {tp}", format_timestamp(tp.time))
+					})
+					.collect();
+				let details = (self.timing_point_state.selected())
+					.and_then(|i| self.beatmap.timing_points.get(i))
+					.map(Self::timing_point_details)
+					.unwrap_or_default();
+				(items, details, "Timing points".to_owned())
+			}
+			Tab::HitObjects => {
+				let items = (self.visible_hit_objects.iter())
+					.map(|&i| {
+						let hit_object = &self.beatmap.hit_objects[i];
+						format!(
+							"{} {:<9} {}",
+							format_timestamp(hit_object.time),
+							hit_object.object_type.to_string(),
+							hit_object.hit_sound.fixed_flags_string()
+						)
+					})
+					.collect();
+				let details = (self.hit_object_state.selected())
+					.and_then(|row| self.visible_hit_objects.get(row))
+					.map(|&i| Self::hit_object_details(&self.beatmap.hit_objects[i]))
+					.unwrap_or_default();
+				(items, details, format!("Hit objects ({})", self.filter.name()))
+			}
+		};
+
+		let list = List::new(items)
+			.block(Block::bordered().title(title))
+			.highlight_style(highlight);
+		let state = self.state();
+		frame.render_stateful_widget(list, list_area, state);
+
+		let details = Paragraph::new(details)
+			.block(Block::bordered().title("Details"))
+			.wrap(Wrap { trim: false });
+		frame.render_widget(details, details_area);
+
+		let status = match &self.goto {
+			Some(goto) => format!("Go to: {goto}_"),
+			None if self.status.is_empty() => {
+				"q: quit  tab: switch list  ↑↓/jk: move  f: filter hit objects  g: go to timestamp".to_owned()
+			}
+			None => self.status.clone(),
+		};
+		frame.render_widget(Paragraph::new(status), status_area);
+	}
+
+	fn run(mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+		loop {
+			terminal.draw(|frame| self.draw(frame))?;
+
+			if let Event::Key(key) = event::read()? {
+				if key.kind == KeyEventKind::Press && !self.handle_key(key.code) {
+					return Ok(());
+				}
+			}
+		}
+	}
+}
+
+/// Opens the interactive browser on a beatmap, until the user quits.
+pub fn run(beatmap: BeatmapFile) -> Result<(), Box<dyn Error>> {
+	let mut terminal = ratatui::init();
+	let result = App::new(beatmap).run(&mut terminal);
+	ratatui::restore();
+	Ok(result?)
+}
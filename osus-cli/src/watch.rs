@@ -0,0 +1,111 @@
+//! Runs commands on a beatmap again each time the editor saves it, writing the result to another difficulty.
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use osus::hitsounds::{copy_hitsounds, CopyHitsoundsOptions};
+use osus::mapset::standard_filename;
+use osus::ops::cleanup_timing_points;
+use osus::ErrorCategory;
+
+use crate::{parse_beatmap, print_copy_hitsounds_report, write_beatmap_out, CliError, Settings};
+
+/// Time given to the editor to finish saving before reading the beatmap.
+const SAVE_DELAY: Duration = Duration::from_millis(200);
+
+/// What is done to the watched beatmap each time it is saved.
+pub struct Pipeline {
+	/// Soundmap whose hitsounds are splatted on the beatmap.
+	pub sound_map: Option<PathBuf>,
+	pub hitsounds: CopyHitsoundsOptions,
+	/// Whether to clean up the timing points after splatting the hitsounds.
+	pub cleanup: bool,
+	/// Added to the difficulty name of the beatmap to name the output difficulty, like `Hard (Hitsounded)`.
+	pub name: String,
+}
+
+impl Pipeline {
+	/// Runs the pipeline on a beatmap and writes the output difficulty next to it.
+	fn run(&self, path: &Path, settings: &Settings) -> Result<(), Box<dyn Error>> {
+		let mut beatmap = parse_beatmap(path, false, settings)?;
+
+		if let Some(sound_map) = &self.sound_map {
+			let soundmap = parse_beatmap(sound_map, false, settings)?;
+			let report = copy_hitsounds(&mut beatmap, &soundmap, &self.hitsounds);
+			print_copy_hitsounds_report(&report);
+		}
+
+		if self.cleanup {
+			let report = cleanup_timing_points(&mut beatmap);
+			println!("Removed {} useless speed changes", report.removed_speed_changes.len());
+		}
+
+		let metadata = beatmap.metadata.get_or_insert_with(Default::default);
+		metadata.version = format!("{} ({})", metadata.version, self.name);
+		metadata.beatmap_id = None;
+
+		let out_path = path.with_file_name(standard_filename(metadata));
+		if out_path == path {
+			return Err(Box::new(CliError::new(
+				ErrorCategory::Validation,
+				"The output difficulty would replace the watched one",
+			)));
+		}
+
+		write_beatmap_out(&beatmap, &out_path, settings)?;
+		Ok(())
+	}
+}
+
+/// Runs the pipeline on a beatmap, then again each time it or the soundmap changes, until interrupted.
+///
+/// Errors of the pipeline are logged without stopping, since the editor might be in the middle of saving.
+pub fn run(path: &Path, pipeline: &Pipeline, settings: &Settings) -> Result<(), Box<dyn Error>> {
+	let path = path.canonicalize()?;
+	let mut watched = vec![path.clone()];
+	if let Some(sound_map) = &pipeline.sound_map {
+		watched.push(sound_map.canonicalize()?);
+	}
+
+	let (sender, receiver) = mpsc::channel();
+	let mut watcher = notify::recommended_watcher(sender)?;
+
+	// editors can replace files instead of writing them in place, which only their folder sees
+	let mut folders: Vec<&Path> = watched.iter().filter_map(|path| path.parent()).collect();
+	folders.dedup();
+	for folder in folders {
+		watcher.watch(folder, RecursiveMode::NonRecursive)?;
+	}
+
+	run_logged(pipeline, &path, settings);
+	tracing::info!("Watching {} for changes...", path.display());
+
+	for event in &receiver {
+		if !changes_any(&event?, &watched) {
+			continue;
+		}
+
+		// one save can come with several events
+		thread::sleep(SAVE_DELAY);
+		while receiver.try_recv().is_ok() {}
+
+		run_logged(pipeline, &path, settings);
+	}
+
+	Ok(())
+}
+
+fn run_logged(pipeline: &Pipeline, path: &Path, settings: &Settings) {
+	if let Err(err) = pipeline.run(path, settings) {
+		tracing::error!("{err}");
+	}
+}
+
+fn changes_any(event: &Event, paths: &[PathBuf]) -> bool {
+	matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+		&& event.paths.iter().any(|path| paths.contains(path))
+}
@@ -1,26 +1,484 @@
 pub mod bezier;
+pub mod catch;
+pub mod difficulty;
+pub mod lazer;
+pub mod mania;
+pub mod simplify;
+pub mod slider_path;
 
+use std::fmt;
+use std::ops::Range;
+
+use crate::algos::slider_path::SliderPath;
+use crate::analysis::patterns::STACK_DISTANCE;
+use crate::analysis::{density, end_position, end_time, kiai_ranges, slider_duration, snap_velocity};
+use crate::edit::ChangeSet;
+use crate::file::beatmap::timing_points::{canonical_order, uninherited_at, TimingPoints};
 use crate::file::beatmap::{
-	BeatmapFile, HitObject, HitObjectParams, SampleBank, SliderCurveType, SliderPoint, Timestamp, TimingPoint,
+	BeatmapFile, DifficultySection, EventParams, HitObject, HitObjectParams, HitSample, HitSampleSet, SampleBank,
+	SliderCurveType, SliderPoint, Timestamp, TimingPoint,
 };
-use crate::{Timestamped, TimestampedSlice};
+use crate::lint::SNAP_DIVISORS;
+use crate::point::{Point, Rect};
+use crate::timeline::TimingTimeline;
+use crate::timestamp::format_timestamp;
+use crate::{is_close, Timestamped, TimestampedSlice, Tolerance};
 
 use self::bezier::{convert_to_bezier_anchors, BezierConversionError};
 
-/// Offsets all timing points and hitobjects' times.
-pub fn offset_map(beatmap: &mut BeatmapFile, offset_millis: f64) {
+/// Calls `f` on every time of the beatmap: timing points, hit objects (and their end times),
+/// events (and the end of breaks), preview time and bookmarks.
+///
+/// Anything that changes times in a beatmap should go through this function,
+/// so that it doesn't forget a field that holds a time.
+pub fn for_each_timestamp_mut(beatmap: &mut BeatmapFile, mut f: impl FnMut(&mut Timestamp)) {
+	if let Some(general) = &mut beatmap.general {
+		// a negative preview time means that there is no preview time
+		if general.preview_time >= 0.0 {
+			f(&mut general.preview_time);
+		}
+	}
+
+	if let Some(editor) = &mut beatmap.editor {
+		for bookmark in &mut editor.bookmarks {
+			let mut time = f64::from(*bookmark);
+			f(&mut time);
+
+			#[allow(clippy::cast_possible_truncation)]
+			let time = time as f32;
+			*bookmark = time;
+		}
+	}
+
+	for event in &mut beatmap.events {
+		match &mut event.params {
+			// the start time of a background is not used
+			EventParams::Background { .. } => {}
+			EventParams::Video { .. } | EventParams::Sample { .. } => f(&mut event.start_time),
+			EventParams::Break { end_time } => {
+				f(&mut event.start_time);
+				f(end_time);
+			}
+		}
+	}
+
 	for timing_point in &mut beatmap.timing_points {
-		timing_point.time += offset_millis;
+		f(&mut timing_point.time);
 	}
 
 	for hit_object in &mut beatmap.hit_objects {
-		hit_object.time += offset_millis;
+		f(&mut hit_object.time);
 		match &mut hit_object.object_params {
-			HitObjectParams::Spinner { end_time } | HitObjectParams::Hold { end_time } => {
-				*end_time += offset_millis;
+			HitObjectParams::Spinner { end_time } | HitObjectParams::Hold { end_time, .. } => f(end_time),
+			HitObjectParams::HitCircle | HitObjectParams::Slider { .. } => {}
+		}
+	}
+}
+
+/// Offsets every time of the beatmap (see [`for_each_timestamp_mut`]), returning what changed.
+pub fn offset_map(beatmap: &mut BeatmapFile, offset_millis: f64) -> ChangeSet {
+	let ((), changes) = ChangeSet::track_moves(beatmap, |beatmap| {
+		for_each_timestamp_mut(beatmap, |time| *time += offset_millis);
+	});
+	changes
+}
+
+/// Beats the countdown takes before the first hit object ("ready?", 3, 2, 1, go), without its offset.
+const COUNTDOWN_BEATS: f64 = 5.0;
+
+/// Options for [`offset_map_checked`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OffsetOptions {
+	/// Increase the audio lead-in when it is too short for the first hit object or the countdown.
+	pub adjust_lead_in: bool,
+}
+
+/// Something that makes a beatmap hard or impossible to play after [`offset_map_checked`].
+#[derive(Clone, Debug)]
+pub enum OffsetWarning {
+	/// The first hit object is before the start of the audio.
+	ObjectBeforeAudio { time: Timestamp },
+	/// The audio lead-in is too short to show the first hit object coming or to play the whole countdown.
+	LeadInTooShort { lead_in: i32, required: i32 },
+	/// The audio lead-in was increased to fit the first hit object and the countdown.
+	LeadInIncreased { from: i32, to: i32 },
+	/// The preview time went before the start of the audio, so the beatmap has no preview time anymore.
+	PreviewTimeRemoved { preview_time: Timestamp },
+}
+
+impl fmt::Display for OffsetWarning {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::ObjectBeforeAudio { time } => {
+				write!(
+					f,
+					"first hit object at {} is before the audio starts",
+					format_timestamp(*time)
+				)
+			}
+			Self::LeadInTooShort { lead_in, required } => {
+				write!(f, "audio lead-in of {lead_in}ms is too short, {required}ms are needed")
+			}
+			Self::LeadInIncreased { from, to } => write!(f, "increased audio lead-in from {from}ms to {to}ms"),
+			Self::PreviewTimeRemoved { preview_time } => write!(
+				f,
+				"preview time {} is before the audio starts and was removed",
+				format_timestamp(*preview_time)
+			),
+		}
+	}
+}
+
+/// Milliseconds of audio lead-in a beatmap needs to show its first hit object coming and to play its whole countdown.
+fn required_lead_in(beatmap: &BeatmapFile) -> Option<f64> {
+	let first_time = beatmap.first_object_time()?;
+	let preempt = beatmap.difficulty.as_ref().map_or(0.0, DifficultySection::preempt);
+	let mut required = preempt - first_time;
+
+	if let Some(general) = &beatmap.general {
+		let speed = match general.countdown {
+			1 => Some(1.0),
+			2 => Some(2.0),
+			3 => Some(0.5),
+			_ => None,
+		};
+		let beat_length = uninherited_at(&beatmap.timing_points, first_time).map(|tp| tp.beat_length);
+		if let (Some(speed), Some(beat_length)) = (speed, beat_length) {
+			let beats = COUNTDOWN_BEATS + f64::from(general.countdown_offset);
+			required = required.max((beats * beat_length).mul_add(speed, -first_time));
+		}
+	}
+
+	Some(required.max(0.0).ceil())
+}
+
+/// Offsets every time of the beatmap like [`offset_map`], checking that it can still be played afterwards.
+///
+/// Offsetting backwards can push the first hit object before the start of the audio, or too close to it
+/// to show it coming or to play the countdown. These problems are returned as warnings, and the audio lead-in
+/// is increased to fix them if `options.adjust_lead_in` is set.
+pub fn offset_map_checked(beatmap: &mut BeatmapFile, offset_millis: f64, options: OffsetOptions) -> OffsetReport {
+	let (warnings, changes) =
+		ChangeSet::track_moves(beatmap, |beatmap| offset_and_check(beatmap, offset_millis, options));
+	OffsetReport { warnings, changes }
+}
+
+/// Body of [`offset_map_checked`], returning its warnings.
+fn offset_and_check(beatmap: &mut BeatmapFile, offset_millis: f64, options: OffsetOptions) -> Vec<OffsetWarning> {
+	let preview_time = beatmap.general.as_ref().map_or(-1.0, |general| general.preview_time);
+	for_each_timestamp_mut(beatmap, |time| *time += offset_millis);
+
+	let mut warnings = Vec::new();
+	if preview_time >= 0.0 && preview_time + offset_millis < 0.0 {
+		if let Some(general) = &mut beatmap.general {
+			general.preview_time = -1.0;
+		}
+		warnings.push(OffsetWarning::PreviewTimeRemoved {
+			preview_time: preview_time + offset_millis,
+		});
+	}
+
+	if let Some(time) = beatmap.first_object_time().filter(|&time| time < 0.0) {
+		warnings.push(OffsetWarning::ObjectBeforeAudio { time });
+	}
+
+	let required = required_lead_in(beatmap);
+	if let (Some(required), Some(general)) = (required, &mut beatmap.general) {
+		#[allow(clippy::cast_possible_truncation)]
+		let required = required as i32;
+		if general.audio_lead_in < required {
+			if options.adjust_lead_in {
+				warnings.push(OffsetWarning::LeadInIncreased {
+					from: general.audio_lead_in,
+					to: required,
+				});
+				general.audio_lead_in = required;
+			} else {
+				warnings.push(OffsetWarning::LeadInTooShort {
+					lead_in: general.audio_lead_in,
+					required,
+				});
+			}
+		}
+	}
+
+	warnings
+}
+
+/// What [`offset_map_checked`] did to a beatmap.
+#[derive(Clone, Debug, Default)]
+pub struct OffsetReport {
+	pub warnings: Vec<OffsetWarning>,
+	pub changes: ChangeSet,
+}
+
+/// Beat grid of a beatmap, to convert between times and positions in beats.
+///
+/// Positions are counted from the first uninherited timing point, and times outside of the timing points
+/// are extrapolated with the closest one.
+struct BeatGrid {
+	/// Time, beat length and position in beats of each uninherited timing point.
+	red_lines: Vec<(Timestamp, f64, f64)>,
+}
+
+impl BeatGrid {
+	/// Builds the beat grid of the timing points, or `None` if there are no valid uninherited timing points.
+	fn new(timing_points: &[TimingPoint]) -> Option<Self> {
+		let mut red_lines: Vec<(Timestamp, f64, f64)> = Vec::new();
+		let valid =
+			(timing_points.iter()).filter(|tp| tp.uninherited && tp.beat_length > 0.0 && tp.beat_length.is_finite());
+		for timing_point in valid {
+			let beat = red_lines.last().map_or(0.0, |&(time, beat_length, beat)| {
+				beat + (timing_point.time - time) / beat_length
+			});
+			red_lines.push((timing_point.time, timing_point.beat_length, beat));
+		}
+
+		(!red_lines.is_empty()).then_some(Self { red_lines })
+	}
+
+	fn beat_at(&self, time: Timestamp) -> f64 {
+		let (red_time, beat_length, beat) = (self.red_lines.iter())
+			.take_while(|&&(red_time, ..)| red_time <= time)
+			.last()
+			.unwrap_or(&self.red_lines[0]);
+		beat + (time - red_time) / beat_length
+	}
+
+	fn time_at(&self, beat: f64) -> Timestamp {
+		let (red_time, beat_length, red_beat) = (self.red_lines.iter())
+			.take_while(|&&(.., red_beat)| red_beat <= beat)
+			.last()
+			.unwrap_or(&self.red_lines[0]);
+		(beat - red_beat).mul_add(*beat_length, *red_time)
+	}
+}
+
+/// Moves the content of the beatmap by an amount of beats along its beat grid.
+///
+/// Unlike [`offset_map`], which moves the whole beatmap against its audio, uninherited timing points stay in place
+/// and everything else (hit objects, inherited timing points, events, preview time and bookmarks) moves by
+/// `beats` beats of the timing it goes through. Objects snapped to the grid stay snapped across BPM changes,
+/// which a shift in milliseconds can't do, for instance when removing the first measure of a map.
+pub fn offset_by_beats(beatmap: &mut BeatmapFile, beats: f64) {
+	let Some(grid) = BeatGrid::new(&beatmap.timing_points) else {
+		tracing::warn!("The beatmap has no valid uninherited timing points, it can't be offset by beats");
+		return;
+	};
+
+	let red_line_times: Vec<(usize, Timestamp)> = (beatmap.timing_points.iter().enumerate())
+		.filter(|(_, tp)| tp.uninherited)
+		.map(|(i, tp)| (i, tp.time))
+		.collect();
+
+	for_each_timestamp_mut(beatmap, |time| *time = grid.time_at(grid.beat_at(*time) + beats));
+
+	for (i, time) in red_line_times {
+		beatmap.timing_points[i].time = time;
+	}
+
+	// inherited timing points may have moved past uninherited ones
+	(beatmap.timing_points).sort_by(canonical_order);
+}
+
+/// Approach rate that gives a preempt time (see [`DifficultySection::preempt`](crate::file::beatmap::DifficultySection::preempt)).
+pub(crate) fn approach_rate_for_preempt(preempt: f64) -> f64 {
+	if preempt > 1200.0 {
+		5.0 - (preempt - 1200.0) / 120.0
+	} else {
+		5.0 + (1200.0 - preempt) / 150.0
+	}
+}
+
+/// Overall difficulty that gives a 300 hit window in a given mode (see [`DifficultySection::hit_windows`](crate::file::beatmap::DifficultySection::hit_windows)).
+fn overall_difficulty_for_great_window(mode: u8, w300: f64) -> Option<f64> {
+	match mode {
+		0 => Some((80.0 - w300) / 6.0),
+		1 => Some((50.0 - w300) / 3.0),
+		3 => Some((64.0 - w300) / 3.0),
+		_ => None,
+	}
+}
+
+/// Clamps a difficulty setting between 0 and 10, rounded to one decimal like in the osu! editor.
+pub(crate) fn round_setting(value: f64) -> f64 {
+	(value.clamp(0.0, 10.0) * 10.0).round() / 10.0
+}
+
+/// Changes the playback rate of a beatmap, like the DT and HT mods do.
+///
+/// Every time is divided by the rate and every BPM is multiplied by it,
+/// so that the beatmap plays at 1.0x the way the original plays with the rate applied.
+/// The audio file has to be time-stretched separately.
+///
+/// If `adjust_difficulty` is true, AR and OD are also changed to keep the same approach time and hit windows
+/// as the original with the rate applied (within the usual 0 to 10 range). AR is left alone in osu!taiko
+/// and osu!mania, which don't use it.
+pub fn change_rate(beatmap: &mut BeatmapFile, rate: f64, adjust_difficulty: bool) {
+	if rate <= 0.0 {
+		return;
+	}
+
+	// timing points can be placed on fractions of milliseconds, which keeps the rhythm accurate,
+	// so they're set aside while everything else is rounded to the whole milliseconds osu! expects
+	let mut timing_points = std::mem::take(&mut beatmap.timing_points);
+	for_each_timestamp_mut(beatmap, |time| *time = (*time / rate).round());
+
+	for timing_point in &mut timing_points {
+		timing_point.time /= rate;
+
+		// inherited timing points hold slider velocity multipliers, which don't depend on the rate
+		if timing_point.uninherited {
+			timing_point.beat_length /= rate;
+		}
+	}
+	beatmap.timing_points = timing_points;
+
+	if let Some(general) = &mut beatmap.general {
+		#[allow(clippy::cast_possible_truncation)]
+		let audio_lead_in = (f64::from(general.audio_lead_in) / rate).round() as i32;
+		general.audio_lead_in = audio_lead_in;
+	}
+
+	if !adjust_difficulty {
+		return;
+	}
+
+	let mode = beatmap.general.as_ref().map_or(0, |general| general.mode);
+	if let Some(difficulty) = &mut beatmap.difficulty {
+		// AR means nothing in osu!taiko and osu!mania
+		if matches!(mode, 0 | 2) {
+			#[allow(clippy::cast_possible_truncation)]
+			let approach_rate = round_setting(approach_rate_for_preempt(difficulty.preempt() / rate)) as f32;
+			difficulty.approach_rate = approach_rate;
+		}
+
+		let overall_difficulty = (difficulty.hit_windows(mode))
+			.and_then(|windows| overall_difficulty_for_great_window(mode, windows.w300 / rate))
+			.map(round_setting);
+
+		if let Some(overall_difficulty) = overall_difficulty {
+			#[allow(clippy::cast_possible_truncation)]
+			let overall_difficulty = overall_difficulty as f32;
+			difficulty.overall_difficulty = overall_difficulty;
+		}
+	}
+}
+
+/// Duration in milliseconds of the section used to judge a preview time candidate.
+const PREVIEW_WINDOW: f64 = 10_000.0;
+
+/// Snaps a time to the start of the measure it is in.
+fn snap_to_measure(timing_points: &[TimingPoint], time: Timestamp) -> Timestamp {
+	let Some(red_line) = uninherited_at(timing_points, time) else {
+		return time;
+	};
+
+	let measure_length = red_line.beat_length * f64::from(red_line.meter.unsigned_abs().max(1));
+	if measure_length <= 0.0 {
+		return time;
+	}
+
+	let measures = ((time - red_line.time) / measure_length).floor();
+	measure_length.mul_add(measures, red_line.time)
+}
+
+/// Suggests a time for the audio preview, like a chorus.
+///
+/// It picks the start of the densest kiai section, or if there are none,
+/// the start of the densest part of the map snapped to a measure.
+#[must_use]
+pub fn suggest_preview_time(beatmap: &BeatmapFile) -> Timestamp {
+	let densest = |candidates: &mut dyn Iterator<Item = Timestamp>| {
+		candidates
+			.map(|t| (t, density(&beatmap.hit_objects, t..(t + PREVIEW_WINDOW))))
+			.max_by(|(_, a), (_, b)| a.total_cmp(b))
+			.map(|(t, _)| t)
+	};
+
+	if let Some(time) = densest(&mut kiai_ranges(beatmap).into_iter().map(|range| range.start)) {
+		return time;
+	}
+
+	densest(&mut beatmap.hit_objects.iter().map(|ho| ho.time))
+		.map_or(0.0, |time| snap_to_measure(&beatmap.timing_points, time))
+}
+
+/// Rate changes that also raise or lower the pitch of the song, usually published as their own beatmap sets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RatePreset {
+	/// 1.5x, like the NC mod.
+	Nightcore,
+	/// 0.75x, like the HT mod with the pitch lowered.
+	Daycore,
+}
+
+impl RatePreset {
+	#[must_use]
+	pub const fn rate(self) -> f64 {
+		match self {
+			Self::Nightcore => 1.5,
+			Self::Daycore => 0.75,
+		}
+	}
+
+	/// Name of the preset, added to the title of the song.
+	#[must_use]
+	pub const fn name(self) -> &'static str {
+		match self {
+			Self::Nightcore => "Nightcore",
+			Self::Daycore => "Daycore",
+		}
+	}
+}
+
+/// Scales a tag giving the BPM of the song, like `180bpm`.
+fn scale_bpm_tag(tag: &str, rate: f64) -> Option<String> {
+	let bpm_len = tag.len().checked_sub(3)?;
+	if !tag.is_char_boundary(bpm_len) || !tag[bpm_len..].eq_ignore_ascii_case("bpm") {
+		return None;
+	}
+
+	let bpm: f64 = tag[..bpm_len].parse().ok()?;
+	Some(format!("{}bpm", (bpm * rate).round()))
+}
+
+/// Turns a beatmap into its nightcore or daycore version.
+///
+/// The rate is changed with [`change_rate`] (AR and OD included), the preset's name is added to the title,
+/// BPM tags like `180bpm` are scaled and the preset's name is added as a tag.
+/// The beatmap gets a preview time if it had none, and loses its online IDs since it belongs to a new beatmap set.
+/// The audio file has to be resampled separately.
+pub fn apply_rate_preset(beatmap: &mut BeatmapFile, preset: RatePreset) {
+	let rate = preset.rate();
+	change_rate(beatmap, rate, true);
+
+	let preview_time = suggest_preview_time(beatmap);
+	let general = beatmap.general.get_or_insert_with(Default::default);
+	if general.preview_time < 0.0 {
+		general.preview_time = preview_time;
+	}
+
+	if let Some(metadata) = &mut beatmap.metadata {
+		let suffix = format!(" ({})", preset.name());
+		metadata.title.push_str(&suffix);
+		if !metadata.title_unicode.is_empty() {
+			metadata.title_unicode.push_str(&suffix);
+		}
+
+		for tag in &mut metadata.tags {
+			if let Some(scaled) = scale_bpm_tag(tag, rate) {
+				*tag = scaled;
 			}
-			_ => (),
 		}
+
+		let tag = preset.name().to_lowercase();
+		if !metadata.tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+			metadata.tags.push(tag);
+		}
+
+		metadata.beatmap_id = None;
+		metadata.beatmap_set_id = None;
 	}
 }
 
@@ -31,6 +489,121 @@ pub fn mix_volume(timing_points: &mut [TimingPoint], val: i8) {
 	}
 }
 
+/// Inserts an inherited timing point at `time` that continues the timing point in effect,
+/// so that changes made to the timing points after `time` don't affect what happens before it.
+///
+/// Nothing is inserted if there already is a timing point at that time, or if there is no timing point before it.
+/// Timing points need to be sorted.
+pub fn split_timing_points_at(timing_points: &mut Vec<TimingPoint>, time: Timestamp) {
+	let index = timing_points.partition_point(|tp| tp.time < time);
+	let neighbors = &timing_points[index.saturating_sub(1)..(index + 1).min(timing_points.len())];
+	if index == 0 || neighbors.iter().any(|tp| tp.basically_at(time)) {
+		return;
+	}
+
+	let prevailing = &timing_points[index - 1];
+	let beat_length = if prevailing.uninherited {
+		-100.0
+	} else {
+		prevailing.beat_length
+	};
+	let timing_point = TimingPoint {
+		time,
+		beat_length,
+		uninherited: false,
+		..prevailing.clone()
+	};
+	timing_points.insert(index, timing_point);
+}
+
+/// Splits the timing points at both ends of a time range (see [`split_timing_points_at`]),
+/// and returns the ones in that range, which can then be changed without affecting the rest of the map.
+pub fn isolate_timing_points(timing_points: &mut Vec<TimingPoint>, range: Range<Timestamp>) -> &mut [TimingPoint] {
+	for time in [range.start, range.end] {
+		if time.is_finite() {
+			split_timing_points_at(timing_points, time);
+		}
+	}
+
+	timing_points.between_mut(range)
+}
+
+/// How the volume evolves during a [`volume_ramp`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VolumeCurve {
+	/// The volume changes by the same amount at each step.
+	#[default]
+	Linear,
+	/// The volume changes by the same ratio at each step, which sounds more natural for fade-outs.
+	Exponential,
+}
+
+impl VolumeCurve {
+	/// Volume at `progress` (from 0 to 1) of a ramp going from `from` to `to`.
+	#[must_use]
+	pub fn volume_at(self, from: u8, to: u8, progress: f64) -> u8 {
+		let (from, to) = (f64::from(from), f64::from(to));
+		let progress = progress.clamp(0.0, 1.0);
+
+		let volume = match self {
+			Self::Linear => (to - from).mul_add(progress, from),
+			Self::Exponential => {
+				// a volume of 0 would never change when multiplied
+				let (from, to) = (from.max(1.0), to.max(1.0));
+				from * (to / from).powf(progress)
+			}
+		};
+
+		#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+		let volume = volume.round().clamp(0.0, 100.0) as u8;
+		volume
+	}
+}
+
+/// Fades the volume from `from_volume` to `to_volume` over a time range.
+///
+/// Inherited timing points are inserted every `step_beats` beats (keeping the slider velocity in effect),
+/// and the volume of every timing point already in the range is changed to follow the fade.
+///
+/// Timing points need to be sorted.
+#[allow(clippy::while_float)]
+pub fn volume_ramp(
+	timing_points: &mut Vec<TimingPoint>,
+	range: Range<Timestamp>,
+	from_volume: u8,
+	to_volume: u8,
+	step_beats: f64,
+	curve: VolumeCurve,
+) {
+	let duration = range.end - range.start;
+	if duration <= 0.0 || step_beats <= 0.0 {
+		return;
+	}
+
+	let volume_at = |time: Timestamp| curve.volume_at(from_volume, to_volume, (time - range.start) / duration);
+
+	let mut time = range.start;
+	while time <= range.end {
+		split_timing_points_at(timing_points, time);
+
+		let Some(beat_length) = uninherited_at(timing_points, time).map(|tp| tp.beat_length) else {
+			break;
+		};
+
+		if beat_length <= 0.0 {
+			break;
+		}
+
+		time += beat_length * step_beats;
+	}
+
+	for timing_point in timing_points.iter_mut() {
+		if range.contains(&timing_point.time) || timing_point.basically_at(range.end) {
+			timing_point.volume = volume_at(timing_point.time);
+		}
+	}
+}
+
 /// Resets all hitsounds in timing points, including volume.
 pub fn reset_hitsounds(timing_points: &mut [TimingPoint], sample_set: SampleBank) {
 	for timing_point in timing_points {
@@ -40,6 +613,75 @@ pub fn reset_hitsounds(timing_points: &mut [TimingPoint], sample_set: SampleBank
 	}
 }
 
+/// Parts of a beatmap reset by [`reset_hitsounds_deep`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResetScope {
+	/// Sample set, index and volume of timing points.
+	pub timing_points: bool,
+	/// Hit samples of hit objects.
+	pub objects: bool,
+	/// Sample sets of slider edges.
+	pub slider_edges: bool,
+}
+
+impl ResetScope {
+	pub const TIMING_POINTS: Self = Self {
+		timing_points: true,
+		objects: false,
+		slider_edges: false,
+	};
+
+	pub const ALL: Self = Self {
+		timing_points: true,
+		objects: true,
+		slider_edges: true,
+	};
+}
+
+/// Resets sample sets in the given time range, going further than [`reset_hitsounds`] depending on `scope`.
+///
+/// Hit objects and slider edges get `sample_set` as their normal set and an auto addition set,
+/// so that additions follow the normal sound.
+/// Their custom sample index, volume and filename are reset as well, but not which sounds they play.
+pub fn reset_hitsounds_deep(
+	beatmap: &mut BeatmapFile,
+	sample_set: SampleBank,
+	scope: ResetScope,
+	range: Range<Timestamp>,
+) {
+	if scope.timing_points {
+		beatmap.timing_points.sort_by(|a, b| a.time.total_cmp(&b.time));
+		reset_hitsounds(
+			isolate_timing_points(&mut beatmap.timing_points, range.clone()),
+			sample_set,
+		);
+	}
+
+	let edge_set = HitSampleSet {
+		normal_set: sample_set,
+		addition_set: SampleBank::Auto,
+	};
+
+	for hit_object in &mut beatmap.hit_objects {
+		if !range.contains(&hit_object.time) {
+			continue;
+		}
+
+		if scope.objects {
+			hit_object.hit_sample = HitSample {
+				normal_set: sample_set,
+				..HitSample::default()
+			};
+		}
+
+		if scope.slider_edges {
+			if let HitObjectParams::Slider { edge_samplesets, .. } = &mut hit_object.object_params {
+				edge_samplesets.fill(edge_set);
+			}
+		}
+	}
+}
+
 /// Removes all duplicate timing points. It will keep every uninherited one.
 ///
 /// A timing point is a duplicate if all its fields except `time` and `uninherited` are the same as the direct previous timing point.
@@ -62,102 +704,633 @@ pub fn remove_duplicates(timing_points: &[TimingPoint]) -> Vec<TimingPoint> {
 	unduped_points
 }
 
-/// Removes all timing points that introduce useless speed changes.
+/// Whether a timing point only changes the sample settings of the one before it,
+/// keeping its slider velocity, meter and effects.
+fn is_hitsound_only(timing_point: &TimingPoint, prev: &TimingPoint) -> bool {
+	!timing_point.uninherited
+		&& is_close(
+			timing_point.sv_multiplier(),
+			prev.sv_multiplier(),
+			TimingPoint::SV_TOLERANCE,
+		) && timing_point.meter == prev.meter
+		&& timing_point.effects == prev.effects
+}
+
+/// Merges timing points that are less than `tolerance` milliseconds apart when one of them only changes hitsounds,
+/// like the ones left when inserting hitsound timing points next to existing ones.
 ///
-/// Currently osu!lazer does this weird thing where it generates a timing point, just changing the speed to x1.00, only to then use the same speed as the previous slider for the next one...
+/// Slider velocity changes stay at their time: a hitsound-only point gives its sample settings to the point
+/// just before it, and a hitsound-only point right before another one is replaced by it.
+/// Timing points need to be sorted.
+#[must_use]
+pub fn merge_adjacent_timing_points(timing_points: &[TimingPoint], tolerance: f64) -> Vec<TimingPoint> {
+	let mut merged_points: Vec<TimingPoint> = Vec::with_capacity(timing_points.len());
+
+	for timing_point in timing_points {
+		let len = merged_points.len();
+		let Some(prev) = merged_points.last_mut() else {
+			merged_points.push(timing_point.clone());
+			continue;
+		};
+
+		if timing_point.time - prev.time >= tolerance {
+			merged_points.push(timing_point.clone());
+		} else if is_hitsound_only(timing_point, prev) {
+			prev.sample_set = timing_point.sample_set;
+			prev.sample_index = timing_point.sample_index;
+			prev.volume = timing_point.volume;
+		} else if len >= 2 && is_hitsound_only(&merged_points[len - 1], &merged_points[len - 2]) {
+			merged_points[len - 1] = timing_point.clone();
+		} else {
+			merged_points.push(timing_point.clone());
+		}
+	}
+
+	merged_points
+}
+
+/// Why [`remove_useless_speed_changes`] removed a timing point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UselessSpeedChange {
+	/// Another inherited timing point at the same time overrides it.
+	Overridden,
+	/// It sets the slider velocity that is already in effect.
+	SameVelocity,
+	/// No slider starts before the next timing point, in osu! or osu!catch where nothing else uses slider velocity.
+	NoSliders,
+}
+
+/// A timing point removed by [`remove_useless_speed_changes`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct RemovedSpeedChange {
+	pub time: Timestamp,
+	pub sv_multiplier: f64,
+	pub reason: UselessSpeedChange,
+}
+
+/// Result of [`remove_useless_speed_changes`].
+#[derive(Clone, Debug, Default)]
+pub struct SpeedChangeReport {
+	/// Timing points that were kept, in [canonical order](canonical_order).
+	pub timing_points: Vec<TimingPoint>,
+	pub removed: Vec<RemovedSpeedChange>,
+}
+
+/// Removes the inherited timing points that only change the slider velocity, when that change is useless.
 ///
-/// This is completely useless, so here's a function to remove them.
+/// osu!lazer likes to put a timing point on every slider, even when it only brings back the slider velocity
+/// that was already there. Only inherited timing points that keep the sample settings and effects in effect
+/// are looked at, and they are removed if:
+/// - another inherited timing point at the same time overrides them,
+/// - their slider velocity is the one already in effect (after an uninherited timing point, that's 1x),
+/// - or in osu! and osu!catch, no slider starts until the next timing point. osu!taiko and osu!mania
+///   scroll at the slider velocity, so their speed changes are always kept.
+///
+/// Hit objects need to be sorted.
 #[must_use]
 pub fn remove_useless_speed_changes(
 	mode: u8,
 	timing_points: &[TimingPoint],
 	hit_objects: &[HitObject],
-) -> Vec<TimingPoint> {
-	if timing_points.is_empty() || hit_objects.is_empty() {
-		return Vec::new();
-	}
+) -> SpeedChangeReport {
+	let mut sorted: Vec<&TimingPoint> = timing_points.iter().collect();
+	sorted.sort_by(|a, b| canonical_order(a, b));
 
-	let mut result_points = vec![timing_points[0].clone()];
-	let mut prev_timing_point = &timing_points[0];
-	let mut prev_timing_point_was_added = true;
+	let sv_only_matters_for_sliders = mode == 0 || mode == 2;
+	let mut report = SpeedChangeReport::default();
 
-	for timing_point in &timing_points[1..] {
-		if timing_point.uninherited
-			|| timing_point.meter != prev_timing_point.meter
-			|| timing_point.effects != prev_timing_point.effects
-			|| timing_point.sample_index != prev_timing_point.sample_index
-			|| timing_point.sample_set != prev_timing_point.sample_set
-			|| timing_point.volume != prev_timing_point.volume
+	for (i, &timing_point) in sorted.iter().enumerate() {
+		let next_time = sorted.get(i + 1).map_or(f64::INFINITY, |next| next.time);
+		let in_effect = report.timing_points.last();
+
+		let only_changes_speed = !timing_point.uninherited
+			&& in_effect.is_some_and(|prev| {
+				timing_point.sample_set == prev.sample_set
+					&& timing_point.sample_index == prev.sample_index
+					&& timing_point.volume == prev.volume
+					&& timing_point.effects == prev.effects
+			});
+
+		let reason = if timing_point.uninherited {
+			None
+		} else if next_time.total_cmp(&timing_point.time).is_eq() {
+			// uninherited timing points come first, so the next one is inherited and overrides everything
+			Some(UselessSpeedChange::Overridden)
+		} else if !only_changes_speed {
+			None
+		} else if in_effect.is_some_and(|prev| {
+			is_close(
+				prev.sv_multiplier(),
+				timing_point.sv_multiplier(),
+				TimingPoint::SV_TOLERANCE,
+			)
+		}) {
+			Some(UselessSpeedChange::SameVelocity)
+		} else if sv_only_matters_for_sliders
+			&& !(hit_objects.between(timing_point.time..next_time).iter()).any(HitObject::is_slider)
 		{
-			// Something non-useless changed
-			if !prev_timing_point_was_added {
-				result_points.push(prev_timing_point.clone());
-			}
+			Some(UselessSpeedChange::NoSliders)
+		} else {
+			None
+		};
 
-			result_points.push(timing_point.clone());
-			prev_timing_point = timing_point;
-			prev_timing_point_was_added = true;
-		} else if !prev_timing_point_was_added {
-			if mode == 0 {
-				// verify if prev timing point falls on a hitobject
-				let ho_slice = hit_objects.between(prev_timing_point.time..timing_point.time);
-
-				if ho_slice.iter().all(|ho| ho.is_hit_circle() || ho.is_spinner()) {
-					// prev_timing_point is useless
-				} else {
-					// prev_timing_point is useful
-					result_points.push(prev_timing_point.clone());
-				}
-			} else {
-				// Speed changes only depend on hitobjects in std mode, so they're otherwise always relevant.
-				result_points.push(prev_timing_point.clone());
+		match reason {
+			Some(reason) => report.removed.push(RemovedSpeedChange {
+				time: timing_point.time,
+				sv_multiplier: timing_point.sv_multiplier(),
+				reason,
+			}),
+			None => report.timing_points.push(timing_point.clone()),
+		}
+	}
+
+	report
+}
+
+/// A fix made by [`repair_timing`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum TimingRepair {
+	/// The timing points were not in chronological order.
+	Sorted,
+	/// An inherited timing point came before the first uninherited one, where osu! ignores it.
+	RemovedLeadingInherited { time: Timestamp },
+	/// A timing point had a volume above 100%.
+	ClampedVolume { time: Timestamp, volume: u8 },
+	/// An inherited timing point had a slider velocity that isn't negative, and got a slider velocity of 1.
+	FixedSliderVelocity { time: Timestamp, beat_length: f64 },
+	/// An uninherited timing point had a beat length that isn't positive, and got the one of a neighbouring red line.
+	FixedBeatLength {
+		time: Timestamp,
+		beat_length: f64,
+		new_beat_length: f64,
+	},
+}
+
+impl fmt::Display for TimingRepair {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Sorted => write!(f, "sorted timing points"),
+			Self::RemovedLeadingInherited { time } => write!(
+				f,
+				"{}: removed inherited timing point before the first red line",
+				format_timestamp(*time)
+			),
+			Self::ClampedVolume { time, volume } => {
+				write!(f, "{}: clamped volume {volume} to 100", format_timestamp(*time))
 			}
+			Self::FixedSliderVelocity { time, beat_length } => write!(
+				f,
+				"{}: replaced invalid slider velocity {beat_length} by -100",
+				format_timestamp(*time)
+			),
+			Self::FixedBeatLength {
+				time,
+				beat_length,
+				new_beat_length,
+			} => write!(
+				f,
+				"{}: replaced invalid beat length {beat_length} by {new_beat_length}",
+				format_timestamp(*time)
+			),
+		}
+	}
+}
 
-			prev_timing_point = timing_point;
-			prev_timing_point_was_added = false;
-		} else {
-			prev_timing_point = timing_point;
-			prev_timing_point_was_added = false;
+/// Fixes timing points that osu! tolerates but that break algorithms relying on sane timing, returning every fix made.
+///
+/// - timing points are sorted, with uninherited ones before inherited ones at the same time;
+/// - inherited timing points before the first uninherited one are removed;
+/// - volumes are clamped to 100;
+/// - inherited timing points with a beat length that isn't negative and finite get a slider velocity of 1;
+/// - uninherited timing points with a beat length that isn't positive and finite get the beat length
+///   of the previous red line, or of the next one. They are left as they are if there is no valid red line at all.
+///
+/// The parser never reads infinite beat lengths, but timing points built by hand can have them.
+pub fn repair_timing(beatmap: &mut BeatmapFile) -> Vec<TimingRepair> {
+	let mut repairs = Vec::new();
+	let timing_points = &mut beatmap.timing_points;
+
+	if !timing_points.is_sorted_by(|a, b| canonical_order(a, b).is_le()) {
+		timing_points.sort_by(canonical_order);
+		repairs.push(TimingRepair::Sorted);
+	}
+
+	if let Some(first_red_line) = timing_points.iter().position(|tp| tp.uninherited) {
+		for timing_point in timing_points.drain(..first_red_line) {
+			repairs.push(TimingRepair::RemovedLeadingInherited {
+				time: timing_point.time,
+			});
 		}
 	}
 
-	if !prev_timing_point_was_added {
-		result_points.push(prev_timing_point.clone());
+	let is_valid_beat_length = |tp: &TimingPoint| tp.beat_length > 0.0 && tp.beat_length.is_finite();
+	let mut prev_beat_length = (timing_points.iter())
+		.find(|tp| tp.uninherited && is_valid_beat_length(tp))
+		.map(|tp| tp.beat_length);
+
+	for timing_point in timing_points.iter_mut() {
+		if timing_point.volume > 100 {
+			repairs.push(TimingRepair::ClampedVolume {
+				time: timing_point.time,
+				volume: timing_point.volume,
+			});
+			timing_point.volume = 100;
+		}
+
+		if !timing_point.uninherited {
+			if !(timing_point.beat_length < 0.0 && timing_point.beat_length.is_finite()) {
+				repairs.push(TimingRepair::FixedSliderVelocity {
+					time: timing_point.time,
+					beat_length: timing_point.beat_length,
+				});
+				timing_point.beat_length = -100.0;
+			}
+		} else if is_valid_beat_length(timing_point) {
+			prev_beat_length = Some(timing_point.beat_length);
+		} else if let Some(new_beat_length) = prev_beat_length {
+			repairs.push(TimingRepair::FixedBeatLength {
+				time: timing_point.time,
+				beat_length: timing_point.beat_length,
+				new_beat_length,
+			});
+			timing_point.beat_length = new_beat_length;
+		}
 	}
 
-	result_points
+	repairs
 }
 
 /// Insert a timing point for hitsounding purposes.
+///
+/// The last timing point at `timestamp` gets the new sample settings if there is one.
+/// Otherwise, an inherited timing point continuing the one in effect is inserted with them,
+/// after any uninherited timing point at the same time so that it doesn't reset its slider velocity.
 pub fn insert_hitsound_timing_point(
-	timing_points: &mut Vec<TimingPoint>,
+	timing_points: &mut TimingPoints,
 	timestamp: Timestamp,
 	sample_set: SampleBank,
 	sample_index: u32,
 	volume: u8,
 ) {
-	let index = timing_points.binary_search_by(|o| o.timestamp().total_cmp(&timestamp));
-	match index {
-		Ok(i) => {
-			// timestamp is the same, override timing point hitsound and volume info
-			let timing_point = &mut timing_points[i];
+	if let Some(index) = timing_points.last_index_at(timestamp) {
+		// timestamp is the same, override timing point hitsound and volume info
+		timing_points.modify(index, |timing_point| {
 			timing_point.sample_set = sample_set;
 			timing_point.sample_index = sample_index;
 			timing_point.volume = volume;
+		});
+		return;
+	}
+
+	let Some((prevailing, sv_beat_length)) = prevailing_point_at(timing_points, timestamp) else {
+		// timestamp is before the first timing point, let's not do anything for now
+		tracing::warn!("Tried to insert hitsound timing point before the first timing point of the map");
+		return;
+	};
+
+	// timestamp is not the same, insert new timestamp based on previous one
+	let timing_point = TimingPoint {
+		time: timestamp,
+		beat_length: sv_beat_length,
+		raw_beat_length: None,
+		uninherited: false,
+		sample_set,
+		sample_index,
+		volume,
+		..prevailing.clone()
+	};
+	timing_points.insert(timing_point);
+}
+
+/// Options for [`copy_timing`].
+#[derive(Clone, Debug)]
+pub struct CopyTimingOptions {
+	/// Hit objects further than this many milliseconds from a tick of the new timing are snapped to the closest one.
+	pub drift_tolerance: f64,
+	/// Beat divisors of the ticks hit objects can be snapped to.
+	pub beat_divisors: Vec<u32>,
+	/// Tolerance used to find timing points of both beatmaps at the same time.
+	pub tolerance: Tolerance,
+}
+
+impl Default for CopyTimingOptions {
+	fn default() -> Self {
+		Self {
+			drift_tolerance: 2.0,
+			beat_divisors: vec![1, 2, 3, 4, 6, 8, 12, 16],
+			tolerance: Tolerance::DEFAULT,
 		}
-		Err(i) if i > 0 => {
-			// timestamp is not the same, insert new timestamp based on previous one
-			let mut timing_point = timing_points[i - 1].clone();
-			timing_point.sample_set = sample_set;
-			timing_point.sample_index = sample_index;
-			timing_point.volume = volume;
-			timing_points.insert(i + 1, timing_point);
+	}
+}
+
+/// Timing point in effect at a given time, and the beat length of the inherited point setting its slider velocity.
+///
+/// An uninherited timing point resets the slider velocity, so the beat length is `-100` right after one.
+fn prevailing_point_at(timing_points: &[TimingPoint], time: Timestamp) -> Option<(&TimingPoint, f64)> {
+	let current = timing_points[..timing_points.partition_point(|tp| tp.time <= time)].last()?;
+	let sv_beat_length = if current.uninherited {
+		-100.0
+	} else {
+		current.beat_length
+	};
+	Some((current, sv_beat_length))
+}
+
+/// Closest tick to a time, among the ticks of the given beat divisors.
+pub(crate) fn closest_tick(timing_points: &[TimingPoint], time: Timestamp, beat_divisors: &[u32]) -> Option<Timestamp> {
+	let red_line = uninherited_at(timing_points, time)?;
+	let beats = (time - red_line.time) / red_line.beat_length;
+
+	(beat_divisors.iter())
+		.map(|&divisor| {
+			let divisor = f64::from(divisor.max(1));
+			let tick = (beats * divisor).round() / divisor;
+			tick.mul_add(red_line.beat_length, red_line.time)
+		})
+		.min_by(|a, b| (a - time).abs().total_cmp(&(b - time).abs()))
+}
+
+/// Makes the slider velocity in effect at the time of an inherited timing point be the one it sets,
+/// by changing the inherited timing point at that time or inserting one.
+///
+/// An inserted timing point copies the sample settings and effects of the timing point in effect.
+fn set_sv_at(timing_points: &mut TimingPoints, inherited: &TimingPoint) {
+	let time = inherited.time;
+	if let Some(index) = (timing_points.iter()).rposition(|tp| !tp.uninherited && tp.basically_at(time)) {
+		timing_points.modify(index, |existing| {
+			existing.beat_length = inherited.beat_length;
+			existing.raw_beat_length.clone_from(&inherited.raw_beat_length);
+		});
+		return;
+	}
+
+	let Some((current, _)) = prevailing_point_at(timing_points, time) else {
+		return;
+	};
+
+	let timing_point = TimingPoint {
+		time,
+		beat_length: inherited.beat_length,
+		raw_beat_length: inherited.raw_beat_length.clone(),
+		uninherited: false,
+		..current.clone()
+	};
+	timing_points.insert(timing_point);
+}
+
+/// Sets the slider velocity of every slider starting in a time range, like a greenline generator.
+///
+/// An inherited timing point is put on each slider, and another one right after it (on the next hit object)
+/// brings back the slider velocity that was there before, so that only the selected sliders change.
+/// Timing points are then cleaned up like [`remove_duplicates`] and [`remove_useless_speed_changes`] do.
+///
+/// Returns the amount of sliders whose slider velocity changed, or 0 if `multiplier` isn't a positive number.
+pub fn set_slider_velocity(beatmap: &mut BeatmapFile, range: Range<Timestamp>, multiplier: f64) -> usize {
+	if multiplier <= 0.0 || !multiplier.is_finite() {
+		tracing::warn!("Invalid slider velocity multiplier {multiplier}");
+		return 0;
+	}
+
+	let mut timing_points = TimingPoints::from_vec(std::mem::take(&mut beatmap.timing_points));
+	let original_points = timing_points.clone();
+
+	let mut changed = 0;
+	for (i, hit_object) in beatmap.hit_objects.iter().enumerate() {
+		if !hit_object.is_slider() || !range.contains(&hit_object.time) {
+			continue;
+		}
+
+		let sv_before = prevailing_point_at(&original_points, hit_object.time).map(|(_, sv)| sv);
+		if sv_before.is_some_and(|sv| is_close(-100.0 / sv, multiplier, TimingPoint::SV_TOLERANCE)) {
+			continue;
 		}
-		Err(_) => {
-			// timestamp is before the first timing point, let's not do anything for now
-			tracing::warn!("Tried to insert hitsound timing point before the first timing point of the map");
+
+		set_sv_at(&mut timing_points, &TimingPoint::inherited(hit_object.time, multiplier));
+		changed += 1;
+
+		// bring back the previous slider velocity for the next object, unless a timing point already does
+		let Some(next) = (beatmap.hit_objects[i + 1..].iter()).find(|ho| ho.time > hit_object.time) else {
+			continue;
+		};
+		let next_is_selected = next.is_slider() && range.contains(&next.time);
+		let has_point_between = (original_points.iter()).any(|tp| tp.time > hit_object.time && tp.time <= next.time);
+		if !next_is_selected && !has_point_between {
+			if let Some((current, _)) = prevailing_point_at(&original_points, next.time) {
+				// an uninherited timing point brings back a slider velocity of 1
+				let restored = if current.uninherited {
+					TimingPoint::inherited(next.time, 1.0)
+				} else {
+					TimingPoint {
+						time: next.time,
+						..current.clone()
+					}
+				};
+				set_sv_at(&mut timing_points, &restored);
+			}
 		}
 	}
+
+	beatmap.timing_points = timing_points.into_vec();
+	if changed > 0 {
+		let mode = beatmap.general.as_ref().map_or(0, |general| general.mode);
+		let timing_points = remove_duplicates(&beatmap.timing_points);
+		let report = remove_useless_speed_changes(mode, &timing_points, &beatmap.hit_objects);
+		beatmap.timing_points = remove_duplicates(&report.timing_points);
+	}
+
+	changed
+}
+
+/// How [`refit_slider_lengths`] makes sliders fit the timing of a beatmap again.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RefitPolicy {
+	/// Changes the length of each slider so that it lasts as long as it did with the old timing.
+	#[default]
+	PreserveDuration,
+	/// Keeps the length (and so the shape) of each slider, letting its end time move with the timing.
+	PreserveGeometry,
+}
+
+/// Slider whose end isn't on a tick of the timing after [`refit_slider_lengths`].
+#[derive(Clone, Debug)]
+pub struct UnsnappedSliderEnd {
+	pub time: Timestamp,
+	pub end_time: Timestamp,
+	/// Milliseconds between the end of the slider and the closest tick, rounded.
+	pub offset: f64,
+}
+
+/// What [`refit_slider_lengths`] changed in a beatmap.
+#[derive(Clone, Debug, Default)]
+pub struct RefitReport {
+	/// Amount of sliders whose length changed.
+	pub resized: usize,
+	/// Sliders ending more than 1ms away from a tick, like the osu! editor considers them unsnapped.
+	pub unsnapped: Vec<UnsnappedSliderEnd>,
+}
+
+/// Makes sliders fit the timing of a beatmap again after its BPM or slider velocities changed,
+/// for example after [`copy_timing`] or [`change_rate`].
+///
+/// `old_timing_points` are the timing points the sliders were made for.
+/// With [`RefitPolicy::PreserveDuration`], slider lengths are recomputed so that sliders last as long as they did
+/// with them. With [`RefitPolicy::PreserveGeometry`], slider lengths stay the same and only their end times move.
+/// Either way, sliders that end off the timing are reported.
+pub fn refit_slider_lengths(
+	beatmap: &mut BeatmapFile,
+	old_timing_points: &[TimingPoint],
+	policy: RefitPolicy,
+) -> RefitReport {
+	let old_timeline = TimingTimeline::new(old_timing_points);
+	let timeline = TimingTimeline::new(&beatmap.timing_points);
+
+	let mut report = RefitReport::default();
+	for i in 0..beatmap.hit_objects.len() {
+		let hit_object = &beatmap.hit_objects[i];
+		let HitObjectParams::Slider { length, slides, .. } = hit_object.object_params else {
+			continue;
+		};
+
+		let time = hit_object.time;
+		let Some(velocity) = snap_velocity(beatmap, &timeline, time) else {
+			continue;
+		};
+		let mut slide_duration = length / velocity;
+
+		if policy == RefitPolicy::PreserveDuration {
+			if let Some(old_velocity) = snap_velocity(beatmap, &old_timeline, time) {
+				let old_slide_duration = length / old_velocity;
+				// sliders whose velocity didn't change already last as long as before
+				if !is_close(old_slide_duration, slide_duration, 0.01) {
+					if let HitObjectParams::Slider { length, .. } = &mut beatmap.hit_objects[i].object_params {
+						*length = old_slide_duration * velocity;
+					}
+					slide_duration = old_slide_duration;
+					report.resized += 1;
+				}
+			}
+		}
+
+		let end_time = slide_duration.mul_add(f64::from(slides.max(1)), time);
+		if let Some(tick) = closest_tick(&beatmap.timing_points, end_time, &SNAP_DIVISORS) {
+			let offset = (end_time - tick).round();
+			if offset.abs() > 1.0 {
+				report.unsnapped.push(UnsnappedSliderEnd { time, end_time, offset });
+			}
+		}
+	}
+
+	report
+}
+
+/// Replaces the uninherited timing points of `target` with the ones of `source`.
+///
+/// Slider velocities of inherited timing points are rescaled so that sliders keep their duration
+/// (and scroll speeds stay the same in osu!taiko and osu!mania) under the new BPM,
+/// and hitsound settings carried by the old uninherited timing points are kept.
+/// Hit objects that drift too far from the new timing are snapped to the closest tick.
+///
+/// Returns the amount of hit objects that have been moved.
+pub fn copy_timing(source: &BeatmapFile, target: &mut BeatmapFile, options: &CopyTimingOptions) -> usize {
+	let old_points = &target.timing_points;
+	let new_red_lines: Vec<TimingPoint> = (source.timing_points.iter())
+		.filter(|tp| tp.uninherited)
+		.cloned()
+		.collect();
+
+	if new_red_lines.is_empty() {
+		tracing::warn!("The source beatmap has no uninherited timing points, the timing won't be copied");
+		return 0;
+	}
+
+	let same_time = options.tolerance.same_time;
+
+	// ratio to apply to an inherited beat length at a given time to keep the same slider duration
+	let rescale = |time: Timestamp, sv_beat_length: f64| {
+		let old_beat_length = uninherited_at(old_points, time).map_or(1.0, |tp| tp.beat_length);
+		let new_beat_length = uninherited_at(&new_red_lines, time).map_or(1.0, |tp| tp.beat_length);
+		(sv_beat_length * old_beat_length / new_beat_length).clamp(-10_000.0, -10.0)
+	};
+
+	let mut timing_points = TimingPoints::new();
+
+	for red_line in &new_red_lines {
+		let mut red_line = red_line.clone();
+		if let Some((prevailing, sv_beat_length)) = prevailing_point_at(old_points, red_line.time) {
+			red_line.sample_set = prevailing.sample_set;
+			red_line.sample_index = prevailing.sample_index;
+			red_line.volume = prevailing.volume;
+			red_line.effects = prevailing.effects;
+
+			// the new red line resets the slider velocity, which must be restored if it wasn't 1.0x
+			let green_line_here =
+				(old_points.iter()).any(|tp| !tp.uninherited && tp.basically_at_with(red_line.time, same_time));
+			if !green_line_here && !is_close(-100.0 / sv_beat_length, 1.0, TimingPoint::SV_TOLERANCE) {
+				timing_points.insert(TimingPoint {
+					beat_length: rescale(red_line.time, sv_beat_length),
+					uninherited: false,
+					..red_line.clone()
+				});
+			}
+		}
+
+		timing_points.insert(red_line);
+	}
+
+	for timing_point in old_points {
+		if !timing_point.uninherited {
+			timing_points.insert(TimingPoint {
+				beat_length: rescale(timing_point.time, timing_point.beat_length),
+				..timing_point.clone()
+			});
+			continue;
+		}
+
+		// a removed red line carries hitsound settings and resets the slider velocity, keep that with a green line
+		let replaced = new_red_lines
+			.iter()
+			.any(|tp| tp.basically_eq_with(timing_point, same_time));
+		let green_line_here =
+			(old_points.iter()).any(|tp| !tp.uninherited && tp.basically_eq_with(timing_point, same_time));
+		if !replaced && !green_line_here {
+			timing_points.insert(TimingPoint {
+				beat_length: rescale(timing_point.time, -100.0),
+				uninherited: false,
+				..timing_point.clone()
+			});
+		}
+	}
+
+	target.timing_points = timing_points.into_vec();
+
+	let snap = |time: &mut Timestamp| {
+		let Some(tick) = closest_tick(&new_red_lines, *time, &options.beat_divisors) else {
+			return false;
+		};
+
+		if (tick - *time).abs() <= options.drift_tolerance {
+			return false;
+		}
+
+		*time = tick.round();
+		true
+	};
+
+	let mut moved = 0;
+	for hit_object in &mut target.hit_objects {
+		let mut was_moved = snap(&mut hit_object.time);
+		if let HitObjectParams::Spinner { end_time } | HitObjectParams::Hold { end_time, .. } =
+			&mut hit_object.object_params
+		{
+			was_moved |= snap(end_time);
+		}
+
+		if was_moved {
+			moved += 1;
+		}
+	}
+
+	moved
 }
 
 /// Converts a slider's control points so that they can work with `osu! file format v14`.
@@ -225,12 +1398,7 @@ pub fn convert_slider_points_to_legacy(
 			for segment in segments {
 				let points = convert_to_bezier_anchors(segment)?;
 
-				#[allow(clippy::cast_possible_truncation)]
-				curve_points.extend(points.iter().map(|p| SliderPoint {
-					curve_type: SliderCurveType::Inherit,
-					x: p.x as f32,
-					y: p.y as f32,
-				}));
+				curve_points.extend(points.iter().map(|&p| SliderPoint::from(p)));
 			}
 
 			if let Some(first_point) = curve_points.first_mut() {
@@ -241,3 +1409,570 @@ pub fn convert_slider_points_to_legacy(
 		}
 	})
 }
+
+/// Area of the playfield covered by a hit object: its position, and the whole flattened path of a slider.
+///
+/// Spinners always spin in the middle of the playfield, so they have no bounds.
+#[must_use]
+pub fn object_bounds(hit_object: &HitObject) -> Option<Rect> {
+	if hit_object.is_spinner() {
+		return None;
+	}
+
+	let path = SliderPath::from_hit_object(hit_object);
+	let points = path.as_ref().map_or(&[][..], SliderPath::points);
+	Rect::from_points(points.iter().copied().chain([hit_object.position()]))
+}
+
+/// Options for [`nudge_into_bounds`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NudgeOptions {
+	/// Shrink sliders that are too big to fit in the playfield, around their head.
+	///
+	/// This also shortens their declared length, which makes them shorter in time as well.
+	pub rescale: bool,
+}
+
+/// Moves the hit objects that go out of the playfield back into it, by whole osu! pixels.
+///
+/// Objects are moved as little as possible, and sliders keep their shape unless they are rescaled
+/// (see [`NudgeOptions::rescale`]). Sliders that are too big to fit are moved so that they overflow evenly.
+///
+/// Returns the amount of hit objects that have been moved.
+pub fn nudge_into_bounds(beatmap: &mut BeatmapFile, options: NudgeOptions) -> usize {
+	let playfield = Rect::PLAYFIELD;
+	let mut moved = 0;
+
+	for hit_object in &mut beatmap.hit_objects {
+		let Some(mut bounds) = object_bounds(hit_object) else {
+			continue;
+		};
+		if playfield.overflow(bounds) <= 0.0 {
+			continue;
+		}
+
+		// leave a pixel for control points to be rounded
+		let scale = ((playfield.width() - 1.0) / bounds.width()).min((playfield.height() - 1.0) / bounds.height());
+		if options.rescale && scale < 1.0 {
+			let head = hit_object.position();
+			if let HitObjectParams::Slider {
+				curve_points, length, ..
+			} = &mut hit_object.object_params
+			{
+				#[allow(clippy::cast_possible_truncation)]
+				for point in curve_points.iter_mut() {
+					let scaled = head.lerp(point.to_point(), scale);
+					point.x = scaled.x.round() as f32;
+					point.y = scaled.y.round() as f32;
+				}
+				*length *= scale;
+			}
+
+			bounds = match object_bounds(hit_object) {
+				Some(bounds) => bounds,
+				None => continue,
+			};
+		}
+
+		let shift = |min: f64, max: f64, playfield_min: f64, playfield_max: f64| {
+			if max - min > playfield_max - playfield_min {
+				(playfield_min + playfield_max - min - max) / 2.0
+			} else if min < playfield_min {
+				playfield_min - min
+			} else if max > playfield_max {
+				playfield_max - max
+			} else {
+				0.0
+			}
+		};
+
+		let dx = shift(bounds.min.x, bounds.max.x, playfield.min.x, playfield.max.x);
+		let dy = shift(bounds.min.y, bounds.max.y, playfield.min.y, playfield.max.y);
+		// round away from zero so that the object ends up fully inside
+		let (dx, dy) = (dx.signum() * dx.abs().ceil(), dy.signum() * dy.abs().ceil());
+
+		move_hit_object(hit_object, Point::new(dx, dy));
+		moved += 1;
+	}
+
+	moved
+}
+
+/// Moves hit objects so that they all have the same distance snap to the previous object,
+/// keeping the direction they come from.
+#[derive(Clone, Debug)]
+pub struct EqualizeSpacing {
+	/// Objects starting in this range are moved. The object before the range is the anchor they are spaced from.
+	pub range: Range<Timestamp>,
+	/// Distance snap to space the objects with, or the one saved in the beatmap's editor settings if `None`.
+	pub distance_snap: Option<f64>,
+}
+
+impl EqualizeSpacing {
+	/// Applies the spacing to an osu!standard beatmap, returning the amount of hit objects that have been moved.
+	///
+	/// Hit objects need to be sorted by time. Objects stacked on the previous one stay stacked on it,
+	/// spinners are left in place, and sliders are moved as a whole.
+	/// Objects are not kept inside the playfield, see [`nudge_into_bounds`] for that.
+	pub fn apply(&self, beatmap: &mut BeatmapFile) -> usize {
+		let timeline = TimingTimeline::new(&beatmap.timing_points);
+		let distance_snap = (self.distance_snap)
+			.or_else(|| beatmap.editor.as_ref().map(|editor| editor.distance_spacing))
+			.unwrap_or(1.0);
+
+		// directions and stacks are taken from the objects before any of them moved
+		let original = beatmap.hit_objects.clone();
+		let mut prev_shift = Point::default();
+		let mut moved = 0;
+		for (index, pair) in original.windows(2).enumerate() {
+			let [prev, next] = pair else {
+				continue;
+			};
+			let index = index + 1;
+			if !self.range.contains(&next.time) || prev.is_spinner() || next.is_spinner() {
+				prev_shift = Point::default();
+				continue;
+			}
+
+			let prev_end_time = end_time(beatmap, prev);
+			let offset = next.position() - end_position(prev);
+			let shift = if offset.len() < STACK_DISTANCE {
+				// stacks move with the object they are stacked on
+				prev_shift
+			} else if let Some(velocity) = snap_velocity(beatmap, &timeline, prev_end_time) {
+				let distance = distance_snap * (next.time - prev_end_time) * velocity;
+				let target = end_position(&beatmap.hit_objects[index - 1]) + offset.normalized() * distance;
+				Point::new(target.x.round(), target.y.round()) - next.position()
+			} else {
+				Point::default()
+			};
+
+			prev_shift = shift;
+			if shift.len() > 0.0 {
+				move_hit_object(&mut beatmap.hit_objects[index], shift);
+				moved += 1;
+			}
+		}
+
+		moved
+	}
+}
+
+/// Moves a hit object and the control points of a slider by some offset.
+fn move_hit_object(hit_object: &mut HitObject, offset: Point) {
+	#[allow(clippy::cast_possible_truncation)]
+	let (dx, dy) = (offset.x as f32, offset.y as f32);
+
+	hit_object.x += dx;
+	hit_object.y += dy;
+	if let HitObjectParams::Slider { curve_points, .. } = &mut hit_object.object_params {
+		for point in curve_points {
+			point.x += dx;
+			point.y += dy;
+		}
+	}
+}
+
+/// What [`cut_with`] does with the sliders, spinners and holds crossing a boundary of the range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StraddlingPolicy {
+	/// Remove every object that isn't entirely in the range.
+	#[default]
+	Drop,
+	/// Keep every object that overlaps the range.
+	Keep,
+	/// Shorten the objects ending after the range so that they end in it (removing slides of sliders),
+	/// and remove the ones starting before it.
+	Clip,
+}
+
+/// Options for [`cut_with`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CutOptions {
+	pub straddling: StraddlingPolicy,
+}
+
+/// Shortens an object so that it ends at or before `end`, or returns `None` if it can't.
+fn clip_hit_object_end(beatmap: &BeatmapFile, mut hit_object: HitObject, end: Timestamp) -> Option<HitObject> {
+	let duration = slider_duration(beatmap, &hit_object);
+	match &mut hit_object.object_params {
+		HitObjectParams::Spinner { end_time } | HitObjectParams::Hold { end_time, .. } => *end_time = end_time.min(end),
+		params @ HitObjectParams::Slider { .. } => {
+			let HitObjectParams::Slider { slides, .. } = params else {
+				return None;
+			};
+
+			let slide_duration = duration? / f64::from((*slides).max(1));
+			#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+			let fitting_slides = ((end - hit_object.time) / slide_duration)
+				.floor()
+				.min(f64::from(*slides)) as u32;
+			if fitting_slides == 0 {
+				return None;
+			}
+
+			*slides = fitting_slides;
+			params.normalize_edges();
+		}
+		HitObjectParams::HitCircle => {}
+	}
+
+	Some(hit_object)
+}
+
+/// Copy of a beatmap with only what happens in a time range, dropping the objects crossing its boundaries.
+///
+/// See [`cut_with`].
+#[must_use]
+pub fn cut(beatmap: &BeatmapFile, range: Range<Timestamp>) -> BeatmapFile {
+	cut_with(beatmap, range, CutOptions::default())
+}
+
+/// Copy of a beatmap with only what happens in a time range.
+///
+/// Times are kept as they are, so that the copy still plays on the same audio.
+/// The uninherited timing point in effect at the start of the range is kept at its original time so that the beat grid
+/// doesn't change, and the inherited timing point in effect there is copied to the start of the range.
+/// Breaks are clipped to the range, and storyboard samples, bookmarks and the preview time outside of it are removed.
+#[must_use]
+pub fn cut_with(beatmap: &BeatmapFile, range: Range<Timestamp>, options: CutOptions) -> BeatmapFile {
+	let mut part = beatmap.clone();
+
+	part.hit_objects = (beatmap.hit_objects.iter())
+		.filter_map(|hit_object| {
+			let end = end_time(beatmap, hit_object);
+			let starts_in_range = range.contains(&hit_object.time);
+			let overlaps = hit_object.time < range.end && end >= range.start;
+
+			if starts_in_range && end <= range.end {
+				return Some(hit_object.clone());
+			}
+
+			match options.straddling {
+				StraddlingPolicy::Keep => overlaps.then(|| hit_object.clone()),
+				StraddlingPolicy::Clip if starts_in_range => {
+					clip_hit_object_end(beatmap, hit_object.clone(), range.end)
+				}
+				// objects starting before the range can't be clipped
+				StraddlingPolicy::Drop | StraddlingPolicy::Clip => None,
+			}
+		})
+		.collect();
+
+	let mut timing_points: Vec<TimingPoint> = Vec::new();
+	if let Some(red_line) = uninherited_at(&beatmap.timing_points, range.start) {
+		if red_line.time < range.start {
+			timing_points.push(red_line.clone());
+		}
+	}
+	if let Some((current, _)) = prevailing_point_at(&beatmap.timing_points, range.start) {
+		if !current.uninherited && current.time < range.start {
+			timing_points.push(TimingPoint {
+				time: range.start,
+				..current.clone()
+			});
+		}
+	}
+	timing_points.extend(
+		(beatmap.timing_points.iter())
+			.filter(|tp| range.contains(&tp.time))
+			.cloned(),
+	);
+	part.timing_points = timing_points;
+
+	part.events.retain_mut(|event| match &mut event.params {
+		EventParams::Background { .. } | EventParams::Video { .. } => true,
+		EventParams::Sample { .. } => range.contains(&event.start_time),
+		EventParams::Break { end_time } => {
+			event.start_time = event.start_time.max(range.start);
+			*end_time = end_time.min(range.end);
+			*end_time > event.start_time
+		}
+	});
+
+	if let Some(general) = &mut part.general {
+		if !range.contains(&general.preview_time) {
+			general.preview_time = -1.0;
+		}
+	}
+
+	if let Some(editor) = &mut part.editor {
+		editor
+			.bookmarks
+			.retain(|&bookmark| range.contains(&f64::from(bookmark)));
+	}
+
+	part
+}
+
+/// Joins several parts of beatmaps (like the ones made by [`cut`]) into one, in order.
+///
+/// Parts keep their times unless their hit objects start before the end of the previous part,
+/// in which case they are offset to start right after it. Uninherited timing points of a part that are still before
+/// the end of the previous part are moved forward by whole beats past it, so that they don't change its timing,
+/// and the timing points and breaks of the previous parts after their last object are removed.
+///
+/// The settings, backgrounds and videos of the first part are used for the whole beatmap.
+/// Parts with a different slider multiplier than the first one will have sliders of a different duration.
+#[must_use]
+pub fn concat(parts: &[BeatmapFile]) -> BeatmapFile {
+	let Some((first, rest)) = parts.split_first() else {
+		return BeatmapFile::default();
+	};
+
+	let slider_multiplier = first.difficulty.as_ref().map(|difficulty| difficulty.slider_multiplier);
+	let mut beatmap = first.clone();
+	let mut end = first.playable_range().map_or(f64::NEG_INFINITY, |span| span.end);
+
+	for part in rest {
+		let mut part = part.clone();
+		if part.difficulty.as_ref().map(|difficulty| difficulty.slider_multiplier) != slider_multiplier {
+			tracing::warn!("Concatenating a part with a different slider multiplier, its sliders will change duration");
+		}
+
+		if let Some(span) = part.playable_range() {
+			if span.start < end {
+				offset_map(&mut part, end - span.start);
+			}
+		}
+
+		for timing_point in &mut part.timing_points {
+			if timing_point.time >= end {
+				continue;
+			}
+
+			if timing_point.uninherited && timing_point.beat_length > 0.0 {
+				let beats = ((end - timing_point.time) / timing_point.beat_length).ceil();
+				timing_point.time = beats.mul_add(timing_point.beat_length, timing_point.time);
+			} else {
+				timing_point.time = end;
+			}
+		}
+
+		// the timing and breaks of the previous parts after their last object would overlap this part
+		if !part.timing_points.is_empty() {
+			beatmap.timing_points.retain(|tp| tp.time < end);
+		}
+		let part_start = part.playable_range().map_or(end, |span| span.start);
+		beatmap.events.retain_mut(|event| match &mut event.params {
+			EventParams::Break { end_time } => {
+				*end_time = end_time.min(part_start);
+				*end_time > event.start_time
+			}
+			_ => true,
+		});
+
+		beatmap.timing_points.append(&mut part.timing_points);
+		beatmap.hit_objects.append(&mut part.hit_objects);
+		(beatmap.events).extend(
+			(part.events.into_iter())
+				.filter(|event| matches!(event.params, EventParams::Break { .. } | EventParams::Sample { .. })),
+		);
+
+		if let (Some(editor), Some(part_editor)) = (&mut beatmap.editor, &part.editor) {
+			editor.bookmarks.extend_from_slice(&part_editor.bookmarks);
+		}
+
+		end = beatmap.playable_range().map_or(end, |span| span.end);
+	}
+
+	(beatmap.timing_points).sort_by(canonical_order);
+	beatmap.hit_objects.sort_by(|a, b| a.time.total_cmp(&b.time));
+	beatmap
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+	use super::*;
+	use crate::file::beatmap::parsing::{parse_hit_object, parse_osu_str, parse_timing_point};
+
+	fn slider_length(beatmap: &BeatmapFile) -> f64 {
+		match beatmap.hit_objects[0].object_params {
+			HitObjectParams::Slider { length, .. } => length,
+			_ => unreachable!(),
+		}
+	}
+
+	#[test]
+	fn offsets_report_what_they_moved() {
+		let mut beatmap = parse_osu_str(
+			"osu file format v14\n\n[General]\nPreviewTime: 500\n\n[Events]\n0,0,\"bg.jpg\",0,0\n2,1500,2500\n\n\
+			[TimingPoints]\n0,500,4,1,0,100,1,0\n\n[HitObjects]\n256,192,1000,1,0,0:0:0:0:\n",
+		)
+		.unwrap();
+
+		let changes = offset_map(&mut beatmap, 25.0);
+		assert_eq!(changes.hit_objects.modified.len(), 1);
+		assert_eq!(changes.hit_objects.modified[0].0.time, 1000.0);
+		assert_eq!(changes.hit_objects.modified[0].1.time, 1025.0);
+		assert_eq!(changes.timing_points.modified.len(), 1);
+		// the background doesn't move, only the break does
+		assert_eq!(changes.events.modified.len(), 1);
+		assert_eq!(changes.settings.len(), 1);
+		assert_eq!(changes.settings[0].key, "PreviewTime");
+		assert_eq!(changes.settings[0].new.as_deref(), Some("525"));
+
+		let report = offset_map_checked(&mut beatmap, -1025.0, OffsetOptions { adjust_lead_in: true });
+		assert_eq!(report.changes.hit_objects.modified[0].1.time, 0.0);
+		assert!(report.changes.settings.iter().any(|change| change.key == "AudioLeadIn"));
+		assert!(offset_map(&mut beatmap, 0.0).is_empty());
+	}
+
+	#[test]
+	fn rate_changes_only_touch_the_settings_the_mode_uses() {
+		let beatmap = |mode: u8| {
+			parse_osu_str(&format!(
+				"osu file format v14\n\n[General]\nMode: {mode}\n\n[Difficulty]\nHPDrainRate:5\nCircleSize:4\n\
+				OverallDifficulty:5\nApproachRate:5\nSliderMultiplier:1.4\nSliderTickRate:1\n\n\
+				[TimingPoints]\n1000,500,4,1,0,100,1,0\n\n[HitObjects]\n64,192,1000,1,0,0:0:0:0:\n"
+			))
+			.unwrap()
+		};
+
+		let mut mania = beatmap(3);
+		change_rate(&mut mania, 1.5, true);
+		let difficulty = mania.difficulty.as_ref().unwrap();
+		assert_eq!(difficulty.approach_rate, 5.0);
+		assert!(difficulty.overall_difficulty > 5.0);
+
+		let mut standard = beatmap(0);
+		change_rate(&mut standard, 1.5, true);
+		assert!(standard.difficulty.as_ref().unwrap().approach_rate > 5.0);
+
+		// timing points keep their fractional time while hit objects are rounded
+		assert_eq!(standard.timing_points[0].time, 1000.0 / 1.5);
+		assert_eq!(standard.timing_points[0].beat_length, 500.0 / 1.5);
+		assert_eq!(standard.hit_objects[0].time, 667.0);
+	}
+
+	#[test]
+	fn refit_preserves_the_old_duration() {
+		let mut beatmap = parse_osu_str(
+			"osu file format v14\n\n[Difficulty]\nSliderMultiplier:1\n\n[TimingPoints]\n0,500,4,1,0,100,1,0\n\n\
+			[HitObjects]\n100,100,1000,2,0,L|200:100,1,100\n",
+		)
+		.unwrap();
+		let old_timing_points = beatmap.timing_points.clone();
+		let duration = slider_duration(&beatmap, &beatmap.hit_objects[0]).unwrap();
+
+		// from 120 to 160 BPM, a slider lasting a beat lasts 4/3 beats
+		beatmap.timing_points[0].beat_length = 375.0;
+
+		let report = refit_slider_lengths(&mut beatmap, &old_timing_points, RefitPolicy::PreserveDuration);
+		assert_eq!(report.resized, 1);
+		assert!(is_close(
+			slider_duration(&beatmap, &beatmap.hit_objects[0]).unwrap(),
+			duration,
+			1e-9
+		));
+		assert!(is_close(slider_length(&beatmap), 100.0 * 500.0 / 375.0, 1e-9));
+	}
+
+	fn speed_changes() -> Vec<TimingPoint> {
+		[
+			"0,500,4,1,0,100,1,0",
+			"0,-100,4,1,0,100,0,0",
+			"1000,-50,4,1,0,100,0,0",
+			"1000,-200,4,1,0,100,0,0",
+			"2000,-199.99999999999,4,1,0,100,0,0",
+			"3000,-100,4,1,0,100,0,0",
+			"4000,-50,4,2,0,80,0,0",
+		]
+		.into_iter()
+		.map(|line| parse_timing_point(line).unwrap())
+		.collect()
+	}
+
+	#[test]
+	fn useless_speed_changes_are_removed() {
+		let hit_objects = [parse_hit_object("100,100,1500,2,0,L|200:100,1,100").unwrap()];
+		let report = remove_useless_speed_changes(0, &speed_changes(), &hit_objects);
+
+		let removed: Vec<(Timestamp, UselessSpeedChange)> = (report.removed.iter())
+			.map(|removed| (removed.time, removed.reason))
+			.collect();
+		assert_eq!(
+			removed,
+			[
+				(0.0, UselessSpeedChange::SameVelocity),
+				(1000.0, UselessSpeedChange::Overridden),
+				(2000.0, UselessSpeedChange::SameVelocity),
+				(3000.0, UselessSpeedChange::NoSliders),
+			]
+		);
+
+		let kept: Vec<Timestamp> = report.timing_points.iter().map(|tp| tp.time).collect();
+		assert_eq!(kept, [0.0, 1000.0, 4000.0]);
+		assert_eq!(report.timing_points[1].sv_multiplier(), 0.5);
+	}
+
+	#[test]
+	fn scroll_speed_changes_are_kept_in_mania() {
+		let report = remove_useless_speed_changes(3, &speed_changes(), &[]);
+
+		let kept: Vec<Timestamp> = report.timing_points.iter().map(|tp| tp.time).collect();
+		assert_eq!(kept, [0.0, 1000.0, 3000.0, 4000.0]);
+		assert!(!(report.removed.iter()).any(|removed| removed.reason == UselessSpeedChange::NoSliders));
+	}
+
+	#[test]
+	fn slider_velocities_are_set_on_sliders() {
+		let mut beatmap = parse_osu_str(
+			"osu file format v14\n\n[TimingPoints]\n0,500,4,2,1,60,1,0\n\n[HitObjects]\n\
+			100,100,1000,2,0,L|200:100,1,100\n100,100,2000,2,0,L|200:100,1,100\n",
+		)
+		.unwrap();
+
+		for multiplier in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+			assert_eq!(set_slider_velocity(&mut beatmap, 0.0..3000.0, multiplier), 0);
+		}
+		assert_eq!(beatmap.timing_points.len(), 1);
+
+		assert_eq!(set_slider_velocity(&mut beatmap, 0.0..1500.0, 2.0), 1);
+		let sv: Vec<(Timestamp, f64)> = (beatmap.timing_points.iter())
+			.map(|tp| (tp.time, tp.sv_multiplier()))
+			.collect();
+		assert_eq!(sv, [(0.0, 1.0), (1000.0, 2.0), (2000.0, 1.0)]);
+		// the new timing points keep the sample settings in effect
+		assert!(beatmap
+			.timing_points
+			.iter()
+			.all(|tp| tp.volume == 60 && tp.sample_index == 1));
+	}
+
+	#[test]
+	fn repair_fixes_non_finite_beat_lengths() {
+		let mut beatmap = BeatmapFile::default();
+		for line in [
+			"0,500,4,1,0,100,1,0",
+			"1000,-50,4,1,0,100,0,0",
+			"2000,500,4,1,0,100,1,0",
+			"3000,-50,4,1,0,100,0,0",
+		] {
+			beatmap.timing_points.push(parse_timing_point(line).unwrap());
+		}
+		beatmap.timing_points[1].beat_length = f64::NEG_INFINITY;
+		beatmap.timing_points[2].beat_length = f64::INFINITY;
+		beatmap.timing_points[3].beat_length = f64::NAN;
+
+		let repairs = repair_timing(&mut beatmap);
+		assert_eq!(repairs.len(), 3);
+		let beat_lengths: Vec<f64> = beatmap.timing_points.iter().map(|tp| tp.beat_length).collect();
+		assert_eq!(beat_lengths, [500.0, -100.0, 500.0, -100.0]);
+	}
+
+	#[test]
+	fn refit_preserves_the_geometry() {
+		let mut beatmap = parse_osu_str(
+			"osu file format v14\n\n[Difficulty]\nSliderMultiplier:1\n\n[TimingPoints]\n0,500,4,1,0,100,1,0\n\n\
+			[HitObjects]\n100,100,1000,2,0,L|200:100,1,100\n",
+		)
+		.unwrap();
+		beatmap.timing_points[0].beat_length = 375.0;
+
+		let report = refit_slider_lengths(&mut beatmap, &[], RefitPolicy::PreserveGeometry);
+		assert_eq!(report.resized, 0);
+		assert!(is_close(slider_length(&beatmap), 100.0, 1e-9));
+	}
+}
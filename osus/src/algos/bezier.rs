@@ -4,7 +4,9 @@ use std::f64::consts::TAU;
 
 use crate::file::beatmap::{SliderCurveType, SliderPoint};
 use crate::is_close;
-use crate::point::Point;
+use crate::point::{Point, Rounding};
+
+use super::slider_path::bezier_point;
 
 #[derive(Clone, Debug)]
 pub struct CirclePreset<const N: usize> {
@@ -84,6 +86,33 @@ pub enum BezierConversionError {
 	PerfectCurveWithMoreThan3Points,
 }
 
+/// Options of the conversion of perfect curves to bezier anchors.
+#[derive(Clone, Copy, Debug)]
+pub struct BezierConversionOptions {
+	/// Maximum amount of iterations made to fit the bezier anchors to the angle of the arc.
+	pub max_iterations: usize,
+	/// Iterations stop once the ratio between the angle of the arc and the one of the anchors is this close to 1.
+	pub tolerance: f64,
+}
+
+impl Default for BezierConversionOptions {
+	fn default() -> Self {
+		Self {
+			max_iterations: 100,
+			tolerance: 0.000_001,
+		}
+	}
+}
+
+/// Bezier anchors converted from a slider's control points.
+#[derive(Clone, Debug)]
+pub struct BezierAnchors {
+	pub anchors: Vec<Point>,
+	/// Distance in osu! pixels between the end of the converted arc and the actual end of the perfect curve,
+	/// before the last anchor is snapped onto it. It is 0 for every other curve type.
+	pub error: f64,
+}
+
 /// Converts a slider's control points to bezier anchors.
 ///
 /// # Errors
@@ -91,24 +120,42 @@ pub enum BezierConversionError {
 /// This function will return an error if there are no control points
 /// or if the control points do not represent a valid slider segment.
 pub fn convert_to_bezier_anchors(control_points: &[SliderPoint]) -> Result<Vec<Point>, BezierConversionError> {
+	convert_to_bezier_anchors_with(control_points, &BezierConversionOptions::default()).map(|bezier| bezier.anchors)
+}
+
+/// Converts a slider's control points to bezier anchors, with options for perfect curves.
+///
+/// Perfect curves whose points are collinear or too close to each other to make a circle are converted as linear,
+/// like osu! draws them.
+///
+/// # Errors
+///
+/// This function will return an error if there are no control points
+/// or if the control points do not represent a valid slider segment.
+pub fn convert_to_bezier_anchors_with(
+	control_points: &[SliderPoint],
+	options: &BezierConversionOptions,
+) -> Result<BezierAnchors, BezierConversionError> {
 	if control_points.is_empty() {
 		return Err(BezierConversionError::NoControlPoints);
 	}
 
-	Ok(match control_points[0].curve_type {
+	let anchors = match control_points[0].curve_type {
 		SliderCurveType::Linear => convert_linear_to_bezier_anchors(control_points),
 		SliderCurveType::PerfectCurve => {
 			if control_points.len() == 2 {
 				convert_linear_to_bezier_anchors(control_points)
 			} else if let Ok(control_points) = control_points.try_into() {
-				convert_circle_to_bezier_anchors(control_points)
+				return Ok(convert_circle_to_bezier_anchors(control_points, options));
 			} else {
 				return Err(BezierConversionError::PerfectCurveWithMoreThan3Points);
 			}
 		}
 		SliderCurveType::Catmull => convert_catmull_to_bezier_anchors(control_points),
 		_ => control_points.iter().map(SliderPoint::to_point).collect(),
-	})
+	};
+
+	Ok(BezierAnchors { anchors, error: 0.0 })
 }
 
 #[derive(Clone, Debug, Default)]
@@ -126,34 +173,22 @@ fn get_circle_arc_properties(control_points: &[SliderPoint; 3]) -> Option<Circle
 	let b = control_points[1].to_point();
 	let c = control_points[2].to_point();
 
-	if is_close(
-		0.0,
-		(b.y - a.y).mul_add(c.x - a.x, -(b.x - a.x) * (c.y - a.y)),
-		f64::EPSILON,
-	) {
+	let (ab, ac) = (b - a, c - a);
+	let cross = ab.cross(ac);
+	if is_close(0.0, cross, f64::EPSILON) {
 		return None;
 	}
 
-	let d = 2.0 * c.x.mul_add((a - b).y, a.x.mul_add((b - c).y, b.x * (c - a).y));
-
-	let a_sq = a.dot(a);
-	let b_sq = b.dot(b);
-	let c_sq = c.dot(c);
-
-	let center = Point {
-		x: c_sq.mul_add((a - b).y, a_sq.mul_add((b - c).y, b_sq * (c - a).y)),
-		y: c_sq.mul_add((b - a).x, a_sq.mul_add((c - b).x, b_sq * (a - c).x)),
-	};
-	let center = center / d;
+	let center = a + (ab * ac.len_squared() - ac * ab.len_squared()).perpendicular() / (2.0 * cross);
 
 	let da = a - center;
 	let dc = c - center;
 
 	let radius = da.len();
 
-	let theta_start = da.y.atan2(da.x);
+	let theta_start = da.angle();
 	let theta_end = {
-		let theta_end = dc.y.atan2(dc.x);
+		let theta_end = dc.angle();
 		// turn as many times as necessary so that theta_end >= theta_start
 		TAU.mul_add(((theta_start - theta_end) / TAU).ceil(), theta_end)
 	};
@@ -162,13 +197,7 @@ fn get_circle_arc_properties(control_points: &[SliderPoint; 3]) -> Option<Circle
 	let mut direction = 1.0;
 
 	// Decide in which direction to draw the circle, depending on which side of AC B lies.
-	let ortho_a_to_c = c - a;
-	let ortho_a_to_c = Point {
-		x: ortho_a_to_c.y,
-		y: -ortho_a_to_c.x,
-	};
-
-	if ortho_a_to_c.dot(b - a) < 0.0 {
+	if ac.cross(ab) > 0.0 {
 		direction = -direction;
 		theta_range = TAU - theta_range;
 	}
@@ -182,11 +211,22 @@ fn get_circle_arc_properties(control_points: &[SliderPoint; 3]) -> Option<Circle
 	})
 }
 
-fn convert_circle_to_bezier_anchors(points: &[SliderPoint; 3]) -> Vec<Point> {
+fn convert_circle_to_bezier_anchors(points: &[SliderPoint; 3], options: &BezierConversionOptions) -> BezierAnchors {
+	let linear = || BezierAnchors {
+		anchors: convert_linear_to_bezier_anchors(points),
+		error: 0.0,
+	};
+
 	let Some(cs) = get_circle_arc_properties(points) else {
-		return points.iter().map(SliderPoint::to_point).collect();
+		return linear();
 	};
 
+	// points that are almost collinear make a huge circle, or no circle at all
+	let is_valid = cs.radius.is_finite() && cs.center.x.is_finite() && cs.center.y.is_finite() && cs.theta_range > 0.0;
+	if !is_valid {
+		return linear();
+	}
+
 	let mut arc;
 	let mut arc_len;
 
@@ -216,8 +256,11 @@ fn convert_circle_to_bezier_anchors(points: &[SliderPoint; 3]) -> Vec<Point> {
 	let n = arc.len() - 1;
 	let mut tf = cs.theta_range / arc_len;
 
-	#[allow(clippy::while_float)]
-	while (tf - 1.0).abs() > 0.000_001 {
+	for _ in 0..options.max_iterations {
+		if (tf - 1.0).abs() <= options.tolerance {
+			break;
+		}
+
 		for j in 0..n {
 			for i in ((j + 1)..=n).rev() {
 				arc[i] = arc[i] * tf + arc[i - 1] * (1.0 - tf);
@@ -230,6 +273,9 @@ fn convert_circle_to_bezier_anchors(points: &[SliderPoint; 3]) -> Vec<Point> {
 			arc_len += TAU;
 		}
 		tf = cs.theta_range / arc_len;
+		if !tf.is_finite() {
+			return linear();
+		}
 	}
 
 	// adjust rotation, radius and position
@@ -250,10 +296,13 @@ fn convert_circle_to_bezier_anchors(points: &[SliderPoint; 3]) -> Vec<Point> {
 		} + cs.center;
 	}
 
+	let end = points[2].to_point();
+	let error = arc.last().map_or(0.0, |&last| (last - end).len());
+
 	*arc.first_mut().unwrap() = points[0].to_point();
-	*arc.last_mut().unwrap() = points[2].to_point();
+	*arc.last_mut().unwrap() = end;
 
-	arc
+	BezierAnchors { anchors: arc, error }
 }
 
 #[must_use]
@@ -302,3 +351,152 @@ fn convert_linear_to_bezier_anchors(points: &[SliderPoint]) -> Vec<Point> {
 
 	bezier
 }
+
+/// Amount of points of a bezier curve checked against the shape it is refitted to.
+const REFIT_SAMPLES: usize = 50;
+
+/// A segment of bezier anchors, recognized as a simpler curve type by [`simplify_anchors`].
+struct Segment {
+	curve_type: SliderCurveType,
+	points: Vec<Point>,
+}
+
+/// Splits bezier anchors into curves, on every repeated anchor (red anchors in the editor).
+fn split_red_anchors(points: &[Point]) -> Vec<Vec<Point>> {
+	let mut curves = vec![Vec::new()];
+	for &point in points {
+		let current = curves.last_mut().expect("there is always a curve");
+		match current.last() {
+			Some(&last) if point.distance(last) < 1e-9 => curves.push(vec![point]),
+			_ => current.push(point),
+		}
+	}
+
+	curves.retain(|curve| curve.len() > 1);
+	curves
+}
+
+fn sample_bezier(anchors: &[Point]) -> impl Iterator<Item = Point> + '_ {
+	#[allow(clippy::cast_precision_loss)]
+	(0..=REFIT_SAMPLES).map(move |i| bezier_point(anchors, i as f64 / REFIT_SAMPLES as f64))
+}
+
+/// Whether a bezier curve is a straight line from its first anchor to its last one.
+fn is_linear(anchors: &[Point], tolerance: f64) -> bool {
+	let (Some(&start), Some(&end)) = (anchors.first(), anchors.last()) else {
+		return false;
+	};
+
+	let chord = end - start;
+	let length = chord.len();
+	if length < tolerance {
+		return false;
+	}
+
+	let direction = chord / length;
+	let mut progress = 0.0;
+	sample_bezier(anchors).all(|point| {
+		let offset = point - start;
+		let along = offset.dot(direction);
+		let across = offset.cross(direction);
+
+		// the curve must not go back on itself, or a line would be shorter than it
+		let is_forward = along >= progress - tolerance;
+		progress = along.max(progress);
+		is_forward && across.abs() <= tolerance
+	})
+}
+
+/// Point in the middle of a bezier curve, if it lies on a circular arc from its first anchor to its last one.
+fn fit_perfect_curve(anchors: &[Point], tolerance: f64) -> Option<Point> {
+	let (&start, &end) = (anchors.first()?, anchors.last()?);
+	let middle = bezier_point(anchors, 0.5);
+
+	let arc = get_circle_arc_properties(&[start.into(), middle.into(), end.into()])?;
+	if arc.theta_range >= TAU {
+		return None;
+	}
+
+	let angle_slack = tolerance / arc.radius;
+	let is_on_arc = |point: Point| {
+		let offset = point - arc.center;
+		let angle = (offset.angle() - arc.theta_start) * arc.direction;
+		let angle = angle.rem_euclid(TAU);
+		let is_within_arc = angle <= arc.theta_range + angle_slack || angle >= TAU - angle_slack;
+		is_within_arc && is_close(point.distance(arc.center), arc.radius, tolerance)
+	};
+
+	sample_bezier(anchors).all(is_on_arc).then_some(middle)
+}
+
+/// Converts legacy bezier anchors back to segments of the simplest curve types that draw the same path,
+/// in the way osu!lazer writes them. This is roughly the reverse of [`convert_to_bezier_anchors`].
+///
+/// `points` are the control points of a slider including its head, as written by osu!stable:
+/// repeated anchors start a new curve (red anchors in the editor).
+/// Each curve becomes a linear segment if it is a straight line, a perfect curve if it is a circular arc,
+/// or stays a bezier curve otherwise. A curve only gets a simpler type if every point of it
+/// is within `tolerance` osu! pixels of the new shape, and consecutive linear segments are merged together.
+///
+/// The first returned point has the type of the first segment, and every point starting a new segment has its type.
+/// Points that don't start with a bezier curve are returned as they are.
+#[must_use]
+pub fn simplify_anchors(points: &[SliderPoint], tolerance: f64) -> Vec<SliderPoint> {
+	if points.first().is_none_or(|p| p.curve_type != SliderCurveType::Bezier) {
+		return points.to_vec();
+	}
+
+	let anchors: Vec<Point> = points.iter().map(SliderPoint::to_point).collect();
+
+	let mut segments: Vec<Segment> = Vec::new();
+	for curve in split_red_anchors(&anchors) {
+		let segment = if is_linear(&curve, tolerance) {
+			Segment {
+				curve_type: SliderCurveType::Linear,
+				points: vec![curve[0], curve[curve.len() - 1]],
+			}
+		} else if let Some(middle) = fit_perfect_curve(&curve, tolerance) {
+			Segment {
+				curve_type: SliderCurveType::PerfectCurve,
+				points: vec![curve[0], middle, curve[curve.len() - 1]],
+			}
+		} else {
+			Segment {
+				curve_type: SliderCurveType::Bezier,
+				points: curve,
+			}
+		};
+
+		match segments.last_mut() {
+			Some(last)
+				if last.curve_type == SliderCurveType::Linear && segment.curve_type == SliderCurveType::Linear =>
+			{
+				last.points.extend_from_slice(&segment.points[1..]);
+			}
+			_ => segments.push(segment),
+		}
+	}
+
+	if segments.is_empty() {
+		return points.to_vec();
+	}
+
+	let mut simplified = Vec::new();
+	for segment in &segments {
+		// segments share their first point with the end of the previous one
+		if !simplified.is_empty() {
+			simplified.pop();
+		}
+
+		simplified.extend(segment.points.iter().enumerate().map(|(i, &p)| {
+			let curve_type = if i == 0 {
+				segment.curve_type
+			} else {
+				SliderCurveType::Inherit
+			};
+			SliderPoint::from_point(curve_type, p, Rounding::Exact)
+		}));
+	}
+
+	simplified
+}
@@ -0,0 +1,247 @@
+//! osu!catch helpers: fruit positions, hyperdash detection, and converting osu! beatmaps to catch-friendly ones.
+//!
+//! The hyperdash computation follows osu!lazer's `CatchBeatmapProcessor`, which matches osu!stable.
+//! Only the horizontal position of objects matters in osu!catch.
+
+use crate::algos::difficulty::DifficultyTranslator;
+use crate::analysis::slider_duration;
+use crate::file::beatmap::{BeatmapFile, HitObject, HitObjectParams, Timestamp};
+
+/// Width of the catcher in osu! pixels at CS 5.
+pub const CATCHER_BASE_SIZE: f64 = 106.75;
+
+/// Speed of the catcher while dashing, in osu! pixels per millisecond.
+pub const BASE_DASH_SPEED: f64 = 1.0;
+
+/// Width of the playfield in osu! pixels.
+pub const PLAYFIELD_WIDTH: f64 = 512.0;
+
+/// Distance to a hyperdash (in osu! pixels) under which a dash is considered an edge dash.
+pub const EDGE_DASH_DISTANCE: f64 = 5.0;
+
+/// Grace time given to the player before a hyperdash is required (a quarter of a frame at 60 FPS).
+const HYPERDASH_GRACE_TIME: f64 = 1000.0 / 60.0 / 4.0;
+
+/// Width of the catcher in osu! pixels, depending on CS.
+#[must_use]
+pub fn catcher_width(circle_size: f32) -> f64 {
+	let scale = 0.7f64.mul_add(-(f64::from(circle_size) - 5.0) / 5.0, 1.0);
+	CATCHER_BASE_SIZE * scale.abs()
+}
+
+/// An object that has to be caught: a fruit, or a droplet at the head, repeats and tail of a slider.
+///
+/// Tiny droplets (slider ticks) and bananas (spinners) don't count.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fruit {
+	/// Index of the hit object the fruit comes from.
+	pub index: usize,
+	pub time: Timestamp,
+	pub x: f64,
+}
+
+/// Fruits of a single hit object.
+fn object_fruits(beatmap: &BeatmapFile, index: usize) -> Vec<Fruit> {
+	let hit_object = &beatmap.hit_objects[index];
+	let head = Fruit {
+		index,
+		time: hit_object.time,
+		x: f64::from(hit_object.x),
+	};
+
+	match &hit_object.object_params {
+		HitObjectParams::HitCircle => vec![head],
+		HitObjectParams::Slider {
+			curve_points, slides, ..
+		} => {
+			let mut fruits = vec![head];
+			let (Some(duration), Some(end)) = (slider_duration(beatmap, hit_object), curve_points.last()) else {
+				return fruits;
+			};
+
+			let slide_duration = duration / f64::from((*slides).max(1));
+			for slide in 1..=*slides {
+				// odd slides end at the end of the curve, even slides come back to the head
+				let x = if slide % 2 == 1 { f64::from(end.x) } else { head.x };
+				let time = slide_duration.mul_add(f64::from(slide), hit_object.time);
+				fruits.push(Fruit { index, time, x });
+			}
+
+			fruits
+		}
+		HitObjectParams::Spinner { .. } | HitObjectParams::Hold { .. } => Vec::new(),
+	}
+}
+
+/// Every fruit of a beatmap, in order.
+///
+/// The positions of slider repeats and tails are approximated with the ends of their curve,
+/// which is exact unless the slider's length cuts the curve short.
+#[must_use]
+pub fn fruits(beatmap: &BeatmapFile) -> Vec<Fruit> {
+	let mut fruits: Vec<Fruit> = (0..beatmap.hit_objects.len())
+		.flat_map(|index| object_fruits(beatmap, index))
+		.collect();
+
+	fruits.sort_by(|a, b| a.time.total_cmp(&b.time));
+	fruits
+}
+
+/// Movement between two consecutive fruits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CatchMovement {
+	pub from: Fruit,
+	pub to: Fruit,
+	/// Whether the movement requires a hyperdash.
+	pub hyperdash: bool,
+	/// How many osu! pixels are left before the movement would require a hyperdash.
+	/// It is 0 for hyperdashes, and very small values are known as edge dashes.
+	pub distance_to_hyperdash: f64,
+}
+
+/// Movement state carried from a movement to the next one.
+#[derive(Clone, Copy, Debug)]
+struct HyperdashState {
+	half_catcher_width: f64,
+	last_direction: i8,
+	last_excess: f64,
+}
+
+impl HyperdashState {
+	fn new(beatmap: &BeatmapFile) -> Self {
+		let circle_size = beatmap.difficulty.as_ref().map_or(5.0, |d| d.circle_size);
+		// osu!stable uses the full catcher width here
+		let half_catcher_width = catcher_width(circle_size) / 2.0;
+
+		Self {
+			half_catcher_width,
+			last_direction: 0,
+			last_excess: half_catcher_width,
+		}
+	}
+
+	/// Distance left before moving from `from` to `to` requires a hyperdash (negative if it does).
+	fn distance_to_hyperdash(&self, from: &Fruit, to: &Fruit) -> f64 {
+		let direction = if to.x > from.x { 1 } else { -1 };
+
+		// times are truncated like in osu!stable
+		let time_to_next = to.time.trunc() - from.time.trunc() - HYPERDASH_GRACE_TIME;
+		let excess = if direction == self.last_direction {
+			self.last_excess
+		} else {
+			self.half_catcher_width
+		};
+
+		let distance_to_next = (to.x - from.x).abs() - excess;
+		time_to_next.mul_add(BASE_DASH_SPEED, -distance_to_next)
+	}
+
+	fn advance(&mut self, from: &Fruit, to: &Fruit, distance_to_hyperdash: f64) {
+		self.last_direction = if to.x > from.x { 1 } else { -1 };
+		self.last_excess = if distance_to_hyperdash < 0.0 {
+			self.half_catcher_width
+		} else {
+			distance_to_hyperdash.clamp(0.0, self.half_catcher_width)
+		};
+	}
+}
+
+/// Computes every movement between consecutive fruits, and which ones require a hyperdash.
+#[must_use]
+pub fn movements(beatmap: &BeatmapFile) -> Vec<CatchMovement> {
+	let fruits = fruits(beatmap);
+	let mut state = HyperdashState::new(beatmap);
+
+	(fruits.windows(2))
+		.map(|pair| {
+			let (from, to) = (pair[0], pair[1]);
+			let distance_to_hyperdash = state.distance_to_hyperdash(&from, &to);
+			state.advance(&from, &to, distance_to_hyperdash);
+
+			CatchMovement {
+				from,
+				to,
+				hyperdash: distance_to_hyperdash < 0.0,
+				distance_to_hyperdash: distance_to_hyperdash.max(0.0),
+			}
+		})
+		.collect()
+}
+
+/// Moves a hit object horizontally, along with its slider curve.
+fn shift_x(hit_object: &mut HitObject, dx: f64) {
+	#[allow(clippy::cast_possible_truncation)]
+	let dx = dx as f32;
+
+	hit_object.x += dx;
+	if let HitObjectParams::Slider { curve_points, .. } = &mut hit_object.object_params {
+		for point in curve_points {
+			point.x += dx;
+		}
+	}
+}
+
+/// Converts an osu! beatmap to osu!catch.
+///
+/// Objects are kept inside the playfield horizontally,
+/// and if `allow_hyperdashes` is false, objects are pulled towards the previous one
+/// until reaching them requires neither a hyperdash nor an edge dash.
+/// Only movements between objects are adjusted, not the ones inside of a slider.
+/// Difficulty settings are translated with a [`DifficultyTranslator`].
+pub fn convert_to_catch(beatmap: &mut BeatmapFile, allow_hyperdashes: bool) {
+	let mode = beatmap.general.as_ref().map_or(0, |general| general.mode);
+	DifficultyTranslator::new(mode, 2).apply(beatmap);
+
+	for hit_object in &mut beatmap.hit_objects {
+		let x = f64::from(hit_object.x);
+		let (min_x, max_x) = match &hit_object.object_params {
+			HitObjectParams::Slider { curve_points, .. } => curve_points
+				.iter()
+				.map(|p| f64::from(p.x))
+				.fold((x, x), |(min, max), x| (min.min(x), max.max(x))),
+			_ => (x, x),
+		};
+
+		if min_x < 0.0 {
+			shift_x(hit_object, -min_x);
+		} else if max_x > PLAYFIELD_WIDTH {
+			shift_x(hit_object, (PLAYFIELD_WIDTH - max_x).max(-min_x));
+		}
+	}
+
+	if allow_hyperdashes {
+		return;
+	}
+
+	let mut state = HyperdashState::new(beatmap);
+	let mut prev_last_fruit: Option<Fruit> = None;
+	for index in 0..beatmap.hit_objects.len() {
+		let mut current = object_fruits(beatmap, index);
+		if let (Some(prev), Some(head)) = (prev_last_fruit, current.first()) {
+			let distance_to_hyperdash = state.distance_to_hyperdash(&prev, head);
+			if distance_to_hyperdash < 0.0 {
+				// shrink the distance by what is missing, leaving enough room to not make it an edge dash
+				let distance = ((head.x - prev.x).abs() + distance_to_hyperdash - EDGE_DASH_DISTANCE)
+					.floor()
+					.max(0.0);
+				let target_x = if head.x > prev.x {
+					prev.x + distance
+				} else {
+					prev.x - distance
+				};
+				shift_x(&mut beatmap.hit_objects[index], target_x.round() - head.x);
+				current = object_fruits(beatmap, index);
+			}
+		}
+
+		let mut prev = prev_last_fruit;
+		for fruit in current {
+			if let Some(prev) = prev {
+				let distance_to_hyperdash = state.distance_to_hyperdash(&prev, &fruit);
+				state.advance(&prev, &fruit, distance_to_hyperdash);
+			}
+			prev = Some(fruit);
+		}
+		prev_last_fruit = prev;
+	}
+}
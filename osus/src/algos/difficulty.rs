@@ -0,0 +1,97 @@
+//! Difficulty settings across game modes.
+//!
+//! The same fields of the `[Difficulty]` section mean different things depending on the mode:
+//! CS is the amount of columns in osu!mania and the size of the fruits and catcher in osu!catch,
+//! AR does nothing in osu!taiko and osu!mania, and OD does nothing in osu!catch.
+//! Conversions go through [`DifficultyTranslator`] so that a converted beatmap doesn't keep settings
+//! that only made sense in its original mode.
+
+use crate::file::beatmap::{BeatmapFile, DifficultySection};
+
+/// Key count of osu!mania beatmaps converted from other modes.
+pub const DEFAULT_KEY_COUNT: u8 = 4;
+
+/// Circle size given to beatmaps converted from a mode where CS means something else.
+const NEUTRAL_CIRCLE_SIZE: f32 = 4.0;
+
+/// Value given to settings that do nothing in the target mode.
+const UNUSED_SETTING: f32 = 5.0;
+
+/// Lowest HP of osu!mania beatmaps converted from other modes, which drain much less per note.
+const MANIA_MIN_HP: f32 = 7.0;
+
+/// Maps the difficulty settings of a beatmap from one game mode to another.
+///
+/// Settings that keep their meaning are kept, the ones that mean something else are mapped or reset,
+/// and the ones that do nothing in the target mode are set to neutral values:
+/// - osu!mania gets [`key_count`](Self::key_count) as CS, and enough HP to not fail on the first misses.
+/// - osu!catch keeps the CS of osu! beatmaps, since it scales fruits like it scales circles, and OD follows AR.
+/// - osu!taiko and osu!mania don't use AR, and beatmaps converted from them get their OD as AR.
+#[derive(Clone, Copy, Debug)]
+pub struct DifficultyTranslator {
+	/// Mode the settings are for (0 = osu!, 1 = osu!taiko, 2 = osu!catch, 3 = osu!mania).
+	pub from: u8,
+	/// Mode to translate the settings to.
+	pub to: u8,
+	/// Amount of columns when translating to osu!mania.
+	pub key_count: u8,
+}
+
+impl DifficultyTranslator {
+	#[must_use]
+	pub const fn new(from: u8, to: u8) -> Self {
+		Self {
+			from,
+			to,
+			key_count: DEFAULT_KEY_COUNT,
+		}
+	}
+
+	/// Returns the translator with another key count for osu!mania.
+	#[must_use]
+	pub const fn with_key_count(mut self, key_count: u8) -> Self {
+		self.key_count = key_count;
+		self
+	}
+
+	/// Difficulty settings translated to the target mode.
+	#[must_use]
+	pub fn translate(&self, difficulty: &DifficultySection) -> DifficultySection {
+		let mut translated = difficulty.clone();
+		if self.from == self.to {
+			return translated;
+		}
+
+		// CS only means the same thing between osu! and osu!catch
+		let keeps_circle_size = matches!((self.from, self.to), (0 | 2, 0 | 2));
+		if !keeps_circle_size {
+			translated.circle_size = NEUTRAL_CIRCLE_SIZE;
+		}
+
+		// AR means nothing in osu!taiko and osu!mania, so their OD is the best guess of how hard they are
+		if matches!(self.from, 1 | 3) {
+			translated.approach_rate = difficulty.overall_difficulty;
+		}
+
+		match self.to {
+			1 => translated.approach_rate = UNUSED_SETTING,
+			2 => translated.overall_difficulty = translated.approach_rate,
+			3 => {
+				translated.circle_size = f32::from(self.key_count);
+				translated.approach_rate = UNUSED_SETTING;
+				translated.hp_drain_rate = translated.hp_drain_rate.max(MANIA_MIN_HP);
+			}
+			_ => {}
+		}
+
+		translated
+	}
+
+	/// Sets the mode of a beatmap to the target mode and translates its difficulty settings.
+	pub fn apply(&self, beatmap: &mut BeatmapFile) {
+		beatmap.general.get_or_insert_with(Default::default).mode = self.to;
+		if let Some(difficulty) = &beatmap.difficulty {
+			beatmap.difficulty = Some(self.translate(difficulty));
+		}
+	}
+}
@@ -0,0 +1,299 @@
+//! Conversion of osu!lazer beatmaps (`osu file format v128`) to beatmaps osu!stable can read (`osu file format v14`).
+//!
+//! osu!lazer is more permissive than osu!stable: it keeps sub-millisecond times, lets every control point of a slider
+//! have its own curve type, and accepts values outside of the ranges of the stable editor.
+
+use std::fmt;
+
+use crate::edit::{ChangeSet, ElementChanges};
+use crate::file::beatmap::{BeatmapFile, HitObject, HitObjectParams, HitSound, SliderPoint, Timestamp};
+use crate::point::Rounding;
+use crate::timestamp::format_timestamp;
+use crate::Timestamped;
+
+use super::bezier::BezierConversionError;
+use super::{convert_slider_points_to_legacy, for_each_timestamp_mut};
+
+/// Latest `osu file format` version that osu!stable understands.
+pub const STABLE_FILE_FORMAT: u32 = 14;
+
+/// Something [`to_stable`] had to change with a loss of information, or couldn't change at all.
+#[derive(Clone, Debug)]
+pub enum ConversionWarning {
+	/// A value was outside of the range osu!stable accepts, and got clamped to it.
+	Clamped {
+		field: &'static str,
+		time: Option<Timestamp>,
+		value: f64,
+		clamped: f64,
+	},
+	/// A beat length was written in scientific notation, and will be written as a plain decimal number.
+	ScientificNotation { time: Timestamp, text: String },
+	/// A slider's control points couldn't be converted, and were left as they are.
+	SliderNotConverted {
+		time: Timestamp,
+		error: BezierConversionError,
+	},
+	/// The additions played when releasing a hold were moved to the note starting at its end,
+	/// or dropped if there is none.
+	HoldTailSounds {
+		end_time: Timestamp,
+		moved_to: Option<Timestamp>,
+	},
+}
+
+impl fmt::Display for ConversionWarning {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Clamped {
+				field,
+				time,
+				value,
+				clamped,
+			} => {
+				if let Some(time) = time {
+					write!(f, "{}: ", format_timestamp(*time))?;
+				}
+				write!(f, "clamped {field} {value} to {clamped}")
+			}
+			Self::ScientificNotation { time, text } => write!(
+				f,
+				"{}: rewrote beat length {text:?} without scientific notation",
+				format_timestamp(*time)
+			),
+			Self::SliderNotConverted { time, error } => {
+				write!(f, "{}: could not convert slider ({error})", format_timestamp(*time))
+			}
+			Self::HoldTailSounds {
+				end_time,
+				moved_to: Some(_),
+			} => write!(
+				f,
+				"{}: moved the release sounds of a hold to the note starting there",
+				format_timestamp(*end_time)
+			),
+			Self::HoldTailSounds {
+				end_time,
+				moved_to: None,
+			} => write!(
+				f,
+				"{}: dropped the release sounds of a hold, osu!stable can't play them",
+				format_timestamp(*end_time)
+			),
+		}
+	}
+}
+
+/// What [`to_stable`] changed in a beatmap.
+#[derive(Clone, Debug, Default)]
+pub struct ConversionReport {
+	/// Amount of times that were rounded down to a whole millisecond.
+	pub floored_times: usize,
+	/// Amount of hit objects whose position or slider control points were rounded to whole osu! pixels.
+	pub rounded_positions: usize,
+	/// Amount of sliders whose control points were converted to curve types osu!stable understands.
+	pub converted_sliders: usize,
+	/// Amount of events removed because they were duplicated or empty (see [`BeatmapFile::normalize_events`]).
+	pub removed_events: usize,
+	pub warnings: Vec<ConversionWarning>,
+	pub changes: ChangeSet,
+}
+
+/// Clamps a value to a range, recording a warning if it was outside of it.
+fn clamp_value<T>(
+	warnings: &mut Vec<ConversionWarning>,
+	field: &'static str,
+	time: Option<Timestamp>,
+	value: &mut T,
+	min: T,
+	max: T,
+) where
+	T: Copy + PartialOrd + Into<f64>,
+{
+	let clamped = if *value < min {
+		min
+	} else if *value > max {
+		max
+	} else {
+		return;
+	};
+
+	warnings.push(ConversionWarning::Clamped {
+		field,
+		time,
+		value: (*value).into(),
+		clamped: clamped.into(),
+	});
+	*value = clamped;
+}
+
+/// Removes the release sounds of osu!mania holds, which osu!stable can't play.
+///
+/// Their additions are moved to a note starting at the end of the hold if there is one, keeping them audible.
+fn remove_hold_tails(hit_objects: &mut [HitObject], warnings: &mut Vec<ConversionWarning>) {
+	for index in 0..hit_objects.len() {
+		let HitObjectParams::Hold { end_time, tail } = &mut hit_objects[index].object_params else {
+			continue;
+		};
+
+		let end_time = *end_time;
+		let Some(tail) = tail.take() else {
+			continue;
+		};
+
+		let additions = tail.hit_sound & (HitSound::WHISTLE | HitSound::FINISH | HitSound::CLAP);
+		if additions == HitSound::NONE {
+			continue;
+		}
+
+		let target = (0..hit_objects.len()).find(|&i| i != index && hit_objects[i].basically_at(end_time));
+		if let Some(target) = target {
+			let target = &mut hit_objects[target];
+			if target.hit_sound.iter_additions().next().is_none() {
+				target.hit_sample.addition_set = tail.sample_set.addition_set;
+			}
+			target.hit_sound |= additions;
+		}
+
+		warnings.push(ConversionWarning::HoldTailSounds {
+			end_time,
+			moved_to: target.map(|target| hit_objects[target].time),
+		});
+	}
+}
+
+/// Converts an osu!lazer beatmap so that osu!stable can read it.
+///
+/// - every time is rounded down to a whole millisecond;
+/// - slider control points are converted to curve types osu!stable understands
+///   (see [`convert_slider_points_to_legacy`]);
+/// - positions of hit objects and slider control points are rounded to whole osu! pixels;
+/// - difficulty settings, slider velocities and volumes are clamped to the ranges of the osu!stable editor;
+/// - beat lengths written in scientific notation are rewritten as plain decimal numbers;
+/// - the release sounds of osu!mania holds are removed, moving their additions to a note starting at the same time;
+/// - events are put in the order osu!stable expects, without duplicates;
+/// - the file format is set to [`STABLE_FILE_FORMAT`].
+#[allow(clippy::too_many_lines)]
+pub fn to_stable(beatmap: &mut BeatmapFile) -> ConversionReport {
+	let before = beatmap.clone();
+	let mut report = ConversionReport::default();
+	let warnings = &mut report.warnings;
+
+	for_each_timestamp_mut(beatmap, |time| {
+		if time.fract() != 0.0 {
+			*time = time.floor();
+			report.floored_times += 1;
+		}
+	});
+
+	if let Some(difficulty) = &mut beatmap.difficulty {
+		clamp_value(warnings, "HP", None, &mut difficulty.hp_drain_rate, 0.0, 10.0);
+		clamp_value(warnings, "CS", None, &mut difficulty.circle_size, 0.0, 10.0);
+		clamp_value(warnings, "OD", None, &mut difficulty.overall_difficulty, 0.0, 10.0);
+		clamp_value(warnings, "AR", None, &mut difficulty.approach_rate, 0.0, 10.0);
+		clamp_value(
+			warnings,
+			"slider multiplier",
+			None,
+			&mut difficulty.slider_multiplier,
+			0.4,
+			3.6,
+		);
+		clamp_value(
+			warnings,
+			"slider tick rate",
+			None,
+			&mut difficulty.slider_tick_rate,
+			0.5,
+			8.0,
+		);
+	}
+
+	if let Some(general) = &mut beatmap.general {
+		clamp_value(warnings, "stack leniency", None, &mut general.stack_leniency, 0.0, 1.0);
+	}
+
+	for timing_point in &mut beatmap.timing_points {
+		let time = Some(timing_point.time);
+		clamp_value(warnings, "volume", time, &mut timing_point.volume, 0, 100);
+		// slider velocities from 0.1x to 10x, invalid ones are left to `repair_timing`
+		if !timing_point.uninherited && timing_point.beat_length < 0.0 {
+			clamp_value(
+				warnings,
+				"beat length",
+				time,
+				&mut timing_point.beat_length,
+				-1000.0,
+				-10.0,
+			);
+		}
+
+		let is_scientific = (timing_point.raw_beat_length.as_ref()).is_some_and(|raw| raw.text().contains(['e', 'E']));
+		if is_scientific {
+			if let Some(raw) = timing_point.raw_beat_length.take() {
+				warnings.push(ConversionWarning::ScientificNotation {
+					time: timing_point.time,
+					text: raw.text().to_owned(),
+				});
+			}
+		}
+	}
+
+	for hit_object in &mut beatmap.hit_objects {
+		let time = Some(hit_object.time);
+		clamp_value(
+			warnings,
+			"hit sample volume",
+			time,
+			&mut hit_object.hit_sample.volume,
+			0,
+			100,
+		);
+
+		let HitObjectParams::Slider {
+			first_curve_type,
+			curve_points,
+			..
+		} = &mut hit_object.object_params
+		else {
+			continue;
+		};
+
+		let mut points = Vec::with_capacity(curve_points.len() + 1);
+		points.push(SliderPoint::new(*first_curve_type, hit_object.x, hit_object.y));
+		points.extend_from_slice(curve_points);
+
+		match convert_slider_points_to_legacy(&points) {
+			Ok(mut points) => {
+				let head = points.remove(0);
+				*first_curve_type = head.curve_type;
+				*curve_points = points;
+				report.converted_sliders += 1;
+			}
+			Err(error) => warnings.push(ConversionWarning::SliderNotConverted {
+				time: hit_object.time,
+				error,
+			}),
+		}
+	}
+
+	// after converting sliders, whose new control points can land between pixels
+	for hit_object in &mut beatmap.hit_objects {
+		if hit_object.round_positions(Rounding::Round) {
+			report.rounded_positions += 1;
+		}
+	}
+
+	remove_hold_tails(&mut beatmap.hit_objects, warnings);
+
+	let event_count = beatmap.events.len();
+	beatmap.normalize_events();
+	report.removed_events = event_count - beatmap.events.len();
+
+	beatmap.osu_file_format = STABLE_FILE_FORMAT;
+
+	// events are the only elements put in another order
+	report.changes = ChangeSet::paired(&before, beatmap);
+	report.changes.events = ElementChanges::diff(&before.events, &beatmap.events);
+	report
+}
@@ -0,0 +1,503 @@
+//! osu!mania pattern transforms, changing which column notes are in while keeping their times,
+//! and conversion of osu! beatmaps to osu!mania.
+
+use crate::algos::difficulty::{DifficultyTranslator, DEFAULT_KEY_COUNT};
+use crate::analysis::end_time;
+use crate::file::beatmap::{BeatmapFile, HitObject, HitObjectParams, HitObjectType, HitSample, HitSound, Timestamp};
+use crate::timeline::TimingTimeline;
+use crate::{ExtTimestamped, Tolerance};
+
+/// Indices of the notes of each column, in the order of the beatmap's hit objects.
+///
+/// Hit objects need to be sorted for each column to be in chronological order.
+#[must_use]
+pub fn columns(beatmap: &BeatmapFile) -> Vec<Vec<usize>> {
+	let key_count = beatmap.key_count();
+	let mut columns = vec![Vec::new(); key_count as usize];
+	for (index, hit_object) in beatmap.hit_objects.iter().enumerate() {
+		columns[hit_object.column(key_count) as usize].push(index);
+	}
+
+	columns
+}
+
+/// Moves every note to the column given by `map`, which is given the current column.
+fn remap_columns(beatmap: &mut BeatmapFile, map: impl Fn(u32) -> u32) {
+	let key_count = beatmap.key_count();
+	for hit_object in &mut beatmap.hit_objects {
+		let column = hit_object.column(key_count);
+		hit_object.x = HitObject::column_x(map(column).min(key_count - 1), key_count);
+	}
+}
+
+/// Mirrors the columns of every note, like the Mirror mod.
+pub fn mirror(beatmap: &mut BeatmapFile) {
+	let key_count = beatmap.key_count();
+	remap_columns(beatmap, |column| key_count - 1 - column);
+}
+
+/// Small deterministic random number generator (`SplitMix64`), so that a seed always gives the same pattern.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+	const fn next(&mut self) -> u64 {
+		self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+		let mut z = self.0;
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+		z ^ (z >> 31)
+	}
+
+	/// Random number in `0..bound`.
+	fn below(&mut self, bound: u32) -> u32 {
+		#[allow(clippy::cast_possible_truncation)]
+		let n = (self.next() % u64::from(bound.max(1))) as u32;
+		n
+	}
+}
+
+/// Shuffles the columns of the beatmap with the given seed, like the Random mod.
+///
+/// Every note of a column ends up in the same new column, so patterns are kept but mixed up.
+/// Returns the new column of each original column.
+pub fn shuffle_columns(beatmap: &mut BeatmapFile, seed: u64) -> Vec<u32> {
+	let key_count = beatmap.key_count();
+	let mut rng = SplitMix64(seed);
+
+	// Fisher-Yates shuffle
+	let mut permutation: Vec<u32> = (0..key_count).collect();
+	for i in (1..permutation.len()).rev() {
+		#[allow(clippy::cast_possible_truncation)]
+		let j = rng.below(i as u32 + 1) as usize;
+		permutation.swap(i, j);
+	}
+
+	remap_columns(beatmap, |column| permutation[column as usize]);
+	permutation
+}
+
+/// Time at which a note stops occupying its column.
+const fn note_end(hit_object: &HitObject) -> Timestamp {
+	match hit_object.object_params {
+		HitObjectParams::Hold { end_time, .. } => end_time,
+		_ => hit_object.time,
+	}
+}
+
+/// Breaks up jacks (notes in the same column on consecutive rows) longer than `max_jack_len` notes,
+/// moving the offending notes to the closest free column.
+///
+/// A column is free if no note of the current or previous row is in it, and no hold is still going on in it.
+/// Notes with no free column are left as they are. Returns the amount of moved notes.
+pub fn avoid_jacks(beatmap: &mut BeatmapFile, max_jack_len: usize) -> usize {
+	let key_count = beatmap.key_count();
+	let columns = key_count as usize;
+	beatmap.sort_and_fix();
+
+	let mut moved = 0;
+	let mut jack_lengths = vec![0_usize; columns];
+	let mut busy_until = vec![f64::NEG_INFINITY; columns];
+	let mut prev_row = vec![false; columns];
+
+	for row in beatmap.hit_objects.group_timestamped_mut() {
+		let time = row.first().map_or(0.0, |ho| ho.time);
+		let mut current_row = vec![false; columns];
+		for hit_object in row.iter() {
+			current_row[hit_object.column(key_count) as usize] = true;
+		}
+
+		for hit_object in row.iter_mut() {
+			let column = hit_object.column(key_count) as usize;
+			if !prev_row[column] || jack_lengths[column] < max_jack_len.max(1) {
+				continue;
+			}
+
+			let is_free = |c: usize| !current_row[c] && !prev_row[c] && busy_until[c] < time;
+			let free = (1..columns)
+				.flat_map(|distance| [column.checked_sub(distance), Some(column + distance)])
+				.flatten()
+				.find(|&c| c < columns && is_free(c));
+
+			if let Some(free) = free {
+				current_row[column] = false;
+				current_row[free] = true;
+
+				#[allow(clippy::cast_possible_truncation)]
+				let free_column = free as u32;
+				hit_object.x = HitObject::column_x(free_column, key_count);
+				moved += 1;
+			}
+		}
+
+		for hit_object in row.iter() {
+			let column = hit_object.column(key_count) as usize;
+			busy_until[column] = busy_until[column].max(note_end(hit_object));
+		}
+
+		for (column, length) in jack_lengths.iter_mut().enumerate() {
+			*length = if current_row[column] {
+				if prev_row[column] {
+					*length + 1
+				} else {
+					1
+				}
+			} else {
+				0
+			};
+		}
+
+		prev_row = current_row;
+	}
+
+	moved
+}
+
+/// Turns rice notes into holds lasting until `gap_ms` before the next note of their column.
+///
+/// Holds that would be shorter than `min_len_ms` are left as rice, and so is the last note of each column.
+/// Returns the amount of converted notes.
+pub fn to_long_notes(beatmap: &mut BeatmapFile, gap_ms: f64, min_len_ms: f64) -> usize {
+	beatmap.sort_and_fix();
+
+	let mut converted = 0;
+	for column in columns(beatmap) {
+		for pair in column.windows(2) {
+			let next_time = beatmap.hit_objects[pair[1]].time;
+			let hit_object = &mut beatmap.hit_objects[pair[0]];
+			if !hit_object.is_hit_circle() {
+				continue;
+			}
+
+			let end_time = (next_time - gap_ms).round();
+			if end_time - hit_object.time < min_len_ms.max(1.0) {
+				continue;
+			}
+
+			hit_object.object_type = HitObjectType::Hold;
+			hit_object.object_params = HitObjectParams::Hold { end_time, tail: None };
+			converted += 1;
+		}
+	}
+
+	converted
+}
+
+/// Turns every hold into a rice note, returning the amount of converted notes.
+pub fn to_rice(beatmap: &mut BeatmapFile) -> usize {
+	let mut converted = 0;
+	for hit_object in &mut beatmap.hit_objects {
+		if hit_object.is_osu_mania_hold() {
+			hit_object.object_type = HitObjectType::HitCircle;
+			hit_object.object_params = HitObjectParams::HitCircle;
+			converted += 1;
+		}
+	}
+
+	converted
+}
+
+/// Time in milliseconds a column stays busy after a note ends, so that two notes don't end up at the same time.
+const COLUMN_GAP: f64 = Tolerance::DEFAULT.same_time;
+
+/// What to do with a note starting while its column is still busy, see [`resolve_conflicts`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictPolicy {
+	/// Move the note to the closest free column, or drop it if there are none.
+	MoveToFreeColumn,
+	/// Merge the note into the one occupying the column, which becomes a hold if the note ends clearly later.
+	Merge,
+	/// Remove the note.
+	Drop,
+}
+
+/// Fixes notes that start in a column at (nearly) the same time as another note or during a hold,
+/// which osu! doesn't accept.
+///
+/// Hit objects are sorted by time first. Returns the amount of conflicting notes that were moved, merged or dropped.
+pub fn resolve_conflicts(hit_objects: &mut Vec<HitObject>, key_count: u32, policy: ConflictPolicy) -> usize {
+	let columns = key_count.max(1) as usize;
+	hit_objects.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+	let mut resolved = 0;
+	let mut kept: Vec<HitObject> = Vec::with_capacity(hit_objects.len());
+	// index in `kept` of the last note of each column
+	let mut occupants: Vec<Option<usize>> = vec![None; columns];
+	let is_busy = |kept: &[HitObject], occupant: Option<usize>, time: Timestamp| {
+		occupant.is_some_and(|i| time - note_end(&kept[i]) < COLUMN_GAP)
+	};
+
+	for mut hit_object in hit_objects.drain(..) {
+		let column = hit_object.column(key_count) as usize;
+		if !is_busy(&kept, occupants[column], hit_object.time) {
+			occupants[column] = Some(kept.len());
+			kept.push(hit_object);
+			continue;
+		}
+
+		resolved += 1;
+		match policy {
+			ConflictPolicy::Drop => {}
+			ConflictPolicy::Merge => {
+				let Some(occupant) = occupants[column] else {
+					continue;
+				};
+
+				let occupant = &mut kept[occupant];
+				let end_time = note_end(&hit_object);
+				if end_time - note_end(occupant) >= COLUMN_GAP {
+					// the release sounds of the merged note now play at the end of the occupant
+					let tail = match hit_object.object_params {
+						HitObjectParams::Hold { tail, .. } => tail,
+						_ => None,
+					};
+					occupant.object_type = HitObjectType::Hold;
+					occupant.object_params = HitObjectParams::Hold { end_time, tail };
+				}
+			}
+			ConflictPolicy::MoveToFreeColumn => {
+				let free = (1..columns)
+					.flat_map(|distance| [column.checked_sub(distance), Some(column + distance)])
+					.flatten()
+					.find(|&c| c < columns && !is_busy(&kept, occupants[c], hit_object.time));
+
+				if let Some(free) = free {
+					#[allow(clippy::cast_possible_truncation)]
+					let free_column = free as u32;
+					hit_object.x = HitObject::column_x(free_column, key_count);
+					occupants[free] = Some(kept.len());
+					kept.push(hit_object);
+				}
+			}
+		}
+	}
+
+	*hit_objects = kept;
+	resolved
+}
+
+/// How to turn spinners into osu!mania notes, see [`convert_spinners`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpinnerPolicy {
+	/// Notes alternating between the two middle columns every `1/divisor` of a beat.
+	Roll { divisor: u32 },
+	/// A hold in the middle column.
+	Hold,
+	/// Notes going through the columns from left to right and back every `1/divisor` of a beat.
+	Stairs { divisor: u32 },
+	/// No notes at all.
+	Skip,
+}
+
+/// Column of the `n`th note of a spinner converted with `policy`.
+const fn spinner_column(policy: SpinnerPolicy, n: u32, key_count: u32) -> u32 {
+	let middle = key_count / 2;
+	match policy {
+		SpinnerPolicy::Roll { .. } if key_count > 1 => middle - 1 + n % 2,
+		SpinnerPolicy::Stairs { .. } if key_count > 1 => {
+			let step = n % (2 * (key_count - 1));
+			if step < key_count {
+				step
+			} else {
+				2 * (key_count - 1) - step
+			}
+		}
+		_ => middle,
+	}
+}
+
+/// Replaces every spinner with osu!mania notes in `key_count` columns, lasting as long as the spinner.
+///
+/// This is meant for beatmaps being converted to osu!mania, whose circle size isn't a key count yet.
+/// The hitsounds of a spinner go to its first note. Returns the amount of converted spinners.
+pub fn convert_spinners(beatmap: &mut BeatmapFile, key_count: u32, policy: SpinnerPolicy) -> usize {
+	let key_count = key_count.max(1);
+	let timeline = TimingTimeline::new(&beatmap.timing_points);
+
+	let mut converted = 0;
+	let mut hit_objects = Vec::with_capacity(beatmap.hit_objects.len());
+	for hit_object in beatmap.hit_objects.drain(..) {
+		let HitObjectParams::Spinner { end_time } = hit_object.object_params else {
+			hit_objects.push(hit_object);
+			continue;
+		};
+
+		converted += 1;
+		let note = |time: Timestamp, column: u32, first: bool| HitObject {
+			x: HitObject::column_x(column, key_count),
+			y: 192.0,
+			time,
+			object_type: HitObjectType::HitCircle,
+			combo_color_skip: None,
+			hit_sound: if first { hit_object.hit_sound } else { HitSound::NONE },
+			object_params: HitObjectParams::HitCircle,
+			hit_sample: if first {
+				hit_object.hit_sample.clone()
+			} else {
+				HitSample::default()
+			},
+		};
+
+		let divisor = match policy {
+			SpinnerPolicy::Skip => continue,
+			SpinnerPolicy::Hold => {
+				let mut hold = note(hit_object.time, spinner_column(policy, 0, key_count), true);
+				if end_time > hit_object.time {
+					hold.object_type = HitObjectType::Hold;
+					hold.object_params = HitObjectParams::Hold { end_time, tail: None };
+				}
+				hit_objects.push(hold);
+				continue;
+			}
+			SpinnerPolicy::Roll { divisor } | SpinnerPolicy::Stairs { divisor } => divisor.max(1),
+		};
+
+		let step = (timeline.beat_length_at(hit_object.time))
+			.filter(|beat_length| *beat_length > 0.0)
+			.map_or(f64::INFINITY, |beat_length| beat_length / f64::from(divisor));
+
+		let mut n = 0;
+		let mut time = hit_object.time;
+		while time <= end_time || n == 0 {
+			hit_objects.push(note(time.round(), spinner_column(policy, n, key_count), n == 0));
+			n += 1;
+			time = f64::from(n).mul_add(step, hit_object.time);
+		}
+	}
+
+	hit_objects.sort_by(|a, b| a.time.total_cmp(&b.time));
+	beatmap.hit_objects = hit_objects;
+	converted
+}
+
+/// Options for [`convert_to_mania`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ManiaConversionOptions {
+	pub key_count: u8,
+	/// What spinners become, see [`convert_spinners`].
+	pub spinners: SpinnerPolicy,
+	/// How notes ending up in a busy column are fixed, see [`resolve_conflicts`].
+	pub conflicts: ConflictPolicy,
+}
+
+/// What [`convert_to_mania`] did to a beatmap.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ManiaConversionReport {
+	/// Amount of converted spinners.
+	pub spinners: usize,
+	/// Amount of notes that started in a busy column, see [`resolve_conflicts`].
+	pub conflicts: usize,
+}
+
+impl Default for ManiaConversionOptions {
+	fn default() -> Self {
+		Self {
+			key_count: DEFAULT_KEY_COUNT,
+			spinners: SpinnerPolicy::Hold,
+			conflicts: ConflictPolicy::MoveToFreeColumn,
+		}
+	}
+}
+
+/// Converts an osu! beatmap to osu!mania.
+///
+/// Every object goes to the column under its position. Hit circles become notes and sliders become holds lasting
+/// until their end, playing the hitsound of their head. Spinners are converted according to `options.spinners`.
+/// Difficulty settings are translated with a [`DifficultyTranslator`].
+///
+/// Objects close to each other often end up in the same column, like a slider's end and the next circle,
+/// which osu! doesn't accept. So the conversion ends with [`resolve_conflicts`], using `options.conflicts`.
+pub fn convert_to_mania(beatmap: &mut BeatmapFile, options: ManiaConversionOptions) -> ManiaConversionReport {
+	let key_count = options.key_count.max(1);
+	let columns = u32::from(key_count);
+
+	// slider durations need the difficulty settings of the original mode
+	let end_times: Vec<Timestamp> = (beatmap.hit_objects.iter())
+		.map(|hit_object| end_time(beatmap, hit_object))
+		.collect();
+
+	for (hit_object, end_time) in beatmap.hit_objects.iter_mut().zip(end_times) {
+		if hit_object.is_spinner() {
+			continue;
+		}
+
+		hit_object.x = HitObject::column_x(hit_object.column(columns), columns);
+		hit_object.y = 192.0;
+		hit_object.combo_color_skip = None;
+
+		if let HitObjectParams::Slider { edge_hitsounds, .. } = &hit_object.object_params {
+			hit_object.hit_sound = edge_hitsounds.first().copied().unwrap_or(hit_object.hit_sound);
+			let end_time = end_time.round();
+			if end_time - hit_object.time >= COLUMN_GAP {
+				hit_object.object_type = HitObjectType::Hold;
+				hit_object.object_params = HitObjectParams::Hold { end_time, tail: None };
+			} else {
+				hit_object.object_type = HitObjectType::HitCircle;
+				hit_object.object_params = HitObjectParams::HitCircle;
+			}
+		}
+	}
+
+	let spinners = convert_spinners(beatmap, columns, options.spinners);
+	let conflicts = resolve_conflicts(&mut beatmap.hit_objects, columns, options.conflicts);
+
+	let mode = beatmap.general.as_ref().map_or(0, |general| general.mode);
+	DifficultyTranslator::new(mode, 3)
+		.with_key_count(key_count)
+		.apply(beatmap);
+	ManiaConversionReport { spinners, conflicts }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+	use super::*;
+	use crate::file::beatmap::parsing::parse_osu_str;
+
+	#[test]
+	fn spinners_follow_their_policy() {
+		let contents =
+			"osu file format v14\n\n[General]\nMode: 0\n\n[Difficulty]\nCircleSize:4\nSliderMultiplier:1\n\n\
+			[TimingPoints]\n0,500,4,1,0,100,1,0\n\n[HitObjects]\n0,0,0,1,0,0:0:0:0:\n\
+			500,192,500,2,0,L|400:192,1,100,2|0,0:0|0:0,0:0:0:0:\n256,192,2000,12,0,3000,0:0:0:0:\n";
+		let beatmap = parse_osu_str(contents).unwrap();
+
+		let mut converted = beatmap.clone();
+		let options = ManiaConversionOptions {
+			key_count: 4,
+			spinners: SpinnerPolicy::Stairs { divisor: 2 },
+			conflicts: ConflictPolicy::Drop,
+		};
+		assert_eq!(convert_to_mania(&mut converted, options).spinners, 1);
+		assert_eq!(converted.key_count(), 4);
+		assert_eq!(converted.general.as_ref().unwrap().mode, 3);
+
+		let columns: Vec<u32> = converted.hit_objects.iter().map(|ho| ho.column(4)).collect();
+		assert_eq!(columns, [0, 3, 0, 1, 2, 3, 2]);
+		assert!(matches!(
+			converted.hit_objects[1].object_params,
+			HitObjectParams::Hold { end_time, .. } if end_time == 1000.0
+		));
+		assert_eq!(converted.hit_objects[1].hit_sound, HitSound::WHISTLE);
+
+		let mut skipped = beatmap;
+		let options = ManiaConversionOptions {
+			spinners: SpinnerPolicy::Skip,
+			..ManiaConversionOptions::default()
+		};
+		convert_to_mania(&mut skipped, options);
+		assert_eq!(skipped.hit_objects.len(), 2);
+	}
+
+	#[test]
+	fn conversions_resolve_conflicts() {
+		// the slider ends where the circle starts, in the same column
+		let contents = "osu file format v14\n\n[General]\nMode: 0\n\n[Difficulty]\nSliderMultiplier:1\n\n\
+			[TimingPoints]\n0,500,4,1,0,100,1,0\n\n[HitObjects]\n0,192,0,2,0,L|100:192,1,100\n100,192,500,1,0,0:0:0:0:\n";
+		let mut beatmap = parse_osu_str(contents).unwrap();
+
+		let report = convert_to_mania(&mut beatmap, ManiaConversionOptions::default());
+		assert_eq!(report.conflicts, 1);
+		let columns: Vec<u32> = beatmap.hit_objects.iter().map(|ho| ho.column(4)).collect();
+		assert_eq!(columns, [0, 1]);
+	}
+}
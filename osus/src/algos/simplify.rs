@@ -0,0 +1,195 @@
+//! Automated difficulty reduction, to make an easier difficulty out of an existing one.
+//!
+//! This is a heuristic starting point rather than a finished difficulty:
+//! dense sections get every other object removed, sliders lose their repeats and get shortened to musical boundaries,
+//! and difficulty settings are lowered. Spacing is left untouched, so the result still needs to be mapped over.
+
+use crate::file::beatmap::{BeatmapFile, HitObject, HitObjectParams, Timestamp};
+use crate::timeline::TimingTimeline;
+
+/// Options of [`simplify`].
+#[derive(Clone, Debug)]
+pub struct SimplifyOptions {
+	/// Minimum gap between the end of an object and the start of the next one, in beats.
+	pub min_gap_beats: f64,
+	/// Slider lengths are snapped down to multiples of `1 / slider_snap_divisor` beats.
+	pub slider_snap_divisor: u32,
+	/// Whether slider repeats are removed.
+	pub remove_repeats: bool,
+	/// AR, OD, CS and HP are lowered to these values if they are higher.
+	pub approach_rate: Option<f32>,
+	pub overall_difficulty: Option<f32>,
+	pub circle_size: Option<f32>,
+	pub hp_drain_rate: Option<f32>,
+	/// Difficulty name of the simplified beatmap.
+	pub version: String,
+}
+
+impl Default for SimplifyOptions {
+	/// Options going from a Hard to a Normal.
+	fn default() -> Self {
+		Self {
+			min_gap_beats: 1.0,
+			slider_snap_divisor: 2,
+			remove_repeats: true,
+			approach_rate: Some(6.0),
+			overall_difficulty: Some(5.0),
+			circle_size: Some(3.5),
+			hp_drain_rate: Some(4.0),
+			version: "Normal".to_owned(),
+		}
+	}
+}
+
+/// Amount of beats a single slide of a slider lasts.
+fn slide_beats(hit_object: &HitObject, timeline: &TimingTimeline, slider_multiplier: f64) -> Option<f64> {
+	let HitObjectParams::Slider { length, .. } = hit_object.object_params else {
+		return None;
+	};
+
+	let velocity = slider_multiplier * 100.0 * timeline.sv_at(hit_object.time);
+	(velocity > 0.0).then(|| length / velocity)
+}
+
+/// Time at which a hit object ends.
+fn end_time(hit_object: &HitObject, timeline: &TimingTimeline, slider_multiplier: f64) -> Timestamp {
+	match hit_object.object_params {
+		HitObjectParams::Slider { slides, .. } => {
+			let beats = slide_beats(hit_object, timeline, slider_multiplier).unwrap_or_default();
+			let beat_length = timeline.beat_length_at(hit_object.time).unwrap_or_default();
+			(beats * beat_length).mul_add(f64::from(slides), hit_object.time)
+		}
+		HitObjectParams::Spinner { end_time } | HitObjectParams::Hold { end_time, .. } => end_time,
+		HitObjectParams::HitCircle => hit_object.time,
+	}
+}
+
+/// Changes the length of a slider so that a single slide lasts the given amount of beats.
+fn set_slide_beats(hit_object: &mut HitObject, beats: f64, timeline: &TimingTimeline, slider_multiplier: f64) {
+	let velocity = slider_multiplier * 100.0 * timeline.sv_at(hit_object.time);
+	if let HitObjectParams::Slider { length, .. } = &mut hit_object.object_params {
+		*length = beats * velocity;
+	}
+}
+
+/// Shortens a slider so that it ends before `deadline`, on a multiple of `1 / divisor` beats.
+///
+/// Returns false if the slider would have to be shorter than `1 / divisor` beats.
+fn shorten_slider(
+	hit_object: &mut HitObject,
+	deadline: Timestamp,
+	divisor: f64,
+	timeline: &TimingTimeline,
+	slider_multiplier: f64,
+) -> bool {
+	let HitObjectParams::Slider { slides, .. } = hit_object.object_params else {
+		return true;
+	};
+
+	let Some(beat_length) = timeline.beat_length_at(hit_object.time).filter(|bl| *bl > 0.0) else {
+		return true;
+	};
+
+	let Some(current_beats) = slide_beats(hit_object, timeline, slider_multiplier) else {
+		return true;
+	};
+
+	let available_beats = (deadline - hit_object.time) / beat_length / f64::from(slides.max(1));
+	let beats = current_beats.min(available_beats).mul_add(divisor, 0.01).floor() / divisor;
+	if beats <= 0.0 {
+		return false;
+	}
+
+	// sliders that are already on a boundary keep their exact length
+	if beats < current_beats - 0.01 / divisor {
+		set_slide_beats(hit_object, beats, timeline, slider_multiplier);
+	}
+	true
+}
+
+/// Makes an easier difficulty out of a beatmap, returning the amount of removed hit objects.
+///
+/// Objects are kept greedily: an object is removed if it starts less than `min_gap_beats` after the end
+/// of the previous kept object, unless shortening that previous object (if it is a slider) makes enough room.
+/// In a regular stream or a stream of jumps, this removes every other object.
+/// New combos of removed objects are carried over to the next kept object.
+pub fn simplify(beatmap: &mut BeatmapFile, options: &SimplifyOptions) -> usize {
+	beatmap.sort_and_fix();
+
+	let timeline = TimingTimeline::new(&beatmap.timing_points);
+	let slider_multiplier = beatmap
+		.difficulty
+		.as_ref()
+		.map_or(1.4, |difficulty| f64::from(difficulty.slider_multiplier));
+	let divisor = f64::from(options.slider_snap_divisor.max(1));
+
+	let hit_objects = std::mem::take(&mut beatmap.hit_objects);
+	let original_count = hit_objects.len();
+
+	let mut kept: Vec<HitObject> = Vec::with_capacity(hit_objects.len());
+	let mut pending_new_combo = None;
+	for mut hit_object in hit_objects {
+		if options.remove_repeats {
+			if let HitObjectParams::Slider { slides, .. } = &mut hit_object.object_params {
+				*slides = 1;
+			}
+		}
+
+		// sliders always end on a musical boundary
+		if hit_object.is_slider() {
+			shorten_slider(&mut hit_object, f64::INFINITY, divisor, &timeline, slider_multiplier);
+		}
+
+		let min_gap = timeline.beat_length_at(hit_object.time).unwrap_or_default() * options.min_gap_beats;
+		// a bit of leeway for rounding errors and unsnapped objects
+		let deadline = hit_object.time - min_gap + 2.0;
+
+		let fits = match kept.last_mut() {
+			None => true,
+			Some(prev) if end_time(prev, &timeline, slider_multiplier) <= deadline => true,
+			Some(prev) if prev.is_slider() && prev.time <= deadline => {
+				let mut shortened = prev.clone();
+				let fits = shorten_slider(&mut shortened, deadline, divisor, &timeline, slider_multiplier);
+				if fits {
+					*prev = shortened;
+				}
+				fits
+			}
+			Some(_) => false,
+		};
+
+		if fits {
+			if hit_object.combo_color_skip.is_none() {
+				hit_object.combo_color_skip = pending_new_combo.take();
+			}
+			pending_new_combo = None;
+			kept.push(hit_object);
+		} else if hit_object.combo_color_skip.is_some() {
+			pending_new_combo = hit_object.combo_color_skip;
+		}
+	}
+
+	let removed = original_count - kept.len();
+	beatmap.hit_objects = kept;
+	beatmap.sort_and_fix();
+
+	if let Some(difficulty) = &mut beatmap.difficulty {
+		let lower = |value: &mut f32, target: Option<f32>| {
+			if let Some(target) = target {
+				*value = value.min(target);
+			}
+		};
+
+		lower(&mut difficulty.approach_rate, options.approach_rate);
+		lower(&mut difficulty.overall_difficulty, options.overall_difficulty);
+		lower(&mut difficulty.circle_size, options.circle_size);
+		lower(&mut difficulty.hp_drain_rate, options.hp_drain_rate);
+	}
+
+	if let Some(metadata) = &mut beatmap.metadata {
+		metadata.version.clone_from(&options.version);
+		metadata.beatmap_id = None;
+	}
+
+	removed
+}
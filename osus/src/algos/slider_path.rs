@@ -0,0 +1,241 @@
+//! Slider path geometry: turning the control points of a slider into the path the slider ball follows.
+//!
+//! Every curve type is first converted to bezier anchors (see [`bezier`](super::bezier)),
+//! which are then flattened into a polyline and cut (or extended) to the slider's declared length, like osu! does.
+
+use crate::file::beatmap::{HitObject, HitObjectParams, SliderCurveType, SliderPoint};
+use crate::point::{Point, Rounding};
+
+use super::bezier::convert_to_bezier_anchors;
+
+/// Maximum distance in osu! pixels between two consecutive points of a flattened bezier curve.
+const BEZIER_TOLERANCE: f64 = 2.0;
+
+/// Maximum amount of points a single bezier curve is flattened into.
+const MAX_BEZIER_STEPS: usize = 1000;
+
+/// Part of a slider path using a single curve type.
+#[derive(Clone, Debug)]
+pub struct PathSegment {
+	pub curve_type: SliderCurveType,
+	/// Control points of the segment, including the ones it shares with its neighbours.
+	pub control_points: Vec<Point>,
+}
+
+/// Path of a slider, flattened into a polyline.
+#[derive(Clone, Debug)]
+pub struct SliderPath {
+	segments: Vec<PathSegment>,
+	/// Length of the path given by its control points alone.
+	calculated_length: f64,
+	/// Points of the path, cut to the declared length.
+	points: Vec<Point>,
+	/// Distance from the start of the path of each point.
+	distances: Vec<f64>,
+}
+
+fn is_same_point(a: Point, b: Point) -> bool {
+	a.distance(b) < 1e-9
+}
+
+/// Evaluates a bezier curve with De Casteljau's algorithm.
+pub(crate) fn bezier_point(anchors: &[Point], t: f64) -> Point {
+	let mut points = anchors.to_vec();
+	for n in (1..points.len()).rev() {
+		for i in 0..n {
+			points[i] = points[i].lerp(points[i + 1], t);
+		}
+	}
+
+	points.first().copied().unwrap_or_default()
+}
+
+/// Flattens bezier anchors into points, starting a new curve on every repeated anchor (red anchors in the editor).
+fn flatten_bezier(anchors: &[Point], points: &mut Vec<Point>) {
+	let mut start = 0;
+	for end in 1..=anchors.len() {
+		let is_curve_end = end == anchors.len() || is_same_point(anchors[end - 1], anchors[end]);
+		if !is_curve_end {
+			continue;
+		}
+
+		let curve = &anchors[start..end];
+		start = end;
+
+		let polygon_length: f64 = curve.windows(2).map(|pair| pair[0].distance(pair[1])).sum();
+
+		#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+		let steps = ((polygon_length / BEZIER_TOLERANCE).ceil() as usize).clamp(1, MAX_BEZIER_STEPS);
+
+		for step in 0..=steps {
+			#[allow(clippy::cast_precision_loss)]
+			let point = bezier_point(curve, step as f64 / steps as f64);
+			if points.last().is_none_or(|&last| !is_same_point(last, point)) {
+				points.push(point);
+			}
+		}
+	}
+}
+
+/// Splits the control points of a slider into segments of a single curve type.
+///
+/// A point with an explicit curve type ends the current segment and starts a new one, like in osu!lazer.
+fn split_segments(head: Point, first_curve_type: SliderCurveType, curve_points: &[SliderPoint]) -> Vec<PathSegment> {
+	let mut segments = vec![PathSegment {
+		curve_type: first_curve_type,
+		control_points: vec![head],
+	}];
+
+	for curve_point in curve_points {
+		let point = curve_point.to_point();
+		let current = segments.last_mut().expect("there is always a segment");
+		current.control_points.push(point);
+
+		if curve_point.curve_type != SliderCurveType::Inherit {
+			segments.push(PathSegment {
+				curve_type: curve_point.curve_type,
+				control_points: vec![point],
+			});
+		}
+	}
+
+	segments.retain(|segment| segment.control_points.len() > 1);
+	segments
+}
+
+impl SliderPath {
+	/// Computes the path of a slider from its head, control points and declared length.
+	///
+	/// If the declared length is not positive, the whole path given by the control points is used.
+	#[must_use]
+	pub fn new(head: Point, first_curve_type: SliderCurveType, curve_points: &[SliderPoint], length: f64) -> Self {
+		let segments = split_segments(head, first_curve_type, curve_points);
+
+		let mut points = vec![head];
+		for segment in &segments {
+			// control points come from f32 coordinates in the first place
+			let control_points: Vec<SliderPoint> = (segment.control_points.iter())
+				.enumerate()
+				.map(|(i, &p)| {
+					let curve_type = if i == 0 {
+						segment.curve_type
+					} else {
+						SliderCurveType::Inherit
+					};
+					SliderPoint::from_point(curve_type, p, Rounding::Exact)
+				})
+				.collect();
+
+			// perfect curves that can't be drawn as a circle are drawn as a bezier curve, like in osu!
+			let anchors = convert_to_bezier_anchors(&control_points).unwrap_or_else(|_| segment.control_points.clone());
+			flatten_bezier(&anchors, &mut points);
+		}
+
+		let mut distances = vec![0.0];
+		for pair in points.windows(2) {
+			let distance = distances.last().copied().unwrap_or_default() + pair[0].distance(pair[1]);
+			distances.push(distance);
+		}
+
+		let calculated_length = distances.last().copied().unwrap_or_default();
+		let mut path = Self {
+			segments,
+			calculated_length,
+			points,
+			distances,
+		};
+
+		if length > 0.0 {
+			path.cut_to_length(length);
+		}
+
+		path
+	}
+
+	/// Computes the path of a slider hit object, or `None` if it is not a slider.
+	#[must_use]
+	pub fn from_hit_object(hit_object: &HitObject) -> Option<Self> {
+		let HitObjectParams::Slider {
+			first_curve_type,
+			curve_points,
+			length,
+			..
+		} = &hit_object.object_params
+		else {
+			return None;
+		};
+
+		let head = Point::new(f64::from(hit_object.x), f64::from(hit_object.y));
+		Some(Self::new(head, *first_curve_type, curve_points, *length))
+	}
+
+	/// Cuts the path at the given length, or extends its last part in a straight line if it is too short.
+	fn cut_to_length(&mut self, length: f64) {
+		let end = self.distances.partition_point(|&distance| distance < length);
+		if end < self.points.len() {
+			self.points.truncate(end + 1);
+			self.distances.truncate(end + 1);
+		}
+
+		let [.., before, last] = self.points[..] else {
+			return;
+		};
+
+		let last_distance = self.distances[self.distances.len() - 2];
+		let direction = (last - before).normalized();
+		if let Some(point) = self.points.last_mut() {
+			*point = before + direction * (length - last_distance);
+		}
+		if let Some(distance) = self.distances.last_mut() {
+			*distance = length;
+		}
+	}
+
+	/// Segments of the path, each with a single curve type.
+	#[must_use]
+	pub fn segments(&self) -> &[PathSegment] {
+		&self.segments
+	}
+
+	/// Points of the flattened path.
+	#[must_use]
+	pub fn points(&self) -> &[Point] {
+		&self.points
+	}
+
+	/// Length of the path as drawn by its control points, before cutting it to the declared length.
+	#[must_use]
+	pub const fn calculated_length(&self) -> f64 {
+		self.calculated_length
+	}
+
+	/// Length of the path, which is the slider's declared length when it has one.
+	#[must_use]
+	pub fn length(&self) -> f64 {
+		self.distances.last().copied().unwrap_or_default()
+	}
+
+	/// Position at a given distance from the start of the path, clamped to the path.
+	#[must_use]
+	pub fn position_at_distance(&self, distance: f64) -> Point {
+		let index = self.distances.partition_point(|&d| d < distance);
+		if index == 0 {
+			return self.points.first().copied().unwrap_or_default();
+		}
+
+		let Some(&end) = self.points.get(index) else {
+			return self.points.last().copied().unwrap_or_default();
+		};
+
+		let start = self.points[index - 1];
+		let (d0, d1) = (self.distances[index - 1], self.distances[index]);
+		let t = if d1 > d0 { (distance - d0) / (d1 - d0) } else { 0.0 };
+		start.lerp(end, t)
+	}
+
+	/// Position at a given progress along the path, from 0 (head) to 1 (end).
+	#[must_use]
+	pub fn position_at(&self, progress: f64) -> Point {
+		self.position_at_distance(progress.clamp(0.0, 1.0) * self.length())
+	}
+}
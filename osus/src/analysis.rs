@@ -0,0 +1,472 @@
+pub mod patterns;
+
+use std::ops::Range;
+
+use crate::algos::slider_path::SliderPath;
+use crate::algos::{approach_rate_for_preempt, round_setting};
+use crate::file::beatmap::timing_points::uninherited_at;
+use crate::file::beatmap::{BeatmapFile, DifficultySection, HitObject, HitObjectParams, Timestamp, TimingPoint};
+use crate::point::Point;
+use crate::timeline::TimingTimeline;
+use crate::TimestampedSlice;
+
+use self::patterns::STACK_DISTANCE;
+
+/// Fastest rotation speed of a spinner in osu!stable, reached by auto.
+pub const MAX_SPINNER_RPM: f64 = 477.0;
+
+/// Highest rotation speed that we consider reasonable to ask of a player.
+///
+/// This is a heuristic: spinners asking for more than this are flagged as too short.
+pub const RANKABLE_SPINNER_RPM: f64 = 400.0;
+
+/// Information about how hard a spinner is to clear.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpinnerInfo {
+	/// Start time of the spinner.
+	pub time: Timestamp,
+	/// End time of the spinner.
+	pub end_time: Timestamp,
+	/// Duration of the spinner in milliseconds.
+	pub duration: f64,
+	/// Amount of full rotations required to clear the spinner.
+	pub required_rotations: u32,
+	/// Rotations per minute needed to clear the spinner in time.
+	pub required_rpm: f64,
+	/// Whether the spinner asks for more than [`RANKABLE_SPINNER_RPM`].
+	pub too_short: bool,
+	/// Whether the spinner asks for more than [`MAX_SPINNER_RPM`], so not even auto can clear it.
+	pub impossible: bool,
+}
+
+/// Amount of rotations per second required to clear a spinner, depending on OD.
+#[must_use]
+pub fn spinner_rotations_per_second(overall_difficulty: f32) -> f64 {
+	let od = f64::from(overall_difficulty);
+
+	if od < 5.0 {
+		0.4f64.mul_add(-(5.0 - od), 5.0)
+	} else {
+		0.5f64.mul_add(od - 5.0, 5.0)
+	}
+}
+
+/// Computes clearing requirements for every spinner of a beatmap.
+#[must_use]
+pub fn spinner_requirements(beatmap: &BeatmapFile) -> Vec<SpinnerInfo> {
+	let overall_difficulty = beatmap.difficulty.as_ref().map_or(5.0, |d| d.overall_difficulty);
+	let rotations_per_second = spinner_rotations_per_second(overall_difficulty);
+
+	(beatmap.hit_objects.iter())
+		.filter_map(|hit_object| match hit_object.object_params {
+			HitObjectParams::Spinner { end_time } => {
+				let duration = end_time - hit_object.time;
+
+				#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+				let required_rotations = (duration / 1000.0 * rotations_per_second).max(0.0) as u32;
+
+				let required_rpm = if duration > 0.0 {
+					f64::from(required_rotations) * 60_000.0 / duration
+				} else {
+					f64::INFINITY
+				};
+
+				Some(SpinnerInfo {
+					time: hit_object.time,
+					end_time,
+					duration,
+					required_rotations,
+					required_rpm,
+					too_short: required_rpm > RANKABLE_SPINNER_RPM,
+					impossible: required_rpm > MAX_SPINNER_RPM,
+				})
+			}
+			_ => None,
+		})
+		.collect()
+}
+
+/// Time ranges during which kiai time is active.
+///
+/// Timing points are assumed to be sorted by time.
+/// If kiai is still active at the last timing point, the range ends at the end of the last hit object.
+#[must_use]
+pub fn kiai_ranges(beatmap: &BeatmapFile) -> Vec<Range<Timestamp>> {
+	let mut ranges = Vec::new();
+	let mut kiai_start: Option<Timestamp> = None;
+
+	for timing_point in &beatmap.timing_points {
+		match (kiai_start, timing_point.is_kiai()) {
+			(None, true) => kiai_start = Some(timing_point.time),
+			(Some(start), false) => {
+				ranges.push(start..timing_point.time);
+				kiai_start = None;
+			}
+			_ => (),
+		}
+	}
+
+	if let Some(start) = kiai_start {
+		let end = beatmap.last_object_end_time().map_or(start, |end| end.max(start));
+		ranges.push(start..end);
+	}
+
+	ranges
+}
+
+/// Density of hit objects in a time range, in objects per second.
+///
+/// Hit objects are assumed to be sorted by time.
+#[must_use]
+pub fn density(hit_objects: &[HitObject], range: Range<Timestamp>) -> f64 {
+	let duration = range.end - range.start;
+	if duration <= 0.0 {
+		return 0.0;
+	}
+
+	#[allow(clippy::cast_precision_loss)]
+	let count = hit_objects.between(range).len() as f64;
+
+	count * 1000.0 / duration
+}
+
+/// Slider velocity multiplier in effect at a given time.
+///
+/// Timing points need to be sorted. An uninherited timing point resets the multiplier to 1.
+#[must_use]
+pub fn slider_velocity_at(timing_points: &[TimingPoint], time: Timestamp) -> f64 {
+	(timing_points[..timing_points.partition_point(|tp| tp.time <= time)].last())
+		.map_or(1.0, TimingPoint::sv_multiplier)
+}
+
+/// Duration in milliseconds of a slider, with all its slides.
+///
+/// Returns `None` if the hit object is not a slider, or if the beatmap has no timing or difficulty settings.
+#[must_use]
+pub fn slider_duration(beatmap: &BeatmapFile, hit_object: &HitObject) -> Option<f64> {
+	let HitObjectParams::Slider { length, slides, .. } = hit_object.object_params else {
+		return None;
+	};
+
+	let slider_multiplier = f64::from(beatmap.difficulty.as_ref()?.slider_multiplier);
+	let beat_length = uninherited_at(&beatmap.timing_points, hit_object.time)?.beat_length;
+
+	let velocity = slider_multiplier * 100.0 * slider_velocity_at(&beatmap.timing_points, hit_object.time);
+	if velocity <= 0.0 {
+		return None;
+	}
+
+	Some(length / velocity * beat_length * f64::from(slides))
+}
+
+/// Times of the ticks of a slider, with all its slides, in order.
+///
+/// Ticks closer than 10 milliseconds to the end of a slide are skipped, like in osu!.
+/// Returns an empty list if the hit object is not a slider, or if the beatmap has no timing or difficulty settings.
+#[must_use]
+pub fn slider_tick_times(beatmap: &BeatmapFile, hit_object: &HitObject) -> Vec<Timestamp> {
+	let HitObjectParams::Slider { length, slides, .. } = hit_object.object_params else {
+		return Vec::new();
+	};
+
+	let Some(difficulty) = &beatmap.difficulty else {
+		return Vec::new();
+	};
+
+	let timeline = TimingTimeline::new(&beatmap.timing_points);
+	let Some(beat_length) = timeline.beat_length_at(hit_object.time).filter(|bl| *bl > 0.0) else {
+		return Vec::new();
+	};
+
+	let distance_per_beat = f64::from(difficulty.slider_multiplier) * 100.0 * timeline.sv_at(hit_object.time);
+	let tick_distance = distance_per_beat / f64::from(difficulty.slider_tick_rate);
+	if tick_distance <= 0.0 || !tick_distance.is_finite() {
+		return Vec::new();
+	}
+
+	let velocity = distance_per_beat / beat_length;
+	let min_distance_from_end = velocity * 10.0;
+	let slide_duration = length / velocity;
+
+	let mut distances = Vec::new();
+	let mut distance = tick_distance;
+	#[allow(clippy::while_float)]
+	while distance < length - min_distance_from_end {
+		distances.push(distance);
+		distance += tick_distance;
+	}
+
+	let mut ticks = Vec::new();
+	for slide in 0..slides {
+		let slide_start = slide_duration.mul_add(f64::from(slide), hit_object.time);
+		let reversed = slide % 2 == 1;
+		let slide_ticks: Vec<Timestamp> = (distances.iter())
+			.map(|&d| if reversed { length - d } else { d })
+			.map(|d| slide_start + d / velocity)
+			.collect();
+
+		if reversed {
+			ticks.extend(slide_ticks.into_iter().rev());
+		} else {
+			ticks.extend(slide_ticks);
+		}
+	}
+
+	ticks
+}
+
+/// How far the distance snap of two objects can be from the reference one, relatively, before it is inconsistent.
+pub const SPACING_TOLERANCE: f64 = 0.1;
+
+/// Spacing between a hit object and the previous one, see [`spacing`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpacingInfo {
+	/// Index of the hit object.
+	pub index: usize,
+	pub time: Timestamp,
+	/// Distance in osu! pixels from the end of the previous object.
+	pub distance: f64,
+	/// Time in milliseconds since the end of the previous object.
+	pub delta_time: f64,
+	/// Distance snap multiplier of the spacing, as shown in the osu! editor.
+	pub distance_snap: f64,
+	/// Whether the distance snap is more than [`SPACING_TOLERANCE`] away from the reference one.
+	/// Stacked objects are never inconsistent.
+	pub inconsistent: bool,
+}
+
+/// Position at which a hit object ends: the end of the last slide of a slider, or its position otherwise.
+#[must_use]
+pub fn end_position(hit_object: &HitObject) -> Point {
+	match (&hit_object.object_params, SliderPath::from_hit_object(hit_object)) {
+		(HitObjectParams::Slider { slides, .. }, Some(path)) if slides % 2 == 1 => path.position_at(1.0),
+		_ => hit_object.position(),
+	}
+}
+
+/// Time at which a hit object ends, including every slide of a slider.
+pub(crate) fn end_time(beatmap: &BeatmapFile, hit_object: &HitObject) -> Timestamp {
+	match hit_object.object_params {
+		HitObjectParams::Slider { .. } => hit_object.time + slider_duration(beatmap, hit_object).unwrap_or_default(),
+		HitObjectParams::Spinner { end_time } | HitObjectParams::Hold { end_time, .. } => end_time,
+		HitObjectParams::HitCircle => hit_object.time,
+	}
+}
+
+/// Distance in osu! pixels corresponding to a distance snap of 1 for every millisecond at a given time,
+/// which is the distance a slider would travel.
+pub(crate) fn snap_velocity(beatmap: &BeatmapFile, timeline: &TimingTimeline, time: Timestamp) -> Option<f64> {
+	let slider_multiplier = f64::from(beatmap.difficulty.as_ref()?.slider_multiplier);
+	let beat_length = timeline.beat_length_at(time)?;
+	let velocity = slider_multiplier * 100.0 * timeline.sv_at(time) / beat_length;
+	(velocity > 0.0 && velocity.is_finite()).then_some(velocity)
+}
+
+/// Computes the spacing between every pair of consecutive osu!standard objects, like distance snap does in the editor.
+///
+/// Distances go from the end of the previous object to the start of the next one, and are compared to
+/// the distance snap saved in the beatmap's editor settings (1 if there are none).
+/// Hit objects are assumed to be sorted by time, and spinners are skipped.
+#[must_use]
+pub fn spacing(beatmap: &BeatmapFile) -> Vec<SpacingInfo> {
+	let timeline = TimingTimeline::new(&beatmap.timing_points);
+	let reference = beatmap.editor.as_ref().map_or(1.0, |editor| editor.distance_spacing);
+
+	let mut infos = Vec::new();
+	for (index, pair) in beatmap.hit_objects.windows(2).enumerate() {
+		let [prev, next] = pair else {
+			continue;
+		};
+		if prev.is_spinner() || next.is_spinner() {
+			continue;
+		}
+
+		let prev_end_time = end_time(beatmap, prev);
+		let delta_time = next.time - prev_end_time;
+		let Some(velocity) = snap_velocity(beatmap, &timeline, prev_end_time) else {
+			continue;
+		};
+		if delta_time <= 0.0 {
+			continue;
+		}
+
+		let distance = end_position(prev).distance(next.position());
+		let distance_snap = distance / (delta_time * velocity);
+		let inconsistent = distance >= STACK_DISTANCE && (distance_snap / reference - 1.0).abs() > SPACING_TOLERANCE;
+
+		infos.push(SpacingInfo {
+			index: index + 1,
+			time: next.time,
+			distance,
+			delta_time,
+			distance_snap,
+			inconsistent,
+		});
+	}
+
+	infos
+}
+
+/// Measures of a beatmap used to suggest its difficulty settings, see [`measure_difficulty`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DifficultyMeasures {
+	/// Hit objects per second of drain time.
+	pub density: f64,
+	/// Hit objects per second in the fastest parts, from the shortest 10% of gaps between hit objects.
+	/// Hit objects at the same time (like osu!mania chords) count as one.
+	pub peak_rate: f64,
+	/// BPM of the uninherited timing point lasting the longest in the playable range.
+	pub main_bpm: Option<f64>,
+	/// Median speed between consecutive osu!standard objects, in osu! pixels per millisecond.
+	pub spacing_velocity: f64,
+}
+
+/// Measures how dense and spread out the hit objects of a beatmap are.
+#[must_use]
+pub fn measure_difficulty(beatmap: &BeatmapFile) -> DifficultyMeasures {
+	let drain_time = beatmap.drain_time();
+	#[allow(clippy::cast_precision_loss)]
+	let density = if drain_time > 0.0 {
+		beatmap.hit_objects.len() as f64 * 1000.0 / drain_time
+	} else {
+		0.0
+	};
+
+	let mut gaps: Vec<f64> = (beatmap.hit_objects.windows(2))
+		.map(|pair| pair[1].time - pair[0].time)
+		.filter(|gap| *gap > 0.0)
+		.collect();
+	gaps.sort_by(f64::total_cmp);
+	let peak_rate = gaps.get(gaps.len() / 10).map_or(0.0, |gap| 1000.0 / gap);
+
+	let mut velocities: Vec<f64> = (spacing(beatmap).iter())
+		.map(|info| info.distance / info.delta_time)
+		.collect();
+	velocities.sort_by(f64::total_cmp);
+	let spacing_velocity = velocities.get(velocities.len() / 2).copied().unwrap_or_default();
+
+	DifficultyMeasures {
+		density,
+		peak_rate,
+		main_bpm: main_bpm(beatmap),
+		spacing_velocity,
+	}
+}
+
+/// BPM of the uninherited timing point lasting the longest in the playable range of a beatmap.
+fn main_bpm(beatmap: &BeatmapFile) -> Option<f64> {
+	let red_lines: Vec<&TimingPoint> = beatmap.timing_points.iter().filter(|tp| tp.uninherited).collect();
+	let range = beatmap.playable_range().unwrap_or(0.0..0.0);
+
+	let ends = (red_lines.iter().skip(1).map(|tp| tp.time)).chain(std::iter::once(f64::INFINITY));
+	let longest = (red_lines.iter().zip(ends))
+		.map(|(tp, end)| (tp, end.min(range.end) - tp.time.max(range.start)))
+		.reduce(|longest, section| if section.1 > longest.1 { section } else { longest });
+
+	longest.and_then(|(tp, _)| tp.bpm())
+}
+
+/// Difficulty settings of a difficulty level: (score, AR, OD, HP).
+type DifficultyReference = (f64, f64, f64, f64);
+
+/// References for osu!standard, scored by peak rate and spacing: Easy, Normal, Hard, Insane and Expert.
+const STANDARD_REFERENCES: [DifficultyReference; 5] = [
+	(1.5, 4.0, 3.0, 3.0),
+	(3.0, 6.0, 5.0, 4.0),
+	(4.5, 8.0, 7.0, 5.0),
+	(6.0, 9.0, 8.0, 6.0),
+	(8.0, 9.5, 9.0, 6.5),
+];
+
+/// References for osu!taiko, scored by peak rate: Kantan, Futsuu, Muzukashii, Oni and Inner Oni.
+const TAIKO_REFERENCES: [DifficultyReference; 5] = [
+	(3.0, 5.0, 4.0, 6.0),
+	(5.0, 5.0, 5.0, 6.0),
+	(7.0, 5.0, 5.5, 5.5),
+	(9.0, 5.0, 6.0, 5.0),
+	(12.0, 5.0, 6.5, 5.0),
+];
+
+/// References for osu!catch, scored by peak rate: Cup, Salad, Platter, Rain and Overdose.
+const CATCH_REFERENCES: [DifficultyReference; 5] = [
+	(1.5, 6.0, 6.0, 3.0),
+	(3.0, 7.5, 7.5, 4.0),
+	(4.5, 8.5, 8.5, 5.0),
+	(6.0, 9.0, 9.0, 6.0),
+	(8.0, 9.5, 9.5, 6.5),
+];
+
+/// References for osu!mania, scored by density: Easy, Normal, Hard, Insane and Expert.
+const MANIA_REFERENCES: [DifficultyReference; 5] = [
+	(2.0, 5.0, 6.0, 6.0),
+	(4.0, 5.0, 7.0, 7.0),
+	(7.0, 5.0, 8.0, 8.0),
+	(10.0, 5.0, 8.5, 8.5),
+	(14.0, 5.0, 9.0, 9.0),
+];
+
+/// Settings at a score, interpolated between the two closest references.
+fn interpolate_references(references: &[DifficultyReference], score: f64) -> (f64, f64, f64) {
+	let next = references.partition_point(|reference| reference.0 < score);
+	let (low, high) = match next {
+		0 => (references[0], references[0]),
+		n if n == references.len() => (references[n - 1], references[n - 1]),
+		n => (references[n - 1], references[n]),
+	};
+
+	let t = if high.0 > low.0 {
+		(score - low.0) / (high.0 - low.0)
+	} else {
+		0.0
+	};
+	let lerp = |a: f64, b: f64| t.mul_add(b - a, a);
+	(lerp(low.1, high.1), lerp(low.2, high.2), lerp(low.3, high.3))
+}
+
+/// Suggests AR, OD and HP for a beatmap from how dense and spread out its hit objects are (see [`measure_difficulty`]).
+///
+/// The settings are interpolated between the usual ones of each difficulty level in ranked beatmaps.
+/// AR is kept in osu!taiko and osu!mania, where it does nothing, and OD follows AR in osu!catch like it usually does.
+/// AR is also lowered so that hit objects appear at least a beat before their time at the main BPM.
+/// Every other setting is kept, and beatmaps with less than 2 hit objects keep their settings.
+///
+/// This is a starting point for new difficulties and conversions, not a replacement for testing.
+#[must_use]
+pub fn suggest_difficulty_settings(beatmap: &BeatmapFile) -> DifficultySection {
+	let mut difficulty = beatmap.difficulty.clone().unwrap_or_default();
+	if beatmap.hit_objects.len() < 2 {
+		return difficulty;
+	}
+
+	let mode = beatmap.general.as_ref().map_or(0, |general| general.mode);
+	let measures = measure_difficulty(beatmap);
+	let (references, score): (&[DifficultyReference], f64) = match mode {
+		1 => (&TAIKO_REFERENCES, measures.peak_rate),
+		2 => (&CATCH_REFERENCES, measures.peak_rate),
+		3 => (&MANIA_REFERENCES, measures.density),
+		// jumps are harder to read than stacks at the same rhythm
+		_ => (
+			&STANDARD_REFERENCES,
+			measures.peak_rate * 0.3f64.mul_add(measures.spacing_velocity.min(1.5), 1.0),
+		),
+	};
+
+	let (mut approach_rate, overall_difficulty, hp_drain_rate) = interpolate_references(references, score);
+	if let Some(bpm) = measures.main_bpm.filter(|bpm| *bpm > 0.0) {
+		approach_rate = approach_rate.min(approach_rate_for_preempt(60_000.0 / bpm));
+	}
+
+	#[allow(clippy::cast_possible_truncation)]
+	let setting = |value: f64| round_setting(value) as f32;
+	if matches!(mode, 0 | 2) {
+		difficulty.approach_rate = setting(approach_rate);
+	}
+	difficulty.overall_difficulty = if mode == 2 {
+		difficulty.approach_rate
+	} else {
+		setting(overall_difficulty)
+	};
+	difficulty.hp_drain_rate = setting(hp_drain_rate);
+
+	difficulty
+}
@@ -0,0 +1,116 @@
+//! Segmentation of osu!standard maps into streams, jumps, stacks and slider sections.
+
+use std::ops::Range;
+
+use crate::file::beatmap::timing_points::uninherited_at;
+use crate::file::beatmap::{BeatmapFile, HitObject, Timestamp};
+use crate::point::Point;
+
+/// Distance in osu! pixels under which two objects are considered stacked.
+pub const STACK_DISTANCE: f64 = 3.0;
+
+/// Distance in osu! pixels from which two consecutive objects are considered a jump.
+pub const JUMP_DISTANCE: f64 = 110.0;
+
+/// Kind of pattern found in a section of a map.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatternKind {
+	/// Consecutive circles spaced by 1/4 of a beat or less.
+	Stream,
+	/// Consecutive objects far away from each other, spaced by 1/2 of a beat or more.
+	Jump,
+	/// Consecutive objects at the same position.
+	Stack,
+	/// Consecutive sliders.
+	Sliders,
+}
+
+/// A section of a map made of one kind of pattern.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pattern {
+	/// Kind of the pattern.
+	pub kind: PatternKind,
+	/// Time range from the first object to the last object of the pattern.
+	pub range: Range<Timestamp>,
+	/// Amount of hit objects in the pattern.
+	pub object_count: usize,
+}
+
+fn position(hit_object: &HitObject) -> Point {
+	Point::new(f64::from(hit_object.x), f64::from(hit_object.y))
+}
+
+/// Classifies the relationship between two consecutive hit objects.
+fn classify(prev: &HitObject, next: &HitObject, beat_length: f64) -> Option<PatternKind> {
+	let distance = (position(next) - position(prev)).len();
+	let delta_time = next.time - prev.time;
+
+	// a bit of leeway for unsnapped objects
+	let is_quarter_beat_or_less = delta_time <= beat_length / 4.0 * 1.1;
+	let is_half_beat_or_more = delta_time >= beat_length / 2.0 * 0.9;
+
+	if prev.is_spinner() || next.is_spinner() {
+		None
+	} else if prev.is_slider() && next.is_slider() {
+		Some(PatternKind::Sliders)
+	} else if distance < STACK_DISTANCE {
+		Some(PatternKind::Stack)
+	} else if is_quarter_beat_or_less && prev.is_hit_circle() && next.is_hit_circle() {
+		Some(PatternKind::Stream)
+	} else if is_half_beat_or_more && distance >= JUMP_DISTANCE {
+		Some(PatternKind::Jump)
+	} else {
+		None
+	}
+}
+
+/// Minimum amount of objects a section needs to be considered a pattern.
+const fn min_object_count(kind: PatternKind) -> usize {
+	match kind {
+		PatternKind::Stream => 4,
+		PatternKind::Jump | PatternKind::Sliders => 3,
+		PatternKind::Stack => 2,
+	}
+}
+
+/// Segments an osu!standard map into labeled time ranges of patterns.
+///
+/// Hit objects are assumed to be sorted by time. Sections that don't match any pattern are not returned,
+/// and nothing is returned for other modes.
+#[must_use]
+pub fn detect_patterns(beatmap: &BeatmapFile) -> Vec<Pattern> {
+	if beatmap.general.as_ref().is_some_and(|general| general.mode != 0) {
+		return Vec::new();
+	}
+
+	let mut patterns = Vec::new();
+	let mut current: Option<Pattern> = None;
+
+	for pair in beatmap.hit_objects.windows(2) {
+		let [prev, next] = pair else {
+			continue;
+		};
+
+		let kind =
+			uninherited_at(&beatmap.timing_points, next.time).and_then(|tp| classify(prev, next, tp.beat_length));
+
+		match (&mut current, kind) {
+			(Some(pattern), Some(kind)) if pattern.kind == kind => {
+				pattern.range.end = next.time;
+				pattern.object_count += 1;
+			}
+			_ => {
+				patterns.extend(current.take());
+				current = kind.map(|kind| Pattern {
+					kind,
+					range: prev.time..next.time,
+					object_count: 2,
+				});
+			}
+		}
+	}
+
+	patterns.extend(current);
+	patterns.retain(|pattern| pattern.object_count >= min_object_count(pattern.kind));
+	patterns
+}
@@ -0,0 +1,183 @@
+//! Parsed beatmaps kept between uses, for tools going over the same files again and again.
+//!
+//! [`BeatmapCache`] keeps each parsed beatmap along with the modification time and size of its file,
+//! and only parses the file again when they change (or when its content changes, see [`Validation`]).
+//! The cache can be saved to a file and loaded back, so that the next run over a songs folder
+//! only parses the beatmaps that changed since.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::file::beatmap::errors::BeatmapFileParseError;
+use crate::file::beatmap::BeatmapFile;
+use crate::file::hash_file;
+use crate::ErrorCategory;
+
+/// Version of the saved cache format, changed whenever the beatmap model changes.
+const CACHE_VERSION: u32 = 2;
+
+/// How the cache decides that a file changed since it was parsed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Validation {
+	/// The modification time or the size of the file changed. Cheap, but misses changes
+	/// that keep both, like files copied with their modification time.
+	#[default]
+	Modified,
+	/// The MD5 hash of the file changed, which means reading every file again (but not parsing it).
+	ContentHash,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct CacheEntry {
+	modified: SystemTime,
+	len: u64,
+	/// Only computed with [`Validation::ContentHash`].
+	hash: Option<String>,
+	beatmap: BeatmapFile,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[allow(clippy::module_name_repetitions)]
+pub enum CacheError {
+	#[error(transparent)]
+	Io(#[from] io::Error),
+
+	#[error("Invalid cache file: {0}")]
+	Decode(#[from] bincode::Error),
+
+	#[error("Cache file of version {found}, expected version {CACHE_VERSION}")]
+	OutdatedVersion { found: u32 },
+}
+
+impl CacheError {
+	#[must_use]
+	pub const fn category(&self) -> ErrorCategory {
+		match self {
+			Self::Io(_) => ErrorCategory::Io,
+			Self::Decode(_) | Self::OutdatedVersion { .. } => ErrorCategory::Parse,
+		}
+	}
+}
+
+/// Parsed beatmaps by path, parsed again when their file changes.
+#[derive(Clone, Debug, Default)]
+pub struct BeatmapCache {
+	pub validation: Validation,
+	entries: HashMap<PathBuf, CacheEntry>,
+	hits: usize,
+	misses: usize,
+}
+
+impl BeatmapCache {
+	#[must_use]
+	pub fn new(validation: Validation) -> Self {
+		Self {
+			validation,
+			..Self::default()
+		}
+	}
+
+	/// Loads a cache saved with [`save`](Self::save).
+	///
+	/// # Errors
+	///
+	/// This function will return an error if the file can't be read, isn't a cache file,
+	/// or was saved by a version of this crate with another beatmap model.
+	pub fn load<P: AsRef<Path>>(path: P, validation: Validation) -> Result<Self, CacheError> {
+		let mut reader = BufReader::new(File::open(path)?);
+
+		let version: u32 = bincode::deserialize_from(&mut reader)?;
+		if version != CACHE_VERSION {
+			return Err(CacheError::OutdatedVersion { found: version });
+		}
+
+		Ok(Self {
+			entries: bincode::deserialize_from(&mut reader)?,
+			..Self::new(validation)
+		})
+	}
+
+	/// Saves the cache to a file, to [`load`](Self::load) it in a later run.
+	///
+	/// # Errors
+	///
+	/// This function will return an error if the file can't be written.
+	pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CacheError> {
+		let mut writer = BufWriter::new(File::create(path)?);
+		bincode::serialize_into(&mut writer, &CACHE_VERSION)?;
+		bincode::serialize_into(&mut writer, &self.entries)?;
+		Ok(())
+	}
+
+	/// Parsed beatmap of a file, parsing it only if it isn't cached or changed since it was.
+	///
+	/// # Errors
+	///
+	/// This function will return an error if the file can't be read or parsed.
+	pub fn get<P: AsRef<Path>>(&mut self, path: P) -> Result<&BeatmapFile, BeatmapFileParseError> {
+		let path = path.as_ref();
+		let io_error = |e: io::Error| BeatmapFileParseError {
+			filename: path.as_os_str().to_owned(),
+			line_number: None,
+			kind: e.into(),
+		};
+
+		let metadata = fs::metadata(path).map_err(io_error)?;
+		let modified = metadata.modified().map_err(io_error)?;
+		let len = metadata.len();
+		let hash = match self.validation {
+			Validation::Modified => None,
+			Validation::ContentHash => Some(hash_file(path).map_err(io_error)?),
+		};
+
+		let is_fresh = self.entries.get(path).is_some_and(|entry| match self.validation {
+			Validation::Modified => entry.modified == modified && entry.len == len,
+			Validation::ContentHash => entry.hash == hash,
+		});
+
+		if is_fresh {
+			self.hits += 1;
+		} else {
+			self.misses += 1;
+			let entry = CacheEntry {
+				modified,
+				len,
+				hash,
+				beatmap: BeatmapFile::parse(path)?,
+			};
+			self.entries.insert(path.to_owned(), entry);
+		}
+
+		Ok(&self.entries[path].beatmap)
+	}
+
+	/// Removes the beatmaps whose file doesn't exist anymore, returning how many were removed.
+	pub fn prune(&mut self) -> usize {
+		let len = self.entries.len();
+		self.entries.retain(|path, _| path.is_file());
+		len - self.entries.len()
+	}
+
+	/// Amount of cached beatmaps.
+	#[must_use]
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	/// Amount of calls to [`get`](Self::get) that used a cached beatmap and that parsed the file,
+	/// since the cache was created or loaded.
+	#[must_use]
+	pub const fn hits_and_misses(&self) -> (usize, usize) {
+		(self.hits, self.misses)
+	}
+}
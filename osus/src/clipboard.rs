@@ -0,0 +1,68 @@
+//! Selections copied from the osu! editor.
+//!
+//! Copying hit objects in the editor puts an [editor timestamp](crate::timestamp) like `00:45:123 (1,2,3) -`
+//! in the clipboard. These functions turn such text into the hit objects it refers to and back,
+//! so that changes can be scoped to exactly what was selected in the editor.
+
+use std::ops::Range;
+
+use crate::file::beatmap::{BeatmapFile, Timestamp};
+use crate::timestamp::{EditorTimestamp, InvalidTimestampError};
+
+/// Finds the hit objects selected in a text copied from the osu! editor, as sorted indices in the beatmap's hit objects.
+///
+/// The text can hold several timestamps, on separate lines or one after the other (`00:45:123 (1,2) - 00:50:000 (1) -`).
+/// References that don't match any hit object are ignored. Hit objects need to be sorted.
+///
+/// # Errors
+///
+/// This function will return an error if one of the timestamps is invalid.
+pub fn parse_selection(text: &str, beatmap: &BeatmapFile) -> Result<Vec<usize>, InvalidTimestampError> {
+	let mut indices = Vec::new();
+	for timestamp in text.lines().flat_map(|line| line.split(" -")) {
+		if timestamp.trim().is_empty() {
+			continue;
+		}
+
+		let timestamp: EditorTimestamp = timestamp.parse()?;
+		indices.extend(timestamp.resolve(beatmap));
+	}
+
+	indices.sort_unstable();
+	indices.dedup();
+	Ok(indices)
+}
+
+/// Writes hit objects as the osu! editor would when copying them, like `00:45:123 (1,2,3) -`.
+///
+/// `indices` are indices in the beatmap's hit objects, in any order. An empty selection gives an empty text.
+#[must_use]
+pub fn format_selection(beatmap: &BeatmapFile, indices: &[usize]) -> String {
+	let mut indices = indices.to_vec();
+	indices.sort_unstable();
+	indices.dedup();
+
+	if indices.is_empty() {
+		return String::new();
+	}
+
+	EditorTimestamp::from_hit_objects(beatmap, &indices).to_string()
+}
+
+/// Time range going from the first to the last selected hit object, included.
+///
+/// Returns `None` if no hit object is selected.
+#[must_use]
+pub fn selection_range(beatmap: &BeatmapFile, indices: &[usize]) -> Option<Range<Timestamp>> {
+	let times = || {
+		indices
+			.iter()
+			.filter_map(|&i| beatmap.hit_objects.get(i))
+			.map(|ho| ho.time)
+	};
+	let start = times().reduce(f64::min)?;
+	let end = times().reduce(f64::max)?;
+
+	// hit objects are at least a millisecond apart, unless they are at the same time
+	Some(start..end + 1.0)
+}
@@ -0,0 +1,1062 @@
+//! Reversible edits of a beatmap, for tools that need undo/redo or want to know what a change did.
+//!
+//! A [`Transaction`] applies [`Edit`]s to a [`BeatmapFile`] and remembers them, so that they can be undone and redone.
+//! Its edit log can be written as text, one edit per line, and read back to replay the edits on another copy
+//! of the beatmap.
+//!
+//! A [`ChangeSet`] describes what changed between two versions of a beatmap, whatever changed it,
+//! so that automated edits can be audited.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::ops::Range;
+
+use crate::file::beatmap::errors::{EventParseError, HitObjectParseError, TimingPointParseError};
+use crate::file::beatmap::parsing::{parse_event, parse_hit_object, parse_timing_point};
+use crate::file::beatmap::{BeatmapFile, Event, HitObject, Timestamp, TimingPoint};
+use crate::{ErrorCategory, Timestamped};
+
+/// Separator between the fields of a line of the edit log.
+///
+/// Elements are escaped when written (see [`LogField`]), so that the tabs and line breaks
+/// they may contain in their filenames don't split them.
+const LOG_SEPARATOR: char = '\t';
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum EditError {
+	#[error("Cannot edit {kind} {index}, the beatmap only has {len}")]
+	IndexOutOfRange {
+		kind: &'static str,
+		index: usize,
+		len: usize,
+	},
+
+	#[error("Cannot edit {kind} {index}, it isn't the one the edit was made for")]
+	Mismatch { kind: &'static str, index: usize },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EditLogParseError {
+	#[error("Line {0}: expected an operation, a kind of element, an index and the element")]
+	MissingField(usize),
+
+	#[error("Line {line}: unknown operation {operation:?}, expected insert, remove or replace")]
+	UnknownOperation { line: usize, operation: String },
+
+	#[error("Line {line}: unknown kind of element {kind:?}, expected hit_object, timing_point or event")]
+	UnknownKind { line: usize, kind: String },
+
+	#[error("Line {0}: invalid index")]
+	InvalidIndex(usize),
+
+	#[error("Line {0}: invalid escape sequence, expected \\\\, \\t, \\n or \\r")]
+	InvalidEscape(usize),
+
+	#[error("Line {0}: invalid hit object")]
+	HitObject(usize, #[source] HitObjectParseError),
+
+	#[error("Line {0}: invalid timing point")]
+	TimingPoint(usize, #[source] TimingPointParseError),
+
+	#[error("Line {0}: invalid event")]
+	Event(usize, #[source] EventParseError),
+
+	#[error("Line {0}: storyboard events cannot be edited")]
+	StoryboardEvent(usize),
+}
+
+impl EditError {
+	#[must_use]
+	pub const fn category(&self) -> ErrorCategory {
+		ErrorCategory::Validation
+	}
+}
+
+impl EditLogParseError {
+	#[must_use]
+	pub const fn category(&self) -> ErrorCategory {
+		match self {
+			Self::StoryboardEvent(_) => ErrorCategory::Validation,
+			_ => ErrorCategory::Parse,
+		}
+	}
+}
+
+/// A single reversible change of a beatmap.
+///
+/// Removals and replacements keep the elements they remove, so that they can be undone.
+/// Indices are the ones of the element in its list at the time the edit is applied.
+#[derive(Clone, Debug)]
+pub enum Edit {
+	InsertHitObject {
+		index: usize,
+		hit_object: HitObject,
+	},
+	RemoveHitObject {
+		index: usize,
+		hit_object: HitObject,
+	},
+	ReplaceHitObject {
+		index: usize,
+		old: HitObject,
+		new: HitObject,
+	},
+	InsertTimingPoint {
+		index: usize,
+		timing_point: TimingPoint,
+	},
+	RemoveTimingPoint {
+		index: usize,
+		timing_point: TimingPoint,
+	},
+	ReplaceTimingPoint {
+		index: usize,
+		old: TimingPoint,
+		new: TimingPoint,
+	},
+	InsertEvent {
+		index: usize,
+		event: Event,
+	},
+	RemoveEvent {
+		index: usize,
+		event: Event,
+	},
+	ReplaceEvent {
+		index: usize,
+		old: Event,
+		new: Event,
+	},
+}
+
+const fn check_index<T>(list: &[T], kind: &'static str, index: usize, inserting: bool) -> Result<(), EditError> {
+	let len = list.len();
+	if index < len || (inserting && index == len) {
+		Ok(())
+	} else {
+		Err(EditError::IndexOutOfRange { kind, index, len })
+	}
+}
+
+fn insert<T: Clone>(list: &mut Vec<T>, kind: &'static str, index: usize, element: &T) -> Result<(), EditError> {
+	check_index(list, kind, index, true)?;
+	list.insert(index, element.clone());
+	Ok(())
+}
+
+/// Checks that the element at an index is the one an edit expects, comparing them as they are written in files.
+fn check_element<T: fmt::Display>(list: &[T], kind: &'static str, index: usize, expected: &T) -> Result<(), EditError> {
+	check_index(list, kind, index, false)?;
+	if list[index].to_string() == expected.to_string() {
+		Ok(())
+	} else {
+		Err(EditError::Mismatch { kind, index })
+	}
+}
+
+fn remove<T: fmt::Display>(list: &mut Vec<T>, kind: &'static str, index: usize, old: &T) -> Result<(), EditError> {
+	check_element(list, kind, index, old)?;
+	list.remove(index);
+	Ok(())
+}
+
+fn replace<T: Clone + fmt::Display>(
+	list: &mut [T],
+	kind: &'static str,
+	index: usize,
+	old: &T,
+	new: &T,
+) -> Result<(), EditError> {
+	check_element(list, kind, index, old)?;
+	list[index] = new.clone();
+	Ok(())
+}
+
+/// An element written in a field of the edit log, with backslashes, tabs and line breaks escaped.
+struct LogField<'a, T>(&'a T);
+
+impl<T: fmt::Display> fmt::Display for LogField<'_, T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for c in self.0.to_string().chars() {
+			match c {
+				'\\' => f.write_str("\\\\")?,
+				'\t' => f.write_str("\\t")?,
+				'\n' => f.write_str("\\n")?,
+				'\r' => f.write_str("\\r")?,
+				c => write!(f, "{c}")?,
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Reverts the escaping of [`LogField`].
+fn unescape_field(field: &str, line_number: usize) -> Result<String, EditLogParseError> {
+	let mut unescaped = String::with_capacity(field.len());
+	let mut chars = field.chars();
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			unescaped.push(c);
+			continue;
+		}
+
+		unescaped.push(match chars.next() {
+			Some('\\') => '\\',
+			Some('t') => '\t',
+			Some('n') => '\n',
+			Some('r') => '\r',
+			_ => return Err(EditLogParseError::InvalidEscape(line_number)),
+		});
+	}
+
+	Ok(unescaped)
+}
+
+impl Edit {
+	/// Applies the edit to a beatmap.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the index of the edit is out of the range of its list, or if the element it removes
+	/// or replaces isn't the one it recorded, in which case nothing is changed.
+	pub fn apply(&self, beatmap: &mut BeatmapFile) -> Result<(), EditError> {
+		let hit_objects = &mut beatmap.hit_objects;
+		let timing_points = &mut beatmap.timing_points;
+		let events = &mut beatmap.events;
+
+		match self {
+			Self::InsertHitObject { index, hit_object } => insert(hit_objects, "hit object", *index, hit_object),
+			Self::RemoveHitObject { index, hit_object } => remove(hit_objects, "hit object", *index, hit_object),
+			Self::ReplaceHitObject { index, old, new } => replace(hit_objects, "hit object", *index, old, new),
+			Self::InsertTimingPoint { index, timing_point } => {
+				insert(timing_points, "timing point", *index, timing_point)
+			}
+			Self::RemoveTimingPoint { index, timing_point } => {
+				remove(timing_points, "timing point", *index, timing_point)
+			}
+			Self::ReplaceTimingPoint { index, old, new } => replace(timing_points, "timing point", *index, old, new),
+			Self::InsertEvent { index, event } => insert(events, "event", *index, event),
+			Self::RemoveEvent { index, event } => remove(events, "event", *index, event),
+			Self::ReplaceEvent { index, old, new } => replace(events, "event", *index, old, new),
+		}
+	}
+
+	/// The edit cancelling this one.
+	#[must_use]
+	pub fn inverse(&self) -> Self {
+		match self.clone() {
+			Self::InsertHitObject { index, hit_object } => Self::RemoveHitObject { index, hit_object },
+			Self::RemoveHitObject { index, hit_object } => Self::InsertHitObject { index, hit_object },
+			Self::ReplaceHitObject { index, old, new } => Self::ReplaceHitObject {
+				index,
+				old: new,
+				new: old,
+			},
+			Self::InsertTimingPoint { index, timing_point } => Self::RemoveTimingPoint { index, timing_point },
+			Self::RemoveTimingPoint { index, timing_point } => Self::InsertTimingPoint { index, timing_point },
+			Self::ReplaceTimingPoint { index, old, new } => Self::ReplaceTimingPoint {
+				index,
+				old: new,
+				new: old,
+			},
+			Self::InsertEvent { index, event } => Self::RemoveEvent { index, event },
+			Self::RemoveEvent { index, event } => Self::InsertEvent { index, event },
+			Self::ReplaceEvent { index, old, new } => Self::ReplaceEvent {
+				index,
+				old: new,
+				new: old,
+			},
+		}
+	}
+
+	/// Parses a line of an edit log, as written by the [`Display`](fmt::Display) implementation.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the line is not a valid edit. `line_number` is only used in the error.
+	pub fn parse_log_line(line: &str, line_number: usize) -> Result<Self, EditLogParseError> {
+		let mut fields = line.split(LOG_SEPARATOR);
+		let mut next_field = || fields.next().ok_or(EditLogParseError::MissingField(line_number));
+
+		let operation = next_field()?;
+		let kind = next_field()?;
+		let index = (next_field()?.parse()).map_err(|_| EditLogParseError::InvalidIndex(line_number))?;
+		let element = unescape_field(next_field()?, line_number)?;
+		let new_element = if operation == "replace" {
+			unescape_field(next_field()?, line_number)?
+		} else {
+			String::new()
+		};
+		let (element, new_element) = (element.as_str(), new_element.as_str());
+
+		let hit_object = |line: &str| parse_hit_object(line).map_err(|e| EditLogParseError::HitObject(line_number, e));
+		let timing_point =
+			|line: &str| parse_timing_point(line).map_err(|e| EditLogParseError::TimingPoint(line_number, e));
+		let event = |line: &str| {
+			(parse_event(line).map_err(|e| EditLogParseError::Event(line_number, e)))?
+				.ok_or(EditLogParseError::StoryboardEvent(line_number))
+		};
+
+		let edit = match (operation, kind) {
+			("insert", "hit_object") => Self::InsertHitObject {
+				index,
+				hit_object: hit_object(element)?,
+			},
+			("remove", "hit_object") => Self::RemoveHitObject {
+				index,
+				hit_object: hit_object(element)?,
+			},
+			("replace", "hit_object") => Self::ReplaceHitObject {
+				index,
+				old: hit_object(element)?,
+				new: hit_object(new_element)?,
+			},
+			("insert", "timing_point") => Self::InsertTimingPoint {
+				index,
+				timing_point: timing_point(element)?,
+			},
+			("remove", "timing_point") => Self::RemoveTimingPoint {
+				index,
+				timing_point: timing_point(element)?,
+			},
+			("replace", "timing_point") => Self::ReplaceTimingPoint {
+				index,
+				old: timing_point(element)?,
+				new: timing_point(new_element)?,
+			},
+			("insert", "event") => Self::InsertEvent {
+				index,
+				event: event(element)?,
+			},
+			("remove", "event") => Self::RemoveEvent {
+				index,
+				event: event(element)?,
+			},
+			("replace", "event") => Self::ReplaceEvent {
+				index,
+				old: event(element)?,
+				new: event(new_element)?,
+			},
+			("insert" | "remove" | "replace", kind) => {
+				return Err(EditLogParseError::UnknownKind {
+					line: line_number,
+					kind: kind.to_owned(),
+				})
+			}
+			(operation, _) => {
+				return Err(EditLogParseError::UnknownOperation {
+					line: line_number,
+					operation: operation.to_owned(),
+				})
+			}
+		};
+
+		Ok(edit)
+	}
+}
+
+impl fmt::Display for Edit {
+	/// Formats the edit as a line of an edit log: the operation, the kind of element, its index and the element
+	/// (or the old and new elements of a replacement), separated by tabs.
+	/// Backslashes, tabs and line breaks in the elements are escaped as `\\`, `\t`, `\n` and `\r`.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let sep = LOG_SEPARATOR;
+		match self {
+			Self::InsertHitObject { index, hit_object } => {
+				write!(f, "insert{sep}hit_object{sep}{index}{sep}{}", LogField(hit_object))
+			}
+			Self::RemoveHitObject { index, hit_object } => {
+				write!(f, "remove{sep}hit_object{sep}{index}{sep}{}", LogField(hit_object))
+			}
+			Self::ReplaceHitObject { index, old, new } => {
+				write!(
+					f,
+					"replace{sep}hit_object{sep}{index}{sep}{}{sep}{}",
+					LogField(old),
+					LogField(new)
+				)
+			}
+			Self::InsertTimingPoint { index, timing_point } => {
+				write!(f, "insert{sep}timing_point{sep}{index}{sep}{}", LogField(timing_point))
+			}
+			Self::RemoveTimingPoint { index, timing_point } => {
+				write!(f, "remove{sep}timing_point{sep}{index}{sep}{}", LogField(timing_point))
+			}
+			Self::ReplaceTimingPoint { index, old, new } => {
+				write!(
+					f,
+					"replace{sep}timing_point{sep}{index}{sep}{}{sep}{}",
+					LogField(old),
+					LogField(new)
+				)
+			}
+			Self::InsertEvent { index, event } => write!(f, "insert{sep}event{sep}{index}{sep}{}", LogField(event)),
+			Self::RemoveEvent { index, event } => write!(f, "remove{sep}event{sep}{index}{sep}{}", LogField(event)),
+			Self::ReplaceEvent { index, old, new } => write!(
+				f,
+				"replace{sep}event{sep}{index}{sep}{}{sep}{}",
+				LogField(old),
+				LogField(new)
+			),
+		}
+	}
+}
+
+/// Records the edits applied to a beatmap, so that they can be undone and redone.
+///
+/// The transaction doesn't own the beatmap: every method takes the beatmap the edits were applied to,
+/// and it is up to the caller to always pass the same one.
+#[derive(Clone, Debug, Default)]
+pub struct Transaction {
+	/// Edits that have been applied, in order.
+	applied: Vec<Edit>,
+	/// Edits that have been undone, the last one being the first to redo.
+	undone: Vec<Edit>,
+}
+
+impl Transaction {
+	#[must_use]
+	pub const fn new() -> Self {
+		Self {
+			applied: Vec::new(),
+			undone: Vec::new(),
+		}
+	}
+
+	/// Edits that have been applied and not undone, in order.
+	#[must_use]
+	pub fn edits(&self) -> &[Edit] {
+		&self.applied
+	}
+
+	#[must_use]
+	pub const fn can_undo(&self) -> bool {
+		!self.applied.is_empty()
+	}
+
+	#[must_use]
+	pub const fn can_redo(&self) -> bool {
+		!self.undone.is_empty()
+	}
+
+	/// Applies an edit and records it. Edits that were undone can't be redone anymore.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the edit couldn't be applied, in which case it isn't recorded.
+	pub fn apply(&mut self, beatmap: &mut BeatmapFile, edit: Edit) -> Result<(), EditError> {
+		edit.apply(beatmap)?;
+		self.applied.push(edit);
+		self.undone.clear();
+		Ok(())
+	}
+
+	/// Inserts a hit object at an index.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the index is greater than the amount of hit objects.
+	pub fn insert_hit_object(
+		&mut self,
+		beatmap: &mut BeatmapFile,
+		index: usize,
+		hit_object: HitObject,
+	) -> Result<(), EditError> {
+		self.apply(beatmap, Edit::InsertHitObject { index, hit_object })
+	}
+
+	/// Removes the hit object at an index.
+	///
+	/// # Errors
+	///
+	/// Returns an error if there is no hit object at this index.
+	pub fn remove_hit_object(&mut self, beatmap: &mut BeatmapFile, index: usize) -> Result<(), EditError> {
+		check_index(&beatmap.hit_objects, "hit object", index, false)?;
+		let hit_object = beatmap.hit_objects[index].clone();
+		self.apply(beatmap, Edit::RemoveHitObject { index, hit_object })
+	}
+
+	/// Changes the fields of the hit object at an index.
+	///
+	/// # Errors
+	///
+	/// Returns an error if there is no hit object at this index.
+	pub fn modify_hit_object(
+		&mut self,
+		beatmap: &mut BeatmapFile,
+		index: usize,
+		modify: impl FnOnce(&mut HitObject),
+	) -> Result<(), EditError> {
+		check_index(&beatmap.hit_objects, "hit object", index, false)?;
+		let old = beatmap.hit_objects[index].clone();
+		let mut new = old.clone();
+		modify(&mut new);
+		self.apply(beatmap, Edit::ReplaceHitObject { index, old, new })
+	}
+
+	/// Inserts a timing point at an index.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the index is greater than the amount of timing points.
+	pub fn insert_timing_point(
+		&mut self,
+		beatmap: &mut BeatmapFile,
+		index: usize,
+		timing_point: TimingPoint,
+	) -> Result<(), EditError> {
+		self.apply(beatmap, Edit::InsertTimingPoint { index, timing_point })
+	}
+
+	/// Removes the timing point at an index.
+	///
+	/// # Errors
+	///
+	/// Returns an error if there is no timing point at this index.
+	pub fn remove_timing_point(&mut self, beatmap: &mut BeatmapFile, index: usize) -> Result<(), EditError> {
+		check_index(&beatmap.timing_points, "timing point", index, false)?;
+		let timing_point = beatmap.timing_points[index].clone();
+		self.apply(beatmap, Edit::RemoveTimingPoint { index, timing_point })
+	}
+
+	/// Changes the fields of the timing point at an index.
+	///
+	/// # Errors
+	///
+	/// Returns an error if there is no timing point at this index.
+	pub fn modify_timing_point(
+		&mut self,
+		beatmap: &mut BeatmapFile,
+		index: usize,
+		modify: impl FnOnce(&mut TimingPoint),
+	) -> Result<(), EditError> {
+		check_index(&beatmap.timing_points, "timing point", index, false)?;
+		let old = beatmap.timing_points[index].clone();
+		let mut new = old.clone();
+		modify(&mut new);
+		self.apply(beatmap, Edit::ReplaceTimingPoint { index, old, new })
+	}
+
+	/// Undoes the last applied edit, returning false if there was nothing to undo.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the beatmap was changed outside of the transaction so that the edit can't be undone.
+	pub fn undo(&mut self, beatmap: &mut BeatmapFile) -> Result<bool, EditError> {
+		let Some(edit) = self.applied.pop() else {
+			return Ok(false);
+		};
+
+		if let Err(e) = edit.inverse().apply(beatmap) {
+			self.applied.push(edit);
+			return Err(e);
+		}
+
+		self.undone.push(edit);
+		Ok(true)
+	}
+
+	/// Redoes the last undone edit, returning false if there was nothing to redo.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the beatmap was changed outside of the transaction so that the edit can't be redone.
+	pub fn redo(&mut self, beatmap: &mut BeatmapFile) -> Result<bool, EditError> {
+		let Some(edit) = self.undone.pop() else {
+			return Ok(false);
+		};
+
+		if let Err(e) = edit.apply(beatmap) {
+			self.undone.push(edit);
+			return Err(e);
+		}
+
+		self.applied.push(edit);
+		Ok(true)
+	}
+
+	/// Writes the applied edits as an edit log, one edit per line.
+	///
+	/// # Errors
+	///
+	/// Returns the I/O errors of the writer.
+	pub fn write_log<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+		for edit in &self.applied {
+			writeln!(writer, "{edit}")?;
+		}
+
+		Ok(())
+	}
+
+	/// Parses an edit log written by [`Transaction::write_log`]. Empty lines are ignored.
+	///
+	/// # Errors
+	///
+	/// Returns an error if a line is not a valid edit.
+	pub fn parse_log(log: &str) -> Result<Vec<Edit>, EditLogParseError> {
+		(log.lines().enumerate())
+			.filter(|(_, line)| !line.trim().is_empty())
+			.map(|(i, line)| Edit::parse_log_line(line, i + 1))
+			.collect()
+	}
+
+	/// Applies the edits of an edit log to a beatmap, recording them.
+	///
+	/// # Errors
+	///
+	/// Returns an error if an edit couldn't be applied, in which case the edits before it stay applied.
+	pub fn replay(
+		&mut self,
+		beatmap: &mut BeatmapFile,
+		edits: impl IntoIterator<Item = Edit>,
+	) -> Result<(), EditError> {
+		for edit in edits {
+			self.apply(beatmap, edit)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Elements of one list of a beatmap that were added, removed or modified.
+#[derive(Clone, Debug)]
+pub struct ElementChanges<T> {
+	pub added: Vec<T>,
+	pub removed: Vec<T>,
+	/// Elements that changed while staying at about the same time, as their old and new versions.
+	pub modified: Vec<(T, T)>,
+}
+
+impl<T> Default for ElementChanges<T> {
+	fn default() -> Self {
+		Self {
+			added: Vec::new(),
+			removed: Vec::new(),
+			modified: Vec::new(),
+		}
+	}
+}
+
+impl<T: Clone + fmt::Display + Timestamped> ElementChanges<T> {
+	/// Compares two versions of a list, as they would be written in a `.osu` file.
+	///
+	/// Elements are compared regardless of their order. The elements left on each side are then paired in time order
+	/// as modified elements: all of them if as many were removed as added (like after an offset),
+	/// or only the ones at about the same time (see [`Timestamped::basically_eq`]) otherwise.
+	#[must_use]
+	pub fn diff(before: &[T], after: &[T]) -> Self {
+		let mut remaining: HashMap<String, usize> = HashMap::new();
+		for element in after {
+			*remaining.entry(element.to_string()).or_default() += 1;
+		}
+
+		let mut removed = Vec::new();
+		for element in before {
+			match remaining.get_mut(&element.to_string()) {
+				Some(count) if *count > 0 => *count -= 1,
+				_ => removed.push(element.clone()),
+			}
+		}
+
+		let mut added = Vec::new();
+		for element in after {
+			if let Some(count) = remaining.get_mut(&element.to_string()) {
+				if *count > 0 {
+					*count -= 1;
+					added.push(element.clone());
+				}
+			}
+		}
+
+		removed.sort_by(|a, b| a.timestamp().total_cmp(&b.timestamp()));
+		added.sort_by(|a, b| a.timestamp().total_cmp(&b.timestamp()));
+		if removed.len() == added.len() {
+			return Self {
+				modified: removed.into_iter().zip(added).collect(),
+				..Self::default()
+			};
+		}
+
+		let mut changes = Self::default();
+		let mut removed = removed.into_iter().peekable();
+		let mut added = added.into_iter().peekable();
+		while let (Some(old), Some(new)) = (removed.peek(), added.peek()) {
+			if old.basically_eq(new) {
+				changes.modified.extend(removed.next().zip(added.next()));
+			} else if old.timestamp() < new.timestamp() {
+				changes.removed.extend(removed.next());
+			} else {
+				changes.added.extend(added.next());
+			}
+		}
+
+		changes.removed.extend(removed);
+		changes.added.extend(added);
+		changes
+	}
+
+	/// Compares two versions of a list whose elements still correspond one for one, in the same order,
+	/// like after moving every element in time. Falls back to [`diff`](Self::diff) if their lengths differ.
+	#[must_use]
+	pub fn paired(before: &[T], after: &[T]) -> Self {
+		if before.len() != after.len() {
+			return Self::diff(before, after);
+		}
+
+		let modified = (before.iter().zip(after))
+			.filter(|(old, new)| old.to_string() != new.to_string())
+			.map(|(old, new)| (old.clone(), new.clone()))
+			.collect();
+
+		Self {
+			modified,
+			..Self::default()
+		}
+	}
+}
+
+impl<T: Timestamped> ElementChanges<T> {
+	/// Pairs two versions of a list whose elements were only moved in time, one for one and in the same order,
+	/// keeping the ones whose time changed. Unlike [`paired`](Self::paired), elements aren't written to be compared.
+	fn moved(before: Vec<T>, after: &[T]) -> Self
+	where
+		T: Clone,
+	{
+		let modified = (before.into_iter().zip(after))
+			.filter(|(old, new)| old.timestamp().total_cmp(&new.timestamp()).is_ne())
+			.map(|(old, new)| (old, new.clone()))
+			.collect();
+
+		Self {
+			modified,
+			..Self::default()
+		}
+	}
+
+	#[must_use]
+	pub const fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+	}
+
+	/// Times of every changed element.
+	fn times(&self) -> impl Iterator<Item = Timestamp> + '_ {
+		(self.added.iter().chain(&self.removed))
+			.chain(self.modified.iter().flat_map(|(old, new)| [old, new]))
+			.map(Timestamped::timestamp)
+	}
+
+	fn summarize(&self, name: &str) -> Option<String> {
+		let counts = [
+			(self.added.len(), "added"),
+			(self.removed.len(), "removed"),
+			(self.modified.len(), "modified"),
+		];
+
+		let counts: Vec<String> = (counts.iter())
+			.filter(|(count, _)| *count > 0)
+			.map(|(count, what)| format!("{count} {what}"))
+			.collect();
+
+		(!counts.is_empty()).then(|| format!("{name}: {}", counts.join(", ")))
+	}
+}
+
+/// A setting of a beatmap (from the `[General]`, `[Editor]`, `[Metadata]`, `[Difficulty]` or `[Colours]` sections,
+/// or the file format) that changed, as written in the `.osu` file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SettingChange {
+	/// Name of the section, like `[Difficulty]`, or an empty string for the file format.
+	pub section: String,
+	pub key: String,
+	/// Value before the change, or `None` if the setting wasn't written.
+	pub old: Option<String>,
+	/// Value after the change, or `None` if the setting isn't written anymore.
+	pub new: Option<String>,
+}
+
+/// Sections holding a list of elements, compared separately from the settings.
+const LIST_SECTIONS: [&str; 3] = ["[Events]", "[TimingPoints]", "[HitObjects]"];
+
+/// Settings of a beatmap as written in a `.osu` file, as `(section, key, value)` in order.
+fn written_settings(beatmap: &BeatmapFile) -> Vec<(String, String, String)> {
+	let mut bytes = Vec::new();
+	// writing to a Vec never fails
+	let _ = beatmap.deserialize(&mut bytes);
+
+	let mut settings = vec![(
+		String::new(),
+		"osu file format".to_owned(),
+		beatmap.osu_file_format.to_string(),
+	)];
+
+	let mut section = String::new();
+	for line in String::from_utf8_lossy(&bytes).lines().skip(1) {
+		if line.starts_with('[') && line.ends_with(']') {
+			line.clone_into(&mut section);
+		} else if let Some((key, value)) = line.split_once(':') {
+			if !LIST_SECTIONS.contains(&section.as_str()) {
+				settings.push((section.clone(), key.trim().to_owned(), value.trim().to_owned()));
+			}
+		}
+	}
+
+	settings
+}
+
+/// Settings that differ between two versions of a beatmap.
+fn diff_settings(before: &BeatmapFile, after: &BeatmapFile) -> Vec<SettingChange> {
+	let old_settings = written_settings(before);
+	let new_settings = written_settings(after);
+	let find = |settings: &[(String, String, String)], section: &str, key: &str| {
+		(settings.iter())
+			.find(|(s, k, _)| s == section && k == key)
+			.map(|(_, _, value)| value.clone())
+	};
+
+	let mut settings = Vec::new();
+	for (section, key, _) in old_settings.iter().chain(&new_settings) {
+		if settings
+			.iter()
+			.any(|c: &SettingChange| &c.section == section && &c.key == key)
+		{
+			continue;
+		}
+
+		let old = find(&old_settings, section, key);
+		let new = find(&new_settings, section, key);
+		if old != new {
+			settings.push(SettingChange {
+				section: section.clone(),
+				key: key.clone(),
+				old,
+				new,
+			});
+		}
+	}
+
+	settings
+}
+
+/// What changed between two versions of a beatmap.
+///
+/// It can describe the result of any operation, see [`ChangeSet::track`]. Operations that know what they changed
+/// return it themselves, like [`offset_map`](crate::algos::offset_map) or [`to_stable`](crate::algos::lazer::to_stable).
+#[derive(Clone, Debug, Default)]
+pub struct ChangeSet {
+	pub settings: Vec<SettingChange>,
+	pub events: ElementChanges<Event>,
+	pub timing_points: ElementChanges<TimingPoint>,
+	pub hit_objects: ElementChanges<HitObject>,
+}
+
+impl ChangeSet {
+	/// Compares two versions of a beatmap.
+	#[must_use]
+	pub fn diff(before: &BeatmapFile, after: &BeatmapFile) -> Self {
+		Self {
+			settings: diff_settings(before, after),
+			events: ElementChanges::diff(&before.events, &after.events),
+			timing_points: ElementChanges::diff(&before.timing_points, &after.timing_points),
+			hit_objects: ElementChanges::diff(&before.hit_objects, &after.hit_objects),
+		}
+	}
+
+	/// Compares two versions of a beatmap whose events, timing points and hit objects still correspond
+	/// one for one (see [`ElementChanges::paired`]), which is much cheaper than [`diff`](Self::diff).
+	#[must_use]
+	pub fn paired(before: &BeatmapFile, after: &BeatmapFile) -> Self {
+		Self {
+			settings: diff_settings(before, after),
+			events: ElementChanges::paired(&before.events, &after.events),
+			timing_points: ElementChanges::paired(&before.timing_points, &after.timing_points),
+			hit_objects: ElementChanges::paired(&before.hit_objects, &after.hit_objects),
+		}
+	}
+
+	/// Runs an operation on a beatmap, returning its result along with what it changed.
+	pub fn track<R>(beatmap: &mut BeatmapFile, operation: impl FnOnce(&mut BeatmapFile) -> R) -> (R, Self) {
+		let before = beatmap.clone();
+		let result = operation(beatmap);
+		(result, Self::diff(&before, beatmap))
+	}
+
+	/// Runs an operation that only moves elements in time, like [`offset_map`](crate::algos::offset_map),
+	/// returning its result along with what it changed.
+	///
+	/// The operation must not add, remove or reorder events, timing points and hit objects,
+	/// and may only change the settings of the `[General]` and `[Editor]` sections.
+	/// This is much cheaper than [`track`](Self::track): only the lists and these two sections are copied,
+	/// and elements are compared by time instead of being written.
+	pub fn track_moves<R>(beatmap: &mut BeatmapFile, operation: impl FnOnce(&mut BeatmapFile) -> R) -> (R, Self) {
+		let time_settings = |beatmap: &BeatmapFile| BeatmapFile {
+			osu_file_format: beatmap.osu_file_format,
+			general: beatmap.general.clone(),
+			editor: beatmap.editor.clone(),
+			..BeatmapFile::default()
+		};
+
+		let settings = time_settings(beatmap);
+		let events = beatmap.events.clone();
+		let timing_points = beatmap.timing_points.clone();
+		let hit_objects = beatmap.hit_objects.clone();
+
+		let result = operation(beatmap);
+		let changes = Self {
+			settings: diff_settings(&settings, &time_settings(beatmap)),
+			events: ElementChanges::moved(events, &beatmap.events),
+			timing_points: ElementChanges::moved(timing_points, &beatmap.timing_points),
+			hit_objects: ElementChanges::moved(hit_objects, &beatmap.hit_objects),
+		};
+		(result, changes)
+	}
+
+	#[must_use]
+	pub const fn is_empty(&self) -> bool {
+		self.settings.is_empty()
+			&& self.events.is_empty()
+			&& self.timing_points.is_empty()
+			&& self.hit_objects.is_empty()
+	}
+
+	/// Time range covering every changed event, timing point and hit object,
+	/// or `None` if only settings changed.
+	#[must_use]
+	pub fn time_range(&self) -> Option<Range<Timestamp>> {
+		let mut times = (self.events.times())
+			.chain(self.timing_points.times())
+			.chain(self.hit_objects.times());
+
+		let first = times.next()?;
+		Some(times.fold(first..first, |range, time| range.start.min(time)..range.end.max(time)))
+	}
+}
+
+impl fmt::Display for ChangeSet {
+	/// Formats a one-line summary of the changes, like `hit objects: 2 added, 1 modified; settings: 1 changed`.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let mut parts: Vec<String> = [
+			self.hit_objects.summarize("hit objects"),
+			self.timing_points.summarize("timing points"),
+			self.events.summarize("events"),
+		]
+		.into_iter()
+		.flatten()
+		.collect();
+
+		if !self.settings.is_empty() {
+			parts.push(format!("settings: {} changed", self.settings.len()));
+		}
+
+		if parts.is_empty() {
+			f.write_str("no changes")
+		} else {
+			f.write_str(&parts.join("; "))
+		}
+	}
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+	use super::*;
+
+	fn hit_objects(lines: &[&str]) -> Vec<HitObject> {
+		lines.iter().map(|line| parse_hit_object(line).unwrap()).collect()
+	}
+
+	#[test]
+	fn offset_objects_are_modified() {
+		let before = hit_objects(&["100,100,500,1,0,0:0:0:0:", "200,100,1000,1,0,0:0:0:0:"]);
+		let after = hit_objects(&["100,100,525,1,0,0:0:0:0:", "200,100,1025,1,0,0:0:0:0:"]);
+
+		let changes = ElementChanges::diff(&before, &after);
+		assert!(changes.added.is_empty() && changes.removed.is_empty());
+		assert_eq!(changes.modified.len(), 2);
+		assert_eq!(changes.modified[0].0.time, 500.0);
+		assert_eq!(changes.modified[0].1.time, 525.0);
+	}
+
+	#[test]
+	fn only_close_objects_are_paired() {
+		let before = hit_objects(&["100,100,500,1,0,0:0:0:0:", "200,100,1000,1,0,0:0:0:0:"]);
+		let after = hit_objects(&["300,100,500,1,0,0:0:0:0:"]);
+
+		let changes = ElementChanges::diff(&before, &after);
+		assert!(changes.added.is_empty());
+		assert_eq!(changes.modified.len(), 1);
+		assert_eq!(changes.removed.len(), 1);
+		assert_eq!(changes.removed[0].time, 1000.0);
+	}
+
+	fn beatmap(lines: &[&str]) -> BeatmapFile {
+		BeatmapFile {
+			hit_objects: hit_objects(lines),
+			..BeatmapFile::default()
+		}
+	}
+
+	fn written(beatmap: &BeatmapFile) -> Vec<String> {
+		beatmap.hit_objects.iter().map(ToString::to_string).collect()
+	}
+
+	#[test]
+	fn logs_replay_and_undo() {
+		let original = beatmap(&["100,100,500,1,0,0:0:0:0:", "200,100,1000,1,0,0:0:0:0:"]);
+		let mut edited = original.clone();
+		let mut transaction = Transaction::new();
+
+		let mut hit_object = parse_hit_object("300,100,750,1,0,0:0:0:0:").unwrap();
+		// separators of the log inside an element
+		hit_object.hit_sample.filename = Some("drums\\kick\t2.wav".to_owned());
+		transaction.insert_hit_object(&mut edited, 1, hit_object).unwrap();
+		transaction.modify_hit_object(&mut edited, 0, |ho| ho.x = 50.0).unwrap();
+		transaction.remove_hit_object(&mut edited, 2).unwrap();
+
+		let mut log = Vec::new();
+		transaction.write_log(&mut log).unwrap();
+		let log = String::from_utf8(log).unwrap();
+		assert_eq!(log.lines().count(), 3);
+		assert!(log.contains("drums\\\\kick\\t2.wav"));
+
+		let edits = Transaction::parse_log(&log).unwrap();
+		let mut replayed = original.clone();
+		let mut replay = Transaction::new();
+		replay.replay(&mut replayed, edits).unwrap();
+		assert_eq!(written(&replayed), written(&edited));
+		assert_eq!(
+			replayed.hit_objects[1].hit_sample.filename.as_deref(),
+			Some("drums\\kick\t2.wav")
+		);
+
+		while replay.undo(&mut replayed).unwrap() {}
+		assert_eq!(written(&replayed), written(&original));
+	}
+
+	#[test]
+	fn edits_of_other_elements_are_rejected() {
+		let mut beatmap = beatmap(&["100,100,500,1,0,0:0:0:0:", "200,100,1000,1,0,0:0:0:0:"]);
+		let before = written(&beatmap);
+
+		let remove = Edit::RemoveHitObject {
+			index: 0,
+			hit_object: beatmap.hit_objects[1].clone(),
+		};
+		let replace = Edit::ReplaceHitObject {
+			index: 1,
+			old: beatmap.hit_objects[0].clone(),
+			new: beatmap.hit_objects[0].clone(),
+		};
+
+		for edit in [remove, replace] {
+			assert!(matches!(edit.apply(&mut beatmap), Err(EditError::Mismatch { .. })));
+		}
+		assert_eq!(written(&beatmap), before);
+
+		assert!(matches!(
+			Transaction::parse_log("remove\thit_object\t0\t100,100,500,1,0,0:0:0:0:a\\b.wav"),
+			Err(EditLogParseError::InvalidEscape(1))
+		));
+	}
+}
@@ -1 +1,96 @@
 pub mod beatmap;
+pub mod binary;
+
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::{self, BufReader, Read};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+/// Files bigger than this are assumed not to be beatmaps without even opening them.
+pub const MAX_OSU_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Number of bytes read from the start of a file to decide whether it is a beatmap.
+pub const SNIFF_LENGTH: usize = 32;
+
+const OSU_FILE_HEADER: &[u8] = b"osu file format v";
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Magic numbers of binary files commonly found next to beatmaps (images, audio, video, archives).
+const BINARY_MAGIC_NUMBERS: &[&[u8]] = &[
+	b"\x89PNG",
+	b"\xFF\xD8\xFF",
+	b"GIF8",
+	b"BM",
+	b"ID3",
+	b"\xFF\xFB",
+	b"\xFF\xF3",
+	b"\xFF\xF2",
+	b"OggS",
+	b"RIFF",
+	b"fLaC",
+	b"\x1A\x45\xDF\xA3",
+	b"FLV",
+	b"PK\x03\x04",
+];
+
+/// Checks whether the first bytes of a file look like the start of an .osu file.
+#[must_use]
+pub fn is_osu_header(bytes: &[u8]) -> bool {
+	if BINARY_MAGIC_NUMBERS.iter().any(|magic| bytes.starts_with(magic)) {
+		return false;
+	}
+
+	// MP4/MOV files have their magic number after the box size
+	if bytes.get(4..8) == Some(b"ftyp") {
+		return false;
+	}
+
+	let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+	let start = bytes
+		.iter()
+		.position(|b| !b.is_ascii_whitespace())
+		.unwrap_or(bytes.len());
+	bytes[start..].starts_with(OSU_FILE_HEADER)
+}
+
+/// Checks whether the file at `path` is an .osu file, regardless of its extension.
+///
+/// Only the first [`SNIFF_LENGTH`] bytes are read, and files bigger than [`MAX_OSU_FILE_SIZE`] are skipped,
+/// which makes this cheap enough to run on every file of an osu!lazer `files` folder.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be opened or read.
+#[cfg(feature = "fs")]
+pub fn sniff_osu_file(path: impl AsRef<Path>) -> io::Result<bool> {
+	let file = File::open(path)?;
+	if file.metadata()?.len() > MAX_OSU_FILE_SIZE {
+		return Ok(false);
+	}
+
+	let mut header = Vec::with_capacity(SNIFF_LENGTH);
+	file.take(SNIFF_LENGTH as u64).read_to_end(&mut header)?;
+	Ok(is_osu_header(&header))
+}
+
+/// Lowercase hexadecimal MD5 hash of some bytes, which is how osu! identifies beatmaps and replays.
+#[must_use]
+pub fn md5_hex(bytes: &[u8]) -> String {
+	format!("{:x}", md5::compute(bytes))
+}
+
+/// Lowercase hexadecimal MD5 hash of a file's raw contents.
+///
+/// For a `.osu` file, this is the hash osu! stores in `osu!.db`, collections, replays and scores.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be opened or read.
+#[cfg(feature = "fs")]
+pub fn hash_file(path: impl AsRef<Path>) -> io::Result<String> {
+	let mut context = md5::Context::new();
+	io::copy(&mut BufReader::new(File::open(path)?), &mut context)?;
+	Ok(format!("{:x}", context.compute()))
+}
@@ -1,25 +1,45 @@
+use std::collections::HashMap;
 use std::fmt;
-use std::io::{self, Write};
+#[cfg(feature = "fs")]
+use std::fs::File;
+use std::io::{self, BufRead, Write};
 use std::num::ParseIntError;
-use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign};
-use std::path::Path;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Range};
+#[cfg(feature = "fs")]
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
+pub mod borrowed;
 pub mod deserializing;
+pub mod errors;
+pub mod hit_objects;
 pub mod parsing;
+pub mod quirks;
+pub mod timing_points;
+pub mod trivia;
 pub mod utils;
 
-use crate::point::Point;
-use crate::{ExtTimestamped, InterleavedTimestampedIterator, Timestamped};
-use deserializing::deserialize_beatmap_file;
-use parsing::parse_osu_file;
-
-use self::parsing::BeatmapFileParseError;
+use crate::analysis::end_time;
+#[cfg(feature = "fs")]
+use crate::mapset::standard_filename;
+use crate::point::{Point, Rounding};
+use crate::{MultiInterleavedIterator, Timestamped};
+use deserializing::{deserialize_beatmap_file, deserialize_beatmap_file_with_options, DeserializeOptions};
+use parsing::{
+	parse_float, parse_osu_reader, parse_osu_str, parse_osu_str_with_options, parse_osu_str_with_warnings, ParseOptions,
+};
+#[cfg(feature = "fs")]
+use parsing::{parse_osu_file, parse_osu_file_with_options, parse_osu_file_with_warnings};
+use quirks::ParseWarning;
+use trivia::Trivia;
+
+use self::errors::{BeatmapFileParseError, FloatParseError};
 
 pub type Timestamp = f64;
 
 /// Draw order of hit circle overlays compared to hit numbers.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OverlayPosition {
 	/// use skin setting
 	NoChange,
@@ -48,6 +68,7 @@ impl FromStr for OverlayPosition {
 
 /// General information about the beatmap
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(clippy::struct_excessive_bools)]
 pub struct GeneralSection {
 	/// Location of the audio file relative to the current folder
@@ -128,6 +149,7 @@ impl Default for GeneralSection {
 
 /// Saved settings for the beatmap editor
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EditorSection {
 	/// Time in milliseconds of bookmarks
 	pub bookmarks: Vec<f32>,
@@ -143,6 +165,7 @@ pub struct EditorSection {
 
 /// Information used to identify the beatmap
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MetadataSection {
 	/// Romanised song title
 	pub title: String,
@@ -166,8 +189,45 @@ pub struct MetadataSection {
 	pub beatmap_set_id: Option<i32>,
 }
 
+impl MetadataSection {
+	/// Fills the romanised title and artist by transliterating the unicode ones to ASCII,
+	/// when they are empty or not ASCII. Returns whether a field changed.
+	///
+	/// Original text written in a romanised field is moved to the unicode field if that one is empty.
+	/// Transliteration is only a starting point: kanji are read as Chinese, for example,
+	/// so the result should be reviewed.
+	#[cfg(feature = "transliteration")]
+	pub fn fill_romanised(&mut self) -> bool {
+		let mut changed = false;
+		let pairs = [
+			(&mut self.title, &mut self.title_unicode),
+			(&mut self.artist, &mut self.artist_unicode),
+		];
+
+		for (romanised, unicode) in pairs {
+			if !romanised.trim().is_empty() && romanised.is_ascii() {
+				continue;
+			}
+
+			if unicode.trim().is_empty() {
+				unicode.clone_from(romanised);
+			}
+
+			let transliterated = deunicode::deunicode(unicode);
+			let transliterated = transliterated.split_whitespace().collect::<Vec<_>>().join(" ");
+			if *romanised != transliterated {
+				*romanised = transliterated;
+				changed = true;
+			}
+		}
+
+		changed
+	}
+}
+
 /// Difficulty settings
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DifficultySection {
 	/// HP setting (0–10)
 	pub hp_drain_rate: f32,
@@ -183,11 +243,108 @@ pub struct DifficultySection {
 	pub slider_tick_rate: f32,
 }
 
+/// Timing windows in milliseconds around a hit object's time, for each judgement.
+///
+/// A hit is given a judgement if it happens within `time ± window`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HitWindows {
+	/// Window for a 300 (GREAT in osu!taiko).
+	pub w300: f64,
+	/// Window for a 100 (GOOD in osu!taiko).
+	pub w100: f64,
+	/// Window for a 50. osu!taiko doesn't have this judgement, so it's the same as `w100`.
+	pub w50: f64,
+}
+
+/// Settings osu! uses for the keys that are missing from the section.
+impl Default for DifficultySection {
+	fn default() -> Self {
+		Self {
+			hp_drain_rate: 5.0,
+			circle_size: 5.0,
+			overall_difficulty: 5.0,
+			approach_rate: 5.0,
+			slider_multiplier: 1.4,
+			slider_tick_rate: 1.0,
+		}
+	}
+}
+
+impl DifficultySection {
+	/// Hit windows of the beatmap depending on its OD, for a given mode (without mods).
+	///
+	/// Returns `None` for osu!catch, as it doesn't judge hits on timing.
+	#[must_use]
+	pub fn hit_windows(&self, mode: u8) -> Option<HitWindows> {
+		let od = f64::from(self.overall_difficulty);
+
+		match mode {
+			0 => Some(HitWindows {
+				w300: 6.0f64.mul_add(-od, 80.0),
+				w100: 8.0f64.mul_add(-od, 140.0),
+				w50: 10.0f64.mul_add(-od, 200.0),
+			}),
+			1 => {
+				let w100 = if od <= 5.0 {
+					8.0f64.mul_add(-od, 120.0)
+				} else {
+					6.0f64.mul_add(-od, 110.0)
+				};
+
+				Some(HitWindows {
+					w300: 3.0f64.mul_add(-od, 50.0),
+					w100,
+					w50: w100,
+				})
+			}
+			3 => Some(HitWindows {
+				w300: 3.0f64.mul_add(-od, 64.0),
+				w100: 3.0f64.mul_add(-od, 127.0),
+				w50: 3.0f64.mul_add(-od, 151.0),
+			}),
+			_ => None,
+		}
+	}
+
+	/// Time in milliseconds before a hit object's time at which it starts to appear, depending on AR.
+	#[must_use]
+	pub fn preempt(&self) -> f64 {
+		let ar = f64::from(self.approach_rate);
+
+		if ar < 5.0 {
+			120.0f64.mul_add(5.0 - ar, 1200.0)
+		} else {
+			150.0f64.mul_add(-(ar - 5.0), 1200.0)
+		}
+	}
+
+	/// Time in milliseconds that a hit object takes to fully fade in after it starts to appear, depending on AR.
+	#[must_use]
+	pub fn fade_in(&self) -> f64 {
+		let ar = f64::from(self.approach_rate);
+
+		if ar < 5.0 {
+			80.0f64.mul_add(5.0 - ar, 800.0)
+		} else {
+			100.0f64.mul_add(-(ar - 5.0), 800.0)
+		}
+	}
+
+	/// Time in milliseconds that the approach circle takes to close in on a hit object.
+	///
+	/// This is the same as the [preempt](Self::preempt) time.
+	#[must_use]
+	pub fn approach_duration(&self) -> f64 {
+		self.preempt()
+	}
+}
+
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EventParams {
 	Background {
 		/// Location of the background image relative to the beatmap directory.
-		/// Double quotes are usually included surrounding the filename, but they are not required.
+		/// Double quotes surrounding the filename in the file are not included.
 		filename: String,
 		/// Offset in osu! pixels from the center of the screen.
 		/// For example, an offset of `50,100` would have the
@@ -204,7 +361,7 @@ pub enum EventParams {
 	},
 	Video {
 		/// Location of the video relative to the beatmap directory.
-		/// Double quotes are usually included surrounding the filename, but they are not required.
+		/// Double quotes surrounding the filename in the file are not included.
 		filename: String,
 		/// Offset in osu! pixels from the center of the screen.
 		/// For example, an offset of `50,100` would have the
@@ -222,10 +379,25 @@ pub enum EventParams {
 	Break {
 		end_time: Timestamp,
 	},
+	/// Storyboard audio sample, played at the event's start time.
+	Sample {
+		/// Layer on which the sample is played.
+		/// - `0` = Background
+		/// - `1` = Fail
+		/// - `2` = Pass
+		/// - `3` = Foreground
+		layer: u8,
+		/// Location of the audio file relative to the beatmap directory.
+		/// Double quotes surrounding the filename in the file are not included.
+		filename: String,
+		/// Volume percentage of the sample. If it is not written, it defaults to `100`.
+		volume: u8,
+	},
 }
 
 /// Beatmap and storyboard graphic event
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Event {
 	/// Type of the event. Some events may be referred to by either a name or a number.
 	pub event_type: String,
@@ -244,6 +416,7 @@ impl Timestamped for Event {
 
 /// Timing and control points
 #[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TimingPoint {
 	/// Start time of the timing section, in milliseconds from the beginning of the beatmap's audio.
 	/// The end of the timing section is the next timing point's time (or never, if this is the last timing point).
@@ -253,6 +426,8 @@ pub struct TimingPoint {
 	/// - For inherited timing points, a negative inverse slider velocity multiplier, as a percentage.
 	///   For example, `-50` would make all sliders in this timing section twice as fast as `slider_multiplier`.
 	pub beat_length: f64,
+	/// Text `beat_length` was parsed from, written back instead of `beat_length` as long as it isn't changed.
+	pub raw_beat_length: Option<RawNumber>,
 	/// Amount of beats in a measure. Inherited timing points ignore this property.
 	/// This number can be negative for some reason???
 	/// See beatmap <https://osu.ppy.sh/beatmapsets/539221#osu/1265214>
@@ -282,6 +457,76 @@ impl PartialOrd for TimingPoint {
 }
 
 impl TimingPoint {
+	/// Bit flag in `effects` that enables kiai time.
+	pub const EFFECT_KIAI: u32 = 1 << 0;
+	/// Bit flag in `effects` that omits the first barline in osu!taiko and osu!mania.
+	pub const EFFECT_OMIT_FIRST_BARLINE: u32 = 1 << 3;
+	/// Slider velocity multipliers closer than this are considered the same.
+	///
+	/// osu!lazer writes beat lengths with long decimals, which rarely convert back to the exact same multiplier.
+	pub const SV_TOLERANCE: f64 = 1e-9;
+
+	/// Creates an uninherited timing point (a red line) with the given BPM and amount of beats per measure.
+	///
+	/// The volume is set to 100%, every other setting is left to its default.
+	#[must_use]
+	pub fn uninherited(time: Timestamp, bpm: f64, meter: i32) -> Self {
+		Self {
+			time,
+			beat_length: 60_000.0 / bpm,
+			meter,
+			volume: 100,
+			uninherited: true,
+			..Self::default()
+		}
+	}
+
+	/// Creates an inherited timing point (a green line) with the given slider velocity multiplier.
+	///
+	/// The volume is set to 100%, every other setting is left to its default.
+	#[must_use]
+	pub fn inherited(time: Timestamp, sv_multiplier: f64) -> Self {
+		Self {
+			time,
+			beat_length: -100.0 / sv_multiplier,
+			meter: 4,
+			volume: 100,
+			uninherited: false,
+			..Self::default()
+		}
+	}
+
+	/// Returns the timing point with another volume.
+	#[must_use]
+	pub const fn with_volume(mut self, volume: u8) -> Self {
+		self.volume = volume;
+		self
+	}
+
+	/// BPM of an uninherited timing point, `None` for inherited ones.
+	#[must_use]
+	pub fn bpm(&self) -> Option<f64> {
+		self.uninherited.then(|| 60_000.0 / self.beat_length)
+	}
+
+	/// Slider velocity multiplier set by this timing point.
+	///
+	/// Uninherited timing points reset it to 1, and osu! clamps it between 0.1 and 10.
+	#[must_use]
+	pub fn sv_multiplier(&self) -> f64 {
+		if self.uninherited || self.beat_length >= 0.0 {
+			1.0
+		} else {
+			(-100.0 / self.beat_length).clamp(0.1, 10.0)
+		}
+	}
+
+	/// Whether kiai time is enabled by this timing point.
+	#[must_use]
+	pub const fn is_kiai(&self) -> bool {
+		self.effects & Self::EFFECT_KIAI != 0
+	}
+
 	/// Whether this timing point is a duplicate of the other.
 	///
 	/// A timing point is a duplicate of the other if all their fields except `time` and `uninherited` are equal.
@@ -296,7 +541,55 @@ impl TimingPoint {
 	}
 }
 
+/// A number parsed from a beatmap, along with the text it was written as.
+///
+/// Writing a float back can change its text (`-100.0` becomes `-100`, and long decimals can be rounded differently),
+/// which makes diffs noisy and can subtly change slider velocities in other tools.
+/// Keeping the text allows writing back the exact same bytes when the number hasn't been modified.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RawNumber {
+	value: f64,
+	text: Box<str>,
+}
+
+impl RawNumber {
+	/// The parsed number.
+	#[must_use]
+	pub const fn value(&self) -> f64 {
+		self.value
+	}
+
+	/// The text the number was parsed from.
+	#[must_use]
+	pub const fn text(&self) -> &str {
+		&self.text
+	}
+
+	/// The original text if `value` is still exactly the parsed number, or `None` if it has been modified.
+	#[must_use]
+	pub fn text_for(&self, value: f64) -> Option<&str> {
+		(self.value.to_bits() == value.to_bits()).then_some(&*self.text)
+	}
+}
+
+impl FromStr for RawNumber {
+	type Err = FloatParseError;
+
+	/// Scientific notation isn't kept, since osu!stable can't read it: `1E-05` is written back as `0.00001`.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let value: f64 = parse_float(s)?;
+		let text = if s.contains(['e', 'E']) {
+			value.to_string().into()
+		} else {
+			s.into()
+		};
+		Ok(Self { value, text })
+	}
+}
+
 #[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Color {
 	/// Red value in range `[0, 255]`.
 	pub r: u8,
@@ -318,6 +611,7 @@ impl Color {
 
 /// Combo and skin colors
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ColorsSection {
 	/// Additive combo colors
 	pub combo_colors: Vec<Color>,
@@ -329,6 +623,7 @@ pub struct ColorsSection {
 
 /// A bank of samples for normal, whistle, finish and clap hitsounds.
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum SampleBank {
 	#[default]
@@ -357,6 +652,7 @@ impl FromStr for SampleBank {
 }
 
 #[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HitSampleSet {
 	/// Sample set of the normal sound.
 	pub normal_set: SampleBank,
@@ -420,6 +716,7 @@ impl FromStr for HitSampleSet {
 
 /// Type of curve used to construct a slider at a particular point.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SliderCurveType {
 	/// inherit the previous point's curve type
 	Inherit,
@@ -435,6 +732,7 @@ pub enum SliderCurveType {
 
 /// Anchor point used to construct a slider.
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SliderPoint {
 	/// Type of curve used to construct this slider.
 	/// (B = bézier, C = centripetal catmull-rom, L = linear, P = perfect circle)
@@ -446,6 +744,38 @@ pub struct SliderPoint {
 }
 
 impl SliderPoint {
+	#[must_use]
+	pub const fn new(curve_type: SliderCurveType, x: f32, y: f32) -> Self {
+		Self { curve_type, x, y }
+	}
+
+	/// A control point continuing the curve of the previous one, see [`SliderCurveType::Inherit`].
+	#[must_use]
+	pub const fn inherit(x: f32, y: f32) -> Self {
+		Self::new(SliderCurveType::Inherit, x, y)
+	}
+
+	/// A control point at integer coordinates, like the ones of osu!stable beatmaps.
+	#[must_use]
+	pub fn new_i16(curve_type: SliderCurveType, x: i16, y: i16) -> Self {
+		Self::new(curve_type, f32::from(x), f32::from(y))
+	}
+
+	/// A control point at a point of the playfield, with its coordinates made whole with `rounding`.
+	#[must_use]
+	pub const fn from_point(curve_type: SliderCurveType, point: Point, rounding: Rounding) -> Self {
+		let point = point.rounded(rounding);
+
+		#[allow(clippy::cast_possible_truncation)]
+		Self::new(curve_type, point.x as f32, point.y as f32)
+	}
+
+	/// This control point with its coordinates made whole with `rounding`.
+	#[must_use]
+	pub fn rounded(self, rounding: Rounding) -> Self {
+		Self::from_point(self.curve_type, self.to_point(), rounding)
+	}
+
 	#[must_use]
 	pub fn to_point(&self) -> Point {
 		Point {
@@ -455,8 +785,16 @@ impl SliderPoint {
 	}
 }
 
+impl From<Point> for SliderPoint {
+	/// A control point inheriting the curve type of the previous one, keeping the exact coordinates of the point.
+	fn from(point: Point) -> Self {
+		Self::from_point(SliderCurveType::Inherit, point, Rounding::Exact)
+	}
+}
+
 /// Extra parameters specific to the object's type.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum HitObjectParams {
 	HitCircle,
 	Slider {
@@ -470,7 +808,7 @@ pub enum HitObjectParams {
 		///
 		/// ## Example of slider curve points
 		///
-		/// ```no_run
+		/// ```text
 		/// P|213:282|P|257:269|234:254|P|158:283|129:306|B|39:234|L|57:105|68:173
 		/// ```
 		///
@@ -479,7 +817,7 @@ pub enum HitObjectParams {
 		///
 		/// For example, this slider has its head in linear curve mode,
 		/// and then the immediate next curve point is in perfect curve mode.
-		/// ```no_run
+		/// ```text
 		/// L|P|12:392|24:369|76:331
 		/// ```
 		///
@@ -505,17 +843,58 @@ pub enum HitObjectParams {
 	/// (osu!mania only)
 	///
 	/// Note: `x` determines the index of the column that the hold will be in.
-	/// It is computed by `floor(x * column_count / 512)` and clamped between `0` and `column_count - 1`.
+	/// It is computed by `floor(x * column_count / 512)` and clamped between `0` and `column_count - 1`
+	/// (see [`HitObject::column`]).
 	///
 	/// `y` does not affect holds. It defaults to the center of the playfield, `192`.
 	Hold {
 		/// End time of the hold, in milliseconds from the beginning of the beatmap's audio.
 		end_time: Timestamp,
+		/// Sounds played when the hold is released, which only osu!lazer beatmaps can have.
+		/// `None` if the hold only plays its head sounds, like every hold of osu!stable.
+		tail: Option<HoldTail>,
 	},
 }
 
+/// Sounds played at the end of an osu!mania hold (osu!lazer only).
+///
+/// osu!lazer keeps them as the node samples of the hold, which its `.osu` files don't store:
+/// a hold is only written as `endTime:hitSample`. So tails are kept in memory only,
+/// and are lost when the beatmap is written (see [`to_stable`](crate::algos::lazer::to_stable) to keep their additions).
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HoldTail {
+	pub hit_sound: HitSound,
+	pub sample_set: HitSampleSet,
+}
+
+impl HitObjectParams {
+	/// Makes a slider's edge hitsounds and samplesets have exactly one value per edge (`slides + 1`).
+	///
+	/// Missing values are filled by carrying the last one forward, and extra values are dropped.
+	/// This does nothing for other hit object types.
+	pub fn normalize_edges(&mut self) {
+		if let Self::Slider {
+			slides,
+			edge_hitsounds,
+			edge_samplesets,
+			..
+		} = self
+		{
+			let edge_count = *slides as usize + 1;
+
+			let last_hitsound = edge_hitsounds.last().copied().unwrap_or(HitSound::NONE);
+			edge_hitsounds.resize(edge_count, last_hitsound);
+
+			let last_sampleset = edge_samplesets.last().copied().unwrap_or_default();
+			edge_samplesets.resize(edge_count, last_sampleset);
+		}
+	}
+}
+
 /// Extra parameters specific to the object's type.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum HitObjectType {
 	/// Hit circle.
 	///
@@ -552,6 +931,7 @@ impl fmt::Display for HitObjectType {
 }
 
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HitSample {
 	/// Sample set of the normal sound.
 	pub normal_set: SampleBank,
@@ -604,6 +984,7 @@ impl HitSample {
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(transparent)]
 pub struct HitSound(u8);
 
@@ -628,6 +1009,19 @@ impl HitSound {
 	pub const FINISH: Self = Self(0b0100);
 	pub const CLAP: Self = Self(0b1000);
 
+	/// Every single flag, in order.
+	pub const FLAGS: [Self; 4] = [Self::NORMAL, Self::WHISTLE, Self::FINISH, Self::CLAP];
+
+	/// Iterates over the single flags that are set, in order.
+	pub fn iter_set_flags(self) -> impl Iterator<Item = Self> {
+		Self::FLAGS.into_iter().filter(move |&flag| self.has_all(flag))
+	}
+
+	/// Iterates over the additions (whistle, finish and clap) that are set, in order.
+	pub fn iter_additions(self) -> impl Iterator<Item = Self> {
+		self.iter_set_flags().filter(|&flag| flag != Self::NORMAL)
+	}
+
 	#[must_use]
 	pub fn flags_string_verbose(&self) -> String {
 		let mut sflags = "(hs)".to_owned();
@@ -741,6 +1135,7 @@ impl BitOrAssign for HitSound {
 
 /// Hit object
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HitObject {
 	/// Horizontal position in osu! pixels of the object.
 	pub x: f32,
@@ -843,6 +1238,63 @@ impl HitObject {
 
 		1 << rt | ccskip
 	}
+
+	/// Position of the hit object on the playfield.
+	#[must_use]
+	pub fn position(&self) -> Point {
+		Point::new(f64::from(self.x), f64::from(self.y))
+	}
+
+	/// Makes the position of the hit object and of its slider control points whole with `rounding`,
+	/// returning whether any of them moved.
+	pub fn round_positions(&mut self, rounding: Rounding) -> bool {
+		if rounding == Rounding::Exact {
+			return false;
+		}
+
+		let is_whole = |x: f32, y: f32| x.fract() == 0.0 && y.fract() == 0.0;
+		let mut moved = !is_whole(self.x, self.y);
+
+		let position = self.position().rounded(rounding);
+		#[allow(clippy::cast_possible_truncation)]
+		let (x, y) = (position.x as f32, position.y as f32);
+		(self.x, self.y) = (x, y);
+
+		if let HitObjectParams::Slider { curve_points, .. } = &mut self.object_params {
+			for curve_point in curve_points {
+				moved |= !is_whole(curve_point.x, curve_point.y);
+				*curve_point = curve_point.rounded(rounding);
+			}
+		}
+
+		moved
+	}
+
+	/// Column of an osu!mania note in a beatmap with `key_count` columns.
+	///
+	/// Like in osu!, it is computed by `floor(x * key_count / 512)` and clamped between `0` and `key_count - 1`.
+	#[must_use]
+	pub fn column(&self, key_count: u32) -> u32 {
+		let key_count = key_count.max(1);
+
+		#[allow(
+			clippy::cast_possible_truncation,
+			clippy::cast_sign_loss,
+			clippy::cast_precision_loss
+		)]
+		let column = (self.x * key_count as f32 / 512.0).floor().max(0.0) as u32;
+		column.min(key_count - 1)
+	}
+
+	/// Horizontal position osu! writes for osu!mania notes in a given column, which is the middle of the column.
+	#[must_use]
+	pub fn column_x(column: u32, key_count: u32) -> f32 {
+		let key_count = key_count.max(1);
+
+		#[allow(clippy::cast_precision_loss)]
+		let x = ((column as f32 + 0.5) * 512.0 / key_count as f32).floor();
+		x
+	}
 }
 
 impl Timestamped for HitObject {
@@ -853,6 +1305,7 @@ impl Timestamped for HitObject {
 
 /// `.osu` is a human-readable file format containing information about a beatmap.
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(clippy::module_name_repetitions)]
 pub struct BeatmapFile {
 	/// The first line of the file which specifies the file format version.
@@ -875,8 +1328,68 @@ pub struct BeatmapFile {
 	pub colors: Option<ColorsSection>,
 	/// Hit objects
 	pub hit_objects: Vec<HitObject>,
+	/// Lines of non-standard sections by name (without brackets), only kept for the sections registered
+	/// in [`ParseOptions::custom_sections`] and written just before `[HitObjects]`
+	pub custom_sections: HashMap<String, Vec<String>>,
+	/// Comments and blank lines, only kept when parsing with [`ParseOptions::preserve_trivia`]
+	pub trivia: Vec<Trivia>,
+}
+
+/// Order in which events are written, see [`BeatmapFile::normalize_events`].
+const fn event_order(params: &EventParams) -> u8 {
+	match params {
+		EventParams::Background { .. } => 0,
+		EventParams::Video { .. } => 1,
+		EventParams::Break { .. } => 2,
+		EventParams::Sample { .. } => 3,
+	}
+}
+
+pub(crate) fn normalize_events(events: &mut Vec<Event>) {
+	let mut backgrounds: Vec<(String, i32, i32)> = Vec::new();
+	events.retain(|event| match &event.params {
+		EventParams::Background {
+			filename,
+			x_offset,
+			y_offset,
+		} => {
+			let background = (filename.clone(), *x_offset, *y_offset);
+			if backgrounds.contains(&background) {
+				false
+			} else {
+				backgrounds.push(background);
+				true
+			}
+		}
+		EventParams::Break { end_time } => *end_time > event.start_time,
+		_ => true,
+	});
+
+	// the sort is stable, so events of the same kind keep their order
+	events.sort_by_key(|event| event_order(&event.params));
+}
+
+/// A timing point, hit object or event of a beatmap, see [`BeatmapFile::iter_elements`].
+#[derive(Clone, Copy, Debug)]
+pub enum BeatmapElement<'a> {
+	TimingPoint(&'a TimingPoint),
+	HitObject(&'a HitObject),
+	Event(&'a Event),
+}
+
+impl Timestamped for BeatmapElement<'_> {
+	fn timestamp(&self) -> Timestamp {
+		match self {
+			Self::TimingPoint(timing_point) => timing_point.time,
+			Self::HitObject(hit_object) => hit_object.time,
+			Self::Event(event) => event.start_time,
+		}
+	}
 }
 
+/// Timing points, hit objects and events of a beatmap merged in chronological order.
+pub type BeatmapElements<'a> = MultiInterleavedIterator<Box<dyn Iterator<Item = BeatmapElement<'a>> + 'a>>;
+
 impl BeatmapFile {
 	/// Parses an osu! beatmap file.
 	///
@@ -888,10 +1401,67 @@ impl BeatmapFile {
 	/// # Errors
 	///
 	/// This function will return an error if the file doesn't exist or could not be parsed correctly.
+	#[cfg(feature = "fs")]
 	pub fn parse<P: AsRef<Path>>(path: P) -> Result<Self, BeatmapFileParseError> {
 		parse_osu_file(path)
 	}
 
+	/// Parses an osu! beatmap from the contents of a `.osu` file.
+	///
+	/// # Errors
+	///
+	/// This function will return an error if the beatmap could not be parsed correctly.
+	pub fn parse_str(contents: &str) -> Result<Self, BeatmapFileParseError> {
+		parse_osu_str(contents)
+	}
+
+	/// Parses an osu! beatmap file, also returning the quirks found in it (see [`quirks`]).
+	///
+	/// # Errors
+	///
+	/// This function will return an error if the file doesn't exist or could not be parsed correctly.
+	#[cfg(feature = "fs")]
+	pub fn parse_with_warnings<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<ParseWarning>), BeatmapFileParseError> {
+		parse_osu_file_with_warnings(path)
+	}
+
+	/// Parses an osu! beatmap from the contents of a `.osu` file, also returning the quirks found in it (see [`quirks`]).
+	///
+	/// # Errors
+	///
+	/// This function will return an error if the beatmap could not be parsed correctly.
+	pub fn parse_str_with_warnings(contents: &str) -> Result<(Self, Vec<ParseWarning>), BeatmapFileParseError> {
+		parse_osu_str_with_warnings(contents)
+	}
+
+	/// Parses an osu! beatmap file with extra options.
+	///
+	/// # Errors
+	///
+	/// This function will return an error if the file doesn't exist or could not be parsed correctly.
+	#[cfg(feature = "fs")]
+	pub fn parse_with_options<P: AsRef<Path>>(path: P, options: &ParseOptions) -> Result<Self, BeatmapFileParseError> {
+		parse_osu_file_with_options(path, options)
+	}
+
+	/// Parses an osu! beatmap from the contents of a `.osu` file with extra options.
+	///
+	/// # Errors
+	///
+	/// This function will return an error if the beatmap could not be parsed correctly.
+	pub fn parse_str_with_options(contents: &str, options: &ParseOptions) -> Result<Self, BeatmapFileParseError> {
+		parse_osu_str_with_options(contents, options)
+	}
+
+	/// Parses an osu! beatmap from a buffered reader.
+	///
+	/// # Errors
+	///
+	/// This function will return an error if the reader fails or if the beatmap could not be parsed correctly.
+	pub fn parse_reader<B: BufRead>(reader: B) -> Result<Self, BeatmapFileParseError> {
+		parse_osu_reader(reader)
+	}
+
 	/// Write this beatmap file as a `.osu` file.
 	///
 	/// # Errors
@@ -901,8 +1471,320 @@ impl BeatmapFile {
 		deserialize_beatmap_file(self, writer)
 	}
 
+	/// Write this beatmap file as a `.osu` file, with extra options.
+	///
+	/// # Errors
+	///
+	/// This function will return an error if an IO issue occured.
+	pub fn deserialize_with_options<W: Write>(&self, writer: &mut W, options: &DeserializeOptions) -> io::Result<()> {
+		deserialize_beatmap_file_with_options(self, writer, options)
+	}
+
+	/// Writes this beatmap in a folder under the name osu! would give it (see [`standard_filename`]),
+	/// returning the path of the written file.
+	///
+	/// An existing file is never replaced: if the name is taken, a number is added to it,
+	/// like `Artist - Title (Creator) [Version] (2).osu`.
+	///
+	/// # Errors
+	///
+	/// This function will return an error if the file couldn't be created or written.
+	/// A file that couldn't be written completely is removed.
+	#[cfg(feature = "fs")]
+	pub fn save_canonically<P: AsRef<Path>>(&self, dir: P) -> io::Result<PathBuf> {
+		let dir = dir.as_ref();
+		let default_metadata = MetadataSection::default();
+		let filename = standard_filename(self.metadata.as_ref().unwrap_or(&default_metadata));
+		let stem = filename.strip_suffix(".osu").unwrap_or(&filename);
+
+		let mut path = dir.join(&filename);
+		let mut number = 1;
+		loop {
+			match File::options().write(true).create_new(true).open(&path) {
+				Ok(file) => {
+					let mut writer = io::BufWriter::new(file);
+					let written = self.deserialize(&mut writer).and_then(|()| writer.flush());
+					drop(writer);
+
+					// don't leave a truncated beatmap behind, which osu! would load anyway
+					if let Err(e) = written {
+						let _ = std::fs::remove_file(&path);
+						return Err(e);
+					}
+					return Ok(path);
+				}
+				Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+					number += 1;
+					path = dir.join(format!("{stem} ({number}).osu"));
+				}
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	/// MD5 hash osu! would give this beatmap, as a lowercase hexadecimal string.
+	///
+	/// The beatmap is written the way osu! stable expects it (with normalized events),
+	/// so the hash matches the one of the file [`deserialize_with_options`](Self::deserialize_with_options) would write
+	/// with these options, not necessarily the one of the file it was parsed from (see [`hash_file`](super::hash_file)).
 	#[must_use]
-	pub fn iter_hit_objects_and_timing_points(&self) -> InterleavedTimestampedIterator<HitObject, TimingPoint> {
-		self.hit_objects.interleave_timestamped(&self.timing_points)
+	pub fn osu_md5(&self) -> String {
+		let mut bytes = Vec::new();
+		let options = DeserializeOptions {
+			normalize_events: true,
+			..DeserializeOptions::default()
+		};
+		// writing to a Vec cannot fail
+		let _ = self.deserialize_with_options(&mut bytes, &options);
+		super::md5_hex(&bytes)
+	}
+
+	/// Sorts timing points and hit objects by time, and fixes inconsistencies that would be written in the file.
+	///
+	/// Currently the fixes are:
+	/// - slider edge hitsounds and samplesets are padded or truncated to `slides + 1` values.
+	pub fn sort_and_fix(&mut self) {
+		self.timing_points.sort_by(|a, b| a.time.total_cmp(&b.time));
+		self.hit_objects.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+		for hit_object in &mut self.hit_objects {
+			hit_object.object_params.normalize_edges();
+		}
+	}
+
+	/// Puts the events in the order osu! expects them, and removes the ones that would be ignored or duplicated.
+	///
+	/// Backgrounds come first, then videos, then breaks, then every other event in their original order.
+	/// Duplicate backgrounds (sometimes exported by osu!lazer) are merged, and empty breaks are removed.
+	pub fn normalize_events(&mut self) {
+		normalize_events(&mut self.events);
+	}
+
+	/// Amount of columns of an osu!mania beatmap, given by its circle size.
+	///
+	/// Like in osu!, the circle size is rounded half to even (so `4.5` is 4 keys and `5.5` is 6 keys),
+	/// and beatmaps without a `[Difficulty]` section have 4 keys.
+	#[must_use]
+	pub fn key_count(&self) -> u32 {
+		#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+		self.difficulty
+			.as_ref()
+			.map_or(4, |d| d.circle_size.round_ties_even().max(1.0) as u32)
+	}
+
+	/// Horizontal position of the osu!mania notes in a column of this beatmap, using its [`key_count`](Self::key_count).
+	///
+	/// See [`HitObject::column_x`] for how the position is rounded.
+	#[must_use]
+	pub fn mania_column_as_position(&self, column: u32) -> f32 {
+		HitObject::column_x(column, self.key_count())
+	}
+
+	/// Start time of the first hit object, or `None` if the beatmap has no hit objects.
+	#[must_use]
+	pub fn first_object_time(&self) -> Option<Timestamp> {
+		self.hit_objects.iter().map(|ho| ho.time).min_by(f64::total_cmp)
+	}
+
+	/// Time at which the last hit object ends, taking the duration of sliders, spinners and holds into account,
+	/// or `None` if the beatmap has no hit objects.
+	#[must_use]
+	pub fn last_object_end_time(&self) -> Option<Timestamp> {
+		(self.hit_objects.iter())
+			.map(|ho| end_time(self, ho))
+			.max_by(f64::total_cmp)
+	}
+
+	/// Time range from the start of the first hit object to the end of the last one.
+	#[must_use]
+	pub fn playable_range(&self) -> Option<Range<Timestamp>> {
+		Some(self.first_object_time()?..self.last_object_end_time()?)
+	}
+
+	/// Total length of the beatmap in milliseconds, like osu! shows it: from the start of the audio
+	/// to the end of the last hit object.
+	#[must_use]
+	pub fn total_length(&self) -> f64 {
+		self.last_object_end_time().unwrap_or_default().max(0.0)
+	}
+
+	/// Drain time of the beatmap in milliseconds: the length of its [playable range](Self::playable_range)
+	/// without the breaks in it.
+	#[must_use]
+	pub fn drain_time(&self) -> f64 {
+		let Some(range) = self.playable_range() else {
+			return 0.0;
+		};
+
+		let breaks: f64 = (self.events.iter())
+			.filter_map(|event| match event.params {
+				EventParams::Break { end_time } => {
+					let start = event.start_time.max(range.start);
+					Some((end_time.min(range.end) - start).max(0.0))
+				}
+				_ => None,
+			})
+			.sum();
+
+		(range.end - range.start - breaks).max(0.0)
+	}
+
+	/// Iterates over the timing points and hit objects of the beatmap in chronological order.
+	///
+	/// Timing points come before the hit objects at the same time, since they apply to them.
+	#[must_use]
+	pub fn iter_hit_objects_and_timing_points(&self) -> BeatmapElements<'_> {
+		MultiInterleavedIterator::new([
+			Box::new(self.timing_points.iter().map(BeatmapElement::TimingPoint)) as Box<dyn Iterator<Item = _>>,
+			Box::new(self.hit_objects.iter().map(BeatmapElement::HitObject)),
+		])
+	}
+
+	/// Iterates over the timing points, hit objects and events of the beatmap in chronological order.
+	///
+	/// At the same time, timing points come first, then hit objects, then events.
+	/// Timing points and hit objects need to be sorted (see [`sort_and_fix`](Self::sort_and_fix)),
+	/// while events are sorted by their start time here, since osu! groups them by type instead.
+	#[must_use]
+	pub fn iter_elements(&self) -> BeatmapElements<'_> {
+		let mut events: Vec<&Event> = self.events.iter().collect();
+		events.sort_by(|a, b| a.start_time.total_cmp(&b.start_time));
+
+		MultiInterleavedIterator::new([
+			Box::new(self.timing_points.iter().map(BeatmapElement::TimingPoint)) as Box<dyn Iterator<Item = _>>,
+			Box::new(self.hit_objects.iter().map(BeatmapElement::HitObject)),
+			Box::new(events.into_iter().map(BeatmapElement::Event)),
+		])
+	}
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+	use super::*;
+	use crate::file::beatmap::parsing::parse_hit_object;
+
+	fn column_at(x: f32, key_count: u32) -> u32 {
+		let mut hit_object = parse_hit_object("0,192,1000,1,0,0:0:0:0:").unwrap();
+		hit_object.x = x;
+		hit_object.column(key_count)
+	}
+
+	#[test]
+	fn columns_at_the_edges() {
+		assert_eq!(column_at(0.0, 4), 0);
+		assert_eq!(column_at(511.0, 4), 3);
+		assert_eq!(column_at(127.0, 4), 0);
+		assert_eq!(column_at(128.0, 4), 1);
+		// out of the playfield, still in the closest column
+		assert_eq!(column_at(-10.0, 4), 0);
+		assert_eq!(column_at(600.0, 4), 3);
+		assert_eq!(column_at(511.0, 1), 0);
+	}
+
+	#[test]
+	fn columns_with_odd_key_counts() {
+		// columns of 7K are 73.14 pixels wide
+		assert_eq!(column_at(73.0, 7), 0);
+		assert_eq!(column_at(74.0, 7), 1);
+		assert_eq!(column_at(511.0, 7), 6);
+		assert_eq!(HitObject::column_x(0, 7), 36.0);
+		assert_eq!(HitObject::column_x(6, 7), 475.0);
+	}
+
+	#[test]
+	fn column_positions_are_in_their_column() {
+		for key_count in 1..=18 {
+			for column in 0..key_count {
+				assert_eq!(column_at(HitObject::column_x(column, key_count), key_count), column);
+			}
+		}
+	}
+
+	#[test]
+	fn points_become_inherited_slider_points() {
+		let slider_point = SliderPoint::from(Point::new(-0.5, 100.5));
+		assert_eq!(slider_point.curve_type, SliderCurveType::Inherit);
+		assert_eq!((slider_point.x, slider_point.y), (-0.5, 100.5));
+	}
+
+	#[test]
+	fn slider_points_are_rounded() {
+		let slider_point = SliderPoint::new(SliderCurveType::Bezier, -0.5, 100.5);
+		let rounded = slider_point.rounded(Rounding::Round);
+		assert_eq!(rounded.curve_type, SliderCurveType::Bezier);
+		assert_eq!((rounded.x, rounded.y), (-1.0, 101.0));
+
+		let floored = slider_point.rounded(Rounding::Floor);
+		assert_eq!((floored.x, floored.y), (-1.0, 100.0));
+	}
+
+	#[cfg(feature = "fs")]
+	#[test]
+	fn canonical_saves_never_overwrite() {
+		let dir = std::env::temp_dir().join(format!("osus-save-canonically-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+
+		let beatmap = BeatmapFile {
+			osu_file_format: 14,
+			..BeatmapFile::default()
+		};
+		let first = beatmap.save_canonically(&dir).unwrap();
+		let second = beatmap.save_canonically(&dir).unwrap();
+		assert_ne!(first, second);
+		assert!(second.to_string_lossy().ends_with(" (2).osu"));
+		assert_eq!(std::fs::read(&first).unwrap(), std::fs::read(&second).unwrap());
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn columns_start_at_their_exact_boundaries() {
+		// stable floors `x * key_count / 512`, which is exact for the integer positions written in files
+		for key_count in 1..=18_u32 {
+			for column in 1..key_count {
+				let first_x = (column * 512).div_ceil(key_count);
+				#[allow(clippy::cast_precision_loss)]
+				let first_x = first_x as f32;
+				assert_eq!(column_at(first_x, key_count), column, "{key_count}K, x = {first_x}");
+				assert_eq!(
+					column_at(first_x - 1.0, key_count),
+					column - 1,
+					"{key_count}K, x = {first_x}"
+				);
+			}
+		}
+
+		// fractional positions are floored too, so the 5K boundary at 102.4 isn't reached by 102.3
+		assert_eq!(column_at(102.3, 5), 0);
+		assert_eq!(column_at(102.5, 5), 1);
+	}
+
+	#[test]
+	fn mania_column_positions_use_the_key_count() {
+		let mut beatmap = BeatmapFile::default();
+		assert_eq!(beatmap.mania_column_as_position(0), 64.0);
+		assert_eq!(beatmap.mania_column_as_position(3), 448.0);
+
+		beatmap.difficulty = Some(DifficultySection {
+			circle_size: 7.0,
+			..DifficultySection::default()
+		});
+		let positions: Vec<f32> = (0..7).map(|column| beatmap.mania_column_as_position(column)).collect();
+		assert_eq!(positions, [36.0, 109.0, 182.0, 256.0, 329.0, 402.0, 475.0]);
+	}
+
+	#[test]
+	fn key_counts_round_half_to_even() {
+		let mut beatmap = BeatmapFile::default();
+		assert_eq!(beatmap.key_count(), 4);
+
+		for (circle_size, key_count) in [(4.5, 4), (5.5, 6), (7.0, 7), (0.0, 1)] {
+			beatmap.difficulty = Some(DifficultySection {
+				circle_size,
+				..DifficultySection::default()
+			});
+			assert_eq!(beatmap.key_count(), key_count, "CS {circle_size}");
+		}
 	}
 }
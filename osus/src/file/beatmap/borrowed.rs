@@ -0,0 +1,336 @@
+//! Zero-copy view of a beatmap, borrowing its strings from the text of the `.osu` file.
+//!
+//! Parsing a whole [`BeatmapFile`] allocates a `String` for every piece of metadata, tag, event and line read.
+//! When going through tens of thousands of maps to only look at their metadata or difficulty settings,
+//! [`BeatmapFileRef`] does the same job by slicing into the source text instead,
+//! and can still be turned into a full [`BeatmapFile`] when needed.
+
+use std::ffi::OsStr;
+#[cfg(feature = "mmap")]
+use std::ffi::OsString;
+#[cfg(feature = "mmap")]
+use std::fs::File;
+#[cfg(feature = "mmap")]
+use std::path::Path;
+
+use super::errors::{BeatmapFileParseError, BeatmapFileParseErrorKind, InvalidKeyValuePairError, SectionParseError};
+use super::parsing::{
+	beatmap_section_err, field_err, parse_difficulty_section, parse_osu_str, section_err, OsuLines, IN_MEMORY_FILENAME,
+	SECTION_DIFFICULTY, SECTION_METADATA,
+};
+use super::{BeatmapFile, DifficultySection, MetadataSection};
+
+/// A section of a `.osu` file, left unparsed.
+#[derive(Clone, Copy, Debug)]
+pub struct RawSection<'a> {
+	/// Section header, brackets included (like `[HitObjects]`).
+	pub header: &'a str,
+	/// Line number (starting from 1) of the section header.
+	pub line_number: usize,
+	/// Text of the section, without its header.
+	pub text: &'a str,
+}
+
+impl<'a> RawSection<'a> {
+	/// Lines of the section, skipping comments and empty lines.
+	pub fn lines(&self) -> impl Iterator<Item = &'a str> {
+		self.text.lines().filter(|line| !is_trivia(line))
+	}
+}
+
+/// Borrowed version of [`MetadataSection`].
+#[derive(Clone, Debug, Default)]
+pub struct MetadataSectionRef<'a> {
+	/// Romanised song title
+	pub title: &'a str,
+	/// Song title
+	pub title_unicode: &'a str,
+	/// Romanised song artist
+	pub artist: &'a str,
+	/// Song artist
+	pub artist_unicode: &'a str,
+	/// Beatmap creator
+	pub creator: &'a str,
+	/// Difficulty name
+	pub version: &'a str,
+	/// Original media the song was produced for
+	pub source: &'a str,
+	/// Search terms
+	pub tags: Vec<&'a str>,
+	/// Difficulty ID
+	pub beatmap_id: Option<i32>,
+	/// Beatmap ID
+	pub beatmap_set_id: Option<i32>,
+}
+
+impl From<MetadataSectionRef<'_>> for MetadataSection {
+	fn from(section: MetadataSectionRef<'_>) -> Self {
+		Self {
+			title: section.title.to_owned(),
+			title_unicode: section.title_unicode.to_owned(),
+			artist: section.artist.to_owned(),
+			artist_unicode: section.artist_unicode.to_owned(),
+			creator: section.creator.to_owned(),
+			version: section.version.to_owned(),
+			source: section.source.to_owned(),
+			tags: section.tags.into_iter().map(ToOwned::to_owned).collect(),
+			beatmap_id: section.beatmap_id,
+			beatmap_set_id: section.beatmap_set_id,
+		}
+	}
+}
+
+/// Borrowed view of a beatmap.
+///
+/// Only the `[Metadata]` and `[Difficulty]` sections are parsed eagerly.
+/// Every other section is kept as [`RawSection`]s, and [`BeatmapFileRef::to_owned_beatmap`] parses everything.
+#[derive(Clone, Debug)]
+pub struct BeatmapFileRef<'a> {
+	pub osu_file_format: u32,
+	pub metadata: Option<MetadataSectionRef<'a>>,
+	pub difficulty: Option<DifficultySection>,
+	pub sections: Vec<RawSection<'a>>,
+	source: &'a str,
+}
+
+impl<'a> BeatmapFileRef<'a> {
+	/// Parses a borrowed view of a beatmap from the contents of a `.osu` file.
+	///
+	/// # Errors
+	///
+	/// This function will return an error if the format version, metadata or difficulty settings are invalid.
+	pub fn parse(source: &'a str) -> Result<Self, BeatmapFileParseError> {
+		parse_beatmap_ref(source, OsStr::new(IN_MEMORY_FILENAME))
+	}
+
+	/// Text of the `.osu` file this beatmap borrows from.
+	#[must_use]
+	pub const fn source(&self) -> &'a str {
+		self.source
+	}
+
+	/// Finds a section by its header, brackets included (like `[HitObjects]`).
+	#[must_use]
+	pub fn section(&self, header: &str) -> Option<&RawSection<'a>> {
+		self.sections.iter().find(|section| section.header == header)
+	}
+
+	/// Parses the whole beatmap into an owned [`BeatmapFile`].
+	///
+	/// # Errors
+	///
+	/// This function will return an error if any section could not be parsed correctly.
+	pub fn to_owned_beatmap(&self) -> Result<BeatmapFile, BeatmapFileParseError> {
+		parse_osu_str(self.source)
+	}
+}
+
+impl TryFrom<BeatmapFileRef<'_>> for BeatmapFile {
+	type Error = BeatmapFileParseError;
+
+	fn try_from(beatmap: BeatmapFileRef<'_>) -> Result<Self, Self::Error> {
+		beatmap.to_owned_beatmap()
+	}
+}
+
+/// A `.osu` file mapped in memory, to parse [`BeatmapFileRef`]s without reading it into a buffer first.
+#[cfg(feature = "mmap")]
+pub struct MappedBeatmapFile {
+	filename: OsString,
+	mmap: memmap2::Mmap,
+}
+
+#[cfg(feature = "mmap")]
+impl MappedBeatmapFile {
+	/// Maps the file at `path` in memory.
+	///
+	/// # Safety
+	///
+	/// The file must not be truncated or modified (by this process or any other) while it is mapped.
+	/// The beatmaps parsed from it borrow its contents as a `str`, which would change under them.
+	///
+	/// # Errors
+	///
+	/// This function will return an error if the file could not be opened or mapped.
+	pub unsafe fn open<P: AsRef<Path>>(path: P) -> Result<Self, BeatmapFileParseError> {
+		let path = path.as_ref();
+		let filename = path
+			.file_name()
+			.map_or_else(|| OsString::from("???"), ToOwned::to_owned);
+
+		let io_err = |e| BeatmapFileParseError {
+			filename: filename.clone(),
+			line_number: None,
+			kind: BeatmapFileParseErrorKind::Io(e),
+		};
+
+		let file = File::open(path).map_err(io_err)?;
+		// SAFETY: the caller guarantees that the file isn't modified while it is mapped.
+		let mmap = unsafe { memmap2::Mmap::map(&file) }.map_err(io_err)?;
+
+		Ok(Self { filename, mmap })
+	}
+
+	/// Parses a borrowed view of the mapped beatmap.
+	///
+	/// # Errors
+	///
+	/// This function will return an error if the file is not valid UTF-8,
+	/// or if its format version, metadata or difficulty settings are invalid.
+	pub fn beatmap(&self) -> Result<BeatmapFileRef<'_>, BeatmapFileParseError> {
+		let source = std::str::from_utf8(&self.mmap).map_err(|e| BeatmapFileParseError {
+			filename: self.filename.clone(),
+			line_number: None,
+			kind: BeatmapFileParseErrorKind::InvalidUtf8(e),
+		})?;
+
+		parse_beatmap_ref(source, &self.filename)
+	}
+}
+
+fn is_trivia(line: &str) -> bool {
+	let l = line.trim();
+	// Ignore comments and empty lines
+	l.is_empty() || l.starts_with("//")
+}
+
+fn is_section_header(line: &str) -> bool {
+	line.starts_with('[') && line.ends_with(']')
+}
+
+/// Splits the source into its format line and its sections.
+fn split_sections<'a>(
+	source: &'a str,
+	filename: &OsStr,
+) -> Result<(&'a str, usize, Vec<RawSection<'a>>), BeatmapFileParseError> {
+	let mut format_line = None;
+	let mut sections: Vec<RawSection<'a>> = Vec::new();
+
+	let mut offset = 0;
+	for (index, full_line) in source.split_inclusive('\n').enumerate() {
+		let line_start = offset;
+		offset += full_line.len();
+
+		let line = full_line.trim_end_matches('\n').trim_end_matches('\r');
+		if is_trivia(line) {
+			continue;
+		}
+
+		if format_line.is_none() {
+			format_line = Some((line, index + 1));
+		} else if is_section_header(line) {
+			if let Some(last) = sections.last_mut() {
+				last.text = &source[source.len() - last.text.len()..line_start];
+			}
+
+			sections.push(RawSection {
+				header: line,
+				line_number: index + 1,
+				text: &source[offset..],
+			});
+		}
+	}
+
+	let (format_line, line_number) = format_line.ok_or_else(|| BeatmapFileParseError {
+		filename: filename.to_os_string(),
+		line_number: None,
+		kind: BeatmapFileParseErrorKind::FileIsEmpty,
+	})?;
+
+	Ok((format_line, line_number, sections))
+}
+
+fn parse_metadata_section_ref<'a>(
+	section: &RawSection<'a>,
+) -> Result<MetadataSectionRef<'a>, (usize, SectionParseError)> {
+	let mut metadata = MetadataSectionRef::default();
+
+	for (index, line) in section.text.lines().enumerate() {
+		if is_trivia(line) {
+			continue;
+		}
+
+		let line_number = section.line_number + index + 1;
+		let (field, value) = line
+			.split_once(':')
+			.map(|(field, value)| (field.trim(), value.trim()))
+			.ok_or_else(|| {
+				let err = InvalidKeyValuePairError { split_char: ':' };
+				(line_number, section_err(SECTION_METADATA, line.to_owned())(err))
+			})?;
+
+		match field {
+			"Title" => metadata.title = value,
+			"TitleUnicode" => metadata.title_unicode = value,
+			"Artist" => metadata.artist = value,
+			"ArtistUnicode" => metadata.artist_unicode = value,
+			"Creator" => metadata.creator = value,
+			"Version" => metadata.version = value,
+			"Source" => metadata.source = value,
+			"Tags" => metadata.tags = value.split(' ').collect(),
+			"BeatmapID" => {
+				metadata.beatmap_id = Some(value.parse().map_err(|e| {
+					(
+						line_number,
+						field_err(SECTION_METADATA, "BeatmapID", line.to_owned())(e),
+					)
+				})?);
+			}
+			"BeatmapSetID" => {
+				metadata.beatmap_set_id = Some(value.parse().map_err(|e| {
+					(
+						line_number,
+						field_err(SECTION_METADATA, "BeatmapSetID", line.to_owned())(e),
+					)
+				})?);
+			}
+			key => tracing::warn!("[Metadata] section: unknown field {key:?}"),
+		}
+	}
+
+	Ok(metadata)
+}
+
+fn parse_beatmap_ref<'a>(source: &'a str, filename: &OsStr) -> Result<BeatmapFileRef<'a>, BeatmapFileParseError> {
+	let (format_line, format_line_number, sections) = split_sections(source, filename)?;
+
+	let invalid_format = || BeatmapFileParseError {
+		filename: filename.to_os_string(),
+		line_number: Some(format_line_number),
+		kind: BeatmapFileParseErrorKind::InvalidOsuFileFormat,
+	};
+
+	let osu_file_format = format_line
+		.trim_start_matches('\u{feff}')
+		.strip_prefix("osu file format v")
+		.ok_or_else(invalid_format)?
+		.parse()
+		.map_err(|_| invalid_format())?;
+
+	let mut beatmap = BeatmapFileRef {
+		osu_file_format,
+		metadata: None,
+		difficulty: None,
+		sections,
+		source,
+	};
+
+	for section in &beatmap.sections {
+		match section.header {
+			SECTION_METADATA => {
+				let metadata = parse_metadata_section_ref(section)
+					.map_err(|(line_number, e)| beatmap_section_err(filename, line_number)(e))?;
+				beatmap.metadata = Some(metadata);
+			}
+			SECTION_DIFFICULTY => {
+				let mut reader = OsuLines::new(section.text.as_bytes());
+				let difficulty = parse_difficulty_section(&mut reader, &mut None)
+					.map_err(|e| beatmap_section_err(filename, section.line_number + reader.line_number)(e))?;
+				beatmap.difficulty = Some(difficulty);
+			}
+			_ => {}
+		}
+	}
+
+	Ok(beatmap)
+}
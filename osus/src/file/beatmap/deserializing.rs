@@ -1,8 +1,16 @@
+use std::borrow::Cow;
+use std::fmt;
 use std::io::{self, Write};
 
+use crate::algos::for_each_timestamp_mut;
+use crate::algos::lazer::STABLE_FILE_FORMAT;
+use crate::point::Rounding;
+
+use super::quirks::FormatQuirks;
+use super::trivia::insert_trivia;
 use super::{
-	BeatmapFile, ColorsSection, DifficultySection, EditorSection, Event, EventParams, GeneralSection, HitObject,
-	HitObjectParams, HitSampleSet, HitSound, MetadataSection, OverlayPosition, SliderCurveType, SliderPoint,
+	normalize_events, BeatmapFile, ColorsSection, DifficultySection, EditorSection, Event, EventParams, GeneralSection,
+	HitObject, HitObjectParams, HitSampleSet, HitSound, MetadataSection, OverlayPosition, SliderCurveType, SliderPoint,
 	TimingPoint,
 };
 
@@ -97,45 +105,74 @@ fn deserialize_difficulty_section<W: Write>(section: &DifficultySection, writer:
 	writeln!(writer)
 }
 
-fn deserialize_event<W: Write>(event: &Event, writer: &mut W) -> io::Result<()> {
-	write!(writer, "{},{},", event.event_type, event.start_time)?;
-	match &event.params {
-		EventParams::Video {
-			filename,
-			x_offset,
-			y_offset,
-		}
-		| EventParams::Background {
-			filename,
-			x_offset,
-			y_offset,
-		} => {
-			writeln!(writer, "{filename},{x_offset},{y_offset}")
-		}
-		EventParams::Break { end_time } => {
-			writeln!(writer, "{end_time}")
+/// Quotes an event filename if it contains spaces or commas, which would otherwise break the line.
+fn quote_filename(filename: &str) -> Cow<'_, str> {
+	if filename.contains([' ', ',']) {
+		Cow::Owned(format!("\"{filename}\""))
+	} else {
+		Cow::Borrowed(filename)
+	}
+}
+
+impl fmt::Display for Event {
+	/// Formats the event as a line of the `[Events]` section.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{},{},", self.event_type, self.start_time)?;
+		match &self.params {
+			EventParams::Video {
+				filename,
+				x_offset,
+				y_offset,
+			}
+			| EventParams::Background {
+				filename,
+				x_offset,
+				y_offset,
+			} => {
+				write!(f, "{},{x_offset},{y_offset}", quote_filename(filename))
+			}
+			EventParams::Break { end_time } => {
+				write!(f, "{end_time}")
+			}
+			EventParams::Sample {
+				layer,
+				filename,
+				volume,
+			} => {
+				write!(f, "{layer},{},{volume}", quote_filename(filename))
+			}
 		}
 	}
 }
 
-fn deserialize_timing_point<W: Write>(timing_point: &TimingPoint, writer: &mut W) -> io::Result<()> {
-	let TimingPoint {
-		time,
-		beat_length,
-		meter,
-		sample_set,
-		sample_index,
-		volume,
-		uninherited,
-		effects,
-	} = timing_point;
+impl fmt::Display for TimingPoint {
+	/// Formats the timing point as a line of the `[TimingPoints]` section.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let Self {
+			time,
+			beat_length,
+			raw_beat_length,
+			meter,
+			sample_set,
+			sample_index,
+			volume,
+			uninherited,
+			effects,
+		} = self;
 
-	writeln!(
-		writer,
-		"{time},{beat_length},{meter},{},{sample_index},{volume},{},{effects}",
-		*sample_set as u8,
-		u8::from(*uninherited),
-	)
+		write!(f, "{time},")?;
+		match raw_beat_length.as_ref().and_then(|raw| raw.text_for(*beat_length)) {
+			Some(text) => f.write_str(text)?,
+			None => write!(f, "{beat_length}")?,
+		}
+
+		write!(
+			f,
+			",{meter},{},{sample_index},{volume},{},{effects}",
+			*sample_set as u8,
+			u8::from(*uninherited),
+		)
+	}
 }
 
 fn deserialize_color_section<W: Write>(section: &ColorsSection, writer: &mut W) -> io::Result<()> {
@@ -152,95 +189,159 @@ fn deserialize_color_section<W: Write>(section: &ColorsSection, writer: &mut W)
 	writeln!(writer)
 }
 
-fn deserialize_curve_points<W: Write>(
+/// Prefix of a slider point starting a new curve type.
+const fn curve_type_prefix(curve_type: SliderCurveType) -> &'static str {
+	match curve_type {
+		SliderCurveType::Inherit => "",
+		SliderCurveType::Bezier => "B|",
+		SliderCurveType::Catmull => "C|",
+		SliderCurveType::Linear => "L|",
+		SliderCurveType::PerfectCurve => "P|",
+	}
+}
+
+impl fmt::Display for SliderPoint {
+	/// Formats the slider point like in a hit object line, with its curve type prefix if it starts a new curve.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let Self { curve_type, x, y } = self;
+		write!(f, "{}{x}:{y}", curve_type_prefix(*curve_type))
+	}
+}
+
+fn format_curve_points(
 	first_curve_type: SliderCurveType,
 	curve_points: &[SliderPoint],
-	writer: &mut W,
-) -> io::Result<()> {
+	f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
 	let mut started = false;
-	for &curve_point in curve_points {
+	for curve_point in curve_points {
 		if started {
-			write!(writer, "|")?;
-		}
-
-		let SliderPoint { curve_type, x, y } = curve_point;
-		let prefix = match curve_type {
-			SliderCurveType::Inherit => "",
-			SliderCurveType::Bezier => "B|",
-			SliderCurveType::Catmull => "C|",
-			SliderCurveType::Linear => "L|",
-			SliderCurveType::PerfectCurve => "P|",
-		};
-
-		if !started && curve_type != first_curve_type {
-			let preprefix = match first_curve_type {
-				SliderCurveType::Inherit => "",
-				SliderCurveType::Bezier => "B|",
-				SliderCurveType::Catmull => "C|",
-				SliderCurveType::Linear => "L|",
-				SliderCurveType::PerfectCurve => "P|",
-			};
-			write!(writer, "{preprefix}")?;
+			write!(f, "|")?;
+		} else if curve_point.curve_type != first_curve_type {
+			write!(f, "{}", curve_type_prefix(first_curve_type))?;
 		}
 
-		write!(writer, "{prefix}{x}:{y}")?;
+		write!(f, "{curve_point}")?;
 		started = true;
 	}
 
 	Ok(())
 }
 
-fn deserialize_hit_object<W: Write>(hit_object: &HitObject, writer: &mut W) -> io::Result<()> {
-	let HitObject {
-		x,
-		y,
-		time,
-		hit_sound,
-		object_params,
-		hit_sample,
-		..
-	} = hit_object;
-
-	let raw_object_type = hit_object.raw_object_type();
-	write!(writer, "{x},{y},{time},{raw_object_type},{hit_sound}")?;
-	match object_params {
-		HitObjectParams::HitCircle => {
-			writeln!(writer, ",{}", hit_sample.to_osu_string())
-		}
-		HitObjectParams::Slider {
-			first_curve_type,
-			curve_points,
-			slides,
-			length,
-			edge_hitsounds,
-			edge_samplesets,
-		} => {
-			write!(writer, ",")?;
-			deserialize_curve_points(*first_curve_type, curve_points, writer)?;
-			write!(writer, ",{slides},{length}")?;
-
-			if !edge_hitsounds.is_empty() && !edge_samplesets.is_empty() {
-				let edge_hitsounds: Vec<_> = edge_hitsounds.iter().map(HitSound::to_string).collect();
-				let edge_samplesets: Vec<_> = edge_samplesets.iter().map(HitSampleSet::to_osu_string).collect();
-				write!(writer, ",{},{}", edge_hitsounds.join("|"), edge_samplesets.join("|"))?;
+impl fmt::Display for HitObject {
+	/// Formats the hit object as a line of the `[HitObjects]` section.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let Self {
+			x,
+			y,
+			time,
+			hit_sound,
+			object_params,
+			hit_sample,
+			..
+		} = self;
+
+		let raw_object_type = self.raw_object_type();
+		write!(f, "{x},{y},{time},{raw_object_type},{hit_sound}")?;
+		match object_params {
+			HitObjectParams::HitCircle => {
+				write!(f, ",{}", hit_sample.to_osu_string())
+			}
+			HitObjectParams::Slider {
+				first_curve_type,
+				curve_points,
+				slides,
+				length,
+				edge_hitsounds,
+				edge_samplesets,
+			} => {
+				write!(f, ",")?;
+				format_curve_points(*first_curve_type, curve_points, f)?;
+				write!(f, ",{slides},{length}")?;
+
+				if !edge_hitsounds.is_empty() && !edge_samplesets.is_empty() {
+					let edge_hitsounds: Vec<_> = edge_hitsounds.iter().map(HitSound::to_string).collect();
+					let edge_samplesets: Vec<_> = edge_samplesets.iter().map(HitSampleSet::to_osu_string).collect();
+					write!(f, ",{},{}", edge_hitsounds.join("|"), edge_samplesets.join("|"))?;
+				}
+				write!(f, ",{}", hit_sample.to_osu_string())
+			}
+			HitObjectParams::Spinner { end_time } => {
+				write!(f, ",{end_time},{}", hit_sample.to_osu_string())
+			}
+			// tails can't be written, see `HoldTail`
+			HitObjectParams::Hold { end_time, .. } => {
+				write!(f, ",{end_time}:{}", hit_sample.to_osu_string())
 			}
-			writeln!(writer, ",{}", hit_sample.to_osu_string())
-		}
-		HitObjectParams::Spinner { end_time } => {
-			writeln!(writer, ",{end_time},{}", hit_sample.to_osu_string())
-		}
-		HitObjectParams::Hold { end_time } => {
-			writeln!(writer, ",{end_time}:{}", hit_sample.to_osu_string())
 		}
 	}
 }
 
+/// Options changing how a beatmap file is written.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DeserializeOptions {
+	/// Write events in the order osu! expects them, without duplicates (see [`BeatmapFile::normalize_events`]).
+	pub normalize_events: bool,
+	/// Round times to this many decimals, like `Some(0)` for the whole milliseconds osu!stable writes.
+	pub time_decimals: Option<u8>,
+	/// How positions of hit objects and slider control points are made whole.
+	///
+	/// By default, osu!lazer beatmaps keep their exact positions and older versions are rounded,
+	/// since osu!stable only reads integer positions.
+	pub position_rounding: Option<Rounding>,
+}
+
 /// Write a beatmap file as a `.osu` file.
 ///
 /// # Errors
 ///
 /// This function will return an error if an IO issue occured.
 pub fn deserialize_beatmap_file<W: Write>(bm_file: &BeatmapFile, writer: &mut W) -> io::Result<()> {
+	deserialize_beatmap_file_with_options(bm_file, writer, &DeserializeOptions::default())
+}
+
+/// Write a beatmap file as a `.osu` file, with extra options.
+///
+/// # Errors
+///
+/// This function will return an error if an IO issue occured.
+pub fn deserialize_beatmap_file_with_options<W: Write>(
+	bm_file: &BeatmapFile,
+	writer: &mut W,
+	options: &DeserializeOptions,
+) -> io::Result<()> {
+	if bm_file.trivia.is_empty() {
+		return write_sections(bm_file, writer, *options);
+	}
+
+	// comments and blank lines are put back by looking at the written lines (see `trivia`)
+	let mut contents = Vec::new();
+	write_sections(bm_file, &mut contents, *options)?;
+	let contents = String::from_utf8(contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+	writer.write_all(insert_trivia(&contents, &bm_file.trivia).as_bytes())
+}
+
+fn write_sections<W: Write>(bm_file: &BeatmapFile, writer: &mut W, options: DeserializeOptions) -> io::Result<()> {
+	// times of old versions are written like osu! reads them (see `FormatQuirks`)
+	let format_quirks = FormatQuirks::for_version(bm_file.osu_file_format);
+	let mut bm_file = Cow::Borrowed(bm_file);
+	if format_quirks.time_offset != 0.0 {
+		for_each_timestamp_mut(bm_file.to_mut(), |time| *time -= format_quirks.time_offset);
+	}
+
+	if let Some(decimals) = options.time_decimals {
+		let factor = 10_f64.powi(i32::from(decimals));
+		for_each_timestamp_mut(bm_file.to_mut(), |time| *time = (*time * factor).round() / factor);
+	}
+
+	let position_rounding = options
+		.position_rounding
+		.unwrap_or(if bm_file.osu_file_format > STABLE_FILE_FORMAT {
+			Rounding::Exact
+		} else {
+			Rounding::Round
+		});
+
 	write!(writer, "osu file format v{}\n\n", bm_file.osu_file_format)?;
 
 	if let Some(general) = &bm_file.general {
@@ -256,10 +357,15 @@ pub fn deserialize_beatmap_file<W: Write>(bm_file: &BeatmapFile, writer: &mut W)
 		deserialize_difficulty_section(difficulty, writer)?;
 	}
 
-	if !bm_file.events.is_empty() {
+	let mut events = Cow::Borrowed(&bm_file.events);
+	if options.normalize_events {
+		normalize_events(events.to_mut());
+	}
+
+	if !events.is_empty() {
 		writeln!(writer, "[Events]")?;
-		for event in &bm_file.events {
-			deserialize_event(event, writer)?;
+		for event in events.iter() {
+			writeln!(writer, "{event}")?;
 		}
 		writeln!(writer)?;
 	}
@@ -267,7 +373,7 @@ pub fn deserialize_beatmap_file<W: Write>(bm_file: &BeatmapFile, writer: &mut W)
 	if !bm_file.timing_points.is_empty() {
 		writeln!(writer, "[TimingPoints]")?;
 		for timing_point in &bm_file.timing_points {
-			deserialize_timing_point(timing_point, writer)?;
+			writeln!(writer, "{timing_point}")?;
 		}
 		writeln!(writer)?;
 	}
@@ -276,12 +382,215 @@ pub fn deserialize_beatmap_file<W: Write>(bm_file: &BeatmapFile, writer: &mut W)
 		deserialize_color_section(colors, writer)?;
 	}
 
+	// sorted to always write them in the same order, and before the hit objects which some tools expect last
+	let mut custom_sections: Vec<_> = bm_file.custom_sections.iter().collect();
+	custom_sections.sort_unstable_by_key(|(name, _)| *name);
+	for (name, lines) in custom_sections {
+		writeln!(writer, "[{name}]")?;
+		for line in lines {
+			writeln!(writer, "{line}")?;
+		}
+		writeln!(writer)?;
+	}
+
 	if !bm_file.hit_objects.is_empty() {
 		writeln!(writer, "[HitObjects]")?;
 		for hit_object in &bm_file.hit_objects {
-			deserialize_hit_object(hit_object, writer)?;
+			if position_rounding == Rounding::Exact {
+				writeln!(writer, "{hit_object}")?;
+			} else {
+				let mut hit_object = hit_object.clone();
+				hit_object.round_positions(position_rounding);
+				writeln!(writer, "{hit_object}")?;
+			}
 		}
 	}
 
 	Ok(())
 }
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+	use super::*;
+	use crate::file::beatmap::parsing::{
+		parse_event, parse_osu_str, parse_osu_str_with_options, ParseOptions, SectionRegistry,
+	};
+	use crate::file::beatmap::{HoldTail, Timestamp};
+
+	fn write(beatmap: &BeatmapFile, options: DeserializeOptions) -> String {
+		let mut contents = Vec::new();
+		deserialize_beatmap_file_with_options(beatmap, &mut contents, &options).unwrap();
+		String::from_utf8(contents).unwrap()
+	}
+
+	fn fractional_beatmap(version: u32) -> BeatmapFile {
+		parse_osu_str(&format!(
+			"osu file format v{version}\n\n[HitObjects]\n100.25,50.75,1000,2,0,B|200.5:-10.5|300:100,1,200,0|0,0:0|0:0,0:0:0:0:\n"
+		))
+		.unwrap()
+	}
+
+	#[test]
+	fn lazer_positions_round_trip_exactly() {
+		let beatmap = fractional_beatmap(128);
+		let contents = write(&beatmap, DeserializeOptions::default());
+		assert!(
+			contents.contains("100.25,50.75,1000,2,0,B|200.5:-10.5|300:100,"),
+			"{contents}"
+		);
+
+		let reparsed = parse_osu_str(&contents).unwrap();
+		let (before, after) = (&beatmap.hit_objects[0], &reparsed.hit_objects[0]);
+		assert_eq!((before.x, before.y), (after.x, after.y));
+		let (
+			HitObjectParams::Slider {
+				curve_points: before, ..
+			},
+			HitObjectParams::Slider {
+				curve_points: after, ..
+			},
+		) = (&before.object_params, &after.object_params)
+		else {
+			panic!("not a slider");
+		};
+		let coordinates = |points: &[SliderPoint]| points.iter().map(|p| (p.x, p.y)).collect::<Vec<_>>();
+		assert_eq!(coordinates(before), coordinates(after));
+	}
+
+	#[test]
+	fn stable_positions_are_rounded() {
+		let beatmap = fractional_beatmap(14);
+		let contents = write(&beatmap, DeserializeOptions::default());
+		assert!(contents.contains("100,51,1000,2,0,B|201:-11|300:100,"), "{contents}");
+	}
+
+	#[test]
+	fn position_rounding_can_be_overridden() {
+		let beatmap = fractional_beatmap(14);
+		let options = DeserializeOptions {
+			position_rounding: Some(Rounding::Floor),
+			..DeserializeOptions::default()
+		};
+		assert!(write(&beatmap, options).contains("100,50,1000,2,0,B|200:-11|300:100,"));
+
+		let options = DeserializeOptions {
+			position_rounding: Some(Rounding::Exact),
+			..DeserializeOptions::default()
+		};
+		assert!(write(&beatmap, options).contains("100.25,50.75,"));
+	}
+
+	const HOLD: &str = "64,192,1000,128,2,1500:1:2:0:0:";
+
+	fn hold_beatmap(line: &str) -> BeatmapFile {
+		parse_osu_str(&format!("osu file format v128\n\n[HitObjects]\n{line}\n")).unwrap()
+	}
+
+	fn hold_end(beatmap: &BeatmapFile) -> (Timestamp, Option<HoldTail>) {
+		match beatmap.hit_objects[0].object_params {
+			HitObjectParams::Hold { end_time, tail } => (end_time, tail),
+			_ => panic!("not a hold"),
+		}
+	}
+
+	#[test]
+	fn holds_round_trip() {
+		let beatmap = hold_beatmap(HOLD);
+		assert!(matches!(hold_end(&beatmap), (1500.0, None)));
+
+		let contents = write(&beatmap, DeserializeOptions::default());
+		assert!(contents.contains(HOLD), "{contents}");
+		let reparsed = parse_osu_str(&contents).unwrap();
+		assert!(matches!(hold_end(&reparsed), (1500.0, None)));
+		assert_eq!(reparsed.hit_objects[0].to_string(), beatmap.hit_objects[0].to_string());
+	}
+
+	#[test]
+	fn hold_tails_are_not_written() {
+		let mut beatmap = hold_beatmap(HOLD);
+		if let HitObjectParams::Hold { tail, .. } = &mut beatmap.hit_objects[0].object_params {
+			*tail = Some(HoldTail {
+				hit_sound: HitSound::FINISH,
+				sample_set: HitSampleSet::default(),
+			});
+		}
+
+		// osu!lazer only reads `endTime:hitSample` for holds
+		let contents = write(&beatmap, DeserializeOptions::default());
+		assert!(contents.lines().any(|line| line == HOLD), "{contents}");
+		let reparsed = parse_osu_str(&contents).unwrap();
+		assert!(matches!(hold_end(&reparsed), (1500.0, None)));
+	}
+
+	#[test]
+	fn holds_with_extra_parameters_are_rejected() {
+		assert!(parse_osu_str(&format!("osu file format v128\n\n[HitObjects]\n{HOLD},2|8,0:0|0:0\n")).is_err());
+	}
+
+	#[test]
+	fn only_filenames_with_spaces_or_commas_are_quoted() {
+		let written = |line: &str| parse_event(line).unwrap().unwrap().to_string();
+		assert_eq!(written("0,0,\"bg.jpg\",0,0"), "0,0,bg.jpg,0,0");
+		assert_eq!(written("0,0,\"my bg.jpg\",0,0"), "0,0,\"my bg.jpg\",0,0");
+		assert_eq!(written("1,0,\"a,b.mp4\",0,0"), "1,0,\"a,b.mp4\",0,0");
+		assert_eq!(
+			written("5,1000,0,\"hit, sound.wav\",70"),
+			"5,1000,0,\"hit, sound.wav\",70"
+		);
+		assert_eq!(written("5,1000,0,hit.wav,70"), "5,1000,0,hit.wav,70");
+	}
+
+	const SMALL_MAP: &str = "osu file format v14\n\n[General]\nAudioFilename: audio.mp3\nMode: 0\n\n\
+		[Metadata]\nTitle:Song\nVersion:Hard\n\n[TimingPoints]\n0,500,4,2,0,60,1,0\n1000,-50,4,2,0,60,0,0\n\n\
+		[HitObjects]\n64,192,500,1,0,0:0:0:0:\n128,192,1000,1,2,0:0:0:0:\n";
+
+	#[test]
+	fn trivia_round_trips() {
+		let canonical = write(&parse_osu_str(SMALL_MAP).unwrap(), DeserializeOptions::default());
+		let commented = canonical
+			.replacen("Mode:", "// standard\nMode:", 1)
+			.replacen("1000,-50", "// sv section start\n\n1000,-50", 1)
+			.replacen("128,192", "// kiai\n128,192", 1);
+		assert_ne!(commented, canonical);
+
+		let options = ParseOptions {
+			preserve_trivia: true,
+			..ParseOptions::default()
+		};
+		let beatmap = parse_osu_str_with_options(&commented, &options).unwrap();
+		assert_eq!(write(&beatmap, DeserializeOptions::default()), commented);
+
+		// without trivia, comments are dropped
+		assert_eq!(
+			write(&parse_osu_str(&commented).unwrap(), DeserializeOptions::default()),
+			canonical
+		);
+	}
+
+	#[test]
+	fn custom_sections_round_trip_before_hit_objects() {
+		let contents = format!("{SMALL_MAP}\n[Mania]\nKeys: 4\nLayout=a,b\n\n[Tool]\nsomething\n");
+		let options = ParseOptions {
+			custom_sections: SectionRegistry::new().register("Mania"),
+			..ParseOptions::default()
+		};
+
+		let beatmap = parse_osu_str_with_options(&contents, &options).unwrap();
+		let mania = vec!["Keys: 4".to_owned(), "Layout=a,b".to_owned()];
+		assert_eq!(beatmap.custom_sections.get("Mania"), Some(&mania));
+		assert!(!beatmap.custom_sections.contains_key("Tool"));
+		assert_eq!(beatmap.hit_objects.len(), 2);
+
+		// written back just before [HitObjects], even though it came after them
+		let written = write(&beatmap, DeserializeOptions::default());
+		assert!(
+			written.contains("\n[Mania]\nKeys: 4\nLayout=a,b\n\n[HitObjects]\n"),
+			"{written}"
+		);
+
+		let reparsed = parse_osu_str_with_options(&written, &options).unwrap();
+		assert_eq!(reparsed.custom_sections, beatmap.custom_sections);
+		assert_eq!(write(&reparsed, DeserializeOptions::default()), written);
+	}
+}
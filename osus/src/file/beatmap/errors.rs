@@ -0,0 +1,459 @@
+//! Errors that can happen while parsing an osu! beatmap file.
+
+use std::ffi::OsString;
+#[cfg(feature = "miette")]
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+use std::num::{ParseFloatError, ParseIntError};
+use std::ops::Range;
+
+use super::{HitSampleSet, HitSound, InvalidOverlayPositionError, InvalidSampleBankError};
+use crate::ErrorCategory;
+
+#[derive(Debug, thiserror::Error)]
+#[error("Could not split line with {split_char:?}")]
+pub struct InvalidKeyValuePairError {
+	pub split_char: char,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid list of {type_name}")]
+pub struct InvalidListError<T> {
+	type_name: &'static str,
+	_phantom_data: PhantomData<T>,
+}
+
+impl<T> InvalidListError<T> {
+	#[must_use]
+	pub fn new() -> Self {
+		Self {
+			type_name: std::any::type_name::<T>(),
+			_phantom_data: PhantomData,
+		}
+	}
+}
+
+impl<T> Default for InvalidListError<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Couldn't parse section {section} at line {line:?}")]
+pub struct SectionParseError {
+	pub section: &'static str,
+	pub line: String,
+	#[source]
+	pub kind: SectionParseErrorKind,
+}
+
+impl SectionParseError {
+	/// Column span of the offending text in the line.
+	///
+	/// For field values, this is the span of the value only. Otherwise it's the whole line without surrounding spaces.
+	#[must_use]
+	pub fn span(&self) -> Range<usize> {
+		let trimmed_start = self.line.len() - self.line.trim_start().len();
+		let whole_line = trimmed_start..self.line.trim_end().len().max(trimmed_start);
+
+		match &self.kind {
+			SectionParseErrorKind::FieldValueParse(_) => {
+				self.line.split_once(':').map_or(whole_line, |(field, value)| {
+					let start = field.len() + 1 + (value.len() - value.trim_start().len());
+					start..(start + value.trim().len())
+				})
+			}
+			_ => whole_line,
+		}
+	}
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SectionParseErrorKind {
+	#[error(transparent)]
+	Io(#[from] io::Error),
+
+	#[error("Invalid key-value pair")]
+	InvalidKeyValuePair(
+		#[from]
+		#[source]
+		InvalidKeyValuePairError,
+	),
+
+	#[error(transparent)]
+	FieldValueParse(#[from] FieldValueParseError),
+
+	#[error(transparent)]
+	UnspecifiedField(#[from] UnspecifiedFieldError),
+
+	#[error(transparent)]
+	EventParse(#[from] EventParseError),
+
+	#[error("Could not parse timing point")]
+	TimingPointParse(
+		#[from]
+		#[source]
+		TimingPointParseError,
+	),
+
+	#[error(transparent)]
+	HitObjectParse(#[from] HitObjectParseError),
+
+	#[error("Invalid color")]
+	ColorParse(
+		#[from]
+		#[source]
+		ColorParseError,
+	),
+}
+
+impl SectionParseErrorKind {
+	/// A hint on how the line should have been written.
+	#[must_use]
+	pub const fn help(&self) -> Option<&'static str> {
+		match self {
+			Self::Io(_) => None,
+			Self::InvalidKeyValuePair(_) => Some("fields are written as `Key: Value`"),
+			Self::FieldValueParse(_) => Some("the value doesn't have the type expected for this field"),
+			Self::UnspecifiedField(_) => Some("this field is required in this section"),
+			Self::EventParse(_) => Some("events are written as `eventType,startTime,eventParams`"),
+			Self::TimingPointParse(_) => Some(
+				"timing points are written as `time,beatLength,meter,sampleSet,sampleIndex,volume,uninherited,effects`",
+			),
+			Self::HitObjectParse(_) => {
+				Some("hit objects are written as `x,y,time,type,hitSound,objectParams,hitSample`")
+			}
+			Self::ColorParse(_) => Some("colors are written as `r,g,b` or `r,g,b,a`, with values between 0 and 255"),
+		}
+	}
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Couldn't parse value of field [{field:?}]")]
+pub struct FieldValueParseError {
+	pub field: &'static str,
+	#[source]
+	pub kind: FieldValueParseErrorKind,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FieldValueParseErrorKind {
+	#[error("Invalid int")]
+	InvalidInt(
+		#[from]
+		#[source]
+		ParseIntError,
+	),
+
+	#[error("Invalid float")]
+	InvalidFloat(
+		#[from]
+		#[source]
+		FloatParseError,
+	),
+
+	#[error("Invalid float list")]
+	InvalidFloatList(
+		#[from]
+		#[source]
+		InvalidListError<f32>,
+	),
+
+	#[error("Invalid oerlay position")]
+	InvalidOverlayPosition(
+		#[from]
+		#[source]
+		InvalidOverlayPositionError,
+	),
+}
+
+/// Error parsing a float, see [`parse_float`](super::parsing::parse_float).
+#[derive(Debug, thiserror::Error)]
+pub enum FloatParseError {
+	#[error(transparent)]
+	Invalid(#[from] ParseFloatError),
+
+	#[error("Number is not finite")]
+	NotFinite,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Field {0} unspecified")]
+pub struct UnspecifiedFieldError(pub &'static str);
+
+#[derive(Debug, thiserror::Error)]
+pub enum EventParseError {
+	#[error("Unknown event type: {0:?}")]
+	UnknownEventType(String),
+
+	#[error("Event is empty")]
+	Empty,
+
+	#[error("Event does not have a start time")]
+	NoStartTime,
+
+	#[error("Invalid start time")]
+	InvalidStartTime(#[source] FloatParseError),
+
+	#[error(transparent)]
+	SpecificEvent(#[from] SpecificEventParseError),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{event} event{kind}")]
+pub struct SpecificEventParseError {
+	pub event: &'static str,
+	#[source]
+	pub kind: SpecificEventParseErrorKind,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SpecificEventParseErrorKind {
+	#[error(" has no filename")]
+	NoFileName,
+
+	#[error(" has no end time")]
+	NoEndTime,
+
+	#[error(" has no layer")]
+	NoLayer,
+
+	#[error(": {0}")]
+	InvalidInt(#[from] ParseIntError),
+
+	#[error(": {0}")]
+	InvalidFloat(#[from] FloatParseError),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TimingPointParseError {
+	#[error("Expected at least 2 values, got {0}")]
+	LessThan2Values(usize),
+
+	#[error("Expected at most 8 values, got {0}")]
+	MoreThan8Values(usize),
+
+	#[error("Invalid float")]
+	InvalidFloat(
+		#[from]
+		#[source]
+		FloatParseError,
+	),
+
+	#[error("Invalid int")]
+	InvalidInt(
+		#[from]
+		#[source]
+		ParseIntError,
+	),
+
+	#[error(transparent)]
+	InvalidSampleBank(#[from] InvalidSampleBankError),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ColorParseError {
+	#[error("Invalid RGB(A) values list")]
+	InvalidList(
+		#[from]
+		#[source]
+		InvalidListError<u8>,
+	),
+
+	#[error("Expected 3 or 4 numbers between 0 and 255")]
+	WrongNumberCount,
+
+	#[error("Unknown color field: {0:?}")]
+	UnknownColorField(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HitSampleParseError {
+	#[error("Expected at least 5 colon-separated arguments, got {0}")]
+	NotEnoughArguments(usize),
+
+	#[error(transparent)]
+	InvalidSampleBank(#[from] InvalidSampleBankError),
+
+	#[error("Invalid int")]
+	InvalidInt(
+		#[from]
+		#[source]
+		ParseIntError,
+	),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CurvePointsParseError {
+	#[error("Not enough tokens")]
+	NotEnoughTokens,
+
+	#[error("Unknown curve type: {0:?}")]
+	UnknownCurveType(String),
+
+	#[error("Invalid slider point")]
+	InvalidSliderPoint,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HitObjectParseError {
+	#[error("Unknown hit object type: {0:?}")]
+	UnknownHitObjectType(String),
+
+	#[error("Expected at least 7 comma-separated arguments for the hit object, got {0}")]
+	NotEnoughArguments(usize),
+
+	#[error("Expected at least 3 object parameters for slider, got {0}")]
+	WrongSliderParameterCount(usize),
+
+	#[error("Expected 1 object parameter for spinner, got {0}")]
+	WrongSpinnerParameterCount(usize),
+
+	#[error("Expected 1 object parameter for hold, got {0}")]
+	WrongHoldParameterCount(usize),
+
+	#[error("Invalid hitsound list")]
+	InvalidHitSoundList(
+		#[from]
+		#[source]
+		InvalidListError<HitSound>,
+	),
+
+	#[error("Invalid hitsample set list")]
+	InvalidHitSampleSetList(
+		#[from]
+		#[source]
+		InvalidListError<HitSampleSet>,
+	),
+
+	#[error("Couldn't parse curve points")]
+	CurvePointsParse(
+		#[from]
+		#[source]
+		CurvePointsParseError,
+	),
+
+	#[error("Couldn't parse hitsample")]
+	HitSampleParse(
+		#[from]
+		#[source]
+		HitSampleParseError,
+	),
+
+	#[error("Invalid hold")]
+	InvalidHold,
+
+	#[error("Invalid float")]
+	InvalidFloat(
+		#[from]
+		#[source]
+		FloatParseError,
+	),
+
+	#[error("Invalid int")]
+	InvalidInt(
+		#[from]
+		#[source]
+		ParseIntError,
+	),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Could not parse osu! beatmap file {filename:?}")]
+pub struct BeatmapFileParseError {
+	pub filename: OsString,
+	/// Line number (starting from 1) where the error occured, if it is known.
+	pub line_number: Option<usize>,
+	#[source]
+	pub kind: BeatmapFileParseErrorKind,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BeatmapFileParseErrorKind {
+	#[error("File is empty")]
+	FileIsEmpty,
+
+	#[error("The file name ends with '..'")]
+	InvalidFileName,
+
+	#[error("First line doesn't match \"osu file format v<version>\"")]
+	InvalidOsuFileFormat,
+
+	#[error(transparent)]
+	SectionParse(#[from] SectionParseError),
+
+	#[error("File is not valid UTF-8")]
+	InvalidUtf8(#[from] std::str::Utf8Error),
+
+	#[error(transparent)]
+	Io(#[from] io::Error),
+}
+
+impl BeatmapFileParseError {
+	#[must_use]
+	pub const fn category(&self) -> ErrorCategory {
+		match &self.kind {
+			BeatmapFileParseErrorKind::Io(_) => ErrorCategory::Io,
+			_ => ErrorCategory::Parse,
+		}
+	}
+
+	/// The text of the line where the error occured, if it is known.
+	#[must_use]
+	pub fn offending_text(&self) -> Option<&str> {
+		match &self.kind {
+			BeatmapFileParseErrorKind::SectionParse(e) => Some(&e.line),
+			_ => None,
+		}
+	}
+
+	/// Column span of the offending text in its line, if it is known.
+	#[must_use]
+	pub fn span(&self) -> Option<Range<usize>> {
+		match &self.kind {
+			BeatmapFileParseErrorKind::SectionParse(e) => Some(e.span()),
+			_ => None,
+		}
+	}
+
+	/// A hint on how to fix the error, if there is one.
+	#[must_use]
+	pub const fn help_text(&self) -> Option<&'static str> {
+		match &self.kind {
+			BeatmapFileParseErrorKind::FileIsEmpty => {
+				Some("an osu! beatmap file should at least contain its format version")
+			}
+			BeatmapFileParseErrorKind::InvalidOsuFileFormat => {
+				Some("the first line should be the format version, like `osu file format v14`")
+			}
+			BeatmapFileParseErrorKind::SectionParse(e) => e.kind.help(),
+			BeatmapFileParseErrorKind::InvalidFileName
+			| BeatmapFileParseErrorKind::InvalidUtf8(_)
+			| BeatmapFileParseErrorKind::Io(_) => None,
+		}
+	}
+}
+
+#[cfg(feature = "miette")]
+impl miette::Diagnostic for BeatmapFileParseError {
+	fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+		self.help_text().map(|help| Box::new(help) as Box<dyn fmt::Display>)
+	}
+
+	fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+		match &self.kind {
+			BeatmapFileParseErrorKind::SectionParse(e) => Some(&e.line as &dyn miette::SourceCode),
+			_ => None,
+		}
+	}
+
+	fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+		let span = self.span()?;
+		let label = self.line_number.map(|line_number| format!("at line {line_number}"));
+		Some(Box::new(std::iter::once(miette::LabeledSpan::new_with_span(
+			label, span,
+		))))
+	}
+}
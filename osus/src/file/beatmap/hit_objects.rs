@@ -0,0 +1,345 @@
+//! Sorted collection of hit objects, with lookups by time and position.
+//!
+//! Finding objects close to each other both in time and on the playfield (for stacking, patterns or overlaps)
+//! means scanning the whole list with a plain `Vec`. [`HitObjects`] keeps its hit objects sorted by time,
+//! so that time windows are binary searches, and can index them in a coarse grid of the playfield.
+
+use std::collections::HashMap;
+use std::ops::{Deref, Range};
+
+use crate::point::{Point, Rect};
+
+use super::{HitObject, Timestamp};
+
+/// Cell of the spatial grid, in units of the cell size.
+type Cell = (i64, i64);
+
+/// Coarse grid of the playfield, with the indices of the hit objects in each cell.
+#[derive(Clone, Debug)]
+struct SpatialGrid {
+	cell_size: f64,
+	cells: HashMap<Cell, Vec<usize>>,
+	/// Smallest and largest cells with hit objects, or `None` if the grid is empty.
+	bounds: Option<(Cell, Cell)>,
+}
+
+impl SpatialGrid {
+	fn new(hit_objects: &[HitObject], cell_size: f64) -> Self {
+		let mut grid = Self {
+			cell_size,
+			cells: HashMap::new(),
+			bounds: None,
+		};
+
+		for (index, hit_object) in hit_objects.iter().enumerate() {
+			let cell = grid.cell_of(hit_object.position());
+			grid.cells.entry(cell).or_default().push(index);
+			grid.bounds = Some(match grid.bounds {
+				Some((min, max)) => (
+					(min.0.min(cell.0), min.1.min(cell.1)),
+					(max.0.max(cell.0), max.1.max(cell.1)),
+				),
+				None => (cell, cell),
+			});
+		}
+
+		grid
+	}
+
+	#[allow(clippy::cast_possible_truncation)]
+	fn cell_of(&self, point: Point) -> Cell {
+		(
+			(point.x / self.cell_size).floor() as i64,
+			(point.y / self.cell_size).floor() as i64,
+		)
+	}
+
+	/// Indices of the hit objects in the cells at a distance of exactly `ring` cells around `center`.
+	fn ring(&self, center: Cell, ring: i64) -> impl Iterator<Item = usize> + '_ {
+		let (cx, cy) = center;
+		((cx - ring)..=(cx + ring))
+			.flat_map(move |x| ((cy - ring)..=(cy + ring)).map(move |y| (x, y)))
+			.filter(move |&(x, y)| (x - cx).abs() == ring || (y - cy).abs() == ring)
+			.filter_map(|cell| self.cells.get(&cell))
+			.flatten()
+			.copied()
+	}
+
+	/// Amount of rings around `center` needed to reach every cell of the grid, or `None` if it is empty.
+	fn max_ring(&self, center: Cell) -> Option<i64> {
+		let (min, max) = self.bounds?;
+		Some(
+			(center.0 - min.0)
+				.max(max.0 - center.0)
+				.max(center.1 - min.1)
+				.max(max.1 - center.1),
+		)
+	}
+}
+
+/// Hit objects kept sorted by time.
+///
+/// It dereferences to a slice for reading, and every method that adds or changes hit objects
+/// puts them back in their place. Hit objects at the same time keep the order they were added in.
+///
+/// Positional queries can use a spatial grid, enabled with [`with_spatial_index`](Self::with_spatial_index).
+/// It is rebuilt after every change, so it is best enabled once the hit objects are done changing.
+#[derive(Clone, Debug, Default)]
+pub struct HitObjects {
+	hit_objects: Vec<HitObject>,
+	grid: Option<SpatialGrid>,
+}
+
+impl HitObjects {
+	#[must_use]
+	pub const fn new() -> Self {
+		Self {
+			hit_objects: Vec::new(),
+			grid: None,
+		}
+	}
+
+	/// Sorts hit objects by time.
+	///
+	/// The sort is stable, so hit objects at the same time keep their order.
+	#[must_use]
+	pub fn from_vec(mut hit_objects: Vec<HitObject>) -> Self {
+		hit_objects.sort_by(|a, b| a.time.total_cmp(&b.time));
+		Self {
+			hit_objects,
+			grid: None,
+		}
+	}
+
+	/// Indexes the hit objects in a grid of square cells of `cell_size` osu! pixels,
+	/// to speed up [`in_rect`](Self::in_rect) and [`nearest`](Self::nearest).
+	#[must_use]
+	pub fn with_spatial_index(mut self, cell_size: f64) -> Self {
+		self.grid = (cell_size > 0.0).then(|| SpatialGrid::new(&self.hit_objects, cell_size));
+		self
+	}
+
+	#[must_use]
+	pub fn into_vec(self) -> Vec<HitObject> {
+		self.hit_objects
+	}
+
+	#[must_use]
+	pub fn as_slice(&self) -> &[HitObject] {
+		&self.hit_objects
+	}
+
+	fn rebuild_grid(&mut self) {
+		if let Some(grid) = &self.grid {
+			self.grid = Some(SpatialGrid::new(&self.hit_objects, grid.cell_size));
+		}
+	}
+
+	/// Inserts a hit object at its place, after the ones at the same time, and returns its index.
+	pub fn insert(&mut self, hit_object: HitObject) -> usize {
+		let index = self.hit_objects.partition_point(|ho| ho.time <= hit_object.time);
+		self.hit_objects.insert(index, hit_object);
+		self.rebuild_grid();
+		index
+	}
+
+	/// Removes the hit object at an index.
+	///
+	/// # Panics
+	///
+	/// Panics if the index is out of bounds.
+	pub fn remove(&mut self, index: usize) -> HitObject {
+		let hit_object = self.hit_objects.remove(index);
+		self.rebuild_grid();
+		hit_object
+	}
+
+	/// Keeps only the hit objects for which `f` returns true.
+	pub fn retain(&mut self, f: impl FnMut(&HitObject) -> bool) {
+		self.hit_objects.retain(f);
+		self.rebuild_grid();
+	}
+
+	/// Changes the hit object at an index, then moves it back to its place.
+	/// Returns its new index, or `None` if the index is out of bounds.
+	pub fn modify(&mut self, index: usize, f: impl FnOnce(&mut HitObject)) -> Option<usize> {
+		let mut hit_object = self.hit_objects.get(index)?.clone();
+		f(&mut hit_object);
+
+		let before = index.checked_sub(1).map(|i| &self.hit_objects[i]);
+		let after = self.hit_objects.get(index + 1);
+		let in_place =
+			before.is_none_or(|ho| ho.time <= hit_object.time) && after.is_none_or(|ho| hit_object.time < ho.time);
+		if in_place {
+			self.hit_objects[index] = hit_object;
+			self.rebuild_grid();
+			return Some(index);
+		}
+
+		self.hit_objects.remove(index);
+		Some(self.insert(hit_object))
+	}
+
+	/// Changes every hit object, then sorts them by time again.
+	pub fn for_each_mut(&mut self, f: impl FnMut(&mut HitObject)) {
+		self.hit_objects.iter_mut().for_each(f);
+		self.hit_objects.sort_by(|a, b| a.time.total_cmp(&b.time));
+		self.rebuild_grid();
+	}
+
+	/// Range of indices of the hit objects starting in a time range.
+	#[must_use]
+	pub fn indices_between(&self, time_range: Range<Timestamp>) -> Range<usize> {
+		let start = self.hit_objects.partition_point(|ho| ho.time < time_range.start);
+		let end = self.hit_objects.partition_point(|ho| ho.time < time_range.end);
+		start..end.max(start)
+	}
+
+	/// Hit objects starting exactly at a time, like the notes of an osu!mania chord.
+	#[must_use]
+	pub fn at_time(&self, time: Timestamp) -> &[HitObject] {
+		let start = self.hit_objects.partition_point(|ho| ho.time < time);
+		let end = self.hit_objects.partition_point(|ho| ho.time <= time);
+		&self.hit_objects[start..end]
+	}
+
+	/// Indices of the hit objects starting in a time range whose position is in a rectangle, in order.
+	#[must_use]
+	pub fn in_rect(&self, rect: Rect, time_range: Range<Timestamp>) -> Vec<usize> {
+		let indices = self.indices_between(time_range);
+		let is_inside = |&index: &usize| rect.contains(self.hit_objects[index].position());
+
+		let Some(grid) = &self.grid else {
+			return indices.filter(is_inside).collect();
+		};
+
+		let Some((grid_min, grid_max)) = grid.bounds else {
+			return Vec::new();
+		};
+
+		let (min, max) = (grid.cell_of(rect.min), grid.cell_of(rect.max));
+		let mut found: Vec<usize> = (min.0.max(grid_min.0)..=max.0.min(grid_max.0))
+			.flat_map(|x| (min.1.max(grid_min.1)..=max.1.min(grid_max.1)).map(move |y| (x, y)))
+			.filter_map(|cell| grid.cells.get(&cell))
+			.flatten()
+			.copied()
+			.filter(|index| indices.contains(index))
+			.filter(is_inside)
+			.collect();
+		found.sort_unstable();
+		found
+	}
+
+	/// Index of the hit object starting in a time window that is the closest to a point.
+	///
+	/// Among hit objects at the same distance, the earliest one is returned.
+	#[must_use]
+	pub fn nearest(&self, point: Point, time_window: Range<Timestamp>) -> Option<usize> {
+		let indices = self.indices_between(time_window);
+		let distance = |index: usize| self.hit_objects[index].position().distance(point);
+		let closest = |best: Option<(usize, f64)>, index: usize| {
+			let d = distance(index);
+			match best {
+				Some((best_index, best_d)) if best_d < d || (best_d <= d && best_index < index) => best,
+				_ => Some((index, d)),
+			}
+		};
+
+		let Some(grid) = &self.grid else {
+			return indices.fold(None, closest).map(|(index, _)| index);
+		};
+
+		// rings of cells around the point, until the next ring can't have anything closer
+		let center = grid.cell_of(point);
+		let mut best: Option<(usize, f64)> = None;
+		for ring in 0..=grid.max_ring(center)? {
+			#[allow(clippy::cast_precision_loss)]
+			let ring_distance = (ring - 1) as f64 * grid.cell_size;
+			if best.is_some_and(|(_, d)| d < ring_distance) {
+				break;
+			}
+
+			best = (grid.ring(center, ring))
+				.filter(|index| indices.contains(index))
+				.fold(best, closest);
+		}
+
+		best.map(|(index, _)| index)
+	}
+}
+
+impl Deref for HitObjects {
+	type Target = [HitObject];
+
+	fn deref(&self) -> &Self::Target {
+		&self.hit_objects
+	}
+}
+
+impl From<Vec<HitObject>> for HitObjects {
+	fn from(hit_objects: Vec<HitObject>) -> Self {
+		Self::from_vec(hit_objects)
+	}
+}
+
+impl From<HitObjects> for Vec<HitObject> {
+	fn from(hit_objects: HitObjects) -> Self {
+		hit_objects.hit_objects
+	}
+}
+
+impl FromIterator<HitObject> for HitObjects {
+	fn from_iter<I: IntoIterator<Item = HitObject>>(iter: I) -> Self {
+		Self::from_vec(iter.into_iter().collect())
+	}
+}
+
+impl Extend<HitObject> for HitObjects {
+	/// Adds hit objects, each one going after the ones at the same time.
+	fn extend<I: IntoIterator<Item = HitObject>>(&mut self, iter: I) {
+		self.hit_objects.extend(iter);
+		self.hit_objects.sort_by(|a, b| a.time.total_cmp(&b.time));
+		self.rebuild_grid();
+	}
+}
+
+impl<'a> IntoIterator for &'a HitObjects {
+	type Item = &'a HitObject;
+	type IntoIter = std::slice::Iter<'a, HitObject>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.hit_objects.iter()
+	}
+}
+
+impl IntoIterator for HitObjects {
+	type Item = HitObject;
+	type IntoIter = std::vec::IntoIter<HitObject>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.hit_objects.into_iter()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::file::beatmap::parsing::parse_hit_object;
+
+	#[test]
+	fn nearest_in_empty_grid() {
+		let hit_objects = HitObjects::new().with_spatial_index(32.0);
+		assert_eq!(hit_objects.nearest(Point::new(256.0, 192.0), 0.0..1000.0), None);
+		assert!(hit_objects.in_rect(Rect::PLAYFIELD, 0.0..1000.0).is_empty());
+	}
+
+	#[test]
+	fn nearest_with_one_object() {
+		let hit_object = parse_hit_object("100,100,500,1,0,0:0:0:0:").unwrap();
+		let hit_objects = HitObjects::from_vec(vec![hit_object]).with_spatial_index(32.0);
+
+		assert_eq!(hit_objects.nearest(Point::new(256.0, 192.0), 0.0..1000.0), Some(0));
+		assert_eq!(hit_objects.nearest(Point::new(100.0, 100.0), 0.0..1000.0), Some(0));
+		assert_eq!(hit_objects.nearest(Point::new(256.0, 192.0), 600.0..1000.0), None);
+	}
+}
@@ -1,24 +1,33 @@
-use std::ffi::{OsStr, OsString};
-use std::fmt::Debug;
+use std::collections::HashSet;
+use std::ffi::OsStr;
+#[cfg(feature = "fs")]
+use std::ffi::OsString;
+#[cfg(feature = "fs")]
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
-use std::marker::PhantomData;
-use std::num::{ParseFloatError, ParseIntError};
+#[cfg(feature = "fs")]
+use std::io::BufReader;
+use std::io::{self, BufRead};
+use std::num::ParseFloatError;
+#[cfg(feature = "fs")]
 use std::path::Path;
 use std::str::FromStr;
 
+use crate::algos::for_each_timestamp_mut;
+
+use super::errors::{
+	BeatmapFileParseError, BeatmapFileParseErrorKind, ColorParseError, CurvePointsParseError, EventParseError,
+	FieldValueParseError, FieldValueParseErrorKind, FloatParseError, HitObjectParseError, HitSampleParseError,
+	InvalidKeyValuePairError, InvalidListError, SectionParseError, SectionParseErrorKind, SpecificEventParseError,
+	SpecificEventParseErrorKind, TimingPointParseError, UnspecifiedFieldError,
+};
+use super::quirks::{parse_lenient_int, round_hit_object_times, FormatQuirks, ParseWarning, Quirk};
+use super::trivia::{anchor_of, Trivia, TriviaAnchor};
 use super::{
 	BeatmapFile, Color, ColorsSection, DifficultySection, EditorSection, Event, EventParams, GeneralSection, HitObject,
-	HitObjectParams, HitObjectType, HitSample, HitSampleSet, HitSound, InvalidOverlayPositionError,
-	InvalidSampleBankError, MetadataSection, OverlayPosition, SliderCurveType, SliderPoint, TimingPoint,
+	HitObjectParams, HitObjectType, HitSample, HitSampleSet, HitSound, MetadataSection, OverlayPosition, RawNumber,
+	SliderCurveType, SliderPoint, TimingPoint,
 };
 
-#[derive(Debug, thiserror::Error)]
-#[error("Could not split line with {split_char:?}")]
-pub struct InvalidKeyValuePairError {
-	pub split_char: char,
-}
-
 /// Parse a `field:value` pair (arbitrary spaces allowed).
 pub(crate) fn parse_field_value_pair(line: &str) -> Result<(String, String), InvalidKeyValuePairError> {
 	let (field, value) = (line.split_once(':')).ok_or(InvalidKeyValuePairError { split_char: ':' })?;
@@ -29,29 +38,6 @@ pub(crate) fn parse_field_value_pair(line: &str) -> Result<(String, String), Inv
 	Ok((field, value))
 }
 
-#[derive(Debug, thiserror::Error)]
-#[error("Invalid list of {type_name}")]
-pub struct InvalidListError<T> {
-	type_name: &'static str,
-	_phantom_data: PhantomData<T>,
-}
-
-impl<T> InvalidListError<T> {
-	#[must_use]
-	pub fn new() -> Self {
-		Self {
-			type_name: std::any::type_name::<T>(),
-			_phantom_data: PhantomData,
-		}
-	}
-}
-
-impl<T> Default for InvalidListError<T> {
-	fn default() -> Self {
-		Self::new()
-	}
-}
-
 pub(crate) fn parse_list_of_with_sep<T: FromStr>(line: &str, sep: char) -> Result<Vec<T>, InvalidListError<T>> {
 	let mut tobjs = Vec::new();
 	for value in line.split(sep) {
@@ -76,62 +62,17 @@ pub(crate) fn to_standardized_path(path: &str) -> String {
 
 const SECTION_GENERAL: &str = "[General]";
 const SECTION_EDITOR: &str = "[Editor]";
-const SECTION_METADATA: &str = "[Metadata]";
-const SECTION_DIFFICULTY: &str = "[Difficulty]";
+pub(crate) const SECTION_METADATA: &str = "[Metadata]";
+pub(crate) const SECTION_DIFFICULTY: &str = "[Difficulty]";
 const SECTION_EVENTS: &str = "[Events]";
 const SECTION_TIMING_POINTS: &str = "[TimingPoints]";
 const SECTION_COLOURS: &str = "[Colours]";
 const SECTION_HIT_OBJECTS: &str = "[HitObjects]";
 
-#[derive(Debug, thiserror::Error)]
-#[error("Couldn't parse section {section} at line {line:?}")]
-pub struct SectionParseError {
-	pub section: &'static str,
-	pub line: String,
-	#[source]
-	pub kind: SectionParseErrorKind,
-}
-
-#[derive(Debug, thiserror::Error)]
-pub enum SectionParseErrorKind {
-	#[error(transparent)]
-	Io(#[from] io::Error),
-
-	#[error("Invalid key-value pair")]
-	InvalidKeyValuePair(
-		#[from]
-		#[source]
-		InvalidKeyValuePairError,
-	),
-
-	#[error(transparent)]
-	FieldValueParse(#[from] FieldValueParseError),
-
-	#[error(transparent)]
-	UnspecifiedField(#[from] UnspecifiedFieldError),
-
-	#[error(transparent)]
-	EventParse(#[from] EventParseError),
-
-	#[error("Could not parse timing point")]
-	TimingPointParse(
-		#[from]
-		#[source]
-		TimingPointParseError,
-	),
-
-	#[error(transparent)]
-	HitObjectParse(#[from] HitObjectParseError),
-
-	#[error("Invalid color")]
-	ColorParse(
-		#[from]
-		#[source]
-		ColorParseError,
-	),
-}
+/// Name given in errors to beatmaps that are not parsed from a file.
+pub(crate) const IN_MEMORY_FILENAME: &str = "(in memory)";
 
-fn section_err<T: Into<SectionParseErrorKind>>(
+pub(crate) fn section_err<T: Into<SectionParseErrorKind>>(
 	section: &'static str,
 	line: String,
 ) -> impl FnOnce(T) -> SectionParseError {
@@ -142,46 +83,27 @@ fn section_err<T: Into<SectionParseErrorKind>>(
 	}
 }
 
-#[derive(Debug, thiserror::Error)]
-#[error("Couldn't parse value of field [{field:?}]")]
-pub struct FieldValueParseError {
-	pub field: &'static str,
-	#[source]
-	pub kind: FieldValueParseErrorKind,
-}
-
-#[derive(Debug, thiserror::Error)]
-pub enum FieldValueParseErrorKind {
-	#[error("Invalid int")]
-	InvalidInt(
-		#[from]
-		#[source]
-		ParseIntError,
-	),
-
-	#[error("Invalid float")]
-	InvalidFloat(
-		#[from]
-		#[source]
-		ParseFloatError,
-	),
-
-	#[error("Invalid float list")]
-	InvalidFloatList(
-		#[from]
-		#[source]
-		InvalidListError<f32>,
-	),
-
-	#[error("Invalid oerlay position")]
-	InvalidOverlayPosition(
-		#[from]
-		#[source]
-		InvalidOverlayPositionError,
-	),
+/// Parses a float like osu! does.
+///
+/// Scientific notation (`1E-05`) and very long decimals, which osu!lazer can write, are accepted.
+/// Infinities and NaN are not, as they can't be written back in a way osu! reads.
+///
+/// # Errors
+///
+/// This function will return an error if the value is not a number, or is not finite.
+pub fn parse_float<T>(value: &str) -> Result<T, FloatParseError>
+where
+	T: FromStr<Err = ParseFloatError> + Into<f64> + Copy,
+{
+	let parsed: T = value.trim().parse()?;
+	if parsed.into().is_finite() {
+		Ok(parsed)
+	} else {
+		Err(FloatParseError::NotFinite)
+	}
 }
 
-fn field_err<T: Into<FieldValueParseErrorKind>>(
+pub(crate) fn field_err<T: Into<FieldValueParseErrorKind>>(
 	section: &'static str,
 	field: &'static str,
 	line: String,
@@ -222,7 +144,7 @@ fn parse_general_section(
 				"AudioHash" => section.audio_hash = Some(value),
 				"PreviewTime" => {
 					section.preview_time =
-						(value.parse::<f64>()).map_err(field_err(SECTION_GENERAL, "PreviewTime", line.clone()))?;
+						(parse_float(&value)).map_err(field_err(SECTION_GENERAL, "PreviewTime", line.clone()))?;
 				}
 				"Countdown" => {
 					section.countdown =
@@ -231,7 +153,7 @@ fn parse_general_section(
 				"SampleSet" => section.sample_set = value,
 				"StackLeniency" => {
 					section.stack_leniency =
-						(value.parse::<f64>()).map_err(field_err(SECTION_GENERAL, "StackLeniency", line.clone()))?;
+						(parse_float(&value)).map_err(field_err(SECTION_GENERAL, "StackLeniency", line.clone()))?;
 				}
 				"Mode" => {
 					section.mode = (value.parse::<u8>()).map_err(field_err(SECTION_GENERAL, "Mode", line.clone()))?;
@@ -304,24 +226,30 @@ fn parse_general_section(
 	Ok(section)
 }
 
-#[derive(Debug, thiserror::Error)]
-#[error("Field {0} unspecified")]
-pub struct UnspecifiedFieldError(&'static str);
-
 /// Parse a `[Editor]` section
+///
+/// Errors come with the line number they occured at. A missing field is reported on the last line of the section,
+/// where it should have been written.
 fn parse_editor_section(
-	reader: &mut impl Iterator<Item = Result<String, io::Error>>,
+	reader: &mut OsuLines<impl BufRead>,
 	section_header: &mut Option<String>,
-) -> Result<EditorSection, SectionParseError> {
+) -> Result<EditorSection, (usize, SectionParseError)> {
 	let mut bookmarks: Vec<f32> = Vec::new();
 	let mut distance_spacing: Option<f64> = None;
 	let mut beat_divisor: Option<f64> = None;
 	let mut grid_size: Option<i32> = None;
 	let mut timeline_zoom: Option<f64> = None;
 
+	// the header was the last line read
+	let mut last_line = (reader.line_number, SECTION_EDITOR.to_string());
+
 	loop {
 		if let Some(line) = reader.next() {
-			let line = line.map_err(section_err(SECTION_EDITOR, "(corrupted line)".to_string()))?;
+			let line_number = reader.line_number;
+			let at_line = |e| (line_number, e);
+			let line = line
+				.map_err(section_err(SECTION_EDITOR, "(corrupted line)".to_string()))
+				.map_err(at_line)?;
 
 			// We stop once we encounter a new section
 			if line.starts_with('[') && line.ends_with(']') {
@@ -329,29 +257,48 @@ fn parse_editor_section(
 				break;
 			}
 
-			let (field, value) = parse_field_value_pair(&line).map_err(section_err(SECTION_EDITOR, line.clone()))?;
+			let (field, value) = parse_field_value_pair(&line)
+				.map_err(section_err(SECTION_EDITOR, line.clone()))
+				.map_err(at_line)?;
 
 			match field.as_str() {
 				"Bookmarks" => {
-					bookmarks = parse_list_of(&value).map_err(field_err(SECTION_EDITOR, "Bookmarks", line.clone()))?;
+					bookmarks = parse_list_of(&value)
+						.map_err(field_err(SECTION_EDITOR, "Bookmarks", line.clone()))
+						.map_err(at_line)?;
 				}
 				"DistanceSpacing" => {
-					distance_spacing =
-						Some((value.parse()).map_err(field_err(SECTION_EDITOR, "DistanceSpacing", line.clone()))?);
+					distance_spacing = Some(
+						(parse_float(&value))
+							.map_err(field_err(SECTION_EDITOR, "DistanceSpacing", line.clone()))
+							.map_err(at_line)?,
+					);
 				}
 				"BeatDivisor" => {
-					beat_divisor =
-						Some((value.parse()).map_err(field_err(SECTION_EDITOR, "BeatDivisor", line.clone()))?);
+					beat_divisor = Some(
+						(parse_float(&value))
+							.map_err(field_err(SECTION_EDITOR, "BeatDivisor", line.clone()))
+							.map_err(at_line)?,
+					);
 				}
 				"GridSize" => {
-					grid_size = Some((value.parse()).map_err(field_err(SECTION_EDITOR, "GridSize", line.clone()))?);
+					grid_size = Some(
+						(value.parse())
+							.map_err(field_err(SECTION_EDITOR, "GridSize", line.clone()))
+							.map_err(at_line)?,
+					);
 				}
 				"TimelineZoom" => {
-					timeline_zoom =
-						Some((value.parse()).map_err(field_err(SECTION_EDITOR, "TimelineZoom", line.clone()))?);
+					timeline_zoom = Some(
+						(parse_float(&value))
+							.map_err(field_err(SECTION_EDITOR, "TimelineZoom", line.clone()))
+							.map_err(at_line)?,
+					);
 				}
 				key => tracing::warn!("[Editor] section: unknown field {key:?}"),
 			}
+
+			last_line = (line_number, line);
 		} else {
 			// We stop once we encounter an EOL character
 			*section_header = None;
@@ -359,17 +306,19 @@ fn parse_editor_section(
 		}
 	}
 
+	let (last_line_number, last_line) = last_line;
+	let unspecified = |field| {
+		(
+			last_line_number,
+			section_err(SECTION_EDITOR, last_line.clone())(UnspecifiedFieldError(field)),
+		)
+	};
+
 	Ok(EditorSection {
 		bookmarks,
-		distance_spacing: distance_spacing
-			.ok_or(UnspecifiedFieldError("DistanceSpacing"))
-			.map_err(section_err(SECTION_GENERAL, "[Editor]".to_string()))?,
-		beat_divisor: beat_divisor
-			.ok_or(UnspecifiedFieldError("BeatDivisor"))
-			.map_err(section_err(SECTION_GENERAL, "[Editor]".to_string()))?,
-		grid_size: grid_size
-			.ok_or(UnspecifiedFieldError("GridSize"))
-			.map_err(section_err(SECTION_GENERAL, "[Editor]".to_string()))?,
+		distance_spacing: distance_spacing.ok_or_else(|| unspecified("DistanceSpacing"))?,
+		beat_divisor: beat_divisor.ok_or_else(|| unspecified("BeatDivisor"))?,
+		grid_size: grid_size.ok_or_else(|| unspecified("GridSize"))?,
 		timeline_zoom,
 	})
 }
@@ -425,11 +374,12 @@ fn parse_metadata_section(
 }
 
 /// Parse a `[Difficulty]` section
-fn parse_difficulty_section(
+pub(crate) fn parse_difficulty_section(
 	reader: &mut impl Iterator<Item = Result<String, io::Error>>,
 	section_header: &mut Option<String>,
 ) -> Result<DifficultySection, SectionParseError> {
 	let mut section = DifficultySection::default();
+	let mut has_approach_rate = false;
 
 	loop {
 		if let Some(line) = reader.next() {
@@ -447,27 +397,34 @@ fn parse_difficulty_section(
 			match field.as_str() {
 				"HPDrainRate" => {
 					section.hp_drain_rate =
-						(value.parse()).map_err(field_err(SECTION_DIFFICULTY, "HPDrainRate", line.clone()))?;
+						(parse_float(&value)).map_err(field_err(SECTION_DIFFICULTY, "HPDrainRate", line.clone()))?;
 				}
 				"CircleSize" => {
 					section.circle_size =
-						(value.parse()).map_err(field_err(SECTION_DIFFICULTY, "CircleSize", line.clone()))?;
+						(parse_float(&value)).map_err(field_err(SECTION_DIFFICULTY, "CircleSize", line.clone()))?;
 				}
 				"OverallDifficulty" => {
-					section.overall_difficulty =
-						(value.parse()).map_err(field_err(SECTION_DIFFICULTY, "OverallDifficulty", line.clone()))?;
+					section.overall_difficulty = (parse_float(&value)).map_err(field_err(
+						SECTION_DIFFICULTY,
+						"OverallDifficulty",
+						line.clone(),
+					))?;
 				}
 				"ApproachRate" => {
 					section.approach_rate =
-						(value.parse()).map_err(field_err(SECTION_DIFFICULTY, "ApproachRate", line.clone()))?;
+						(parse_float(&value)).map_err(field_err(SECTION_DIFFICULTY, "ApproachRate", line.clone()))?;
+					has_approach_rate = true;
 				}
 				"SliderMultiplier" => {
-					section.slider_multiplier =
-						(value.parse()).map_err(field_err(SECTION_DIFFICULTY, "SliderMultiplier", line.clone()))?;
+					section.slider_multiplier = (parse_float(&value)).map_err(field_err(
+						SECTION_DIFFICULTY,
+						"SliderMultiplier",
+						line.clone(),
+					))?;
 				}
 				"SliderTickRate" => {
 					section.slider_tick_rate =
-						(value.parse()).map_err(field_err(SECTION_DIFFICULTY, "SliderTickRate", line.clone()))?;
+						(parse_float(&value)).map_err(field_err(SECTION_DIFFICULTY, "SliderTickRate", line.clone()))?;
 				}
 				key => tracing::warn!("[Difficulty] section: unknown field {key:?}"),
 			}
@@ -478,77 +435,66 @@ fn parse_difficulty_section(
 		}
 	}
 
-	Ok(section)
-}
-
-#[derive(Debug, thiserror::Error)]
-pub enum EventParseError {
-	#[error("Unknown event type: {0:?}")]
-	UnknownEventType(String),
-
-	#[error("Event is empty")]
-	Empty,
-
-	#[error("Event does not have a start time")]
-	NoStartTime,
-
-	#[error("Invalid start time")]
-	InvalidStartTime(#[source] ParseFloatError),
+	// the first versions of the format had no approach rate, osu! uses the overall difficulty instead
+	if !has_approach_rate {
+		section.approach_rate = section.overall_difficulty;
+	}
 
-	#[error(transparent)]
-	SpecificEvent(#[from] SpecificEventParseError),
+	Ok(section)
 }
 
-#[derive(Debug, thiserror::Error)]
-#[error("{event} event{kind}")]
-pub struct SpecificEventParseError {
-	pub event: &'static str,
-	#[source]
-	pub kind: SpecificEventParseErrorKind,
+/// Splits an event line on commas, except for commas inside double quotes.
+fn split_event_line(line: &str) -> impl Iterator<Item = &str> {
+	let mut in_quotes = false;
+	line.split(move |c| {
+		if c == '"' {
+			in_quotes = !in_quotes;
+		}
+		c == ',' && !in_quotes
+	})
 }
 
-#[derive(Debug, thiserror::Error)]
-pub enum SpecificEventParseErrorKind {
-	#[error(" has no filename")]
-	NoFileName,
-
-	#[error(" has no end time")]
-	NoEndTime,
-
-	#[error(": {0}")]
-	InvalidInt(#[from] ParseIntError),
-
-	#[error(": {0}")]
-	InvalidFloat(#[from] ParseFloatError),
+/// Removes the double quotes surrounding an event filename.
+fn unquote_filename(filename: &str) -> String {
+	let filename = filename.trim();
+	filename
+		.strip_prefix('"')
+		.and_then(|filename| filename.strip_suffix('"'))
+		.unwrap_or(filename)
+		.to_owned()
 }
 
-fn parse_event(line: &str) -> Result<Option<Event>, EventParseError> {
-	let mut values = line.split(',');
+/// Parses a single line of the `[Events]` section.
+///
+/// Storyboard events are not supported and give `Ok(None)`.
+///
+/// # Errors
+///
+/// This function will return an error if the line is not a valid event.
+#[allow(clippy::too_many_lines)]
+pub fn parse_event(line: &str) -> Result<Option<Event>, EventParseError> {
+	let mut values = split_event_line(line);
 	let event_type: String = values.next().ok_or(EventParseError::Empty)?.trim().to_owned();
 
 	// Ignoring storyboard events
 	match event_type.as_str() {
-		"3" | "4" | "5" | "6" | "Sample" | "Sprite" | "Animation" | "F" | "M" | "MX" | "MY" | "S" | "V" | "R" | "C"
-		| "L" | "T" | "P" => {
+		"3" | "4" | "6" | "Sprite" | "Animation" | "F" | "M" | "MX" | "MY" | "S" | "V" | "R" | "C" | "L" | "T"
+		| "P" => {
 			tracing::info!("Ignoring storyboard event {:?}", line);
 			return Ok(None);
 		}
 		_ => (),
 	}
 
-	let start_time: f64 = (values.next())
-		.ok_or(EventParseError::NoStartTime)?
-		.parse()
-		.map_err(EventParseError::InvalidStartTime)?;
+	let start_time: f64 =
+		parse_float(values.next().ok_or(EventParseError::NoStartTime)?).map_err(EventParseError::InvalidStartTime)?;
 
 	let params: EventParams = match event_type.as_str() {
 		"0" => {
-			let filename = (values.next())
-				.ok_or(SpecificEventParseError {
-					event: "Background",
-					kind: SpecificEventParseErrorKind::NoFileName,
-				})?
-				.to_owned();
+			let filename = unquote_filename(values.next().ok_or(SpecificEventParseError {
+				event: "Background",
+				kind: SpecificEventParseErrorKind::NoFileName,
+			})?);
 
 			let x_offset: i32 = (values.next().unwrap_or("0").parse()).map_err(|err| SpecificEventParseError {
 				event: "Background",
@@ -567,13 +513,10 @@ fn parse_event(line: &str) -> Result<Option<Event>, EventParseError> {
 			}
 		}
 		"1" | "Video" => {
-			let filename = values
-				.next()
-				.ok_or(SpecificEventParseError {
-					event: "Video",
-					kind: SpecificEventParseErrorKind::NoFileName,
-				})?
-				.to_owned();
+			let filename = unquote_filename(values.next().ok_or(SpecificEventParseError {
+				event: "Video",
+				kind: SpecificEventParseErrorKind::NoFileName,
+			})?);
 
 			let x_offset: i32 = (values.next().unwrap_or("0").parse()).map_err(|err| SpecificEventParseError {
 				event: "Video",
@@ -592,18 +535,44 @@ fn parse_event(line: &str) -> Result<Option<Event>, EventParseError> {
 			}
 		}
 		"2" | "Break" => {
-			let end_time: f64 = (values.next())
+			let end_time: f64 = parse_float(values.next().ok_or(SpecificEventParseError {
+				event: "Break",
+				kind: SpecificEventParseErrorKind::NoEndTime,
+			})?)
+			.map_err(|err| SpecificEventParseError {
+				event: "Break",
+				kind: SpecificEventParseErrorKind::InvalidFloat(err),
+			})?;
+
+			EventParams::Break { end_time }
+		}
+		"5" | "Sample" => {
+			let layer: u8 = (values.next())
 				.ok_or(SpecificEventParseError {
-					event: "Video",
-					kind: SpecificEventParseErrorKind::NoEndTime,
+					event: "Sample",
+					kind: SpecificEventParseErrorKind::NoLayer,
 				})?
 				.parse()
 				.map_err(|err| SpecificEventParseError {
-					event: "Video",
-					kind: SpecificEventParseErrorKind::InvalidFloat(err),
+					event: "Sample",
+					kind: SpecificEventParseErrorKind::InvalidInt(err),
 				})?;
 
-			EventParams::Break { end_time }
+			let filename = unquote_filename(values.next().ok_or(SpecificEventParseError {
+				event: "Sample",
+				kind: SpecificEventParseErrorKind::NoFileName,
+			})?);
+
+			let volume: u8 = (values.next().unwrap_or("100").parse()).map_err(|err| SpecificEventParseError {
+				event: "Sample",
+				kind: SpecificEventParseErrorKind::InvalidInt(err),
+			})?;
+
+			EventParams::Sample {
+				layer,
+				filename,
+				volume,
+			}
 		}
 		t => {
 			return Err(EventParseError::UnknownEventType(t.to_string()));
@@ -647,33 +616,24 @@ fn parse_events_section(
 	Ok(events)
 }
 
-#[derive(Debug, thiserror::Error)]
-pub enum TimingPointParseError {
-	#[error("Expected at least 2 values, got {0}")]
-	LessThan2Values(usize),
-
-	#[error("Expected at most 8 values, got {0}")]
-	MoreThan8Values(usize),
-
-	#[error("Invalid float")]
-	InvalidFloat(
-		#[from]
-		#[source]
-		ParseFloatError,
-	),
-
-	#[error("Invalid int")]
-	InvalidInt(
-		#[from]
-		#[source]
-		ParseIntError,
-	),
-
-	#[error(transparent)]
-	InvalidSampleBank(#[from] InvalidSampleBankError),
+/// Parses a single line of the `[TimingPoints]` section, like `1000,500,4,2,0,60,1,0`.
+///
+/// # Errors
+///
+/// This function will return an error if the line is not a valid timing point.
+pub fn parse_timing_point(line: &str) -> Result<TimingPoint, TimingPointParseError> {
+	parse_timing_point_with_quirks(line, &mut Vec::new())
 }
 
-fn parse_timing_point(line: &str) -> Result<TimingPoint, TimingPointParseError> {
+/// Parses a single timing point line, also recording the quirks accepted on it (see [`quirks`](super::quirks)).
+///
+/// # Errors
+///
+/// This function will return an error if the line is not a valid timing point.
+pub fn parse_timing_point_with_quirks(
+	line: &str,
+	quirks: &mut Vec<Quirk>,
+) -> Result<TimingPoint, TimingPointParseError> {
 	let values: Vec<_> = line.split(',').collect();
 
 	if values.len() < 2 {
@@ -687,36 +647,57 @@ fn parse_timing_point(line: &str) -> Result<TimingPoint, TimingPointParseError>
 	let mut values = values.into_iter();
 
 	if let Some(time) = values.next() {
-		timing_point.time = time.parse()?;
-	};
+		timing_point.time = parse_float(time)?;
+	}
 	if let Some(beat_length) = values.next() {
-		timing_point.beat_length = beat_length.parse()?;
-	};
+		// unlike other numbers, NaN beat lengths are read like osu! does (see `Quirk::NanBeatLength`)
+		let raw_beat_length = match beat_length.parse::<RawNumber>() {
+			Ok(raw_beat_length) => Some(raw_beat_length),
+			Err(FloatParseError::NotFinite) if beat_length.trim().parse::<f64>().is_ok_and(f64::is_nan) => None,
+			Err(e) => return Err(e.into()),
+		};
+		timing_point.beat_length = raw_beat_length.as_ref().map_or(f64::NAN, RawNumber::value);
+		timing_point.raw_beat_length = raw_beat_length;
+	}
 	if let Some(meter) = values.next() {
-		timing_point.meter = meter.parse()?;
-	};
+		timing_point.meter = parse_lenient_int(meter, "meter", quirks)?;
+	}
 	if let Some(sample_set) = values.next() {
 		timing_point.sample_set = sample_set.parse()?;
-	};
+	}
 	if let Some(sample_index) = values.next() {
-		timing_point.sample_index = sample_index.parse()?;
-	};
+		timing_point.sample_index = parse_lenient_int(sample_index, "sample index", quirks)?;
+	}
 	if let Some(volume) = values.next() {
-		timing_point.volume = volume.parse()?;
-	};
+		timing_point.volume = parse_lenient_int(volume, "volume", quirks)?;
+	}
 	if let Some(uninherited) = values.next() {
 		timing_point.uninherited = uninherited.parse::<u8>()? != 0;
-	};
+	}
 	if let Some(effects) = values.next() {
-		timing_point.effects = effects.parse()?;
-	};
+		timing_point.effects = parse_lenient_int(effects, "effects", quirks)?;
+	}
+
+	if timing_point.meter < 0 {
+		quirks.push(Quirk::NegativeMeter(timing_point.meter));
+	}
+
+	if timing_point.beat_length.is_nan() {
+		quirks.push(Quirk::NanBeatLength {
+			uninherited: timing_point.uninherited,
+		});
+
+		if !timing_point.uninherited {
+			timing_point.beat_length = -100.0;
+		}
+	}
 
 	Ok(timing_point)
 }
 
 /// Parse a `[TimingPoints]` section
 fn parse_timing_points_section(
-	reader: &mut impl Iterator<Item = Result<String, io::Error>>,
+	reader: &mut OsuLines<impl BufRead>,
 	section_header: &mut Option<String>,
 ) -> Result<Vec<TimingPoint>, SectionParseError> {
 	let mut timing_points: Vec<TimingPoint> = Vec::new();
@@ -731,7 +712,10 @@ fn parse_timing_points_section(
 				break;
 			}
 
-			let timing_point = parse_timing_point(&line).map_err(section_err(SECTION_TIMING_POINTS, line.clone()))?;
+			let mut quirks = Vec::new();
+			let timing_point = parse_timing_point_with_quirks(&line, &mut quirks)
+				.map_err(section_err(SECTION_TIMING_POINTS, line.clone()))?;
+			reader.warn_all(quirks);
 			timing_points.push(timing_point);
 		} else {
 			// We stop once we encounter an EOL character
@@ -743,24 +727,14 @@ fn parse_timing_points_section(
 	Ok(timing_points)
 }
 
-#[derive(Debug, thiserror::Error)]
-pub enum ColorParseError {
-	#[error("Invalid RGB(A) values list")]
-	InvalidList(
-		#[from]
-		#[source]
-		InvalidListError<u8>,
-	),
-
-	#[error("Expected 3 or 4 numbers between 0 and 255")]
-	WrongNumberCount,
-
-	#[error("Unknown color field: {0:?}")]
-	UnknownColorField(String),
-}
-
-fn parse_color(line: &str) -> Result<Color, ColorParseError> {
-	let nums = parse_list_of(line)?;
+/// Parses a color, either on its own (`255,128,0`) or as a whole line of the `[Colours]` section (`Combo1 : 255,128,0`).
+///
+/// # Errors
+///
+/// This function will return an error if the color doesn't have 3 or 4 valid components.
+pub fn parse_color(line: &str) -> Result<Color, ColorParseError> {
+	let value = line.split_once(':').map_or(line, |(_, value)| value);
+	let nums = parse_list_of(value.trim())?;
 	if let [r, g, b] = nums[..] {
 		Ok(Color { r, g, b, a: None })
 	} else if let [r, g, b, a] = nums[..] {
@@ -809,23 +783,21 @@ fn parse_colors_section(
 	Ok(colors_section)
 }
 
-#[derive(Debug, thiserror::Error)]
-pub enum HitSampleParseError {
-	#[error("Expected at least 5 colon-separated arguments, got {0}")]
-	NotEnoughArguments(usize),
-
-	#[error(transparent)]
-	InvalidSampleBank(#[from] InvalidSampleBankError),
-
-	#[error("Invalid int")]
-	InvalidInt(
-		#[from]
-		#[source]
-		ParseIntError,
-	),
+/// Parses the hit sample of a hit object, like `1:0:0:0:` or `0:0:2:70:drum-hitclap.wav`.
+///
+/// # Errors
+///
+/// This function will return an error if the hit sample is not valid.
+pub fn parse_hit_sample(line: &str) -> Result<HitSample, HitSampleParseError> {
+	parse_hit_sample_with_quirks(line, &mut Vec::new())
 }
 
-fn parse_hit_sample(line: &str) -> Result<HitSample, HitSampleParseError> {
+/// Parses a hit sample, also recording the quirks accepted on it (see [`quirks`](super::quirks)).
+///
+/// # Errors
+///
+/// This function will return an error if the hit sample is not valid.
+pub fn parse_hit_sample_with_quirks(line: &str, quirks: &mut Vec<Quirk>) -> Result<HitSample, HitSampleParseError> {
 	let args = line.split(':').collect::<Vec<_>>();
 	if let [normal_set, addition_set, leftover @ ..] = &args[..] {
 		let normal_set = normal_set.parse()?;
@@ -834,13 +806,21 @@ fn parse_hit_sample(line: &str) -> Result<HitSample, HitSampleParseError> {
 		let mut index = 0;
 		let mut volume = 0;
 		let mut filename = None;
-		if let [idx, vol, filn] = leftover {
-			index = idx.parse()?;
-			volume = vol.parse()?;
+		match leftover {
+			[idx, vol, filn] => {
+				index = parse_lenient_int(idx, "sample index", quirks)?;
+				volume = parse_lenient_int(vol, "sample volume", quirks)?;
 
-			if !filn.is_empty() {
-				filename = Some((*filn).to_owned());
+				if !filn.is_empty() {
+					filename = Some((*filn).to_owned());
+				}
 			}
+			[idx, vol] => {
+				quirks.push(Quirk::MissingHitSampleFilename);
+				index = parse_lenient_int(idx, "sample index", quirks)?;
+				volume = parse_lenient_int(vol, "sample volume", quirks)?;
+			}
+			_ => {}
 		}
 
 		Ok(HitSample {
@@ -855,18 +835,6 @@ fn parse_hit_sample(line: &str) -> Result<HitSample, HitSampleParseError> {
 	}
 }
 
-#[derive(Debug, thiserror::Error)]
-pub enum CurvePointsParseError {
-	#[error("Not enough tokens")]
-	NotEnoughTokens,
-
-	#[error("Unknown curve type: {0:?}")]
-	UnknownCurveType(String),
-
-	#[error("Invalid slider point")]
-	InvalidSliderPoint,
-}
-
 fn parse_curve_points(line: &str) -> Result<(SliderCurveType, Vec<SliderPoint>), CurvePointsParseError> {
 	let mut curve_tokens = line.split('|');
 
@@ -902,75 +870,27 @@ fn parse_curve_points(line: &str) -> Result<(SliderCurveType, Vec<SliderPoint>),
 	Ok((first_curve_type, curve_points))
 }
 
-#[derive(Debug, thiserror::Error)]
-pub enum HitObjectParseError {
-	#[error("Unknown hit object type: {0:?}")]
-	UnknownHitObjectType(String),
-
-	#[error("Expected at least 7 comma-separated arguments for the hit object, got {0}")]
-	NotEnoughArguments(usize),
-
-	#[error("Expected at least 3 object parameters for slider, got {0}")]
-	WrongSliderParameterCount(usize),
-
-	#[error("Expected 1 object parameter for spinner, got {0}")]
-	WrongSpinnerParameterCount(usize),
-
-	#[error("Expected 1 object parameter for hold, got {0}")]
-	WrongHoldParameterCount(usize),
-
-	#[error("Invalid hitsound list")]
-	InvalidHitSoundList(
-		#[from]
-		#[source]
-		InvalidListError<HitSound>,
-	),
-
-	#[error("Invalid hitsample set list")]
-	InvalidHitSampleSetList(
-		#[from]
-		#[source]
-		InvalidListError<HitSampleSet>,
-	),
-
-	#[error("Couldn't parse curve points")]
-	CurvePointsParse(
-		#[from]
-		#[source]
-		CurvePointsParseError,
-	),
-
-	#[error("Couldn't parse hitsample")]
-	HitSampleParse(
-		#[from]
-		#[source]
-		HitSampleParseError,
-	),
-
-	#[error("Invalid hold")]
-	InvalidHold,
-
-	#[error("Invalid float")]
-	InvalidFloat(
-		#[from]
-		#[source]
-		ParseFloatError,
-	),
-
-	#[error("Invalid int")]
-	InvalidInt(
-		#[from]
-		#[source]
-		ParseIntError,
-	),
+/// Parses a single line of the `[HitObjects]` section.
+///
+/// # Errors
+///
+/// This function will return an error if the line is not a valid hit object.
+pub fn parse_hit_object(line: &str) -> Result<HitObject, HitObjectParseError> {
+	parse_hit_object_with_quirks(line, &mut Vec::new())
 }
 
-fn parse_hit_object(line: &str) -> Result<HitObject, HitObjectParseError> {
+/// Parses a single hit object line, also recording the quirks accepted on it (see [`quirks`](super::quirks)).
+///
+/// # Errors
+///
+/// This function will return an error if the line is not a valid hit object.
+#[allow(clippy::too_many_lines)]
+pub fn parse_hit_object_with_quirks(line: &str, quirks: &mut Vec<Quirk>) -> Result<HitObject, HitObjectParseError> {
 	let args = line.split(',').collect::<Vec<_>>();
 	if let [x, y, time, object_type, hit_sound, object_params @ ..] = &args[..] {
-		let x = x.parse()?;
-		let y = y.parse()?;
-		let time = time.parse()?;
+		let x = parse_float(x)?;
+		let y = parse_float(y)?;
+		let time = parse_float(time)?;
 		let object_type = object_type.parse()?;
 		let hit_sound = hit_sound.parse()?;
 
@@ -988,20 +908,28 @@ fn parse_hit_object(line: &str) -> Result<HitObject, HitObjectParseError> {
 					let (first_curve_type, curve_points) = parse_curve_points(curve_points)?;
 
 					let slides = slides.parse()?;
-					let length = length.parse()?;
+					let length = parse_float(length)?;
 
 					let mut edge_hitsounds = Vec::new();
 					let mut edge_samplesets = Vec::new();
-					if let [ehitsounds, esamplesets, hit_sample] = leftover {
-						edge_hitsounds = parse_list_of_with_sep::<HitSound>(ehitsounds, '|')?;
-						edge_samplesets = parse_list_of_with_sep::<HitSampleSet>(esamplesets, '|')?;
-
-						hit_sample_leftover = Some(*hit_sample);
+					match leftover {
+						[ehitsounds, esamplesets, hit_sample] => {
+							edge_hitsounds = parse_list_of_with_sep::<HitSound>(ehitsounds, '|')?;
+							edge_samplesets = parse_list_of_with_sep::<HitSampleSet>(esamplesets, '|')?;
+
+							hit_sample_leftover = Some(*hit_sample);
+						}
+						[ehitsounds, esamplesets] => {
+							quirks.push(Quirk::MissingSliderHitSample);
+							edge_hitsounds = parse_list_of_with_sep::<HitSound>(ehitsounds, '|')?;
+							edge_samplesets = parse_list_of_with_sep::<HitSampleSet>(esamplesets, '|')?;
+						}
+						_ => {}
 					}
 
-					// Just in case there were no edge hitsounds/samplesets
+					// without edge hitsounds, osu! plays the hitsound of the slider on every edge
 					if edge_hitsounds.is_empty() {
-						edge_hitsounds = vec![HitSound::NONE; slides as usize + 1];
+						edge_hitsounds = vec![hit_sound; slides as usize + 1];
 						edge_samplesets = vec![HitSampleSet::default(); slides as usize + 1];
 					}
 
@@ -1018,7 +946,7 @@ fn parse_hit_object(line: &str) -> Result<HitObject, HitObjectParseError> {
 				}
 			} else if HitObject::raw_is_spinner(object_type) {
 				if let [end_time, leftover @ ..] = object_params {
-					let end_time = end_time.parse()?;
+					let end_time = parse_float(end_time)?;
 
 					if let [hit_sample] = leftover {
 						hit_sample_leftover = Some(*hit_sample);
@@ -1032,12 +960,12 @@ fn parse_hit_object(line: &str) -> Result<HitObject, HitObjectParseError> {
 				if let [leftover] = object_params {
 					let (end_time, hit_sample) = leftover.split_once(':').ok_or(HitObjectParseError::InvalidHold)?;
 
-					let end_time = end_time.parse()?;
+					let end_time = parse_float(end_time)?;
 
 					if !hit_sample.is_empty() {
 						hit_sample_leftover = Some(hit_sample);
 					}
-					HitObjectParams::Hold { end_time }
+					HitObjectParams::Hold { end_time, tail: None }
 				} else {
 					return Err(HitObjectParseError::WrongHoldParameterCount(object_params.len()));
 				}
@@ -1048,7 +976,7 @@ fn parse_hit_object(line: &str) -> Result<HitObject, HitObjectParseError> {
 
 		let hit_sample = match hit_sample_leftover {
 			Some("") => HitSample::default(),
-			Some(hit_sample_leftover) => parse_hit_sample(hit_sample_leftover)?,
+			Some(hit_sample_leftover) => parse_hit_sample_with_quirks(hit_sample_leftover, quirks)?,
 			_ => HitSample::default(),
 		};
 
@@ -1077,8 +1005,9 @@ fn parse_hit_object(line: &str) -> Result<HitObject, HitObjectParseError> {
 }
 
 fn parse_hit_objects_section(
-	reader: &mut impl Iterator<Item = Result<String, io::Error>>,
+	reader: &mut OsuLines<impl BufRead>,
 	section_header: &mut Option<String>,
+	format_quirks: FormatQuirks,
 ) -> Result<Vec<HitObject>, SectionParseError> {
 	let mut hit_objects: Vec<HitObject> = Vec::new();
 
@@ -1092,7 +1021,13 @@ fn parse_hit_objects_section(
 				break;
 			}
 
-			let hit_object = parse_hit_object(&line).map_err(section_err(SECTION_HIT_OBJECTS, line.clone()))?;
+			let mut quirks = Vec::new();
+			let mut hit_object = parse_hit_object_with_quirks(&line, &mut quirks)
+				.map_err(section_err(SECTION_HIT_OBJECTS, line.clone()))?;
+			if format_quirks.integer_times {
+				round_hit_object_times(&mut hit_object, &mut quirks);
+			}
+			reader.warn_all(quirks);
 			hit_objects.push(hit_object);
 		} else {
 			// We stop once we encounter an EOL character
@@ -1104,83 +1039,339 @@ fn parse_hit_objects_section(
 	Ok(hit_objects)
 }
 
-#[derive(Debug, thiserror::Error)]
-#[error("Could not parse osu! beatmap file {filename:?}")]
-pub struct BeatmapFileParseError {
-	pub filename: OsString,
-	#[source]
-	pub kind: BeatmapFileParseErrorKind,
+/// Options changing how a `.osu` file is parsed.
+#[derive(Clone, Debug, Default)]
+pub struct ParseOptions {
+	/// Keep comments and blank lines in [`BeatmapFile::trivia`], to write them back (see [`trivia`](super::trivia)).
+	pub preserve_trivia: bool,
+	/// Non-standard sections to keep in [`BeatmapFile::custom_sections`].
+	pub custom_sections: SectionRegistry,
 }
 
-#[derive(Debug, thiserror::Error)]
-pub enum BeatmapFileParseErrorKind {
-	#[error("File is empty")]
-	FileIsEmpty,
+/// Non-standard sections to keep when parsing, like `[Mania]` or the sections some editors write.
+///
+/// Their lines are kept as they are in [`BeatmapFile::custom_sections`]. They're written back sorted by name,
+/// wherever they were in the file. They go just before `[HitObjects]`, which some tools expect to be the last section.
+/// Unknown sections that aren't registered are skipped.
+#[derive(Clone, Debug, Default)]
+pub struct SectionRegistry {
+	names: HashSet<String>,
+	all: bool,
+}
 
-	#[error("The file name ends with '..'")]
-	InvalidFileName,
+impl SectionRegistry {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
 
-	#[error("First line doesn't match \"osu file format v<version>\"")]
-	InvalidOsuFileFormat,
+	/// Keeps every non-standard section.
+	#[must_use]
+	pub fn all() -> Self {
+		Self {
+			names: HashSet::new(),
+			all: true,
+		}
+	}
 
-	#[error(transparent)]
-	SectionParse(#[from] SectionParseError),
+	/// Keeps the section with this name, without brackets (like `Mania`).
+	#[must_use]
+	pub fn register(mut self, name: impl Into<String>) -> Self {
+		self.names.insert(name.into());
+		self
+	}
 
-	#[error(transparent)]
-	Io(#[from] io::Error),
+	/// Whether the section with this name is kept.
+	#[must_use]
+	pub fn handles(&self, name: &str) -> bool {
+		self.all || self.names.contains(name)
+	}
+}
+
+/// Reads the lines of a non-standard section as they are, until the next section.
+fn parse_custom_section(
+	reader: &mut impl Iterator<Item = Result<String, io::Error>>,
+	section_header: &mut Option<String>,
+) -> io::Result<Vec<String>> {
+	let mut lines = Vec::new();
+	for line in reader.by_ref() {
+		let line = line?;
+
+		// We stop once we encounter a new section
+		if line.starts_with('[') && line.ends_with(']') {
+			*section_header = Some(line);
+			return Ok(lines);
+		}
+
+		lines.push(line);
+	}
+
+	// We stop once we encounter an EOL character
+	*section_header = None;
+	Ok(lines)
+}
+
+/// Lines of a `.osu` file, skipping comments and empty lines while keeping track of the current line number.
+pub(crate) struct OsuLines<B> {
+	lines: io::Lines<B>,
+	pub(crate) line_number: usize,
+	/// Quirks found so far, see [`quirks`](super::quirks).
+	pub(crate) warnings: Vec<ParseWarning>,
+	/// Comments and empty lines skipped so far, if they are preserved (see [`trivia`](super::trivia)).
+	pub(crate) trivia: Option<Vec<Trivia>>,
+	/// Skipped lines that haven't been attached to the line after them yet.
+	pending_trivia: Vec<String>,
+	/// Header of the section of the current line.
+	section: String,
+}
+
+impl<B: BufRead> OsuLines<B> {
+	pub(crate) fn new(reader: B) -> Self {
+		Self {
+			lines: reader.lines(),
+			line_number: 0,
+			warnings: Vec::new(),
+			trivia: None,
+			pending_trivia: Vec::new(),
+			section: String::new(),
+		}
+	}
+
+	/// Keeps the comments and empty lines, to be taken from [`trivia`](Self::trivia) once every line is read.
+	pub(crate) fn preserving_trivia(mut self) -> Self {
+		self.trivia = Some(Vec::new());
+		self
+	}
+
+	/// Attaches the skipped lines to the line after them, or to the end of the section if there is none.
+	fn attach_trivia(&mut self, next_line: Option<&str>) {
+		let Some(trivia) = &mut self.trivia else {
+			return;
+		};
+
+		let is_section_end = next_line.is_none_or(|line| line.starts_with('[') && line.ends_with(']'));
+		if is_section_end {
+			// sections are separated by an empty line, and files can end with some
+			let keep = if next_line.is_none() {
+				self.pending_trivia
+					.iter()
+					.rposition(|line| !line.trim().is_empty())
+					.map_or(0, |i| i + 1)
+			} else {
+				let last_is_empty = self.pending_trivia.last().is_some_and(|line| line.trim().is_empty());
+				self.pending_trivia.len() - usize::from(last_is_empty)
+			};
+			self.pending_trivia.truncate(keep);
+		}
+
+		if !self.pending_trivia.is_empty() {
+			let anchor = match next_line {
+				Some(line) if !is_section_end => anchor_of(&self.section, line),
+				_ => TriviaAnchor::End,
+			};
+			trivia.push(Trivia {
+				section: self.section.clone(),
+				anchor,
+				lines: std::mem::take(&mut self.pending_trivia),
+			});
+		}
+
+		if let Some(line) = next_line.filter(|_| is_section_end) {
+			line.clone_into(&mut self.section);
+		}
+	}
+
+	/// Records quirks found on the current line.
+	fn warn_all(&mut self, quirks: Vec<Quirk>) {
+		let line_number = self.line_number;
+		(self.warnings).extend(quirks.into_iter().map(|quirk| ParseWarning { line_number, quirk }));
+	}
 }
 
-fn beatmap_section_err(filename: &OsStr) -> impl FnOnce(SectionParseError) -> BeatmapFileParseError {
+impl<B: BufRead> Iterator for OsuLines<B> {
+	type Item = Result<String, io::Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			let Some(line) = self.lines.next() else {
+				self.attach_trivia(None);
+				return None;
+			};
+			self.line_number += 1;
+
+			let is_trivia = line.as_ref().is_ok_and(|line| {
+				let l = line.trim();
+				// Ignore comments and empty lines
+				l.is_empty() || l.starts_with("//")
+			});
+
+			match line {
+				Ok(line) if is_trivia => {
+					if self.trivia.is_some() {
+						self.pending_trivia.push(line);
+					}
+				}
+				Ok(line) => {
+					self.attach_trivia(Some(&line));
+					return Some(Ok(line));
+				}
+				Err(e) => return Some(Err(e)),
+			}
+		}
+	}
+}
+
+pub(crate) fn beatmap_section_err(
+	filename: &OsStr,
+	line_number: usize,
+) -> impl FnOnce(SectionParseError) -> BeatmapFileParseError {
 	let filename = filename.to_os_string();
 
 	move |e| BeatmapFileParseError {
 		filename,
+		line_number: Some(line_number),
 		kind: BeatmapFileParseErrorKind::SectionParse(e),
 	}
 }
 
+/// Opens an osu! beatmap file, along with its file name for error reporting.
+#[cfg(feature = "fs")]
+fn open_osu_file(path: &Path) -> Result<(BufReader<File>, &OsStr), BeatmapFileParseError> {
+	let filename = path.file_name().ok_or_else(|| BeatmapFileParseError {
+		filename: OsString::from("???"),
+		line_number: None,
+		kind: BeatmapFileParseErrorKind::InvalidFileName,
+	})?;
+
+	let file = File::open(path).map_err(|e| BeatmapFileParseError {
+		filename: filename.to_os_string(),
+		line_number: None,
+		kind: BeatmapFileParseErrorKind::Io(e),
+	})?;
+
+	Ok((BufReader::new(file), filename))
+}
+
 /// Parses an osu! beatmap file.
 ///
-/// # Panics
+/// # Errors
 ///
-/// Panics if the provided file path is not valid, meaning it terminates in `..` or if the path is root (`/`).
-/// (though it probably shouldn't...)
+/// This function will return an error if the file doesn't exist or could not be parsed correctly.
+#[cfg(feature = "fs")]
+pub fn parse_osu_file<P>(path: P) -> Result<BeatmapFile, BeatmapFileParseError>
+where
+	P: AsRef<Path>,
+{
+	parse_osu_file_with_options(path, &ParseOptions::default())
+}
+
+/// Parses an osu! beatmap file, also returning the quirks found in it (see [`quirks`](super::quirks)).
 ///
 /// # Errors
 ///
 /// This function will return an error if the file doesn't exist or could not be parsed correctly.
-pub fn parse_osu_file<P>(path: P) -> Result<BeatmapFile, BeatmapFileParseError>
+#[cfg(feature = "fs")]
+pub fn parse_osu_file_with_warnings<P>(path: P) -> Result<(BeatmapFile, Vec<ParseWarning>), BeatmapFileParseError>
 where
 	P: AsRef<Path>,
 {
-	let mut beatmap = BeatmapFile::default();
+	let (reader, filename) = open_osu_file(path.as_ref())?;
+	parse_osu_lines_with_warnings(reader, filename, &ParseOptions::default())
+}
 
-	let filename = path.as_ref().file_name().ok_or_else(|| BeatmapFileParseError {
-		filename: OsString::from_str("???").unwrap(),
-		kind: BeatmapFileParseErrorKind::InvalidFileName,
-	})?;
+/// Parses an osu! beatmap from a buffered reader.
+///
+/// # Errors
+///
+/// This function will return an error if the reader fails or if the beatmap could not be parsed correctly.
+pub fn parse_osu_reader<B: BufRead>(reader: B) -> Result<BeatmapFile, BeatmapFileParseError> {
+	parse_osu_lines(reader, OsStr::new(IN_MEMORY_FILENAME), &ParseOptions::default())
+}
 
-	let file = File::open(&path).map_err(|e| BeatmapFileParseError {
-		filename: filename.to_os_string(),
-		kind: BeatmapFileParseErrorKind::Io(e),
-	})?;
+/// Parses an osu! beatmap from the contents of a `.osu` file.
+///
+/// # Errors
+///
+/// This function will return an error if the beatmap could not be parsed correctly.
+pub fn parse_osu_str(contents: &str) -> Result<BeatmapFile, BeatmapFileParseError> {
+	parse_osu_reader(contents.as_bytes())
+}
 
-	let mut reader = BufReader::new(file).lines().filter(|line| {
-		line.as_ref().map_or(true, |line| {
-			let l = line.trim();
-			// Ignore comments and empty lines
-			!l.is_empty() && !l.starts_with("//")
-		})
-	});
+/// Parses an osu! beatmap from the contents of a `.osu` file, also returning the quirks found in it
+/// (see [`quirks`](super::quirks)).
+///
+/// # Errors
+///
+/// This function will return an error if the beatmap could not be parsed correctly.
+pub fn parse_osu_str_with_warnings(contents: &str) -> Result<(BeatmapFile, Vec<ParseWarning>), BeatmapFileParseError> {
+	parse_osu_lines_with_warnings(
+		contents.as_bytes(),
+		OsStr::new(IN_MEMORY_FILENAME),
+		&ParseOptions::default(),
+	)
+}
+
+/// Parses an osu! beatmap file with extra options.
+///
+/// # Errors
+///
+/// This function will return an error if the file doesn't exist or could not be parsed correctly.
+#[cfg(feature = "fs")]
+pub fn parse_osu_file_with_options<P>(path: P, options: &ParseOptions) -> Result<BeatmapFile, BeatmapFileParseError>
+where
+	P: AsRef<Path>,
+{
+	let (reader, filename) = open_osu_file(path.as_ref())?;
+	parse_osu_lines(reader, filename, options)
+}
+
+/// Parses an osu! beatmap from the contents of a `.osu` file with extra options.
+///
+/// # Errors
+///
+/// This function will return an error if the beatmap could not be parsed correctly.
+pub fn parse_osu_str_with_options(
+	contents: &str,
+	options: &ParseOptions,
+) -> Result<BeatmapFile, BeatmapFileParseError> {
+	parse_osu_lines(contents.as_bytes(), OsStr::new(IN_MEMORY_FILENAME), options)
+}
+
+fn parse_osu_lines<B: BufRead>(
+	reader: B,
+	filename: &OsStr,
+	options: &ParseOptions,
+) -> Result<BeatmapFile, BeatmapFileParseError> {
+	let (beatmap, warnings) = parse_osu_lines_with_warnings(reader, filename, options)?;
+	for warning in warnings {
+		tracing::warn!("{}: {warning}", filename.to_string_lossy());
+	}
+
+	Ok(beatmap)
+}
+
+fn parse_osu_lines_with_warnings<B: BufRead>(
+	reader: B,
+	filename: &OsStr,
+	options: &ParseOptions,
+) -> Result<(BeatmapFile, Vec<ParseWarning>), BeatmapFileParseError> {
+	let mut beatmap = BeatmapFile::default();
+
+	let mut reader = OsuLines::new(reader);
+	if options.preserve_trivia {
+		reader = reader.preserving_trivia();
+	}
 
 	let fformat_string = reader
 		.next()
 		.ok_or_else(|| BeatmapFileParseError {
 			filename: filename.to_os_string(),
+			line_number: None,
 			kind: BeatmapFileParseErrorKind::FileIsEmpty,
 		})?
 		.map_err(|e| BeatmapFileParseError {
 			filename: filename.to_os_string(),
+			line_number: Some(reader.line_number),
 			kind: BeatmapFileParseErrorKind::Io(e),
 		})?;
 
@@ -1192,18 +1383,23 @@ where
 		.strip_prefix("osu file format v")
 		.ok_or_else(|| BeatmapFileParseError {
 			filename: filename.to_os_string(),
+			line_number: Some(reader.line_number),
 			kind: BeatmapFileParseErrorKind::InvalidOsuFileFormat,
 		})?;
 
 	beatmap.osu_file_format = format_version.parse().map_err(|_| BeatmapFileParseError {
 		filename: filename.to_os_string(),
+		line_number: Some(reader.line_number),
 		kind: BeatmapFileParseErrorKind::InvalidOsuFileFormat,
 	})?;
 
+	let format_quirks = FormatQuirks::for_version(beatmap.osu_file_format);
+
 	// Read file lazily section by section
 	if let Some(line) = reader.next() {
 		let line = line.map_err(|e| BeatmapFileParseError {
 			filename: filename.to_os_string(),
+			line_number: Some(reader.line_number),
 			kind: BeatmapFileParseErrorKind::Io(e),
 		})?;
 
@@ -1211,51 +1407,211 @@ where
 		while let Some(section_str) = &section_header {
 			match section_str.as_str() {
 				SECTION_GENERAL => {
-					beatmap.general = Some(
-						parse_general_section(&mut reader, &mut section_header)
-							.map_err(beatmap_section_err(filename))?,
-					);
+					let section = parse_general_section(&mut reader, &mut section_header);
+					beatmap.general = Some(section.map_err(beatmap_section_err(filename, reader.line_number))?);
 				}
 				SECTION_EDITOR => {
-					beatmap.editor = Some(
-						parse_editor_section(&mut reader, &mut section_header)
-							.map_err(beatmap_section_err(filename))?,
-					);
+					let section = parse_editor_section(&mut reader, &mut section_header);
+					beatmap.editor =
+						Some(section.map_err(|(line_number, e)| beatmap_section_err(filename, line_number)(e))?);
 				}
 				SECTION_METADATA => {
-					beatmap.metadata = Some(
-						parse_metadata_section(&mut reader, &mut section_header)
-							.map_err(beatmap_section_err(filename))?,
-					);
+					let section = parse_metadata_section(&mut reader, &mut section_header);
+					beatmap.metadata = Some(section.map_err(beatmap_section_err(filename, reader.line_number))?);
 				}
 				SECTION_DIFFICULTY => {
-					beatmap.difficulty = Some(
-						parse_difficulty_section(&mut reader, &mut section_header)
-							.map_err(beatmap_section_err(filename))?,
-					);
+					let section = parse_difficulty_section(&mut reader, &mut section_header);
+					beatmap.difficulty = Some(section.map_err(beatmap_section_err(filename, reader.line_number))?);
 				}
 				SECTION_EVENTS => {
-					beatmap.events = parse_events_section(&mut reader, &mut section_header)
-						.map_err(beatmap_section_err(filename))?;
+					let section = parse_events_section(&mut reader, &mut section_header);
+					beatmap.events = section.map_err(beatmap_section_err(filename, reader.line_number))?;
 				}
 				SECTION_TIMING_POINTS => {
-					beatmap.timing_points = parse_timing_points_section(&mut reader, &mut section_header)
-						.map_err(beatmap_section_err(filename))?;
+					let section = parse_timing_points_section(&mut reader, &mut section_header);
+					beatmap.timing_points = section.map_err(beatmap_section_err(filename, reader.line_number))?;
 				}
 				SECTION_COLOURS => {
-					beatmap.colors = Some(
-						parse_colors_section(&mut reader, &mut section_header)
-							.map_err(beatmap_section_err(filename))?,
-					);
+					let section = parse_colors_section(&mut reader, &mut section_header);
+					beatmap.colors = Some(section.map_err(beatmap_section_err(filename, reader.line_number))?);
 				}
 				SECTION_HIT_OBJECTS => {
-					beatmap.hit_objects = parse_hit_objects_section(&mut reader, &mut section_header)
-						.map_err(beatmap_section_err(filename))?;
+					let section = parse_hit_objects_section(&mut reader, &mut section_header, format_quirks);
+					beatmap.hit_objects = section.map_err(beatmap_section_err(filename, reader.line_number))?;
+				}
+				header if header.starts_with('[') && header.ends_with(']') => {
+					let name = header[1..header.len() - 1].to_owned();
+					let lines =
+						parse_custom_section(&mut reader, &mut section_header).map_err(|e| BeatmapFileParseError {
+							filename: filename.to_os_string(),
+							line_number: Some(reader.line_number),
+							kind: BeatmapFileParseErrorKind::Io(e),
+						})?;
+
+					if options.custom_sections.handles(&name) {
+						beatmap.custom_sections.insert(name, lines);
+					} else {
+						tracing::warn!("{}: skipping unknown section [{name}]", filename.to_string_lossy());
+					}
 				}
 				_ => section_header = None,
+			}
+		}
+	}
+
+	if format_quirks.time_offset != 0.0 {
+		for_each_timestamp_mut(&mut beatmap, |time| *time += format_quirks.time_offset);
+	}
+
+	beatmap.trivia = reader.trivia.take().unwrap_or_default();
+
+	Ok((beatmap, reader.warnings))
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn floats_in_scientific_notation() {
+		assert_eq!(parse_float::<f64>("1E-05").unwrap(), 0.000_01);
+		assert_eq!(parse_float::<f64>("-2.5e3").unwrap(), -2500.0);
+		assert_eq!(parse_float::<f32>(" 1e2 ").unwrap(), 100.0);
+	}
+
+	#[test]
+	fn missing_editor_fields_are_reported_where_the_section_ends() {
+		let contents = "osu file format v14\n\n[Editor]\nDistanceSpacing: 1.2\nGridSize: 4\n\n[Metadata]\nTitle:a\n";
+		let err = parse_osu_str(contents).unwrap_err();
+		assert_eq!(err.line_number, Some(5));
+		assert_eq!(err.offending_text(), Some("GridSize: 4"));
+		let BeatmapFileParseErrorKind::SectionParse(e) = &err.kind else {
+			panic!("{err:?}");
+		};
+		assert_eq!(e.section, SECTION_EDITOR);
+		assert!(matches!(
+			e.kind,
+			SectionParseErrorKind::UnspecifiedField(UnspecifiedFieldError("BeatDivisor"))
+		));
+
+		let err = parse_osu_str("osu file format v14\n\n[Editor]\nGridSize: x\n").unwrap_err();
+		assert_eq!(err.line_number, Some(4));
+	}
+
+	#[test]
+	fn negative_zero_is_a_float() {
+		let zero: f64 = parse_float("-0").unwrap();
+		assert_eq!(zero, 0.0);
+		assert!(zero.is_sign_negative());
+	}
+
+	#[test]
+	fn non_finite_floats_are_rejected() {
+		for value in ["NaN", "inf", "-inf", "infinity"] {
+			assert!(
+				matches!(parse_float::<f64>(value), Err(FloatParseError::NotFinite)),
+				"{value}"
+			);
+		}
+		// finite as a double, but too big for a float
+		assert!(matches!(parse_float::<f32>("1e39"), Err(FloatParseError::NotFinite)));
+		assert!(matches!(parse_float::<f64>("1,5"), Err(FloatParseError::Invalid(_))));
+	}
+
+	#[test]
+	fn nan_beat_lengths_are_quirks() {
+		let mut quirks = Vec::new();
+		let timing_point = parse_timing_point_with_quirks("1000,NaN,4,1,0,100,0,0", &mut quirks).unwrap();
+		assert_eq!(timing_point.beat_length, -100.0);
+		assert_eq!(quirks, [Quirk::NanBeatLength { uninherited: false }]);
+
+		let mut quirks = Vec::new();
+		let timing_point = parse_timing_point_with_quirks("1000,NaN,4,1,0,100,1,0", &mut quirks).unwrap();
+		assert!(timing_point.beat_length.is_nan());
+		assert_eq!(quirks, [Quirk::NanBeatLength { uninherited: true }]);
+
+		assert!(parse_timing_point("1000,-inf,4,1,0,100,0,0").is_err());
+	}
+
+	#[test]
+	fn color_skips_around_spinners() {
+		let hit_objects: Vec<HitObject> = [
+			"256,192,2000,28,0,3000,0:0:0:0:",
+			"100,100,4000,37,0,0:0:0:0:",
+			"256,192,5000,24,0,6000,0:0:0:0:",
+			"100,100,7000,1,0,0:0:0:0:",
+		]
+		.into_iter()
+		.map(|line| parse_hit_object(line).unwrap())
+		.collect();
+
+		let skips: Vec<Option<u8>> = hit_objects.iter().map(|ho| ho.combo_color_skip).collect();
+		// color skip bits without the new combo bit are ignored
+		assert_eq!(skips, [Some(1), Some(2), None, None]);
+
+		let raw_types: Vec<u8> = hit_objects.iter().map(HitObject::raw_object_type).collect();
+		assert_eq!(raw_types, [28, 37, 8, 1]);
+		assert!(hit_objects[1].to_string().starts_with("100,100,4000,37,"));
+	}
+
+	#[test]
+	fn scientific_beat_lengths_are_written_as_decimals() {
+		let timing_point = parse_timing_point("1000,-1E+02,4,1,0,100,0,0").unwrap();
+		assert_eq!(timing_point.beat_length, -100.0);
+		assert_eq!(timing_point.raw_beat_length.unwrap().text(), "-100");
+	}
+
+	#[test]
+	fn malformed_breaks_are_reported_as_breaks() {
+		for line in ["2,1000", "2,1000,abc", "Break,1000,"] {
+			let err = parse_event(line).unwrap_err();
+			let EventParseError::SpecificEvent(e) = &err else {
+				panic!("{line}: {err:?}");
 			};
+			assert_eq!(e.event, "Break", "{line}");
 		}
+
+		assert!(matches!(
+			parse_event("2,1000").unwrap_err(),
+			EventParseError::SpecificEvent(SpecificEventParseError {
+				kind: SpecificEventParseErrorKind::NoEndTime,
+				..
+			})
+		));
 	}
 
-	Ok(beatmap)
+	fn event_filename(line: &str) -> String {
+		match parse_event(line).unwrap().unwrap().params {
+			EventParams::Background { filename, .. }
+			| EventParams::Video { filename, .. }
+			| EventParams::Sample { filename, .. } => filename,
+			EventParams::Break { .. } => unreachable!(),
+		}
+	}
+
+	#[test]
+	fn quoted_filenames_keep_their_commas_and_spaces() {
+		assert_eq!(event_filename("0,0,\"my bg, final.jpg\",0,0"), "my bg, final.jpg");
+		assert_eq!(event_filename("Video,500,\"intro video.mp4\""), "intro video.mp4");
+		assert_eq!(event_filename("5,1000,0,\"hit, sound.wav\",70"), "hit, sound.wav");
+
+		let event = parse_event("0,0,\"a, b.jpg\",10,-5").unwrap().unwrap();
+		assert!(matches!(
+			event.params,
+			EventParams::Background {
+				x_offset: 10,
+				y_offset: -5,
+				..
+			}
+		));
+	}
+
+	#[test]
+	fn unquoted_filenames() {
+		assert_eq!(event_filename("0,0,bg.jpg,0,0"), "bg.jpg");
+		assert_eq!(event_filename("0,0,bg.jpg"), "bg.jpg");
+		assert_eq!(event_filename("1,0,sb/video.avi,0,0"), "sb/video.avi");
+		assert_eq!(event_filename("5,1000,0,hit.wav"), "hit.wav");
+	}
 }
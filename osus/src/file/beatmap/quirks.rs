@@ -0,0 +1,193 @@
+//! Compatibility layer for the weird values found in real-world beatmaps.
+//!
+//! osu! is very lenient when reading `.osu` files, and plenty of ranked beatmaps rely on it,
+//! knowingly or not (old editor versions, third-party tools, hand-edited files...).
+//! Instead of failing, the parser accepts these values and records a [`ParseWarning`] for each of them.
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::{HitObject, HitObjectParams, Timestamp};
+
+/// Something odd the parser accepted anyway.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Quirk {
+	/// An integer field was written as a decimal number or in scientific notation, and got rounded.
+	NonIntegerValue { field: &'static str, value: String },
+	/// A timing point has a negative meter, which osu! accepts.
+	NegativeMeter(i32),
+	/// A timing point has a beat length that is not a number.
+	/// Inherited timing points fall back to a slider velocity of 1, like in osu!.
+	NanBeatLength { uninherited: bool },
+	/// A hit sample has its index and volume, but not the custom filename after them.
+	MissingHitSampleFilename,
+	/// A slider has edge hitsounds and samplesets, but no hit sample after them.
+	MissingSliderHitSample,
+}
+
+impl fmt::Display for Quirk {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::NonIntegerValue { field, value } => write!(f, "{field} {value:?} is not an integer, rounding it"),
+			Self::NegativeMeter(meter) => write!(f, "negative meter {meter}"),
+			Self::NanBeatLength { uninherited: true } => write!(f, "uninherited timing point has a NaN beat length"),
+			Self::NanBeatLength { uninherited: false } => {
+				write!(
+					f,
+					"inherited timing point has a NaN beat length, using a slider velocity of 1"
+				)
+			}
+			Self::MissingHitSampleFilename => write!(f, "hit sample is missing its filename"),
+			Self::MissingSliderHitSample => write!(f, "slider is missing its hit sample"),
+		}
+	}
+}
+
+/// Latest `osu file format` version whose times osu! offsets (see [`FormatQuirks::time_offset`]).
+const LAST_OFFSET_VERSION: u32 = 4;
+
+/// Latest `osu file format` version whose hit object times osu! reads as whole milliseconds
+/// (see [`FormatQuirks::integer_times`]).
+const LAST_INTEGER_TIMES_VERSION: u32 = 9;
+
+/// Differences in how osu! reads old versions of the file format.
+///
+/// The parser applies them so that a [`BeatmapFile`](super::BeatmapFile) always describes what happens in game,
+/// and the writer reverts them so that the file still says the same thing to osu! when written back.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FormatQuirks {
+	/// Milliseconds osu! adds to every time of the file.
+	/// Beatmaps before v5 were timed for the audio latency of old osu! versions, so they are played 24ms later.
+	pub time_offset: f64,
+	/// Whether osu! reads the times of hit objects as whole milliseconds.
+	/// Beatmaps before v10 were only ever written with integer times, and osu! rounds anything else.
+	/// Newer versions keep fractional times as they are.
+	pub integer_times: bool,
+}
+
+impl FormatQuirks {
+	/// Quirks of a version of the file format (the `N` in `osu file format vN`).
+	#[must_use]
+	pub const fn for_version(version: u32) -> Self {
+		Self {
+			time_offset: if version <= LAST_OFFSET_VERSION { 24.0 } else { 0.0 },
+			integer_times: version <= LAST_INTEGER_TIMES_VERSION,
+		}
+	}
+
+	/// Whether the version is read exactly as it is written.
+	#[must_use]
+	pub fn is_none(&self) -> bool {
+		self.time_offset == 0.0 && !self.integer_times
+	}
+}
+
+/// A quirk found while parsing a beatmap, with the line it was found on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseWarning {
+	/// Line number (starting from 1) of the quirk.
+	pub line_number: usize,
+	pub quirk: Quirk,
+}
+
+impl fmt::Display for ParseWarning {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "line {}: {}", self.line_number, self.quirk)
+	}
+}
+
+/// Parses an integer, also accepting decimal numbers and scientific notation (rounding them) with a quirk.
+pub(crate) fn parse_lenient_int<T>(value: &str, field: &'static str, quirks: &mut Vec<Quirk>) -> Result<T, T::Err>
+where
+	T: FromStr + TryFrom<i64>,
+{
+	let err = match value.parse() {
+		Ok(value) => return Ok(value),
+		Err(e) => e,
+	};
+
+	let rounded = (value.trim().parse::<f64>().ok())
+		.filter(|value| value.is_finite())
+		.map(f64::round);
+
+	#[allow(clippy::cast_possible_truncation)]
+	let Some(parsed) = rounded.and_then(|rounded| T::try_from(rounded as i64).ok()) else {
+		return Err(err);
+	};
+
+	quirks.push(Quirk::NonIntegerValue {
+		field,
+		value: value.to_owned(),
+	});
+	Ok(parsed)
+}
+
+/// Rounds the times of a hit object to whole milliseconds like osu! does for old versions,
+/// with a quirk for each fractional one.
+pub(crate) fn round_hit_object_times(hit_object: &mut HitObject, quirks: &mut Vec<Quirk>) {
+	let mut round = |time: &mut Timestamp, field: &'static str| {
+		if time.fract() != 0.0 {
+			quirks.push(Quirk::NonIntegerValue {
+				field,
+				value: time.to_string(),
+			});
+			*time = time.round();
+		}
+	};
+
+	round(&mut hit_object.time, "time");
+	if let HitObjectParams::Spinner { end_time } | HitObjectParams::Hold { end_time, .. } =
+		&mut hit_object.object_params
+	{
+		round(end_time, "end time");
+	}
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+	use super::*;
+	use crate::file::beatmap::parsing::parse_osu_str_with_warnings;
+
+	#[test]
+	fn old_versions_are_read_like_osu() {
+		let contents = "osu file format v3\n\n[General]\nMode: 0\n\n[HitObjects]\n256,192,1000.4,1,0\n\
+			256,192,2000,2,2,B|300:192,2,40\n";
+		let (beatmap, warnings) = parse_osu_str_with_warnings(contents).unwrap();
+
+		assert_eq!(beatmap.hit_objects[0].time, 1024.0);
+		assert_eq!(beatmap.hit_objects[1].time, 2024.0);
+		assert_eq!(warnings.len(), 1);
+
+		let HitObjectParams::Slider { edge_hitsounds, .. } = &beatmap.hit_objects[1].object_params else {
+			panic!("not a slider");
+		};
+		assert_eq!(edge_hitsounds, &[beatmap.hit_objects[1].hit_sound; 3]);
+	}
+
+	#[test]
+	fn stable_keeps_fractional_times() {
+		let line = "256,192,1000.4,1,0,0:0:0:0:";
+		let (beatmap, warnings) =
+			parse_osu_str_with_warnings(&format!("osu file format v14\n\n[HitObjects]\n{line}\n")).unwrap();
+
+		assert_eq!(beatmap.hit_objects[0].time, 1000.4);
+		assert!(warnings.is_empty());
+		assert!(FormatQuirks::for_version(14).is_none());
+
+		let mut contents = Vec::new();
+		beatmap.deserialize(&mut contents).unwrap();
+		let contents = String::from_utf8(contents).unwrap();
+		assert!(contents.lines().any(|l| l == line), "{contents}");
+	}
+
+	#[test]
+	fn lazer_keeps_fractional_times() {
+		let contents = "osu file format v128\n\n[HitObjects]\n256,192,1000.4,1,0,0:0:0:0:\n";
+		let (beatmap, warnings) = parse_osu_str_with_warnings(contents).unwrap();
+
+		assert_eq!(beatmap.hit_objects[0].time, 1000.4);
+		assert!(warnings.is_empty());
+		assert!(FormatQuirks::for_version(128).is_none());
+	}
+}
@@ -0,0 +1,206 @@
+//! Sorted collection of timing points.
+//!
+//! osu! reads timing points in order, and when several of them share a time, the last one wins for everything
+//! but the beat length. An uninherited timing point resets the slider velocity, so it has to come before
+//! the inherited timing points at the same time, otherwise it would cancel their slider velocity.
+//! [`TimingPoints`] keeps its timing points in this order, whatever the order they're added in.
+
+use std::cmp::Ordering;
+use std::ops::Deref;
+
+use super::{Timestamp, TimingPoint};
+
+/// Canonical order of timing points: by time, and uninherited ones before inherited ones at the same time.
+#[must_use]
+pub fn canonical_order(a: &TimingPoint, b: &TimingPoint) -> Ordering {
+	a.time.total_cmp(&b.time).then(b.uninherited.cmp(&a.uninherited))
+}
+
+/// Uninherited timing point whose beat length is in effect at a time, among sorted timing points.
+/// Times before the first uninherited timing point use the first one, like osu! does.
+#[must_use]
+pub fn uninherited_at(timing_points: &[TimingPoint], time: Timestamp) -> Option<&TimingPoint> {
+	let index = timing_points.partition_point(|tp| tp.time <= time);
+	(timing_points[..index].iter().rev())
+		.find(|tp| tp.uninherited)
+		.or_else(|| timing_points.iter().find(|tp| tp.uninherited))
+}
+
+/// Timing points kept in [canonical order](canonical_order).
+///
+/// It dereferences to a slice for reading, and every method that adds or changes timing points
+/// puts them back in their place.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TimingPoints(Vec<TimingPoint>);
+
+impl TimingPoints {
+	#[must_use]
+	pub const fn new() -> Self {
+		Self(Vec::new())
+	}
+
+	/// Sorts timing points in canonical order.
+	///
+	/// The sort is stable, so timing points of the same kind at the same time keep their order.
+	#[must_use]
+	pub fn from_vec(mut timing_points: Vec<TimingPoint>) -> Self {
+		timing_points.sort_by(canonical_order);
+		Self(timing_points)
+	}
+
+	#[must_use]
+	pub fn into_vec(self) -> Vec<TimingPoint> {
+		self.0
+	}
+
+	#[must_use]
+	pub fn as_slice(&self) -> &[TimingPoint] {
+		&self.0
+	}
+
+	/// Index at which a timing point would be inserted: after every timing point that comes before it
+	/// or at the same place in canonical order.
+	#[must_use]
+	pub fn insertion_index(&self, timing_point: &TimingPoint) -> usize {
+		self.0.partition_point(|tp| canonical_order(tp, timing_point).is_le())
+	}
+
+	/// Inserts a timing point at its place, and returns its index.
+	///
+	/// A timing point at the same time as others goes after the ones of the same kind,
+	/// so that it overrides them like it would in osu!.
+	pub fn insert(&mut self, timing_point: TimingPoint) -> usize {
+		let index = self.insertion_index(&timing_point);
+		self.0.insert(index, timing_point);
+		index
+	}
+
+	/// Removes the timing point at an index.
+	///
+	/// # Panics
+	///
+	/// Panics if the index is out of bounds.
+	pub fn remove(&mut self, index: usize) -> TimingPoint {
+		self.0.remove(index)
+	}
+
+	/// Keeps only the timing points for which `f` returns true.
+	pub fn retain(&mut self, f: impl FnMut(&TimingPoint) -> bool) {
+		self.0.retain(f);
+	}
+
+	/// Changes the timing point at an index, then moves it back to its place.
+	/// Returns its new index, or `None` if the index is out of bounds.
+	pub fn modify(&mut self, index: usize, f: impl FnOnce(&mut TimingPoint)) -> Option<usize> {
+		let mut timing_point = self.0.get(index)?.clone();
+		f(&mut timing_point);
+
+		let before = index.checked_sub(1).map(|i| &self.0[i]);
+		let after = self.0.get(index + 1);
+		let in_place = before.is_none_or(|tp| canonical_order(tp, &timing_point).is_le())
+			&& after.is_none_or(|tp| canonical_order(&timing_point, tp).is_lt());
+		if in_place {
+			self.0[index] = timing_point;
+			return Some(index);
+		}
+
+		self.0.remove(index);
+		Some(self.insert(timing_point))
+	}
+
+	/// Changes every timing point, then puts them back in canonical order.
+	///
+	/// The sort is stable, so timing points whose order didn't change relative to each other stay in place.
+	pub fn for_each_mut(&mut self, mut f: impl FnMut(&mut TimingPoint)) {
+		self.0.iter_mut().for_each(&mut f);
+		self.0.sort_by(canonical_order);
+	}
+
+	/// Removes every timing point.
+	pub fn clear(&mut self) {
+		self.0.clear();
+	}
+
+	/// Timing point whose settings are in effect at a time, which is the last one at or before it.
+	/// Times before the first timing point use the first one.
+	#[must_use]
+	pub fn active_at(&self, time: Timestamp) -> Option<&TimingPoint> {
+		let index = self.0.partition_point(|tp| tp.time <= time);
+		self.0.get(index.saturating_sub(1))
+	}
+
+	/// Uninherited timing point whose beat length is in effect at a time.
+	/// Times before the first uninherited timing point use the first one.
+	#[must_use]
+	pub fn uninherited_at(&self, time: Timestamp) -> Option<&TimingPoint> {
+		uninherited_at(&self.0, time)
+	}
+
+	/// Iterates over the timing points along with the time at which their section ends,
+	/// which is the time of the next timing point, or infinity for the last one.
+	///
+	/// Timing points overridden by a later one at the same time have an empty section.
+	pub fn iter_sections(&self) -> impl Iterator<Item = (&TimingPoint, Timestamp)> {
+		let ends = (self.0.iter().skip(1).map(|tp| tp.time)).chain(std::iter::once(f64::INFINITY));
+		self.0.iter().zip(ends)
+	}
+
+	/// Index of the last timing point at a time, which is the one whose settings osu! uses.
+	#[must_use]
+	pub fn last_index_at(&self, time: Timestamp) -> Option<usize> {
+		let index = self.0.partition_point(|tp| tp.time <= time);
+		(index > 0 && self.0[index - 1].time.total_cmp(&time).is_eq()).then(|| index - 1)
+	}
+}
+
+impl Deref for TimingPoints {
+	type Target = [TimingPoint];
+
+	fn deref(&self) -> &Self::Target {
+		&self.0
+	}
+}
+
+impl From<Vec<TimingPoint>> for TimingPoints {
+	fn from(timing_points: Vec<TimingPoint>) -> Self {
+		Self::from_vec(timing_points)
+	}
+}
+
+impl From<TimingPoints> for Vec<TimingPoint> {
+	fn from(timing_points: TimingPoints) -> Self {
+		timing_points.0
+	}
+}
+
+impl FromIterator<TimingPoint> for TimingPoints {
+	fn from_iter<I: IntoIterator<Item = TimingPoint>>(iter: I) -> Self {
+		Self::from_vec(iter.into_iter().collect())
+	}
+}
+
+impl Extend<TimingPoint> for TimingPoints {
+	/// Adds timing points, each one going after the ones of the same kind at the same time.
+	fn extend<I: IntoIterator<Item = TimingPoint>>(&mut self, iter: I) {
+		self.0.extend(iter);
+		self.0.sort_by(canonical_order);
+	}
+}
+
+impl<'a> IntoIterator for &'a TimingPoints {
+	type Item = &'a TimingPoint;
+	type IntoIter = std::slice::Iter<'a, TimingPoint>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.0.iter()
+	}
+}
+
+impl IntoIterator for TimingPoints {
+	type Item = TimingPoint;
+	type IntoIter = std::vec::IntoIter<TimingPoint>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.0.into_iter()
+	}
+}
@@ -0,0 +1,152 @@
+//! Comments and blank lines of a `.osu` file.
+//!
+//! osu! ignores them, and so does the parser by default. Mappers still use comments to annotate their files
+//! (`// sv section start`), so when parsing with [`ParseOptions::preserve_trivia`](super::parsing::ParseOptions),
+//! they are kept along with the element that follows them, and written back before it.
+//!
+//! Elements are found again by what they are rather than by their position, so that trivia survives edits:
+//! settings by their key, timing points and hit objects by their time, and events by their kind and time.
+//! Trivia before an element that moved in time goes before the first element at or after its old time.
+
+use super::parsing::parse_event;
+use super::Timestamp;
+
+/// Element of a section that trivia comes before.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TriviaAnchor {
+	/// The setting with this key, in a `key: value` section.
+	Key(String),
+	/// The first timing point or hit object at or after this time, as written in the file.
+	Time(Timestamp),
+	/// The first event of this kind at or after this time, as written in the file.
+	Event { kind: String, time: Timestamp },
+	/// The line with this exact text, for storyboard commands and anything else.
+	Line(String),
+	/// The end of the section, after every element.
+	End,
+}
+
+/// Comments and blank lines found before an element of a section.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Trivia {
+	/// Header of the section, brackets included (like `[HitObjects]`).
+	/// It is empty for trivia before the first section.
+	pub section: String,
+	pub anchor: TriviaAnchor,
+	/// Lines of trivia, in order.
+	pub lines: Vec<String>,
+}
+
+/// Whether a section is made of `key: value` lines.
+fn is_key_value_section(section: &str) -> bool {
+	matches!(
+		section,
+		"[General]" | "[Editor]" | "[Metadata]" | "[Difficulty]" | "[Colours]"
+	)
+}
+
+/// Time written on a timing point or hit object line, which is its first or third field.
+fn line_time(section: &str, line: &str) -> Option<Timestamp> {
+	let field = match section {
+		"[TimingPoints]" => 0,
+		"[HitObjects]" => 2,
+		_ => return None,
+	};
+	line.split(',').nth(field)?.trim().parse().ok()
+}
+
+/// Kind and time of an event line, with the kind written like the parser writes it back
+/// (`Video` and `1` are the same kind).
+fn event_kind_and_time(line: &str) -> Option<(String, Timestamp)> {
+	let event = parse_event(line).ok()??;
+	let written = event.to_string();
+	let kind = written.split(',').next()?.to_owned();
+	Some((kind, event.start_time))
+}
+
+/// Anchor of trivia found before a line of a section.
+pub(crate) fn anchor_of(section: &str, line: &str) -> TriviaAnchor {
+	if is_key_value_section(section) {
+		if let Some((key, _)) = line.split_once(':') {
+			return TriviaAnchor::Key(key.trim().to_owned());
+		}
+	}
+
+	if let Some(time) = line_time(section, line) {
+		return TriviaAnchor::Time(time);
+	}
+
+	if section == "[Events]" {
+		if let Some((kind, time)) = event_kind_and_time(line) {
+			return TriviaAnchor::Event { kind, time };
+		}
+	}
+
+	TriviaAnchor::Line(line.to_owned())
+}
+
+/// Whether trivia goes before a line of a section.
+fn comes_before(trivia: &Trivia, section: &str, line: &str) -> bool {
+	match &trivia.anchor {
+		TriviaAnchor::Key(key) => line.split_once(':').is_some_and(|(k, _)| k.trim() == key),
+		TriviaAnchor::Time(time) => line_time(section, line).is_some_and(|t| t >= *time),
+		TriviaAnchor::Event { kind, time } => (section == "[Events]")
+			.then(|| event_kind_and_time(line))
+			.flatten()
+			.is_some_and(|(k, t)| k == *kind && t >= *time),
+		TriviaAnchor::Line(text) => line == text,
+		TriviaAnchor::End => false,
+	}
+}
+
+/// Inserts trivia in the text of a written `.osu` file.
+///
+/// Trivia whose element can't be found anymore goes at the end of its section,
+/// and trivia of sections that aren't in the file is dropped.
+pub(crate) fn insert_trivia(contents: &str, trivia: &[Trivia]) -> String {
+	let mut remaining: Vec<&Trivia> = trivia.iter().collect();
+	let mut output = String::with_capacity(contents.len());
+	let mut section = String::new();
+
+	// writes the trivia of the section matching `is_here`, and forgets it
+	let write_trivia = |remaining: &mut Vec<&Trivia>, output: &mut String, is_here: &dyn Fn(&Trivia) -> bool| {
+		remaining.retain(|trivia| {
+			if !is_here(trivia) {
+				return true;
+			}
+			for line in &trivia.lines {
+				output.push_str(line);
+				output.push('\n');
+			}
+			false
+		});
+	};
+
+	let mut lines = contents.lines().peekable();
+	while let Some(line) = lines.next() {
+		if line.starts_with('[') && line.ends_with(']') {
+			line.clone_into(&mut section);
+		} else if !line.is_empty() {
+			write_trivia(&mut remaining, &mut output, &|trivia| {
+				trivia.section == section && comes_before(trivia, &section, line)
+			});
+		}
+
+		// sections end with a blank line, or with the end of the file
+		let is_section_end = lines.peek().is_none_or(|next| next.starts_with('['));
+		if is_section_end && line.is_empty() {
+			write_trivia(&mut remaining, &mut output, &|trivia| trivia.section == section);
+		}
+
+		output.push_str(line);
+		output.push('\n');
+
+		if is_section_end && !line.is_empty() {
+			write_trivia(&mut remaining, &mut output, &|trivia| trivia.section == section);
+		}
+	}
+
+	output
+}
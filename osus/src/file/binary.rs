@@ -0,0 +1,318 @@
+//! Primitives of osu!'s binary file formats (replays, `osu!.db`, `collection.db`, `scores.db`...).
+//!
+//! These formats come from .NET's `BinaryReader` and `BinaryWriter`: numbers are little-endian,
+//! strings are prefixed by their length in ULEB128 (with an extra byte telling whether the string is there at all),
+//! and dates are .NET ticks, the amount of 100ns intervals since `0001-01-01 00:00:00`.
+
+use std::io::{self, Read, Write};
+use std::string::FromUtf8Error;
+
+use crate::ErrorCategory;
+
+/// Byte that comes before a present string.
+const STRING_PRESENT: u8 = 0x0b;
+/// Byte that stands for an absent (null) string.
+const STRING_ABSENT: u8 = 0x00;
+
+/// .NET ticks of the Unix epoch (`1970-01-01 00:00:00`).
+pub const UNIX_EPOCH_TICKS: i64 = 621_355_968_000_000_000;
+
+/// Amount of .NET ticks in a millisecond.
+pub const TICKS_PER_MILLISECOND: i64 = 10_000;
+
+/// Converts .NET ticks to milliseconds since the Unix epoch, rounding towards the epoch.
+#[must_use]
+pub const fn ticks_to_unix_millis(ticks: i64) -> i64 {
+	(ticks - UNIX_EPOCH_TICKS) / TICKS_PER_MILLISECOND
+}
+
+/// Converts milliseconds since the Unix epoch to .NET ticks.
+#[must_use]
+pub const fn unix_millis_to_ticks(millis: i64) -> i64 {
+	millis * TICKS_PER_MILLISECOND + UNIX_EPOCH_TICKS
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BinaryReadError {
+	#[error(transparent)]
+	Io(#[from] io::Error),
+	#[error("ULEB128 number doesn't fit in 64 bits")]
+	Uleb128Overflow,
+	#[error("Invalid string marker {0:#04x}, expected 0x00 or 0x0b")]
+	InvalidStringMarker(u8),
+	#[error("String is not valid UTF-8")]
+	InvalidUtf8(#[from] FromUtf8Error),
+}
+
+impl BinaryReadError {
+	#[must_use]
+	pub const fn category(&self) -> ErrorCategory {
+		match self {
+			Self::Io(_) => ErrorCategory::Io,
+			_ => ErrorCategory::Parse,
+		}
+	}
+}
+
+macro_rules! read_le {
+	($($name:ident -> $ty:ty),* $(,)?) => {
+		$(
+			#[doc = concat!("Reads a little-endian `", stringify!($ty), "`.")]
+			fn $name(&mut self) -> io::Result<$ty> {
+				let mut bytes = [0; size_of::<$ty>()];
+				self.read_exact(&mut bytes)?;
+				Ok(<$ty>::from_le_bytes(bytes))
+			}
+		)*
+	};
+}
+
+macro_rules! write_le {
+	($($name:ident($ty:ty)),* $(,)?) => {
+		$(
+			#[doc = concat!("Writes a little-endian `", stringify!($ty), "`.")]
+			fn $name(&mut self, value: $ty) -> io::Result<()> {
+				self.write_all(&value.to_le_bytes())
+			}
+		)*
+	};
+}
+
+/// Reads the primitives of osu!'s binary formats, for any reader.
+///
+/// Every method returns the I/O errors of the reader, including unexpected EOFs.
+#[allow(clippy::missing_errors_doc)]
+pub trait ReadOsuExt: Read {
+	read_le! {
+		read_u8 -> u8,
+		read_u16 -> u16,
+		read_u32 -> u32,
+		read_u64 -> u64,
+		read_i8 -> i8,
+		read_i16 -> i16,
+		read_i32 -> i32,
+		read_i64 -> i64,
+		read_f32 -> f32,
+		read_f64 -> f64,
+	}
+
+	/// Reads a boolean, which is a single byte that is true if it isn't 0.
+	fn read_bool(&mut self) -> io::Result<bool> {
+		Ok(self.read_u8()? != 0)
+	}
+
+	/// Reads an unsigned LEB128 number, failing if it doesn't fit in a `u64`.
+	fn read_uleb128(&mut self) -> Result<u64, BinaryReadError> {
+		let mut value = 0_u64;
+		let mut shift = 0;
+		loop {
+			let byte = self.read_u8()?;
+			let bits = u64::from(byte & 0x7f);
+			if shift >= 64 || (shift > 0 && bits >> (64 - shift) != 0) {
+				return Err(BinaryReadError::Uleb128Overflow);
+			}
+
+			value |= bits << shift;
+			if byte & 0x80 == 0 {
+				return Ok(value);
+			}
+			shift += 7;
+		}
+	}
+
+	/// Reads an osu! string, which is `None` when it was written as null.
+	///
+	/// Fails if the byte before the string is invalid or if the string isn't valid UTF-8.
+	fn read_osu_string(&mut self) -> Result<Option<String>, BinaryReadError> {
+		match self.read_u8()? {
+			STRING_ABSENT => Ok(None),
+			STRING_PRESENT => {
+				let len = self.read_uleb128()?;
+				let mut bytes = Vec::new();
+				self.take(len).read_to_end(&mut bytes)?;
+				if (bytes.len() as u64) < len {
+					return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+				}
+
+				Ok(Some(String::from_utf8(bytes)?))
+			}
+			marker => Err(BinaryReadError::InvalidStringMarker(marker)),
+		}
+	}
+
+	/// Reads a date as .NET ticks (see [`ticks_to_unix_millis`]).
+	fn read_ticks(&mut self) -> io::Result<i64> {
+		self.read_i64()
+	}
+}
+
+impl<R: Read + ?Sized> ReadOsuExt for R {}
+
+/// Writes the primitives of osu!'s binary formats, for any writer.
+///
+/// Every method returns the I/O errors of the writer.
+#[allow(clippy::missing_errors_doc)]
+pub trait WriteOsuExt: Write {
+	write_le! {
+		write_u8(u8),
+		write_u16(u16),
+		write_u32(u32),
+		write_u64(u64),
+		write_i8(i8),
+		write_i16(i16),
+		write_i32(i32),
+		write_i64(i64),
+		write_f32(f32),
+		write_f64(f64),
+	}
+
+	/// Writes a boolean as a single byte.
+	fn write_bool(&mut self, value: bool) -> io::Result<()> {
+		self.write_u8(u8::from(value))
+	}
+
+	/// Writes an unsigned LEB128 number.
+	fn write_uleb128(&mut self, mut value: u64) -> io::Result<()> {
+		loop {
+			#[allow(clippy::cast_possible_truncation)]
+			let byte = (value & 0x7f) as u8;
+			value >>= 7;
+			if value == 0 {
+				return self.write_u8(byte);
+			}
+			self.write_u8(byte | 0x80)?;
+		}
+	}
+
+	/// Writes an osu! string, or a null string for `None`.
+	fn write_osu_string(&mut self, value: Option<&str>) -> io::Result<()> {
+		let Some(value) = value else {
+			return self.write_u8(STRING_ABSENT);
+		};
+
+		self.write_u8(STRING_PRESENT)?;
+		self.write_uleb128(value.len() as u64)?;
+		self.write_all(value.as_bytes())
+	}
+
+	/// Writes a date as .NET ticks (see [`unix_millis_to_ticks`]).
+	fn write_ticks(&mut self, ticks: i64) -> io::Result<()> {
+		self.write_i64(ticks)
+	}
+}
+
+impl<W: Write + ?Sized> WriteOsuExt for W {}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn numbers_round_trip() {
+		let mut bytes = Vec::new();
+		bytes.write_u8(0xab).unwrap();
+		bytes.write_u16(0xbeef).unwrap();
+		bytes.write_u32(0xdead_beef).unwrap();
+		bytes.write_u64(u64::MAX - 1).unwrap();
+		bytes.write_i8(-5).unwrap();
+		bytes.write_i16(-1234).unwrap();
+		bytes.write_i32(i32::MIN).unwrap();
+		bytes.write_i64(-42).unwrap();
+		bytes.write_f32(1.5).unwrap();
+		bytes.write_f64(-0.25).unwrap();
+		bytes.write_bool(true).unwrap();
+		bytes.write_bool(false).unwrap();
+		assert_eq!(&bytes[1..3], &[0xef, 0xbe]);
+
+		let mut reader = bytes.as_slice();
+		assert_eq!(reader.read_u8().unwrap(), 0xab);
+		assert_eq!(reader.read_u16().unwrap(), 0xbeef);
+		assert_eq!(reader.read_u32().unwrap(), 0xdead_beef);
+		assert_eq!(reader.read_u64().unwrap(), u64::MAX - 1);
+		assert_eq!(reader.read_i8().unwrap(), -5);
+		assert_eq!(reader.read_i16().unwrap(), -1234);
+		assert_eq!(reader.read_i32().unwrap(), i32::MIN);
+		assert_eq!(reader.read_i64().unwrap(), -42);
+		assert_eq!(reader.read_f32().unwrap(), 1.5);
+		assert_eq!(reader.read_f64().unwrap(), -0.25);
+		assert!(reader.read_bool().unwrap());
+		assert!(!reader.read_bool().unwrap());
+		assert!(reader.is_empty());
+	}
+
+	#[test]
+	fn uleb128_round_trips() {
+		for value in [0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX] {
+			let mut bytes = Vec::new();
+			bytes.write_uleb128(value).unwrap();
+			assert_eq!(bytes.as_slice().read_uleb128().unwrap(), value);
+		}
+
+		let mut bytes = Vec::new();
+		bytes.write_uleb128(300).unwrap();
+		assert_eq!(bytes, [0xac, 0x02]);
+	}
+
+	#[test]
+	fn uleb128_overflow_is_an_error() {
+		let bytes = [0xff; 11];
+		assert!(matches!(
+			bytes.as_slice().read_uleb128(),
+			Err(BinaryReadError::Uleb128Overflow)
+		));
+	}
+
+	#[test]
+	fn strings_round_trip() {
+		for value in [None, Some(""), Some("osu!"), Some("日本語"), Some(&*"a".repeat(200))] {
+			let mut bytes = Vec::new();
+			bytes.write_osu_string(value).unwrap();
+			assert_eq!(bytes.as_slice().read_osu_string().unwrap().as_deref(), value);
+		}
+	}
+
+	#[test]
+	fn invalid_strings_are_errors() {
+		assert!(matches!(
+			[0x01].as_slice().read_osu_string(),
+			Err(BinaryReadError::InvalidStringMarker(0x01))
+		));
+		assert!(matches!(
+			[0x0b, 0x02, 0xff, 0xfe].as_slice().read_osu_string(),
+			Err(BinaryReadError::InvalidUtf8(_))
+		));
+	}
+
+	#[test]
+	fn ticks_round_trip() {
+		let ticks = unix_millis_to_ticks(1_700_000_000_000);
+		assert_eq!(ticks_to_unix_millis(ticks), 1_700_000_000_000);
+		assert_eq!(ticks_to_unix_millis(UNIX_EPOCH_TICKS), 0);
+
+		let mut bytes = Vec::new();
+		bytes.write_ticks(ticks).unwrap();
+		assert_eq!(bytes.as_slice().read_ticks().unwrap(), ticks);
+	}
+
+	#[test]
+	fn truncated_input_is_an_error() {
+		let mut bytes = Vec::new();
+		bytes.write_osu_string(Some("truncated")).unwrap();
+		bytes.truncate(bytes.len() - 1);
+		let Err(BinaryReadError::Io(e)) = bytes.as_slice().read_osu_string() else {
+			panic!("truncated string was read");
+		};
+		assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+		assert_eq!(BinaryReadError::Io(e).category(), ErrorCategory::Io);
+
+		assert!(matches!(
+			[0x80].as_slice().read_uleb128(),
+			Err(BinaryReadError::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof
+		));
+		assert_eq!(
+			[0; 3].as_slice().read_u32().unwrap_err().kind(),
+			io::ErrorKind::UnexpectedEof
+		);
+	}
+}
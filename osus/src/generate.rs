@@ -0,0 +1,123 @@
+//! Templates of new beatmaps, as a starting point for mapping tools.
+
+use crate::file::beatmap::{
+	BeatmapFile, DifficultySection, EditorSection, Event, EventParams, GeneralSection, MetadataSection, Timestamp,
+	TimingPoint,
+};
+use crate::ErrorCategory;
+
+/// Latest file format version of osu!stable, used for new beatmaps.
+pub const NEW_BEATMAP_FORMAT_VERSION: u32 = 14;
+
+/// Settings of a new beatmap, see [`new_beatmap`].
+#[derive(Clone, Debug)]
+pub struct GenerateOptions {
+	/// Audio file, relative to the beatmap folder.
+	pub audio: String,
+	/// BPM of the only uninherited timing point.
+	pub bpm: f64,
+	/// Time of the first beat, in milliseconds.
+	pub offset: Timestamp,
+	/// Game mode (0 = osu!, 1 = osu!taiko, 2 = osu!catch, 3 = osu!mania).
+	pub mode: u8,
+	pub metadata: MetadataSection,
+	/// Background image, relative to the beatmap folder.
+	pub background: String,
+}
+
+impl Default for GenerateOptions {
+	fn default() -> Self {
+		Self {
+			audio: "audio.mp3".to_owned(),
+			bpm: 120.0,
+			offset: 0.0,
+			mode: 0,
+			metadata: MetadataSection::default(),
+			background: "bg.jpg".to_owned(),
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, thiserror::Error)]
+pub enum GenerateError {
+	#[error("BPM must be a positive number, got {0}")]
+	InvalidBpm(f64),
+
+	#[error("Unknown game mode {0}, expected 0 (osu!), 1 (osu!taiko), 2 (osu!catch) or 3 (osu!mania)")]
+	InvalidMode(u8),
+}
+
+impl GenerateError {
+	#[must_use]
+	pub const fn category(&self) -> ErrorCategory {
+		match self {
+			Self::InvalidBpm(_) => ErrorCategory::Validation,
+			Self::InvalidMode(_) => ErrorCategory::UnsupportedMode,
+		}
+	}
+}
+
+/// Creates an empty beatmap with one uninherited timing point, a background and the settings of the osu! editor.
+///
+/// osu!mania beatmaps get 4 keys, which is what the circle size means in this mode.
+///
+/// # Errors
+///
+/// This function will return an error if the BPM isn't positive or if the game mode doesn't exist.
+pub fn new_beatmap(options: GenerateOptions) -> Result<BeatmapFile, GenerateError> {
+	let GenerateOptions {
+		audio,
+		bpm,
+		offset,
+		mode,
+		metadata,
+		background,
+	} = options;
+
+	if !(bpm.is_finite() && bpm > 0.0) {
+		return Err(GenerateError::InvalidBpm(bpm));
+	}
+	if mode > 3 {
+		return Err(GenerateError::InvalidMode(mode));
+	}
+
+	let general = GeneralSection {
+		audio_filename: audio,
+		mode,
+		..GeneralSection::default()
+	};
+
+	let editor = EditorSection {
+		bookmarks: Vec::new(),
+		distance_spacing: 1.0,
+		beat_divisor: 4.0,
+		grid_size: 32,
+		timeline_zoom: Some(1.0),
+	};
+
+	let difficulty = DifficultySection {
+		circle_size: if mode == 3 { 4.0 } else { 5.0 },
+		..DifficultySection::default()
+	};
+
+	let background = Event {
+		event_type: "0".to_owned(),
+		start_time: 0.0,
+		params: EventParams::Background {
+			filename: background,
+			x_offset: 0,
+			y_offset: 0,
+		},
+	};
+
+	Ok(BeatmapFile {
+		osu_file_format: NEW_BEATMAP_FORMAT_VERSION,
+		general: Some(general),
+		editor: Some(editor),
+		metadata: Some(metadata),
+		difficulty: Some(difficulty),
+		events: vec![background],
+		timing_points: vec![TimingPoint::uninherited(offset, bpm, 4)],
+		..BeatmapFile::default()
+	})
+}
@@ -0,0 +1,670 @@
+//! Hitsounding helpers, like copying the hitsounds of a soundmap or applying a rhythmic preset to a whole section.
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+use crate::algos::merge_adjacent_timing_points;
+use crate::analysis::slider_duration;
+use crate::file::beatmap::timing_points::uninherited_at;
+use crate::file::beatmap::{
+	BeatmapFile, Event, EventParams, HitObject, HitObjectParams, HitObjectType, HitSample, HitSampleSet, HitSound,
+	HoldTail, SampleBank, Timestamp, TimingPoint,
+};
+use crate::timeline::TimingTimeline;
+use crate::{close_range, ErrorCategory, ExtTimestamped, Timestamped, TimestampedSlice, Tolerance};
+
+/// Distance in milliseconds from a tick of the grid under which a sound is considered on it, like in the editor.
+const SNAP_TOLERANCE: f64 = 1.0;
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum HitsoundPatternError {
+	#[error("Invalid hitsound {0:?}, expected the letters W (whistle), F (finish) and C (clap)")]
+	InvalidHitsound(char),
+	#[error("A pattern needs a divisor of at least 1")]
+	ZeroDivisor,
+	#[error("A pattern needs at least one step")]
+	NoSteps,
+}
+
+impl HitsoundPatternError {
+	#[must_use]
+	pub const fn category(&self) -> ErrorCategory {
+		match self {
+			Self::InvalidHitsound(_) => ErrorCategory::Parse,
+			Self::ZeroDivisor | Self::NoSteps => ErrorCategory::Validation,
+		}
+	}
+}
+
+/// Additions to put on the beat grid, repeating from every uninherited timing point.
+///
+/// For instance, claps on 2 and 4 are written with a divisor of 1 and the steps `-`, `C`, `-`, `C`,
+/// and whistles on downbeats with a divisor of 1 and the steps `W`, `-`, `-`, `-` in 4/4.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HitsoundPattern {
+	/// Amount of steps per beat, like the beat snap divisor of the editor.
+	pub divisor: u32,
+	/// Additions of each step.
+	pub steps: Vec<HitSound>,
+	/// Replace the additions of the sounds on the grid instead of adding to them.
+	pub overwrite: bool,
+}
+
+impl HitsoundPattern {
+	/// Creates a pattern from steps written as letters, like `WC` for a whistle and a clap.
+	/// Steps without additions can be written as `-`, `.` or an empty string.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the divisor is 0, if there are no steps, or if a step has an unknown letter.
+	pub fn new<S: AsRef<str>>(divisor: u32, steps: &[S], overwrite: bool) -> Result<Self, HitsoundPatternError> {
+		if divisor == 0 {
+			return Err(HitsoundPatternError::ZeroDivisor);
+		}
+		if steps.is_empty() {
+			return Err(HitsoundPatternError::NoSteps);
+		}
+
+		let steps = (steps.iter())
+			.map(|step| parse_additions(step.as_ref()))
+			.collect::<Result<_, _>>()?;
+
+		Ok(Self {
+			divisor,
+			steps,
+			overwrite,
+		})
+	}
+
+	/// Additions of the step a time falls on, or `None` if it isn't on the grid.
+	fn step_at(&self, timing_points: &[TimingPoint], time: Timestamp) -> Option<HitSound> {
+		let red_line = uninherited_at(timing_points, time)?;
+		let step_length = red_line.beat_length / f64::from(self.divisor);
+		if step_length <= 0.0 || !step_length.is_finite() {
+			return None;
+		}
+
+		let step = ((time - red_line.time) / step_length).round();
+		if (step.mul_add(step_length, red_line.time) - time).abs() > SNAP_TOLERANCE {
+			return None;
+		}
+
+		#[allow(clippy::cast_precision_loss)]
+		let len = self.steps.len() as f64;
+		#[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+		self.steps.get(step.rem_euclid(len) as usize).copied()
+	}
+
+	fn apply_to(&self, hit_sound: &mut HitSound, additions: HitSound) {
+		if self.overwrite {
+			*hit_sound &= HitSound::NORMAL;
+		}
+		*hit_sound |= additions;
+	}
+}
+
+/// Parses additions written as letters, like `WC` for a whistle and a clap.
+fn parse_additions(letters: &str) -> Result<HitSound, HitsoundPatternError> {
+	let mut hit_sound = HitSound::NONE;
+	for letter in letters.chars() {
+		hit_sound |= match letter.to_ascii_uppercase() {
+			'W' => HitSound::WHISTLE,
+			'F' => HitSound::FINISH,
+			'C' => HitSound::CLAP,
+			'-' | '.' | ' ' => HitSound::NONE,
+			_ => return Err(HitsoundPatternError::InvalidHitsound(letter)),
+		};
+	}
+
+	Ok(hit_sound)
+}
+
+/// Applies a hitsound pattern to the sounds of hit objects in a time range, returning how many sounds changed.
+///
+/// Sounds are the hit circles, the edges of sliders, the end of spinners and the start of osu!mania holds.
+/// Sounds that are not on the grid of the pattern are left as they are.
+pub fn apply_pattern(beatmap: &mut BeatmapFile, pattern: &HitsoundPattern, range: Range<Timestamp>) -> usize {
+	let slide_durations: Vec<f64> = (beatmap.hit_objects.iter())
+		.map(|hit_object| match hit_object.object_params {
+			HitObjectParams::Slider { slides, .. } => {
+				slider_duration(beatmap, hit_object).unwrap_or_default() / f64::from(slides.max(1))
+			}
+			_ => 0.0,
+		})
+		.collect();
+
+	let mut changed = 0;
+	let mut apply = |hit_sound: &mut HitSound, time: Timestamp| {
+		if !range.contains(&time) {
+			return;
+		}
+
+		if let Some(additions) = pattern.step_at(&beatmap.timing_points, time) {
+			let old = *hit_sound;
+			pattern.apply_to(hit_sound, additions);
+			if *hit_sound != old {
+				changed += 1;
+			}
+		}
+	};
+
+	for (hit_object, slide_duration) in beatmap.hit_objects.iter_mut().zip(slide_durations) {
+		let time = hit_object.time;
+		match &mut hit_object.object_params {
+			HitObjectParams::HitCircle | HitObjectParams::Hold { .. } => apply(&mut hit_object.hit_sound, time),
+			HitObjectParams::Spinner { end_time } => apply(&mut hit_object.hit_sound, *end_time),
+			params @ HitObjectParams::Slider { .. } => {
+				params.normalize_edges();
+				if let HitObjectParams::Slider { edge_hitsounds, .. } = params {
+					for (i, hit_sound) in edge_hitsounds.iter_mut().enumerate() {
+						#[allow(clippy::cast_precision_loss)]
+						apply(hit_sound, slide_duration.mul_add(i as f64, time));
+					}
+				}
+			}
+		}
+	}
+
+	changed
+}
+
+/// What [`copy_hitsounds`] does with the soundmap's hitsounds that fall on the end of an osu!mania hold,
+/// where they can't be played.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HoldEndMode {
+	/// Leave them out silently.
+	#[default]
+	Ignore,
+	/// Move them to the closest note starting around the end of the hold.
+	Adjacent,
+	/// Leave them out, reporting every hold that had some.
+	Discard,
+	/// Play them when the hold is released, which only osu!lazer can do (see [`HoldTail`]).
+	Tail,
+}
+
+/// Options for [`copy_hitsounds`].
+#[derive(Clone, Debug)]
+pub struct CopyHitsoundsOptions {
+	/// Spread the hitsounds of each osu!mania chord on its notes, so that every addition is played by its own note.
+	pub mania: bool,
+	/// Also use the soundmap's storyboard samples as custom samples of the hit objects at the same time.
+	pub samples: bool,
+	pub hold_ends: HoldEndMode,
+	/// Maximum distance in milliseconds between a hold's end and the note its hitsounds are moved to.
+	pub hold_end_window: f64,
+	/// Tolerance used to find the soundmap's objects at the time of a beatmap's sound.
+	pub tolerance: Tolerance,
+}
+
+impl Default for CopyHitsoundsOptions {
+	fn default() -> Self {
+		Self {
+			mania: false,
+			samples: false,
+			hold_ends: HoldEndMode::Ignore,
+			hold_end_window: 50.0,
+			tolerance: Tolerance::DEFAULT,
+		}
+	}
+}
+
+/// Hitsounds of the soundmap that fell on the end of a hold.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HoldEndHitsounds {
+	pub hold_time: Timestamp,
+	pub end_time: Timestamp,
+	/// Start of the note the hitsounds were moved to, or `None` if they were discarded.
+	pub moved_to: Option<Timestamp>,
+}
+
+/// What [`copy_hitsounds`] changed in a beatmap.
+#[derive(Clone, Debug, Default)]
+pub struct CopyHitsoundsReport {
+	/// Amount of hit objects that got hitsounds from the soundmap, on their own or on one of their slider edges.
+	pub hitsounded: usize,
+	/// Amount of hit objects that got a custom sample from the soundmap's storyboard samples.
+	pub sampled: usize,
+	/// Holds whose end had hitsounds, when they are moved or discarded.
+	pub hold_ends: Vec<HoldEndHitsounds>,
+}
+
+/// Combine and merge the hitsound information of a bunch of hitobjects into another one.
+fn hitsound_hit_object(ho: &mut HitObject, ho_sounds: &[HitObject]) {
+	for so in ho_sounds {
+		tracing::info!("affecting {} at {}", ho.object_type, ho.timestamp());
+
+		if so.hit_sample.normal_set != SampleBank::Auto {
+			ho.hit_sample.normal_set = so.hit_sample.normal_set;
+		}
+
+		if so.hit_sample.addition_set != SampleBank::Auto {
+			ho.hit_sample.addition_set = so.hit_sample.addition_set;
+		}
+
+		ho.hit_sample.index = so.hit_sample.index;
+		ho.hit_sample.volume = so.hit_sample.volume;
+
+		if so.hit_sample.filename.is_some() {
+			ho.hit_sample.filename.clone_from(&so.hit_sample.filename);
+		}
+
+		ho.hit_sound |= so.hit_sound;
+	}
+}
+
+/// Adds the hitsounds of the soundmap's objects to a sound that only has a hitsound and a sample set,
+/// like a slider edge or the release of a hold.
+fn hitsound_edge(hit_sound: &mut HitSound, sample_set: &mut HitSampleSet, sounds: &[HitObject]) {
+	for so in sounds {
+		if so.hit_sample.normal_set != SampleBank::Auto {
+			sample_set.normal_set = so.hit_sample.normal_set;
+		}
+
+		if so.hit_sample.addition_set != SampleBank::Auto {
+			sample_set.addition_set = so.hit_sample.addition_set;
+		}
+
+		*hit_sound |= so.hit_sound;
+	}
+}
+
+/// Use storyboard samples as the custom sample of a hitobject.
+fn sample_hit_object(ho: &mut HitObject, samples: &[Event]) {
+	for sample in samples {
+		if let EventParams::Sample { filename, volume, .. } = &sample.params {
+			tracing::info!("sampling {} at {} with {}", ho.object_type, ho.timestamp(), filename);
+
+			ho.hit_sample.filename = Some(filename.clone());
+			ho.hit_sample.volume = u32::from(*volume);
+		}
+	}
+}
+
+/// Resets every hitsound of a beatmap's hit objects, including slider edges and hold releases.
+fn reset_hit_object_sounds(hit_objects: &mut [HitObject]) {
+	for hit_object in hit_objects {
+		hit_object.hit_sample = HitSample::default();
+		hit_object.hit_sound = HitSound::NONE;
+
+		match &mut hit_object.object_params {
+			HitObjectParams::Slider {
+				edge_hitsounds,
+				edge_samplesets,
+				..
+			} => {
+				for eh in edge_hitsounds {
+					*eh = HitSound::NONE;
+				}
+
+				for es in edge_samplesets {
+					*es = HitSampleSet::default();
+				}
+			}
+			HitObjectParams::Hold { tail, .. } => *tail = None,
+			HitObjectParams::HitCircle | HitObjectParams::Spinner { .. } => {}
+		}
+	}
+}
+
+/// Objects of the soundmap that make a sound at their start, sorted by time.
+///
+/// The release sounds of the soundmap's holds are added as hit circles at the end of the holds,
+/// so that they are copied like any other sound.
+fn sound_sources(hit_objects: &[HitObject]) -> Cow<'_, [HitObject]> {
+	let releases: Vec<HitObject> = (hit_objects.iter())
+		.filter_map(|hit_object| match hit_object.object_params {
+			HitObjectParams::Hold {
+				end_time,
+				tail: Some(tail),
+			} => Some(HitObject {
+				x: hit_object.x,
+				y: hit_object.y,
+				time: end_time,
+				object_type: HitObjectType::HitCircle,
+				combo_color_skip: None,
+				hit_sound: tail.hit_sound,
+				object_params: HitObjectParams::HitCircle,
+				hit_sample: HitSample {
+					normal_set: tail.sample_set.normal_set,
+					addition_set: tail.sample_set.addition_set,
+					..HitSample::default()
+				},
+			}),
+			_ => None,
+		})
+		.collect();
+
+	if releases.is_empty() {
+		return Cow::Borrowed(hit_objects);
+	}
+
+	let mut sources = [hit_objects, &releases].concat();
+	sources.sort_by(|a, b| a.time.total_cmp(&b.time));
+	Cow::Owned(sources)
+}
+
+/// Inserts the sample settings of the soundmap's timing points in the beatmap's ones.
+fn copy_sample_settings(beatmap: &mut BeatmapFile, soundmap: &BeatmapFile, tolerance: Tolerance) {
+	let Some(mut last_sound_point) = soundmap.timing_points.first() else {
+		tracing::warn!("The soundmap has no timing points, its sample settings won't be copied");
+		return;
+	};
+
+	let mut new_timing_points: Vec<TimingPoint> = Vec::new();
+	for smtp_bmtp in (soundmap.timing_points).interleave_timestamped(&beatmap.timing_points) {
+		match smtp_bmtp {
+			Ok(soundmap_tp) => {
+				last_sound_point = soundmap_tp;
+
+				if let Some(new_tp) = new_timing_points.last_mut() {
+					if soundmap_tp.basically_eq_with(new_tp, tolerance.same_time) {
+						new_tp.sample_set = soundmap_tp.sample_set;
+						new_tp.sample_index = soundmap_tp.sample_index;
+						new_tp.volume = soundmap_tp.volume;
+					} else {
+						let mut new_tp = new_tp.clone();
+						new_tp.time = soundmap_tp.time;
+						new_tp.uninherited = false;
+						new_tp.sample_set = soundmap_tp.sample_set;
+						new_tp.sample_index = soundmap_tp.sample_index;
+						new_tp.volume = soundmap_tp.volume;
+						new_timing_points.push(new_tp.clone());
+					}
+				}
+			}
+			Err(beatmap_tp) => {
+				let mut new_tp = beatmap_tp.clone();
+				new_tp.sample_set = last_sound_point.sample_set;
+				new_tp.sample_index = last_sound_point.sample_index;
+				new_tp.volume = last_sound_point.volume;
+				new_timing_points.push(new_tp);
+			}
+		}
+	}
+	beatmap.timing_points = merge_adjacent_timing_points(&new_timing_points, tolerance.same_time);
+}
+
+/// Handles the soundmap's hitsounds that fall on the end of a hold, where osu!mania can't play them.
+///
+/// Hitsounds already played by a note starting at the same time are left alone.
+/// The other ones are either played on the release of the hold, moved to the closest note starting
+/// within `window` milliseconds, or discarded. Each hold is reported unless they were put on its release.
+fn splat_hold_end_hitsounds(
+	hit_objects: &mut [HitObject],
+	soundmap: &[HitObject],
+	mode: HoldEndMode,
+	window: f64,
+	tolerance: Tolerance,
+) -> Vec<HoldEndHitsounds> {
+	let starts: Vec<f64> = hit_objects.iter().map(|ho| ho.time).collect();
+	let mut reports = Vec::new();
+
+	for index in 0..hit_objects.len() {
+		let HitObjectParams::Hold { end_time, .. } = hit_objects[index].object_params else {
+			continue;
+		};
+
+		let end_hitsounds = soundmap.between(close_range(end_time, tolerance.same_time));
+		if end_hitsounds.is_empty()
+			|| starts
+				.iter()
+				.any(|start| (start - end_time).abs() <= tolerance.same_time)
+		{
+			continue;
+		}
+
+		if mode == HoldEndMode::Tail {
+			if let HitObjectParams::Hold { tail, .. } = &mut hit_objects[index].object_params {
+				let tail = tail.get_or_insert(HoldTail {
+					hit_sound: HitSound::NONE,
+					sample_set: HitSampleSet::default(),
+				});
+				hitsound_edge(&mut tail.hit_sound, &mut tail.sample_set, end_hitsounds);
+			}
+			continue;
+		}
+
+		let target = (0..starts.len())
+			.filter(|&i| i != index && mode == HoldEndMode::Adjacent)
+			.filter(|&i| (starts[i] - end_time).abs() <= window)
+			.min_by(|&a, &b| (starts[a] - end_time).abs().total_cmp(&(starts[b] - end_time).abs()));
+
+		if let Some(target) = target {
+			hitsound_hit_object(&mut hit_objects[target], end_hitsounds);
+		}
+
+		reports.push(HoldEndHitsounds {
+			hold_time: hit_objects[index].time,
+			end_time,
+			moved_to: target.map(|target| starts[target]),
+		});
+	}
+
+	reports
+}
+
+/// Spreads the hitsounds of each osu!mania chord on its notes, so that every addition is played by its own note.
+///
+/// Notes in a chord all have the same hitsounds before the spread, like after [`copy_hitsounds`].
+/// One note keeps the normal sample set and gets no addition, and the others get the additions.
+/// To keep the sounds balanced between columns, additions go to the columns that didn't get one in the previous chord,
+/// then to the ones that went the longest without one, then to the ones that got the fewest.
+/// Hit objects need to be sorted.
+pub fn mania_spread(hit_objects: &mut [HitObject], key_count: u32, tolerance: Tolerance) {
+	// index of the last chord where each column got an addition, and how many additions each column got
+	let mut last_addition: Vec<Option<usize>> = vec![None; key_count as usize];
+	let mut addition_counts: Vec<usize> = vec![0; key_count as usize];
+
+	for (chord_index, chord) in hit_objects.group_timestamped_mut_with(tolerance.grouping).enumerate() {
+		let Some(first) = chord.first() else {
+			break;
+		};
+
+		let hit_sound = first.hit_sound;
+		let normal_set = first.hit_sample.normal_set;
+		let addition_set = first.hit_sample.addition_set;
+
+		if let [single] = chord {
+			let column = single.column(key_count) as usize;
+			if hit_sound.iter_additions().next().is_some() && column < last_addition.len() {
+				last_addition[column] = Some(chord_index);
+				addition_counts[column] += 1;
+			}
+			continue;
+		}
+
+		// notes that should get additions first, the last one keeping the normal sample set
+		let mut order: Vec<usize> = (0..chord.len()).collect();
+		order.sort_by_key(|&note| {
+			let column = chord[note].column(key_count);
+			let last = last_addition.get(column as usize).copied().flatten();
+			let in_previous_chord = last.is_some_and(|last| last + 1 == chord_index);
+			let count = addition_counts.get(column as usize).copied().unwrap_or_default();
+			(in_previous_chord, last.map_or(0, |last| last + 1), count, column)
+		});
+		let (receivers, base) = order.split_at(order.len() - 1);
+		let base = base[0];
+
+		for (note, hit_object) in chord.iter_mut().enumerate() {
+			hit_object.hit_sound = HitSound::NONE;
+			if note == base {
+				if addition_set != SampleBank::Auto {
+					hit_object.hit_sample.addition_set = SampleBank::Auto;
+				}
+			} else if normal_set != SampleBank::Auto {
+				hit_object.hit_sample.normal_set = SampleBank::Auto;
+			}
+		}
+
+		for (i, addition) in hit_sound.iter_additions().enumerate() {
+			let hit_object = &mut chord[receivers[i % receivers.len()]];
+			hit_object.hit_sound |= addition;
+			let column = hit_object.column(key_count) as usize;
+			if column < last_addition.len() {
+				last_addition[column] = Some(chord_index);
+				addition_counts[column] += 1;
+			}
+		}
+	}
+}
+
+/// Replaces the hitsounds of a beatmap with the ones of a soundmap, a beatmap made only to hold hitsounds.
+///
+/// Every hitsound of the beatmap is reset first. Then the sample settings of the soundmap's timing points are
+/// inserted in the beatmap's timing points, and each sound of the beatmap (hit circles, slider edges, spinner ends
+/// and hold starts) gets the hitsounds of the soundmap's objects at the same time, including the release sounds
+/// of its holds. Hitsounds at the end of holds are handled according to [`CopyHitsoundsOptions::hold_ends`].
+pub fn copy_hitsounds(
+	beatmap: &mut BeatmapFile,
+	soundmap: &BeatmapFile,
+	options: &CopyHitsoundsOptions,
+) -> CopyHitsoundsReport {
+	let tolerance = options.tolerance;
+	let mut report = CopyHitsoundsReport::default();
+
+	// storyboard samples of the soundmap, only used as sources if asked to
+	let mut sound_samples: Vec<Event> = Vec::new();
+	if options.samples {
+		sound_samples = (soundmap.events.iter())
+			.filter(|event| matches!(event.params, EventParams::Sample { .. }))
+			.cloned()
+			.collect();
+		sound_samples.sort_by(|a, b| a.start_time.total_cmp(&b.start_time));
+	}
+
+	tracing::info!("Resetting beatmap's hitsounds...");
+	reset_hit_object_sounds(&mut beatmap.hit_objects);
+	let sources = sound_sources(&soundmap.hit_objects);
+
+	tracing::info!("Inserting soundmap's timing points...");
+	copy_sample_settings(beatmap, soundmap, tolerance);
+
+	tracing::info!("Inserting soundmap's hitsounds...");
+	let slider_multiplier = (beatmap.difficulty.as_ref()).map(|difficulty| f64::from(difficulty.slider_multiplier));
+
+	// TODO: improve performance by somehow walking along both maps
+	//       (instead of binary-searching the soundmap every time)
+
+	let timeline = TimingTimeline::new(&beatmap.timing_points);
+	for hit_object in &mut beatmap.hit_objects {
+		// time of the sound of the hit object itself
+		let sound_time = match hit_object.object_params {
+			HitObjectParams::Spinner { end_time } => end_time,
+			_ => hit_object.timestamp(),
+		};
+
+		let hitsounds = sources.between(close_range(sound_time, tolerance.same_time));
+		let samples = sound_samples.between(close_range(sound_time, tolerance.same_time));
+		let mut hitsounded = !hitsounds.is_empty();
+		report.sampled += usize::from(!samples.is_empty());
+		hitsound_hit_object(hit_object, hitsounds);
+		sample_hit_object(hit_object, samples);
+
+		// affect all edge hitsound properties of the slider
+		let timestamp = hit_object.timestamp();
+		if let HitObjectParams::Slider {
+			length,
+			edge_hitsounds,
+			edge_samplesets,
+			..
+		} = &mut hit_object.object_params
+		{
+			let dur = (slider_multiplier)
+				.and_then(|slider_multiplier| timeline.slide_duration_at(timestamp, *length, slider_multiplier))
+				.unwrap_or_default();
+
+			for (i, (edge_hs, edge_ss)) in (edge_hitsounds.iter_mut()).zip(edge_samplesets.iter_mut()).enumerate() {
+				#[allow(clippy::cast_precision_loss)]
+				let local_timestamp = dur.mul_add(i as f64, timestamp);
+
+				let start_hitsounds = sources.between(close_range(local_timestamp, tolerance.same_time));
+				if !start_hitsounds.is_empty() {
+					tracing::info!("affecting slider edge at {}", local_timestamp);
+					hitsounded = true;
+				}
+
+				hitsound_edge(edge_hs, edge_ss, start_hitsounds);
+			}
+		}
+
+		report.hitsounded += usize::from(hitsounded);
+	}
+
+	if options.hold_ends != HoldEndMode::Ignore {
+		tracing::info!("Handling hitsounds at the end of holds...");
+		report.hold_ends = splat_hold_end_hitsounds(
+			&mut beatmap.hit_objects,
+			&sources,
+			options.hold_ends,
+			options.hold_end_window,
+			tolerance,
+		);
+	}
+
+	if options.mania {
+		tracing::info!("Applying mania hitsound spread-out transformation...");
+		let key_count = beatmap.key_count();
+		mania_spread(&mut beatmap.hit_objects, key_count, tolerance);
+	}
+
+	report
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::file::beatmap::parsing::parse_hit_object;
+
+	/// Chords of notes in the given columns of a 4K map, one every 100ms, all with the given hitsound.
+	fn chords(columns: &[u32], count: u32, hit_sound: u8) -> Vec<HitObject> {
+		(0..count)
+			.flat_map(|chord| {
+				columns.iter().map(move |&column| {
+					let x = HitObject::column_x(column, 4);
+					parse_hit_object(&format!("{x},192,{},1,{hit_sound},0:0:0:0:", chord * 100)).unwrap()
+				})
+			})
+			.collect()
+	}
+
+	/// Columns that got an addition in each chord.
+	fn receivers(hit_objects: &[HitObject], chord_len: usize) -> Vec<Vec<u32>> {
+		(hit_objects.chunks(chord_len))
+			.map(|chord| {
+				(chord.iter())
+					.filter(|ho| ho.hit_sound != HitSound::NONE)
+					.map(|ho| ho.column(4))
+					.collect()
+			})
+			.collect()
+	}
+
+	#[test]
+	fn spread_alternates_between_consecutive_chords() {
+		let mut hit_objects = chords(&[0, 1], 6, 2);
+		mania_spread(&mut hit_objects, 4, Tolerance::DEFAULT);
+
+		assert_eq!(receivers(&hit_objects, 2), [[0], [1], [0], [1], [0], [1]]);
+		// the other note of each chord keeps the normal sample set without addition
+		assert!(hit_objects
+			.iter()
+			.all(|ho| ho.hit_sound == HitSound::NONE || ho.hit_sound == HitSound::WHISTLE));
+	}
+
+	#[test]
+	fn spread_balances_columns() {
+		// whistle, finish and clap on 4-note chords
+		let mut hit_objects = chords(&[0, 1, 2, 3], 8, 14);
+		mania_spread(&mut hit_objects, 4, Tolerance::DEFAULT);
+
+		let mut counts = [0; 4];
+		for chord in receivers(&hit_objects, 4) {
+			assert_eq!(chord.len(), 3);
+			for column in chord {
+				counts[column as usize] += 1;
+			}
+		}
+		assert_eq!(counts, [6; 4]);
+	}
+}
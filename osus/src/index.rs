@@ -0,0 +1,377 @@
+//! Searchable index of every beatmap in a songs folder.
+//!
+//! Building the index only parses the metadata of each `.osu` file (see [`BeatmapFileRef`]),
+//! and the index can be saved to and loaded from a plain text file to avoid walking the folder again.
+//!
+//! There is no star rating calculation in this crate yet, so entries keep the raw difficulty settings instead.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::num::ParseIntError;
+use std::path::{Path, PathBuf};
+
+use crate::file::beatmap::borrowed::{BeatmapFileRef, RawSection};
+use crate::file::beatmap::errors::BeatmapFileParseError;
+use crate::progress::{NoProgress, ProgressSink};
+use crate::ErrorCategory;
+
+/// First line of a saved index, changed whenever the format of entries changes.
+const INDEX_HEADER: &str = "osus index v1";
+
+/// Number of tab-separated columns of an entry in a saved index.
+const INDEX_COLUMNS: usize = 15;
+
+/// Metadata of a single difficulty in the index.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[allow(clippy::module_name_repetitions)]
+pub struct IndexEntry {
+	/// Path of the `.osu` file.
+	pub path: PathBuf,
+	pub title: String,
+	pub title_unicode: String,
+	pub artist: String,
+	pub artist_unicode: String,
+	pub creator: String,
+	/// Difficulty name
+	pub version: String,
+	pub tags: Vec<String>,
+	/// Game mode (0 = osu!, 1 = osu!taiko, 2 = osu!catch, 3 = osu!mania)
+	pub mode: u8,
+	pub beatmap_id: Option<i32>,
+	pub beatmap_set_id: Option<i32>,
+	pub hp_drain_rate: f32,
+	pub circle_size: f32,
+	pub overall_difficulty: f32,
+	pub approach_rate: f32,
+}
+
+impl IndexEntry {
+	/// Creates an entry from the metadata of a beatmap.
+	#[must_use]
+	pub fn from_beatmap(path: PathBuf, beatmap: &BeatmapFileRef<'_>) -> Self {
+		let metadata = beatmap.metadata.clone().unwrap_or_default();
+		let difficulty = beatmap.difficulty.clone().unwrap_or_default();
+
+		// the mode is the only field of the [General] section we care about
+		let mode = (beatmap.section("[General]").into_iter())
+			.flat_map(RawSection::lines)
+			.filter_map(|line| line.split_once(':'))
+			.find(|(field, _)| field.trim() == "Mode")
+			.and_then(|(_, value)| value.trim().parse().ok());
+
+		Self {
+			path,
+			title: metadata.title.to_owned(),
+			title_unicode: metadata.title_unicode.to_owned(),
+			artist: metadata.artist.to_owned(),
+			artist_unicode: metadata.artist_unicode.to_owned(),
+			creator: metadata.creator.to_owned(),
+			version: metadata.version.to_owned(),
+			tags: (metadata.tags.iter())
+				.filter(|tag| !tag.is_empty())
+				.map(|&tag| tag.to_owned())
+				.collect(),
+			mode: mode.unwrap_or(0),
+			beatmap_id: metadata.beatmap_id,
+			beatmap_set_id: metadata.beatmap_set_id,
+			hp_drain_rate: difficulty.hp_drain_rate,
+			circle_size: difficulty.circle_size,
+			overall_difficulty: difficulty.overall_difficulty,
+			approach_rate: difficulty.approach_rate,
+		}
+	}
+
+	/// Whether the entry matches every criteria of the query.
+	#[must_use]
+	pub fn matches(&self, query: &IndexQuery) -> bool {
+		let contains = |haystack: &str, needle: &str| haystack.to_lowercase().contains(&needle.to_lowercase());
+		let contains_any = |needle: &str, fields: &[&str]| fields.iter().any(|field| contains(field, needle));
+
+		if (query.mode).is_some_and(|mode| mode != self.mode) {
+			return false;
+		}
+
+		if let Some(artist) = &query.artist {
+			if !contains_any(artist, &[&self.artist, &self.artist_unicode]) {
+				return false;
+			}
+		}
+
+		if let Some(title) = &query.title {
+			if !contains_any(title, &[&self.title, &self.title_unicode]) {
+				return false;
+			}
+		}
+
+		if let Some(creator) = &query.creator {
+			if !contains(&self.creator, creator) {
+				return false;
+			}
+		}
+
+		if let Some(text) = &query.text {
+			let fields = [
+				self.title.as_str(),
+				&self.title_unicode,
+				&self.artist,
+				&self.artist_unicode,
+				&self.creator,
+				&self.version,
+			];
+
+			if !contains_any(text, &fields) && !self.tags.iter().any(|tag| contains(tag, text)) {
+				return false;
+			}
+		}
+
+		true
+	}
+}
+
+/// Criteria to search the index with. Text criteria are case-insensitive substring matches.
+#[derive(Clone, Debug, Default)]
+#[allow(clippy::module_name_repetitions)]
+pub struct IndexQuery {
+	/// Text to find in any of the title, artist, creator, difficulty name or tags.
+	pub text: Option<String>,
+	pub artist: Option<String>,
+	pub title: Option<String>,
+	pub creator: Option<String>,
+	pub mode: Option<u8>,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[allow(clippy::module_name_repetitions)]
+pub enum IndexError {
+	#[error(transparent)]
+	Io(#[from] io::Error),
+
+	#[error("Not an osus index file (expected the first line to be {INDEX_HEADER:?})")]
+	InvalidHeader,
+
+	#[error("Invalid index entry at line {line_number}")]
+	InvalidEntry { line_number: usize },
+}
+
+impl IndexError {
+	#[must_use]
+	pub const fn category(&self) -> ErrorCategory {
+		match self {
+			Self::Io(_) => ErrorCategory::Io,
+			Self::InvalidHeader | Self::InvalidEntry { .. } => ErrorCategory::Parse,
+		}
+	}
+}
+
+/// Index of beatmaps, searchable by their metadata.
+#[derive(Clone, Debug, Default)]
+pub struct BeatmapIndex {
+	pub entries: Vec<IndexEntry>,
+}
+
+/// Lists all `.osu` files in a folder recursively.
+fn find_osu_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+	for entry in fs::read_dir(dir)? {
+		let path = entry?.path();
+		if path.is_dir() {
+			find_osu_files(&path, files)?;
+		} else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("osu")) {
+			files.push(path);
+		}
+	}
+
+	Ok(())
+}
+
+impl BeatmapIndex {
+	/// Walks a songs folder (osu!stable `Songs` folder or extracted osu!lazer files) and indexes every `.osu` file.
+	///
+	/// Beatmaps that fail to parse are skipped and returned alongside the index.
+	///
+	/// # Errors
+	///
+	/// This function will return an error if the folder couldn't be walked.
+	pub fn build<P: AsRef<Path>>(root: P) -> io::Result<(Self, Vec<(PathBuf, BeatmapFileParseError)>)> {
+		Self::build_with_progress(root, &NoProgress)
+	}
+
+	/// Same as [`build`](Self::build), reporting every indexed file to `progress`.
+	///
+	/// # Errors
+	///
+	/// This function will return an error if the folder couldn't be walked.
+	pub fn build_with_progress<P: AsRef<Path>>(
+		root: P,
+		progress: &impl ProgressSink,
+	) -> io::Result<(Self, Vec<(PathBuf, BeatmapFileParseError)>)> {
+		let mut files = Vec::new();
+		find_osu_files(root.as_ref(), &mut files)?;
+		files.sort();
+
+		progress.start("Indexing beatmaps", Some(files.len() as u64));
+		let mut index = Self::default();
+		let mut failures = Vec::new();
+		for path in files {
+			progress.advance(1);
+			let contents = match fs::read(&path) {
+				Ok(contents) => contents,
+				Err(e) => {
+					tracing::warn!("Could not read {}: {e}", path.display());
+					continue;
+				}
+			};
+
+			let contents = String::from_utf8_lossy(&contents);
+			match BeatmapFileRef::parse(&contents) {
+				Ok(beatmap) => index.entries.push(IndexEntry::from_beatmap(path, &beatmap)),
+				Err(e) => failures.push((path, e)),
+			}
+		}
+
+		progress.finish(&format!("Indexed {} beatmaps", index.entries.len()));
+		Ok((index, failures))
+	}
+
+	/// Finds every entry matching the query.
+	pub fn search<'a>(&'a self, query: &'a IndexQuery) -> impl Iterator<Item = &'a IndexEntry> + 'a {
+		self.entries.iter().filter(|entry| entry.matches(query))
+	}
+
+	/// Loads an index previously written with [`BeatmapIndex::save`].
+	///
+	/// # Errors
+	///
+	/// This function will return an error if the file couldn't be read or isn't a valid index.
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, IndexError> {
+		let mut lines = BufReader::new(fs::File::open(path)?).lines();
+
+		if lines.next().transpose()?.as_deref() != Some(INDEX_HEADER) {
+			return Err(IndexError::InvalidHeader);
+		}
+
+		let mut index = Self::default();
+		for (i, line) in lines.enumerate() {
+			let line = line?;
+			if line.is_empty() {
+				continue;
+			}
+
+			// the header is line 1
+			let line_number = i + 2;
+			let entry = parse_entry(&line).ok_or(IndexError::InvalidEntry { line_number })?;
+			index.entries.push(entry);
+		}
+
+		Ok(index)
+	}
+
+	/// Writes the index to a file, one tab-separated entry per line.
+	///
+	/// # Errors
+	///
+	/// This function will return an error if the file couldn't be written.
+	pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+		let mut writer = BufWriter::new(fs::File::create(path)?);
+		writeln!(writer, "{INDEX_HEADER}")?;
+
+		for entry in &self.entries {
+			writeln!(writer, "{}", serialize_entry(entry))?;
+		}
+
+		writer.flush()
+	}
+}
+
+fn escape(value: &str) -> String {
+	value.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+fn unescape(value: &str) -> String {
+	let mut result = String::with_capacity(value.len());
+	let mut chars = value.chars();
+	while let Some(c) = chars.next() {
+		if c == '\\' {
+			match chars.next() {
+				Some('t') => result.push('\t'),
+				Some('n') => result.push('\n'),
+				Some(c) => result.push(c),
+				None => {}
+			}
+		} else {
+			result.push(c);
+		}
+	}
+
+	result
+}
+
+fn serialize_optional_id(id: Option<i32>) -> String {
+	id.map(|id| id.to_string()).unwrap_or_default()
+}
+
+fn serialize_entry(entry: &IndexEntry) -> String {
+	let mut line = escape(&entry.path.to_string_lossy());
+	for field in [
+		&entry.title,
+		&entry.title_unicode,
+		&entry.artist,
+		&entry.artist_unicode,
+		&entry.creator,
+		&entry.version,
+		&entry.tags.join(" "),
+	] {
+		let _ = write!(line, "\t{}", escape(field));
+	}
+
+	let _ = write!(
+		line,
+		"\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+		entry.mode,
+		serialize_optional_id(entry.beatmap_id),
+		serialize_optional_id(entry.beatmap_set_id),
+		entry.hp_drain_rate,
+		entry.circle_size,
+		entry.overall_difficulty,
+		entry.approach_rate,
+	);
+
+	line
+}
+
+fn parse_optional_id(value: &str) -> Result<Option<i32>, ParseIntError> {
+	if value.is_empty() {
+		Ok(None)
+	} else {
+		value.parse().map(Some)
+	}
+}
+
+fn parse_entry(line: &str) -> Option<IndexEntry> {
+	let columns: Vec<&str> = line.split('\t').collect();
+	if columns.len() != INDEX_COLUMNS {
+		return None;
+	}
+
+	Some(IndexEntry {
+		path: PathBuf::from(unescape(columns[0])),
+		title: unescape(columns[1]),
+		title_unicode: unescape(columns[2]),
+		artist: unescape(columns[3]),
+		artist_unicode: unescape(columns[4]),
+		creator: unescape(columns[5]),
+		version: unescape(columns[6]),
+		tags: unescape(columns[7])
+			.split(' ')
+			.filter(|tag| !tag.is_empty())
+			.map(str::to_owned)
+			.collect(),
+		mode: columns[8].parse().ok()?,
+		beatmap_id: parse_optional_id(columns[9]).ok()?,
+		beatmap_set_id: parse_optional_id(columns[10]).ok()?,
+		hp_drain_rate: columns[11].parse().ok()?,
+		circle_size: columns[12].parse().ok()?,
+		overall_difficulty: columns[13].parse().ok()?,
+		approach_rate: columns[14].parse().ok()?,
+	})
+}
@@ -1,10 +1,34 @@
 #![warn(clippy::pedantic, clippy::nursery)]
 
 pub mod algos;
+pub mod analysis;
+#[cfg(feature = "cache")]
+pub mod cache;
+pub mod clipboard;
+pub mod edit;
 pub mod file;
+pub mod generate;
+pub mod hitsounds;
+#[cfg(feature = "fs")]
+pub mod index;
+pub mod lint;
+#[cfg(feature = "fs")]
+pub mod mapset;
+pub mod ops;
 pub mod point;
+pub mod progress;
+#[cfg(feature = "render")]
+pub mod render;
+pub mod testing;
+pub mod timeline;
+pub mod timestamp;
+
+// where timestamp parsing was first exposed, kept so that it doesn't move
+pub use timestamp::{parse_timestamp, InvalidTimestampError};
 
 use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::iter::Peekable;
 use std::ops::{Bound, Range, RangeBounds};
 
 use file::beatmap::Timestamp;
@@ -19,38 +43,104 @@ pub fn close_range(a: f64, tolerance: f64) -> Range<f64> {
 	(a - tolerance)..(a + tolerance)
 }
 
+/// Broad kind of an error, given by the `category` method of the library's errors,
+/// so that tools can handle errors of different types the same way (like a command choosing its exit code).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCategory {
+	/// A file couldn't be read or written.
+	Io,
+	/// A file or a value isn't written in the expected format.
+	Parse,
+	/// The input is well-formed but can't be used for the operation, like a negative BPM.
+	Validation,
+	/// The operation doesn't support the game mode of the beatmap.
+	UnsupportedMode,
+}
+
+/// How far apart times can be while still being considered the same, in milliseconds.
+///
+/// osu!stable writes integer times, and the defaults leave room for the rounding it does.
+/// osu!lazer writes sub-millisecond times, for which smaller tolerances can be needed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tolerance {
+	/// Tolerance of [`Timestamped::basically_at`] and [`Timestamped::basically_eq`].
+	pub same_time: f64,
+	/// Tolerance between the elements of a group (like a chord in osu!mania), see [`ExtTimestamped::group_timestamped`].
+	pub grouping: f64,
+}
+
+impl Tolerance {
+	pub const DEFAULT: Self = Self {
+		same_time: 2.0,
+		grouping: 1.0,
+	};
+}
+
+impl Default for Tolerance {
+	fn default() -> Self {
+		Self::DEFAULT
+	}
+}
+
 pub trait Timestamped {
 	fn timestamp(&self) -> Timestamp;
 
 	fn basically_at(&self, timestamp: Timestamp) -> bool {
-		is_close(self.timestamp(), timestamp, 2.0)
+		self.basically_at_with(timestamp, Tolerance::DEFAULT.same_time)
 	}
 
 	fn basically_eq(&self, other: &impl Timestamped) -> bool {
 		self.basically_at(other.timestamp())
 	}
+
+	/// Same as [`basically_at`](Self::basically_at), with a tolerance in milliseconds.
+	fn basically_at_with(&self, timestamp: Timestamp, tolerance: f64) -> bool {
+		is_close(self.timestamp(), timestamp, tolerance)
+	}
+
+	/// Same as [`basically_eq`](Self::basically_eq), with a tolerance in milliseconds.
+	fn basically_eq_with(&self, other: &impl Timestamped, tolerance: f64) -> bool {
+		self.basically_at_with(other.timestamp(), tolerance)
+	}
+}
+
+impl<T: Timestamped + ?Sized> Timestamped for &T {
+	fn timestamp(&self) -> Timestamp {
+		(**self).timestamp()
+	}
 }
 
 pub trait TimestampedSlice<T: Timestamped> {
 	fn between(&self, time_range: impl RangeBounds<Timestamp>) -> &[T];
+	fn between_mut(&mut self, time_range: impl RangeBounds<Timestamp>) -> &mut [T];
 	fn at_timestamp(&self, timestamp: Timestamp) -> Option<&T>;
 }
 
+/// Indices of the elements of a sorted slice that are in a time range.
+fn between_indices<T: Timestamped>(slice: &[T], time_range: &impl RangeBounds<Timestamp>) -> Range<usize> {
+	let start_index = match time_range.start_bound() {
+		Bound::Included(start) => slice.partition_point(|o| o.timestamp() < *start),
+		Bound::Excluded(start) => slice.partition_point(|o| o.timestamp() <= *start),
+		Bound::Unbounded => 0,
+	};
+
+	let end_index = match time_range.end_bound() {
+		Bound::Included(end) => slice.partition_point(|o| o.timestamp() <= *end),
+		Bound::Excluded(end) => slice.partition_point(|o| o.timestamp() < *end),
+		Bound::Unbounded => slice.len(),
+	};
+
+	start_index..end_index
+}
+
 impl<T: Timestamped> TimestampedSlice<T> for [T] {
 	fn between(&self, time_range: impl RangeBounds<Timestamp>) -> &[T] {
-		let start_index = match time_range.start_bound() {
-			Bound::Included(start) => self.partition_point(|o| o.timestamp() < *start),
-			Bound::Excluded(start) => self.partition_point(|o| o.timestamp() <= *start),
-			Bound::Unbounded => 0,
-		};
-
-		let end_index = match time_range.end_bound() {
-			Bound::Included(end) => self.partition_point(|o| o.timestamp() <= *end),
-			Bound::Excluded(end) => self.partition_point(|o| o.timestamp() < *end),
-			Bound::Unbounded => self.len(),
-		};
+		&self[between_indices(self, &time_range)]
+	}
 
-		&self[start_index..end_index]
+	fn between_mut(&mut self, time_range: impl RangeBounds<Timestamp>) -> &mut [T] {
+		let indices = between_indices(self, &time_range);
+		&mut self[indices]
 	}
 
 	fn at_timestamp(&self, timestamp: Timestamp) -> Option<&T> {
@@ -100,9 +190,178 @@ where
 			_ => None,
 		}
 	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		let len = self.0.len() + self.1.len();
+		(len, Some(len))
+	}
+}
+
+impl<T, U> DoubleEndedIterator for InterleavedTimestampedIterator<'_, '_, T, U>
+where
+	T: Timestamped,
+	U: Timestamped,
+{
+	fn next_back(&mut self) -> Option<Self::Item> {
+		match (self.0, self.1) {
+			(&[ref remaining_fst @ .., ref fst], &[ref remaining_snd @ .., ref snd]) => {
+				// the opposite of `next`, which gives the second slice's element first on equal timestamps
+				if fst.timestamp() >= snd.timestamp() {
+					self.0 = remaining_fst;
+					Some(Ok(fst))
+				} else {
+					self.1 = remaining_snd;
+					Some(Err(snd))
+				}
+			}
+			(&[ref remaining @ .., ref fst], &[]) => {
+				self.0 = remaining;
+				Some(Ok(fst))
+			}
+			(&[], &[ref remaining @ .., ref snd]) => {
+				self.1 = remaining;
+				Some(Err(snd))
+			}
+			_ => None,
+		}
+	}
+}
+
+impl<T: Timestamped, U: Timestamped> ExactSizeIterator for InterleavedTimestampedIterator<'_, '_, T, U> {}
+
+/// Owned version of [`InterleavedTimestampedIterator`], see [`IntoExtTimestamped::into_interleave_timestamped`].
+pub struct IntoInterleavedTimestampedIterator<T, U>(VecDeque<T>, VecDeque<U>)
+where
+	T: Timestamped,
+	U: Timestamped;
+
+impl<T, U> Iterator for IntoInterleavedTimestampedIterator<T, U>
+where
+	T: Timestamped,
+	U: Timestamped,
+{
+	type Item = std::result::Result<T, U>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		match (self.0.front(), self.1.front()) {
+			(Some(fst), Some(snd)) if fst.timestamp() < snd.timestamp() => self.0.pop_front().map(Ok),
+			(Some(_), None) => self.0.pop_front().map(Ok),
+			_ => self.1.pop_front().map(Err),
+		}
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		let len = self.0.len() + self.1.len();
+		(len, Some(len))
+	}
+}
+
+impl<T, U> DoubleEndedIterator for IntoInterleavedTimestampedIterator<T, U>
+where
+	T: Timestamped,
+	U: Timestamped,
+{
+	fn next_back(&mut self) -> Option<Self::Item> {
+		match (self.0.back(), self.1.back()) {
+			(Some(fst), Some(snd)) if fst.timestamp() >= snd.timestamp() => self.0.pop_back().map(Ok),
+			(Some(_), None) => self.0.pop_back().map(Ok),
+			_ => self.1.pop_back().map(Err),
+		}
+	}
+}
+
+impl<T: Timestamped, U: Timestamped> ExactSizeIterator for IntoInterleavedTimestampedIterator<T, U> {}
+
+/// Merges any amount of sorted streams of timestamped items into a single sorted stream.
+///
+/// Items at the same timestamp come in the order of their streams, and in their order within a stream.
+/// Streams of different types can be merged by mapping them to a common enum first.
+pub struct MultiInterleavedIterator<I: Iterator> {
+	streams: Vec<Peekable<I>>,
+}
+
+impl<I> MultiInterleavedIterator<I>
+where
+	I: Iterator,
+	I::Item: Timestamped,
+{
+	pub fn new(streams: impl IntoIterator<Item = I>) -> Self {
+		Self {
+			streams: streams.into_iter().map(Iterator::peekable).collect(),
+		}
+	}
+}
+
+impl<I> Iterator for MultiInterleavedIterator<I>
+where
+	I: Iterator,
+	I::Item: Timestamped,
+{
+	type Item = I::Item;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let mut earliest: Option<(usize, Timestamp)> = None;
+		for (index, stream) in self.streams.iter_mut().enumerate() {
+			let Some(item) = stream.peek() else {
+				continue;
+			};
+
+			// strictly earlier, so that the first stream wins on equal timestamps
+			let time = item.timestamp();
+			if earliest.is_none_or(|(_, earliest_time)| time < earliest_time) {
+				earliest = Some((index, time));
+			}
+		}
+
+		let (index, _) = earliest?;
+		self.streams[index].next()
+	}
 }
 
-pub struct GroupedTimestampedIterator<'a, T>(&'a [T])
+/// Number of consecutive elements that are basically at the same timestamp as the first one.
+fn group_len<'a, T: Timestamped + 'a>(mut elements: impl Iterator<Item = &'a T>, tolerance: f64) -> usize {
+	let Some(first) = elements.next() else {
+		return 0;
+	};
+
+	1 + elements
+		.take_while(|elem| is_close(elem.timestamp(), first.timestamp(), tolerance))
+		.count()
+}
+
+/// Lengths of the groups left to iterate, measured from the front like forward iteration.
+///
+/// They're only computed once something pulls from the back, so that both ends agree on the boundaries.
+#[derive(Default)]
+struct GroupLens(Option<VecDeque<usize>>);
+
+impl GroupLens {
+	fn front<T: Timestamped>(&mut self, elements: &[T], tolerance: f64) -> usize {
+		self.0.as_mut().map_or_else(
+			|| group_len(elements.iter(), tolerance),
+			|lens| lens.pop_front().unwrap_or(0),
+		)
+	}
+
+	fn back<T: Timestamped>(&mut self, mut elements: &[T], tolerance: f64) -> usize {
+		let lens = self.0.get_or_insert_with(|| {
+			let mut lens = VecDeque::new();
+			while !elements.is_empty() {
+				let count = group_len(elements.iter(), tolerance);
+				lens.push_back(count);
+				elements = &elements[count..];
+			}
+			lens
+		});
+
+		lens.pop_back().unwrap_or(0)
+	}
+}
+
+/// Groups of elements that are basically at the same timestamp, see [`ExtTimestamped::group_timestamped`].
+///
+/// Groups are measured from their first element, in both directions.
+pub struct GroupedTimestampedIterator<'a, T>(&'a [T], f64, GroupLens)
 where
 	T: Timestamped;
 
@@ -113,24 +372,35 @@ where
 	type Item = &'a [T];
 
 	fn next(&mut self) -> Option<Self::Item> {
-		if let Some(elem0) = self.0.first() {
-			// number of consecutive objects that are basically at the same timestamp
-			let count = (self.0.iter())
-				.take_while(|elem| is_close(elem.timestamp(), elem0.timestamp(), 1.0))
-				.count();
-
-			let (group, remaining) = self.0.split_at(count);
-
-			self.0 = remaining;
-			Some(group)
-		} else {
-			// no elements left
-			None
+		let count = self.2.front(self.0, self.1);
+		if count == 0 {
+			return None;
+		}
+
+		let (group, remaining) = self.0.split_at(count);
+		self.0 = remaining;
+		Some(group)
+	}
+}
+
+impl<T> DoubleEndedIterator for GroupedTimestampedIterator<'_, T>
+where
+	T: Timestamped,
+{
+	fn next_back(&mut self) -> Option<Self::Item> {
+		let count = self.2.back(self.0, self.1);
+		if count == 0 {
+			return None;
 		}
+
+		let (remaining, group) = self.0.split_at(self.0.len() - count);
+		self.0 = remaining;
+		Some(group)
 	}
 }
 
-pub struct GroupedTimestampedIteratorMut<'a, T>(&'a mut [T])
+/// Mutable version of [`GroupedTimestampedIterator`].
+pub struct GroupedTimestampedIteratorMut<'a, T>(&'a mut [T], f64, GroupLens)
 where
 	T: Timestamped;
 
@@ -141,21 +411,60 @@ where
 	type Item = &'a mut [T];
 
 	fn next(&mut self) -> Option<Self::Item> {
-		if let Some(elem0) = self.0.first() {
-			// number of consecutive objects that are basically at the same timestamp
-			let count = (self.0.iter())
-				.take_while(|elem| is_close(elem.timestamp(), elem0.timestamp(), 1.0))
-				.count();
-
-			let tmp = std::mem::take(&mut self.0);
-			let (group, remaining) = tmp.split_at_mut(count);
-
-			self.0 = remaining;
-			Some(group)
-		} else {
-			// no elements left
-			None
+		let count = self.2.front(self.0, self.1);
+		if count == 0 {
+			return None;
 		}
+
+		let tmp = std::mem::take(&mut self.0);
+		let (group, remaining) = tmp.split_at_mut(count);
+		self.0 = remaining;
+		Some(group)
+	}
+}
+
+impl<T> DoubleEndedIterator for GroupedTimestampedIteratorMut<'_, T>
+where
+	T: Timestamped,
+{
+	fn next_back(&mut self) -> Option<Self::Item> {
+		let count = self.2.back(self.0, self.1);
+		if count == 0 {
+			return None;
+		}
+
+		let tmp = std::mem::take(&mut self.0);
+		let split = tmp.len() - count;
+		let (remaining, group) = tmp.split_at_mut(split);
+		self.0 = remaining;
+		Some(group)
+	}
+}
+
+/// Owned version of [`GroupedTimestampedIterator`], see [`IntoExtTimestamped::into_group_timestamped`].
+pub struct IntoGroupedTimestampedIterator<T>(VecDeque<T>, f64, GroupLens)
+where
+	T: Timestamped;
+
+impl<T> Iterator for IntoGroupedTimestampedIterator<T>
+where
+	T: Timestamped,
+{
+	type Item = Vec<T>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let count = self.2.front(self.0.make_contiguous(), self.1);
+		(count > 0).then(|| self.0.drain(..count).collect())
+	}
+}
+
+impl<T> DoubleEndedIterator for IntoGroupedTimestampedIterator<T>
+where
+	T: Timestamped,
+{
+	fn next_back(&mut self) -> Option<Self::Item> {
+		let count = self.2.back(self.0.make_contiguous(), self.1);
+		(count > 0).then(|| self.0.split_off(self.0.len() - count).into())
 	}
 }
 
@@ -167,8 +476,19 @@ pub trait ExtTimestamped {
 		other: &'b [U],
 	) -> InterleavedTimestampedIterator<'_, 'b, Self::Item, U>;
 
-	fn group_timestamped(&self) -> GroupedTimestampedIterator<'_, Self::Item>;
-	fn group_timestamped_mut(&mut self) -> GroupedTimestampedIteratorMut<'_, Self::Item>;
+	fn group_timestamped(&self) -> GroupedTimestampedIterator<'_, Self::Item> {
+		self.group_timestamped_with(Tolerance::DEFAULT.grouping)
+	}
+
+	fn group_timestamped_mut(&mut self) -> GroupedTimestampedIteratorMut<'_, Self::Item> {
+		self.group_timestamped_mut_with(Tolerance::DEFAULT.grouping)
+	}
+
+	/// Same as [`group_timestamped`](Self::group_timestamped), with a tolerance in milliseconds.
+	fn group_timestamped_with(&self, tolerance: f64) -> GroupedTimestampedIterator<'_, Self::Item>;
+
+	/// Same as [`group_timestamped_mut`](Self::group_timestamped_mut), with a tolerance in milliseconds.
+	fn group_timestamped_mut_with(&mut self, tolerance: f64) -> GroupedTimestampedIteratorMut<'_, Self::Item>;
 }
 
 impl<T: Timestamped> ExtTimestamped for [T] {
@@ -181,11 +501,91 @@ impl<T: Timestamped> ExtTimestamped for [T] {
 		InterleavedTimestampedIterator(self, other)
 	}
 
-	fn group_timestamped(&self) -> GroupedTimestampedIterator<'_, Self::Item> {
-		GroupedTimestampedIterator(self)
+	fn group_timestamped_with(&self, tolerance: f64) -> GroupedTimestampedIterator<'_, Self::Item> {
+		GroupedTimestampedIterator(self, tolerance, GroupLens::default())
 	}
 
-	fn group_timestamped_mut(&mut self) -> GroupedTimestampedIteratorMut<'_, Self::Item> {
-		GroupedTimestampedIteratorMut(self)
+	fn group_timestamped_mut_with(&mut self, tolerance: f64) -> GroupedTimestampedIteratorMut<'_, Self::Item> {
+		GroupedTimestampedIteratorMut(self, tolerance, GroupLens::default())
+	}
+}
+
+/// Owned versions of the [`ExtTimestamped`] iterators, which give back the elements themselves.
+pub trait IntoExtTimestamped {
+	type Item: Timestamped;
+
+	fn into_interleave_timestamped<U: Timestamped>(
+		self,
+		other: Vec<U>,
+	) -> IntoInterleavedTimestampedIterator<Self::Item, U>;
+
+	fn into_group_timestamped(self) -> IntoGroupedTimestampedIterator<Self::Item>
+	where
+		Self: Sized,
+	{
+		self.into_group_timestamped_with(Tolerance::DEFAULT.grouping)
+	}
+
+	/// Same as [`into_group_timestamped`](Self::into_group_timestamped), with a tolerance in milliseconds.
+	fn into_group_timestamped_with(self, tolerance: f64) -> IntoGroupedTimestampedIterator<Self::Item>;
+}
+
+impl<T: Timestamped> IntoExtTimestamped for Vec<T> {
+	type Item = T;
+
+	fn into_interleave_timestamped<U: Timestamped>(
+		self,
+		other: Vec<U>,
+	) -> IntoInterleavedTimestampedIterator<Self::Item, U> {
+		IntoInterleavedTimestampedIterator(self.into(), other.into())
+	}
+
+	fn into_group_timestamped_with(self, tolerance: f64) -> IntoGroupedTimestampedIterator<Self::Item> {
+		IntoGroupedTimestampedIterator(self.into(), tolerance, GroupLens::default())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[derive(Debug, Clone, Copy, PartialEq)]
+	struct At(f64);
+
+	impl Timestamped for At {
+		fn timestamp(&self) -> Timestamp {
+			self.0
+		}
+	}
+
+	fn times<T: AsRef<[At]>>(group: T) -> Vec<f64> {
+		group.as_ref().iter().map(|at| at.0).collect()
+	}
+
+	#[test]
+	fn grouping_backwards_gives_the_forward_groups() {
+		// 8 is close to 0 and 16 is close to 8 but not to 0, so anchoring on either end would differ
+		let elements: Vec<At> = [0.0, 8.0, 16.0, 100.0, 105.0].into_iter().map(At).collect();
+		let forward: Vec<Vec<f64>> = elements.group_timestamped_with(10.0).map(times).collect();
+		assert_eq!(forward, [vec![0.0, 8.0], vec![16.0], vec![100.0, 105.0]]);
+
+		let mut backward: Vec<Vec<f64>> = elements.group_timestamped_with(10.0).rev().map(times).collect();
+		backward.reverse();
+		assert_eq!(backward, forward);
+
+		let mut groups = elements.group_timestamped_with(10.0);
+		assert_eq!(groups.next_back().map(times), Some(vec![100.0, 105.0]));
+		assert_eq!(groups.next().map(times), Some(vec![0.0, 8.0]));
+		assert_eq!(groups.next_back().map(times), Some(vec![16.0]));
+		assert_eq!(groups.next(), None);
+
+		let mut elements_mut = elements.clone();
+		let mut backward_mut: Vec<Vec<f64>> = elements_mut.group_timestamped_mut_with(10.0).rev().map(times).collect();
+		backward_mut.reverse();
+		assert_eq!(backward_mut, forward);
+
+		let mut backward_owned: Vec<Vec<f64>> = elements.into_group_timestamped_with(10.0).rev().map(times).collect();
+		backward_owned.reverse();
+		assert_eq!(backward_owned, forward);
 	}
 }
@@ -0,0 +1,341 @@
+use std::fmt;
+
+use crate::algos::catch::{movements, EDGE_DASH_DISTANCE};
+use crate::algos::{closest_tick, object_bounds};
+use crate::analysis::spinner_requirements;
+use crate::file::beatmap::{BeatmapFile, HitObjectParams, Timestamp};
+use crate::point::Rect;
+use crate::timestamp::{format_timestamp, EditorTimestamp, ObjectReference};
+use crate::Timestamped;
+
+/// Beat divisors objects are expected to be snapped to.
+pub(crate) const SNAP_DIVISORS: [u32; 8] = [1, 2, 3, 4, 6, 8, 12, 16];
+
+/// How bad an issue is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+	/// Something that should probably be looked at.
+	Warning,
+	/// Something that makes the beatmap unplayable or unrankable.
+	Problem,
+}
+
+impl fmt::Display for Severity {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::Warning => "warning",
+			Self::Problem => "problem",
+		})
+	}
+}
+
+/// An issue found in a beatmap.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LintIssue {
+	/// Time at which the issue happens.
+	pub time: Timestamp,
+	/// How bad the issue is.
+	pub severity: Severity,
+	/// Hit objects concerned by the issue, as they would be referenced in the editor.
+	pub objects: Vec<ObjectReference>,
+	/// Description of the issue.
+	pub message: String,
+}
+
+impl LintIssue {
+	/// Timestamp of the issue, as written in the osu! editor.
+	#[must_use]
+	pub fn editor_timestamp(&self) -> EditorTimestamp {
+		EditorTimestamp {
+			time: self.time,
+			objects: self.objects.clone(),
+		}
+	}
+}
+
+/// References to a single hit object of a beatmap.
+fn object_references(beatmap: &BeatmapFile, index: usize) -> Vec<ObjectReference> {
+	EditorTimestamp::from_hit_objects(beatmap, &[index]).objects
+}
+
+/// Checks that every spinner can be cleared.
+#[must_use]
+pub fn check_spinners(beatmap: &BeatmapFile) -> Vec<LintIssue> {
+	let mut issues = Vec::new();
+
+	for spinner in spinner_requirements(beatmap) {
+		let objects = (beatmap.hit_objects.iter())
+			.position(|ho| ho.is_spinner() && ho.basically_at(spinner.time))
+			.map(|index| object_references(beatmap, index))
+			.unwrap_or_default();
+
+		if spinner.impossible {
+			issues.push(LintIssue {
+				time: spinner.time,
+				severity: Severity::Problem,
+				objects,
+				message: format!(
+					"spinner is impossible to clear ({} rotations in {}ms, {:.0} RPM)",
+					spinner.required_rotations, spinner.duration, spinner.required_rpm
+				),
+			});
+		} else if spinner.too_short {
+			issues.push(LintIssue {
+				time: spinner.time,
+				severity: Severity::Warning,
+				objects,
+				message: format!(
+					"spinner is too short ({} rotations in {}ms, {:.0} RPM)",
+					spinner.required_rotations, spinner.duration, spinner.required_rpm
+				),
+			});
+		}
+	}
+
+	issues
+}
+
+/// Checks that every hit object (and the end of every spinner and hold) is snapped to a tick of the timing.
+///
+/// Like in the osu! editor, objects more than 1ms away from a tick are considered unsnapped.
+#[must_use]
+pub fn check_unsnapped(beatmap: &BeatmapFile) -> Vec<LintIssue> {
+	let unsnap = |time: Timestamp| {
+		let tick = closest_tick(&beatmap.timing_points, time, &SNAP_DIVISORS)?;
+		let offset = (time - tick).round();
+		(offset.abs() > 1.0).then_some(offset)
+	};
+
+	let mut issues = Vec::new();
+	for (index, hit_object) in beatmap.hit_objects.iter().enumerate() {
+		if let Some(offset) = unsnap(hit_object.time) {
+			issues.push(LintIssue {
+				time: hit_object.time,
+				severity: Severity::Problem,
+				objects: object_references(beatmap, index),
+				message: format!("unsnapped by {offset}ms"),
+			});
+		}
+
+		if let HitObjectParams::Spinner { end_time } | HitObjectParams::Hold { end_time, .. } = hit_object.object_params
+		{
+			if let Some(offset) = unsnap(end_time) {
+				issues.push(LintIssue {
+					time: end_time,
+					severity: Severity::Problem,
+					objects: object_references(beatmap, index),
+					message: format!("end is unsnapped by {offset}ms"),
+				});
+			}
+		}
+	}
+
+	issues
+}
+
+/// Checks the movements of an osu!catch beatmap for edge dashes,
+/// dashes that are only a few pixels short of being a hyperdash and are notoriously hard to catch.
+#[must_use]
+pub fn check_catch_edge_dashes(beatmap: &BeatmapFile) -> Vec<LintIssue> {
+	(movements(beatmap).into_iter())
+		.filter(|movement| !movement.hyperdash && movement.distance_to_hyperdash < EDGE_DASH_DISTANCE)
+		// fruits that are that close to each other are simply stacked
+		.filter(|movement| (movement.to.x - movement.from.x).abs() > EDGE_DASH_DISTANCE)
+		.map(|movement| {
+			let mut indices = vec![movement.from.index, movement.to.index];
+			indices.dedup();
+
+			LintIssue {
+				time: movement.to.time,
+				severity: Severity::Warning,
+				objects: EditorTimestamp::from_hit_objects(beatmap, &indices).objects,
+				message: format!(
+					"edge dash ({:.1}px away from a hyperdash)",
+					movement.distance_to_hyperdash
+				),
+			}
+		})
+		.collect()
+}
+
+/// Checks that every hit object stays in the playfield, including the whole path of sliders.
+///
+/// Spinners are ignored, since they always spin in the middle of the playfield.
+#[must_use]
+pub fn check_bounds(beatmap: &BeatmapFile) -> Vec<LintIssue> {
+	let mut issues = Vec::new();
+
+	for (index, hit_object) in beatmap.hit_objects.iter().enumerate() {
+		let Some(bounds) = object_bounds(hit_object) else {
+			continue;
+		};
+
+		let overflow = Rect::PLAYFIELD.overflow(bounds);
+		if overflow > 0.0 {
+			let object = if hit_object.is_slider() { "slider" } else { "object" };
+			issues.push(LintIssue {
+				time: hit_object.time,
+				severity: Severity::Problem,
+				objects: object_references(beatmap, index),
+				message: format!("{object} goes out of the playfield by {overflow:.1}px"),
+			});
+		}
+	}
+
+	issues
+}
+
+/// Whether a character has no business in metadata: control characters and invisible ones, like zero-width spaces.
+fn is_disallowed_char(c: char) -> bool {
+	c.is_control() || matches!(c, '\u{200B}'..='\u{200F}' | '\u{2028}'..='\u{202E}' | '\u{2060}' | '\u{FEFF}')
+}
+
+/// Checks the text of a metadata field for disallowed characters and stray spaces.
+fn check_metadata_text(issues: &mut Vec<LintIssue>, field: &str, value: &str) {
+	let mut push = |severity, message| {
+		issues.push(LintIssue {
+			time: 0.0,
+			severity,
+			objects: Vec::new(),
+			message,
+		});
+	};
+
+	if let Some(c) = value.chars().find(|&c| is_disallowed_char(c)) {
+		push(
+			Severity::Problem,
+			format!(
+				"{field} contains the invisible or control character U+{:04X}",
+				u32::from(c)
+			),
+		);
+	}
+
+	if value.trim() != value || value.contains("  ") {
+		push(
+			Severity::Warning,
+			format!("{field} {value:?} has leading, trailing or repeated spaces"),
+		);
+	}
+}
+
+/// Checks the metadata of a beatmap: romanised fields have to be ASCII, with the original text in
+/// the unicode fields, and no field should contain invisible characters.
+///
+/// Issues are at time 0, since they don't concern a part of the beatmap.
+#[must_use]
+pub fn check_metadata(beatmap: &BeatmapFile) -> Vec<LintIssue> {
+	let mut issues = Vec::new();
+	let Some(metadata) = &beatmap.metadata else {
+		return issues;
+	};
+
+	let mut push = |severity, message| {
+		issues.push(LintIssue {
+			time: 0.0,
+			severity,
+			objects: Vec::new(),
+			message,
+		});
+	};
+
+	let pairs = [
+		("title", &metadata.title, &metadata.title_unicode),
+		("artist", &metadata.artist, &metadata.artist_unicode),
+	];
+	for (field, romanised, unicode) in pairs {
+		if romanised.trim().is_empty() {
+			push(Severity::Problem, format!("romanised {field} is empty"));
+		} else if !romanised.is_ascii() {
+			push(
+				Severity::Problem,
+				format!("romanised {field} {romanised:?} isn't ASCII, the original belongs in the unicode {field}"),
+			);
+		}
+
+		if unicode.trim().is_empty() {
+			if !romanised.trim().is_empty() {
+				push(
+					Severity::Warning,
+					format!("unicode {field} is empty, it should be the original {field}"),
+				);
+			}
+		} else if unicode.is_ascii() && romanised.is_ascii() && unicode != romanised {
+			push(
+				Severity::Warning,
+				format!("unicode {field} {unicode:?} is ASCII but differs from the romanised {field} {romanised:?}"),
+			);
+		}
+	}
+
+	let texts = [
+		("romanised title", &metadata.title),
+		("unicode title", &metadata.title_unicode),
+		("romanised artist", &metadata.artist),
+		("unicode artist", &metadata.artist_unicode),
+		("creator", &metadata.creator),
+		("difficulty name", &metadata.version),
+		("source", &metadata.source),
+	];
+	for (field, value) in texts {
+		check_metadata_text(&mut issues, field, value);
+	}
+
+	for tag in &metadata.tags {
+		check_metadata_text(&mut issues, "tag", tag);
+	}
+
+	issues
+}
+
+/// Runs every check on a beatmap, returning the issues sorted by time.
+///
+/// Mode-specific checks only run on beatmaps of that mode.
+#[must_use]
+pub fn lint(beatmap: &BeatmapFile) -> Vec<LintIssue> {
+	let mut issues = check_metadata(beatmap);
+	issues.extend(check_spinners(beatmap));
+	issues.extend(check_unsnapped(beatmap));
+	if beatmap.general.as_ref().is_none_or(|g| g.mode == 0) {
+		issues.extend(check_bounds(beatmap));
+	}
+	if beatmap.general.as_ref().is_some_and(|g| g.mode == 2) {
+		issues.extend(check_catch_edge_dashes(beatmap));
+	}
+	issues.sort_by(|a, b| a.time.total_cmp(&b.time));
+	issues
+}
+
+/// How issues are written in a report.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReportStyle {
+	/// Ready to be posted on the osu! website, like `00:12:345 (1) - unsnapped by 3ms`.
+	#[default]
+	Forum,
+	/// Same as [`ReportStyle::Forum`], with the severity of each issue.
+	Detailed,
+}
+
+/// Writes issues as a report, one line per issue.
+#[must_use]
+pub fn format_report(issues: &[LintIssue], style: ReportStyle) -> String {
+	let mut report = String::new();
+
+	for issue in issues {
+		let timestamp = if issue.objects.is_empty() {
+			format!("{} -", format_timestamp(issue.time))
+		} else {
+			issue.editor_timestamp().to_string()
+		};
+
+		let line = match style {
+			ReportStyle::Forum => format!("{timestamp} {}", issue.message),
+			ReportStyle::Detailed => format!("{timestamp} [{}] {}", issue.severity, issue.message),
+		};
+
+		report.push_str(&line);
+		report.push('\n');
+	}
+
+	report
+}
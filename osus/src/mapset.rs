@@ -0,0 +1,266 @@
+//! Beatmap sets: a folder containing several difficulties of the same song, with their shared assets.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub mod assets;
+#[cfg(feature = "audio")]
+pub mod audio;
+pub mod rank;
+pub mod samples;
+
+use crate::file::beatmap::errors::BeatmapFileParseError;
+use crate::file::beatmap::{BeatmapFile, MetadataSection};
+use crate::ErrorCategory;
+
+use self::assets::{normalize_asset_path, referenced_assets, AssetReference};
+
+/// Characters osu! removes from the name of a `.osu` file.
+const INVALID_FILENAME_CHARS: [char; 9] = ['\\', '/', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Name osu! gives to the `.osu` file of a beatmap: `Artist - Title (Creator) [Version].osu`,
+/// without the characters that can't be in file names.
+#[must_use]
+pub fn standard_filename(metadata: &MetadataSection) -> String {
+	let MetadataSection {
+		artist,
+		title,
+		creator,
+		version,
+		..
+	} = metadata;
+
+	let filename = format!("{artist} - {title} ({creator}) [{version}].osu");
+	filename.replace(INVALID_FILENAME_CHARS, "")
+}
+
+/// A difficulty of a beatmap set.
+#[derive(Clone, Debug)]
+pub struct Difficulty {
+	/// Name of the `.osu` file of the difficulty.
+	pub filename: String,
+	pub beatmap: BeatmapFile,
+}
+
+/// An asset referenced by a difficulty that doesn't exist in the beatmap set.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MissingAsset {
+	/// Name of the `.osu` file of the difficulty referencing the asset.
+	pub difficulty: String,
+	pub asset: AssetReference,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[allow(clippy::module_name_repetitions)]
+pub enum BeatmapSetError {
+	#[error(transparent)]
+	Io(#[from] io::Error),
+
+	#[error(transparent)]
+	Parse(#[from] BeatmapFileParseError),
+
+	#[cfg(feature = "osz")]
+	#[error("Invalid .osz archive")]
+	Zip(
+		#[from]
+		#[source]
+		zip::result::ZipError,
+	),
+
+	#[error("Beatmap sets loaded from an .osz archive cannot be saved in place")]
+	SaveArchive,
+}
+
+impl BeatmapSetError {
+	#[must_use]
+	pub const fn category(&self) -> ErrorCategory {
+		match self {
+			Self::Io(_) => ErrorCategory::Io,
+			Self::Parse(e) => e.category(),
+			#[cfg(feature = "osz")]
+			Self::Zip(_) => ErrorCategory::Parse,
+			Self::SaveArchive => ErrorCategory::Validation,
+		}
+	}
+}
+
+/// A set of difficulties of the same song, sharing the same folder.
+#[derive(Clone, Debug)]
+#[allow(clippy::module_name_repetitions)]
+pub struct BeatmapSet {
+	/// Folder or `.osz` archive the beatmap set was loaded from.
+	pub path: PathBuf,
+	/// Every `.osu` file of the beatmap set.
+	pub difficulties: Vec<Difficulty>,
+	/// Every other file of the beatmap set, relative to its folder.
+	pub shared_assets: Vec<PathBuf>,
+}
+
+fn is_osu_file(path: &Path) -> bool {
+	path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("osu"))
+}
+
+/// Lists all files in a folder recursively, relative to `root`.
+fn list_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+	for entry in fs::read_dir(dir)? {
+		let path = entry?.path();
+		if path.is_dir() {
+			list_files(root, &path, files)?;
+		} else if let Ok(relative) = path.strip_prefix(root) {
+			files.push(relative.to_path_buf());
+		}
+	}
+
+	Ok(())
+}
+
+impl BeatmapSet {
+	/// Loads a beatmap set from a folder, or from an `.osz` archive if the `osz` feature is enabled.
+	///
+	/// # Errors
+	///
+	/// This function will return an error if a file couldn't be read or if a difficulty couldn't be parsed.
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, BeatmapSetError> {
+		let path = path.as_ref();
+
+		#[cfg(feature = "osz")]
+		if path.is_file() {
+			return Self::load_osz(path);
+		}
+
+		Self::load_dir(path)
+	}
+
+	/// Loads a beatmap set from a folder.
+	///
+	/// # Errors
+	///
+	/// This function will return an error if a file couldn't be read or if a difficulty couldn't be parsed.
+	pub fn load_dir<P: AsRef<Path>>(path: P) -> Result<Self, BeatmapSetError> {
+		let path = path.as_ref();
+
+		let mut files = Vec::new();
+		list_files(path, path, &mut files)?;
+		files.sort();
+
+		let mut difficulties = Vec::new();
+		let mut shared_assets = Vec::new();
+		for file in files {
+			if is_osu_file(&file) && file.parent() == Some(Path::new("")) {
+				difficulties.push(Difficulty {
+					filename: file.to_string_lossy().into_owned(),
+					beatmap: BeatmapFile::parse(path.join(&file))?,
+				});
+			} else {
+				shared_assets.push(file);
+			}
+		}
+
+		Ok(Self {
+			path: path.to_path_buf(),
+			difficulties,
+			shared_assets,
+		})
+	}
+
+	/// Loads a beatmap set from an `.osz` archive.
+	///
+	/// # Errors
+	///
+	/// This function will return an error if the archive couldn't be read or if a difficulty couldn't be parsed.
+	#[cfg(feature = "osz")]
+	pub fn load_osz<P: AsRef<Path>>(path: P) -> Result<Self, BeatmapSetError> {
+		let path = path.as_ref();
+		let mut archive = zip::ZipArchive::new(io::BufReader::new(File::open(path)?))?;
+
+		let mut difficulties = Vec::new();
+		let mut shared_assets = Vec::new();
+		for i in 0..archive.len() {
+			let entry = archive.by_index(i)?;
+			if entry.is_dir() {
+				continue;
+			}
+
+			let Some(file) = entry.enclosed_name() else {
+				continue;
+			};
+
+			if is_osu_file(&file) && file.parent() == Some(Path::new("")) {
+				difficulties.push(Difficulty {
+					filename: file.to_string_lossy().into_owned(),
+					beatmap: BeatmapFile::parse_reader(io::BufReader::new(entry))?,
+				});
+			} else {
+				shared_assets.push(file);
+			}
+		}
+
+		difficulties.sort_by(|a, b| a.filename.cmp(&b.filename));
+		shared_assets.sort();
+
+		Ok(Self {
+			path: path.to_path_buf(),
+			difficulties,
+			shared_assets,
+		})
+	}
+
+	/// Whether an asset path exists among the shared assets.
+	#[must_use]
+	pub fn has_asset(&self, asset_path: &str) -> bool {
+		let asset_path = normalize_asset_path(asset_path);
+		(self.shared_assets.iter()).any(|a| normalize_asset_path(&a.to_string_lossy()) == asset_path)
+	}
+
+	/// Finds every asset that is referenced by a difficulty but doesn't exist in the beatmap set.
+	#[must_use]
+	pub fn validate(&self) -> Vec<MissingAsset> {
+		let mut missing = Vec::new();
+
+		for difficulty in &self.difficulties {
+			for asset in referenced_assets(&difficulty.beatmap) {
+				if !self.has_asset(&asset.path) {
+					missing.push(MissingAsset {
+						difficulty: difficulty.filename.clone(),
+						asset,
+					});
+				}
+			}
+		}
+
+		missing
+	}
+
+	/// Writes every difficulty back to the beatmap set's folder.
+	///
+	/// # Errors
+	///
+	/// This function will return an error if a file couldn't be written,
+	/// or if the beatmap set was loaded from an `.osz` archive.
+	pub fn save(&self) -> Result<(), BeatmapSetError> {
+		if !self.path.is_dir() {
+			return Err(BeatmapSetError::SaveArchive);
+		}
+
+		self.save_to(&self.path)?;
+		Ok(())
+	}
+
+	/// Writes every difficulty to a folder, creating it if needed. Assets are not copied.
+	///
+	/// # Errors
+	///
+	/// This function will return an error if a file couldn't be written.
+	pub fn save_to<P: AsRef<Path>>(&self, dir: P) -> io::Result<()> {
+		let dir = dir.as_ref();
+		fs::create_dir_all(dir)?;
+
+		for difficulty in &self.difficulties {
+			let mut file = io::BufWriter::new(File::create(dir.join(&difficulty.filename))?);
+			difficulty.beatmap.deserialize(&mut file)?;
+		}
+
+		Ok(())
+	}
+}
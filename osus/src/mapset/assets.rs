@@ -0,0 +1,163 @@
+//! Folder-aware tooling for the files referenced by the difficulties of a beatmap set.
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use super::BeatmapSet;
+use crate::file::beatmap::{BeatmapFile, Event, EventParams};
+
+/// Kind of file that a beatmap can reference.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AssetKind {
+	Audio,
+	Background,
+	Video,
+	/// Storyboard sample or custom hit object sample.
+	Sample,
+}
+
+/// A file referenced by a beatmap.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssetReference {
+	pub kind: AssetKind,
+	/// Path of the asset relative to the beatmap folder, without quotes and with forward slashes.
+	pub path: String,
+}
+
+/// Lists every file referenced by a beatmap, without duplicates.
+#[must_use]
+pub fn referenced_assets(beatmap: &BeatmapFile) -> Vec<AssetReference> {
+	let mut assets: Vec<AssetReference> = Vec::new();
+	let mut push = |kind, path: &str| {
+		let path = path.trim().replace('\\', "/");
+		if !path.is_empty() && !assets.iter().any(|a| a.kind == kind && a.path == path) {
+			assets.push(AssetReference { kind, path });
+		}
+	};
+
+	if let Some(general) = &beatmap.general {
+		push(AssetKind::Audio, &general.audio_filename);
+	}
+
+	for event in &beatmap.events {
+		match &event.params {
+			EventParams::Background { filename, .. } => push(AssetKind::Background, filename),
+			EventParams::Video { filename, .. } => push(AssetKind::Video, filename),
+			EventParams::Sample { filename, .. } => push(AssetKind::Sample, filename),
+			EventParams::Break { .. } => (),
+		}
+	}
+
+	for hit_object in &beatmap.hit_objects {
+		if let Some(filename) = &hit_object.hit_sample.filename {
+			push(AssetKind::Sample, filename);
+		}
+	}
+
+	assets
+}
+
+/// Normalizes an asset path for comparisons. osu! runs on case-insensitive file systems on Windows.
+pub(crate) fn normalize_asset_path(path: &str) -> String {
+	path.replace('\\', "/").to_lowercase()
+}
+
+/// Lists every file referenced by the difficulties of a beatmap set, without duplicates.
+#[must_use]
+pub fn list_assets(set: &BeatmapSet) -> Vec<AssetReference> {
+	let mut assets: Vec<AssetReference> = Vec::new();
+
+	for difficulty in &set.difficulties {
+		for asset in referenced_assets(&difficulty.beatmap) {
+			if !assets.contains(&asset) {
+				assets.push(asset);
+			}
+		}
+	}
+
+	assets
+}
+
+/// Replaces the background image of a beatmap, adding a background event if there is none.
+pub fn set_background(beatmap: &mut BeatmapFile, filename: &str) {
+	let mut replaced = false;
+	for event in &mut beatmap.events {
+		if let EventParams::Background { filename: bg, .. } = &mut event.params {
+			filename.clone_into(bg);
+			replaced = true;
+		}
+	}
+
+	if !replaced {
+		beatmap.events.insert(
+			0,
+			Event {
+				event_type: "0".to_owned(),
+				start_time: 0.0,
+				params: EventParams::Background {
+					filename: filename.to_owned(),
+					x_offset: 0,
+					y_offset: 0,
+				},
+			},
+		);
+	}
+}
+
+/// Replaces the background image across all difficulties of a beatmap set.
+pub fn replace_background(set: &mut BeatmapSet, filename: &str) {
+	for difficulty in &mut set.difficulties {
+		set_background(&mut difficulty.beatmap, filename);
+	}
+}
+
+/// Removes every video event from all difficulties of a beatmap set.
+///
+/// Returns the amount of removed video events.
+pub fn strip_videos(set: &mut BeatmapSet) -> usize {
+	let mut removed = 0;
+
+	for difficulty in &mut set.difficulties {
+		let len = difficulty.beatmap.events.len();
+		(difficulty.beatmap.events).retain(|event| !matches!(event.params, EventParams::Video { .. }));
+		removed += len - difficulty.beatmap.events.len();
+	}
+
+	removed
+}
+
+/// Whether a file can be used by osu! without being referenced in any difficulty.
+///
+/// This is the case for storyboard files and custom skin elements.
+fn is_implicitly_used(path: &str) -> bool {
+	const SKIN_SAMPLE_PREFIXES: &[&str] = &["normal-", "soft-", "drum-"];
+	const SKIN_EXTENSIONS: &[&str] = &["wav", "ogg", "mp3", "png", "jpg"];
+
+	let path = Path::new(path);
+	let extension = path.extension().and_then(OsStr::to_str).unwrap_or_default();
+	let filename = path.file_name().and_then(OsStr::to_str).unwrap_or_default();
+	let is_at_root = path.parent() == Some(Path::new(""));
+
+	extension == "osb"
+		|| (is_at_root
+			&& SKIN_EXTENSIONS.contains(&extension)
+			&& SKIN_SAMPLE_PREFIXES.iter().any(|prefix| filename.starts_with(prefix)))
+}
+
+/// Lists the files of a beatmap set that no difficulty references.
+///
+/// Storyboard files and custom hitsound samples (`soft-hitnormal2.wav`, etc.) are considered used.
+#[must_use]
+pub fn unused_files(set: &BeatmapSet) -> Vec<PathBuf> {
+	let used: Vec<String> = (list_assets(set).into_iter())
+		.map(|asset| normalize_asset_path(&asset.path))
+		.collect();
+
+	(set.shared_assets.iter())
+		.filter(|file| {
+			let path = normalize_asset_path(&file.to_string_lossy());
+			!used.contains(&path) && !is_implicitly_used(&path)
+		})
+		.cloned()
+		.collect()
+}
@@ -0,0 +1,126 @@
+//! Properties of the audio file of a beatmap set, read from the file itself.
+//!
+//! Only MP3 files are understood for now: their frames are walked to get the average bitrate,
+//! which also works for files with a variable bitrate.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Bitrates of MPEG-1 Layer III frames in kbps, by bitrate index.
+const MPEG1_BITRATES: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+/// Bitrates of MPEG-2 and MPEG-2.5 Layer III frames in kbps, by bitrate index.
+const MPEG2_BITRATES: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+/// Sample rates of MPEG-1 frames in Hz, halved for MPEG-2 and quartered for MPEG-2.5.
+const MPEG1_SAMPLE_RATES: [u32; 3] = [44_100, 48_000, 32_000];
+
+/// Properties of an MP3 file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mp3Info {
+	/// Average bitrate over every audio frame, in kbps.
+	pub bitrate: f64,
+	/// Duration of the audio, in milliseconds.
+	pub duration: f64,
+	/// Whether every frame has the same bitrate.
+	pub constant_bitrate: bool,
+}
+
+/// Header of an MPEG Layer III frame.
+#[derive(Clone, Copy, Debug)]
+struct FrameHeader {
+	bitrate: u32,
+	sample_rate: u32,
+	samples: u32,
+	len: usize,
+}
+
+fn parse_frame_header(bytes: &[u8]) -> Option<FrameHeader> {
+	let [0xFF, b1, b2, _, ..] = *bytes else {
+		return None;
+	};
+
+	// frame sync, then Layer III
+	if b1 & 0xE0 != 0xE0 || (b1 >> 1) & 0b11 != 0b01 {
+		return None;
+	}
+
+	let (bitrates, sample_rate_divisor, samples) = match (b1 >> 3) & 0b11 {
+		0b11 => (&MPEG1_BITRATES, 1, 1152),
+		0b10 => (&MPEG2_BITRATES, 2, 576),
+		0b00 => (&MPEG2_BITRATES, 4, 576),
+		_ => return None,
+	};
+
+	// free format bitrates can't be walked without searching for the next frame
+	let bitrate = *bitrates.get(usize::from(b2 >> 4)).filter(|&&bitrate| bitrate > 0)?;
+	let sample_rate = MPEG1_SAMPLE_RATES.get(usize::from((b2 >> 2) & 0b11))? / sample_rate_divisor;
+	let padding = u32::from((b2 >> 1) & 1);
+
+	let len = samples / 8 * bitrate * 1000 / sample_rate + padding;
+	Some(FrameHeader {
+		bitrate,
+		sample_rate,
+		samples,
+		len: len as usize,
+	})
+}
+
+/// Length of the `ID3v2` tag at the start of an MP3 file, if it has one.
+fn id3v2_len(bytes: &[u8]) -> usize {
+	let [b'I', b'D', b'3', _, _, flags, size @ ..] = bytes else {
+		return 0;
+	};
+
+	// the size is written on 4 bytes of 7 bits
+	let size = (size.iter().take(4)).fold(0, |size, &byte| (size << 7) | usize::from(byte & 0x7F));
+	let footer = if flags & 0x10 == 0 { 0 } else { 10 };
+	10 + size + footer
+}
+
+/// Reads the average bitrate and duration of MP3 data, or `None` if it has no MP3 frames.
+///
+/// The header frame written by VBR encoders (`Xing` or `Info`) is not counted.
+#[must_use]
+pub fn mp3_info(bytes: &[u8]) -> Option<Mp3Info> {
+	let mut position = id3v2_len(bytes);
+	let mut bits = 0_u64;
+	let mut duration = 0.0;
+	let mut bitrates = (u32::MAX, 0);
+	let mut is_first_frame = true;
+
+	while position + 4 <= bytes.len() {
+		let Some(header) = parse_frame_header(&bytes[position..]) else {
+			// garbage between frames, or the ID3v1 tag at the end
+			position += 1;
+			continue;
+		};
+
+		let frame = &bytes[position..bytes.len().min(position + header.len)];
+		let is_vbr_header = is_first_frame && (frame.windows(4)).any(|window| window == b"Xing" || window == b"Info");
+		is_first_frame = false;
+
+		if !is_vbr_header {
+			bits += frame.len() as u64 * 8;
+			duration += f64::from(header.samples) * 1000.0 / f64::from(header.sample_rate);
+			bitrates = (bitrates.0.min(header.bitrate), bitrates.1.max(header.bitrate));
+		}
+
+		position += header.len.max(1);
+	}
+
+	#[allow(clippy::cast_precision_loss)]
+	(duration > 0.0).then(|| Mp3Info {
+		bitrate: bits as f64 / duration,
+		duration,
+		constant_bitrate: bitrates.0 == bitrates.1,
+	})
+}
+
+/// Reads the average bitrate and duration of an MP3 file, or `None` if it isn't one.
+///
+/// # Errors
+///
+/// This function will return an error if the file can't be read.
+pub fn read_mp3_info<P: AsRef<Path>>(path: P) -> io::Result<Option<Mp3Info>> {
+	Ok(mp3_info(&fs::read(path)?))
+}
@@ -0,0 +1,379 @@
+//! Checks of a beatmap set against the parts of the ranking criteria that can be verified automatically.
+//!
+//! This is no replacement for modding: passing every check only means that nothing obviously unrankable was found.
+
+use std::fmt;
+
+use super::assets::unused_files;
+use super::BeatmapSet;
+use crate::file::beatmap::{BeatmapFile, Timestamp};
+use crate::lint::{lint, Severity};
+use crate::timestamp::format_timestamp;
+
+/// Minimum drain time of a difficulty, in milliseconds.
+pub const MIN_DRAIN_TIME: f64 = 30_000.0;
+
+/// Density of hit objects under which a difficulty looks unfinished, in objects per second of drain time.
+pub const MIN_OBJECT_DENSITY: f64 = 0.25;
+
+/// Lowest average bitrate of an MP3 audio file, in kbps.
+pub const MIN_MP3_BITRATE: f64 = 128.0;
+
+/// Highest average bitrate of an MP3 audio file, in kbps.
+pub const MAX_MP3_BITRATE: f64 = 192.0;
+
+/// Distance in milliseconds under which two kiai toggles of different difficulties are considered the same.
+const KIAI_TOLERANCE: f64 = 1.0;
+
+/// Part of the ranking criteria an issue comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RankCheck {
+	DrainTime,
+	/// Amount of hit objects compared to the drain time.
+	ObjectCount,
+	/// Kiai times consistent across the difficulties.
+	Kiai,
+	/// Metadata consistent across the difficulties.
+	Metadata,
+	AudioBitrate,
+	/// Referenced files that don't exist, and files that nothing references.
+	Files,
+	/// Problems found by [`lint`].
+	Lint,
+}
+
+impl fmt::Display for RankCheck {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::DrainTime => "drain time",
+			Self::ObjectCount => "object count",
+			Self::Kiai => "kiai",
+			Self::Metadata => "metadata",
+			Self::AudioBitrate => "audio bitrate",
+			Self::Files => "files",
+			Self::Lint => "lint",
+		})
+	}
+}
+
+/// Something in a beatmap set that goes against the ranking criteria.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RankIssue {
+	pub check: RankCheck,
+	/// [`Severity::Problem`] for rules, [`Severity::Warning`] for guidelines and guesses.
+	pub severity: Severity,
+	pub message: String,
+}
+
+impl RankIssue {
+	const fn problem(check: RankCheck, message: String) -> Self {
+		Self {
+			check,
+			severity: Severity::Problem,
+			message,
+		}
+	}
+
+	const fn warning(check: RankCheck, message: String) -> Self {
+		Self {
+			check,
+			severity: Severity::Warning,
+			message,
+		}
+	}
+}
+
+/// Issues of a single difficulty of the beatmap set.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DifficultyRankReport {
+	/// Name of the `.osu` file of the difficulty.
+	pub filename: String,
+	pub issues: Vec<RankIssue>,
+}
+
+impl DifficultyRankReport {
+	/// Whether the difficulty has no [problems](Severity::Problem), only warnings at most.
+	#[must_use]
+	pub fn passed(&self) -> bool {
+		self.issues.iter().all(|issue| issue.severity != Severity::Problem)
+	}
+}
+
+/// What [`rank_check`] found in a beatmap set.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RankReport {
+	/// Issues of the beatmap set as a whole, like unused files.
+	pub set_issues: Vec<RankIssue>,
+	pub difficulties: Vec<DifficultyRankReport>,
+}
+
+impl RankReport {
+	/// Whether neither the beatmap set nor any of its difficulties has [problems](Severity::Problem).
+	#[must_use]
+	pub fn passed(&self) -> bool {
+		self.set_issues.iter().all(|issue| issue.severity != Severity::Problem)
+			&& self.difficulties.iter().all(DifficultyRankReport::passed)
+	}
+}
+
+/// Checks a beatmap set against the ranking criteria:
+///
+/// - the drain time of each difficulty is at least [`MIN_DRAIN_TIME`];
+/// - each difficulty has enough hit objects for its length (see [`MIN_OBJECT_DENSITY`]);
+/// - kiai times and metadata are the same in every difficulty, compared to what most difficulties have;
+/// - MP3 audio files have an average bitrate between [`MIN_MP3_BITRATE`] and [`MAX_MP3_BITRATE`]
+///   (only with the `audio` feature, and for beatmap sets loaded from a folder);
+/// - every referenced file exists, and every file is used;
+/// - [`lint`] finds no problems.
+#[must_use]
+pub fn rank_check(set: &BeatmapSet) -> RankReport {
+	let mut difficulties: Vec<DifficultyRankReport> = (set.difficulties.iter())
+		.map(|difficulty| DifficultyRankReport {
+			filename: difficulty.filename.clone(),
+			issues: check_length(&difficulty.beatmap),
+		})
+		.collect();
+
+	let beatmaps: Vec<&BeatmapFile> = set.difficulties.iter().map(|difficulty| &difficulty.beatmap).collect();
+	for (report, issues) in difficulties.iter_mut().zip(check_kiai(&beatmaps)) {
+		report.issues.extend(issues);
+	}
+	for (report, issues) in difficulties.iter_mut().zip(check_metadata(&beatmaps)) {
+		report.issues.extend(issues);
+	}
+
+	#[cfg(feature = "audio")]
+	for (report, difficulty) in difficulties.iter_mut().zip(&set.difficulties) {
+		report.issues.extend(check_audio_bitrate(set, &difficulty.beatmap));
+	}
+
+	for missing in set.validate() {
+		if let Some(report) = difficulties
+			.iter_mut()
+			.find(|report| report.filename == missing.difficulty)
+		{
+			report.issues.push(RankIssue::problem(
+				RankCheck::Files,
+				format!("{:?} file {} doesn't exist", missing.asset.kind, missing.asset.path),
+			));
+		}
+	}
+
+	for (report, difficulty) in difficulties.iter_mut().zip(&set.difficulties) {
+		let problems = lint(&difficulty.beatmap).into_iter();
+		report.issues.extend(
+			problems
+				.filter(|issue| issue.severity == Severity::Problem)
+				.map(|issue| {
+					RankIssue::problem(
+						RankCheck::Lint,
+						format!("{} {}", issue.editor_timestamp(), issue.message),
+					)
+				}),
+		);
+	}
+
+	let set_issues = (unused_files(set).iter())
+		.map(|file| RankIssue::problem(RankCheck::Files, format!("{} is unused", file.display())))
+		.collect();
+
+	RankReport {
+		set_issues,
+		difficulties,
+	}
+}
+
+/// Checks the drain time of a difficulty and its amount of hit objects.
+fn check_length(beatmap: &BeatmapFile) -> Vec<RankIssue> {
+	let mut issues = Vec::new();
+
+	let drain_time = beatmap.drain_time();
+	if drain_time < MIN_DRAIN_TIME {
+		issues.push(RankIssue::problem(
+			RankCheck::DrainTime,
+			format!(
+				"drain time is {}, under {}s",
+				format_duration(drain_time),
+				MIN_DRAIN_TIME / 1000.0
+			),
+		));
+	}
+
+	if beatmap.hit_objects.is_empty() {
+		issues.push(RankIssue::problem(RankCheck::ObjectCount, "no hit objects".to_owned()));
+	} else if drain_time > 0.0 {
+		#[allow(clippy::cast_precision_loss)]
+		let density = beatmap.hit_objects.len() as f64 * 1000.0 / drain_time;
+		if density < MIN_OBJECT_DENSITY {
+			issues.push(RankIssue::warning(
+				RankCheck::ObjectCount,
+				format!(
+					"only {} hit objects in {} of drain time, the difficulty might be unfinished",
+					beatmap.hit_objects.len(),
+					format_duration(drain_time)
+				),
+			));
+		}
+	}
+
+	issues
+}
+
+/// Formats a duration in milliseconds like osu! shows lengths, as `m:ss`.
+fn format_duration(millis: f64) -> String {
+	#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+	let seconds = (millis / 1000.0).floor().max(0.0) as u64;
+	format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Value that most elements have.
+fn most_common<T: PartialEq>(values: &[T]) -> Option<&T> {
+	(values.iter()).max_by_key(|value| values.iter().filter(|other| other == value).count())
+}
+
+/// Times at which kiai gets toggled on or off.
+fn kiai_toggles(beatmap: &BeatmapFile) -> Vec<(Timestamp, bool)> {
+	let mut toggles = Vec::new();
+	let mut kiai = false;
+
+	for timing_point in &beatmap.timing_points {
+		if timing_point.is_kiai() != kiai {
+			kiai = timing_point.is_kiai();
+			toggles.push((timing_point.time, kiai));
+		}
+	}
+
+	toggles
+}
+
+/// Checks that every difficulty has the kiai times most difficulties have, returning the issues of each difficulty.
+fn check_kiai(beatmaps: &[&BeatmapFile]) -> Vec<Vec<RankIssue>> {
+	let toggles: Vec<Vec<(Timestamp, bool)>> = beatmaps.iter().map(|beatmap| kiai_toggles(beatmap)).collect();
+	let same = |a: &[(Timestamp, bool)], b: &[(Timestamp, bool)]| {
+		a.len() == b.len()
+			&& (a.iter().zip(b)).all(|((ta, ka), (tb, kb))| ka == kb && (ta - tb).abs() <= KIAI_TOLERANCE)
+	};
+
+	let reference =
+		(toggles.iter()).max_by_key(|toggles_a| toggles.iter().filter(|toggles_b| same(toggles_a, toggles_b)).count());
+
+	(toggles.iter())
+		.map(|toggles| {
+			let Some(reference) = reference.filter(|reference| !same(toggles, reference)) else {
+				return Vec::new();
+			};
+
+			// first toggle that doesn't match, in either difficulty
+			let mismatch = (toggles.iter().zip(reference.iter()))
+				.find(|((ta, ka), (tb, kb))| ka != kb || (ta - tb).abs() > KIAI_TOLERANCE)
+				.map(|((ta, _), (tb, _))| ta.min(*tb))
+				.or_else(|| {
+					toggles
+						.get(reference.len())
+						.or_else(|| reference.get(toggles.len()))
+						.map(|(t, _)| *t)
+				})
+				.unwrap_or_default();
+
+			vec![RankIssue::warning(
+				RankCheck::Kiai,
+				format!(
+					"kiai times differ from most difficulties, starting at {}",
+					format_timestamp(mismatch)
+				),
+			)]
+		})
+		.collect()
+}
+
+/// Checks that every difficulty has the metadata most difficulties have, returning the issues of each difficulty.
+fn check_metadata(beatmaps: &[&BeatmapFile]) -> Vec<Vec<RankIssue>> {
+	type Field = (&'static str, fn(&BeatmapFile) -> String);
+	const FIELDS: [Field; 10] = [
+		("title", |b| {
+			b.metadata.as_ref().map(|m| m.title.clone()).unwrap_or_default()
+		}),
+		("unicode title", |b| {
+			b.metadata.as_ref().map(|m| m.title_unicode.clone()).unwrap_or_default()
+		}),
+		("artist", |b| {
+			b.metadata.as_ref().map(|m| m.artist.clone()).unwrap_or_default()
+		}),
+		("unicode artist", |b| {
+			b.metadata
+				.as_ref()
+				.map(|m| m.artist_unicode.clone())
+				.unwrap_or_default()
+		}),
+		("creator", |b| {
+			b.metadata.as_ref().map(|m| m.creator.clone()).unwrap_or_default()
+		}),
+		("source", |b| {
+			b.metadata.as_ref().map(|m| m.source.clone()).unwrap_or_default()
+		}),
+		("tags", |b| {
+			b.metadata.as_ref().map(|m| m.tags.join(" ")).unwrap_or_default()
+		}),
+		("beatmap set ID", |b| {
+			(b.metadata.as_ref().and_then(|m| m.beatmap_set_id)).map_or_else(String::new, |id| id.to_string())
+		}),
+		("audio file", |b| {
+			b.general.as_ref().map(|g| g.audio_filename.clone()).unwrap_or_default()
+		}),
+		("preview time", |b| {
+			(b.general.as_ref()).map_or_else(String::new, |g| format_timestamp(g.preview_time))
+		}),
+	];
+
+	let mut issues = vec![Vec::new(); beatmaps.len()];
+	for (name, field) in FIELDS {
+		let values: Vec<String> = beatmaps.iter().map(|beatmap| field(beatmap)).collect();
+		let Some(common) = most_common(&values) else {
+			continue;
+		};
+
+		for (issues, value) in issues.iter_mut().zip(&values) {
+			if value != common {
+				issues.push(RankIssue::problem(
+					RankCheck::Metadata,
+					format!("{name} is {value:?}, unlike most difficulties ({common:?})"),
+				));
+			}
+		}
+	}
+
+	issues
+}
+
+/// Checks the average bitrate of the MP3 audio file of a difficulty.
+#[cfg(feature = "audio")]
+fn check_audio_bitrate(set: &BeatmapSet, beatmap: &BeatmapFile) -> Option<RankIssue> {
+	use std::path::Path;
+
+	let audio = &beatmap.general.as_ref()?.audio_filename;
+	let is_mp3 = Path::new(audio)
+		.extension()
+		.is_some_and(|ext| ext.eq_ignore_ascii_case("mp3"));
+	if !is_mp3 || !set.path.is_dir() {
+		return None;
+	}
+
+	// missing files are reported on their own
+	let info = super::audio::read_mp3_info(set.path.join(audio)).ok()?;
+	let Some(info) = info else {
+		return Some(RankIssue::problem(
+			RankCheck::AudioBitrate,
+			format!("{audio} isn't a valid MP3 file"),
+		));
+	};
+
+	(!(MIN_MP3_BITRATE..=MAX_MP3_BITRATE).contains(&info.bitrate.round())).then(|| {
+		RankIssue::problem(
+			RankCheck::AudioBitrate,
+			format!(
+				"{audio} has an average bitrate of {:.0}kbps, expected {MIN_MP3_BITRATE}kbps to {MAX_MP3_BITRATE}kbps",
+				info.bitrate
+			),
+		)
+	})
+}
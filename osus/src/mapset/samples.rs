@@ -0,0 +1,243 @@
+//! Custom sample index management: which indices a beatmap uses, remapping them, and finding missing sample files.
+//!
+//! A sample index of 0 means the skin's samples, and any other index `n` means the `<bank>-<sound><n>.wav` files
+//! of the beatmap folder (without the number for index 1, like `soft-hitnormal.wav`).
+
+use super::BeatmapSet;
+use crate::analysis::slider_duration;
+use crate::file::beatmap::{BeatmapFile, HitObjectParams, HitSound, SampleBank, Timestamp};
+use crate::timeline::TimingTimeline;
+
+/// Extensions osu! looks for when loading a sample.
+const SAMPLE_EXTENSIONS: &[&str] = &["wav", "ogg", "mp3"];
+
+/// How much a sample index is used in a beatmap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SampleIndexUsage {
+	pub index: u32,
+	/// Amount of timing points using the index.
+	pub timing_points: usize,
+	/// Amount of hit objects overriding the timing point's index with this one.
+	pub hit_objects: usize,
+}
+
+/// Usage of a sample index, inserted in its sorted place if it isn't there yet.
+fn usage_mut(usages: &mut Vec<SampleIndexUsage>, index: u32) -> &mut SampleIndexUsage {
+	let position = usages
+		.binary_search_by_key(&index, |usage| usage.index)
+		.unwrap_or_else(|position| {
+			let usage = SampleIndexUsage {
+				index,
+				timing_points: 0,
+				hit_objects: 0,
+			};
+			usages.insert(position, usage);
+			position
+		});
+
+	&mut usages[position]
+}
+
+/// Lists every sample index used by the timing points and hit samples of a beatmap, sorted by index.
+#[must_use]
+pub fn used_sample_indices(beatmap: &BeatmapFile) -> Vec<SampleIndexUsage> {
+	let mut usages: Vec<SampleIndexUsage> = Vec::new();
+
+	for timing_point in &beatmap.timing_points {
+		usage_mut(&mut usages, timing_point.sample_index).timing_points += 1;
+	}
+
+	for hit_object in &beatmap.hit_objects {
+		// 0 means that the timing point's index is used
+		if hit_object.hit_sample.index != 0 {
+			usage_mut(&mut usages, hit_object.hit_sample.index).hit_objects += 1;
+		}
+	}
+
+	usages
+}
+
+/// Changes every sample index of a beatmap with `map`, which is given the current index.
+///
+/// Returns the amount of changed timing points and hit objects.
+pub fn remap_sample_indices(beatmap: &mut BeatmapFile, map: impl Fn(u32) -> u32) -> usize {
+	let mut changed = 0;
+	let mut remap = |index: &mut u32| {
+		let new_index = map(*index);
+		if new_index != *index {
+			*index = new_index;
+			changed += 1;
+		}
+	};
+
+	for timing_point in &mut beatmap.timing_points {
+		remap(&mut timing_point.sample_index);
+	}
+
+	for hit_object in &mut beatmap.hit_objects {
+		if hit_object.hit_sample.index != 0 {
+			remap(&mut hit_object.hit_sample.index);
+		}
+	}
+
+	changed
+}
+
+/// Name of a custom sample file without its extension, or `None` for index 0 which uses the skin.
+#[must_use]
+pub fn sample_file_stem(bank: SampleBank, sound: &str, index: u32) -> Option<String> {
+	let bank = match bank {
+		SampleBank::Auto | SampleBank::Normal => "normal",
+		SampleBank::Soft => "soft",
+		SampleBank::Drum => "drum",
+	};
+
+	match index {
+		0 => None,
+		1 => Some(format!("{bank}-{sound}")),
+		n => Some(format!("{bank}-{sound}{n}")),
+	}
+}
+
+/// A custom sample file played by a difficulty that doesn't exist in the beatmap set.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MissingSample {
+	/// Name of the `.osu` file of the difficulty.
+	pub difficulty: String,
+	/// Name of the sample file without its extension, like `soft-hitnormal2`.
+	pub stem: String,
+	/// Time of the first hit object playing it.
+	pub first_time: Timestamp,
+}
+
+/// Sound names of the additions played by a hitsound.
+fn addition_sounds(hit_sound: HitSound) -> impl Iterator<Item = &'static str> {
+	hit_sound.iter_additions().map(|flag| match flag {
+		HitSound::WHISTLE => "hitwhistle",
+		HitSound::FINISH => "hitfinish",
+		_ => "hitclap",
+	})
+}
+
+/// Lists every custom sample file a beatmap plays on hit objects and slider edges, with the time it is first played.
+///
+/// Hit objects with a custom filename are left out, as well as slider slides and ticks. Sample sets are resolved like
+/// osu! does, slider edges falling back to the slider's sample set and then to the timing point's.
+#[must_use]
+pub fn played_sample_files(beatmap: &BeatmapFile) -> Vec<(String, Timestamp)> {
+	let timeline = TimingTimeline::new(&beatmap.timing_points);
+	let mut files: Vec<(String, Timestamp)> = Vec::new();
+
+	let mut play =
+		|time: Timestamp, hit_sound: HitSound, normal_set: SampleBank, addition_set: SampleBank, index: u32| {
+			let settings = timeline.sample_settings_at(time);
+			let normal_set = match normal_set {
+				SampleBank::Auto => settings.sample_set,
+				bank => bank,
+			};
+			let addition_set = match addition_set {
+				SampleBank::Auto => normal_set,
+				bank => bank,
+			};
+			let index = if index == 0 { settings.sample_index } else { index };
+
+			let sounds = std::iter::once((normal_set, "hitnormal"))
+				.chain(addition_sounds(hit_sound).map(|sound| (addition_set, sound)));
+
+			for (bank, sound) in sounds {
+				if let Some(stem) = sample_file_stem(bank, sound, index) {
+					if !files.iter().any(|(s, _)| *s == stem) {
+						files.push((stem, time));
+					}
+				}
+			}
+		};
+
+	for hit_object in &beatmap.hit_objects {
+		let hit_sample = &hit_object.hit_sample;
+		if hit_sample.filename.is_some() {
+			continue;
+		}
+
+		let HitObjectParams::Slider {
+			slides,
+			edge_hitsounds,
+			edge_samplesets,
+			..
+		} = &hit_object.object_params
+		else {
+			play(
+				hit_object.time,
+				hit_object.hit_sound,
+				hit_sample.normal_set,
+				hit_sample.addition_set,
+				hit_sample.index,
+			);
+			continue;
+		};
+
+		let slide_duration = slider_duration(beatmap, hit_object).unwrap_or_default() / f64::from((*slides).max(1));
+		for (i, (hit_sound, sample_set)) in edge_hitsounds.iter().zip(edge_samplesets).enumerate() {
+			#[allow(clippy::cast_precision_loss)]
+			let time = slide_duration.mul_add(i as f64, hit_object.time);
+			// an edge without sample set uses the slider's own sample set before the timing point's
+			let or_slider = |edge_set: SampleBank, slider_set: SampleBank| match edge_set {
+				SampleBank::Auto => slider_set,
+				bank => bank,
+			};
+			play(
+				time,
+				*hit_sound,
+				or_slider(sample_set.normal_set, hit_sample.normal_set),
+				or_slider(sample_set.addition_set, hit_sample.addition_set),
+				hit_sample.index,
+			);
+		}
+	}
+
+	files
+}
+
+/// Finds every custom sample file played by a difficulty that doesn't exist in the beatmap set.
+///
+/// osu! falls back to the skin's sample when this happens, which is rarely intended.
+#[must_use]
+pub fn missing_samples(set: &BeatmapSet) -> Vec<MissingSample> {
+	let mut missing = Vec::new();
+
+	for difficulty in &set.difficulties {
+		for (stem, first_time) in played_sample_files(&difficulty.beatmap) {
+			let exists = (SAMPLE_EXTENSIONS.iter()).any(|extension| set.has_asset(&format!("{stem}.{extension}")));
+			if !exists {
+				missing.push(MissingSample {
+					difficulty: difficulty.filename.clone(),
+					stem,
+					first_time,
+				});
+			}
+		}
+	}
+
+	missing
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::file::beatmap::parsing::parse_osu_str;
+
+	#[test]
+	fn slider_edges_fall_back_to_the_slider_sample_set() {
+		let beatmap = parse_osu_str(
+			"osu file format v14\n\n[Difficulty]\nSliderMultiplier:1\n\n[TimingPoints]\n0,500,4,3,2,100,1,0\n\n\
+			[HitObjects]\n100,100,1000,2,0,L|200:100,1,100,2|0,0:0|2:0,1:0:0:0:\n",
+		)
+		.unwrap();
+
+		let stems: Vec<String> = played_sample_files(&beatmap)
+			.into_iter()
+			.map(|(stem, _)| stem)
+			.collect();
+		assert_eq!(stems, ["normal-hitnormal2", "normal-hitwhistle2", "soft-hitnormal2"]);
+	}
+}
@@ -0,0 +1,131 @@
+//! The operations behind the commands of `osus-cli`, for tools that want to do the same thing without it.
+//!
+//! They only change beatmaps in memory: reading, backing up and writing files is left to the caller.
+
+#[cfg(feature = "fs")]
+use crate::algos::{apply_rate_preset, RatePreset};
+use crate::algos::{
+	change_rate, merge_adjacent_timing_points, remove_duplicates, remove_useless_speed_changes, RemovedSpeedChange,
+};
+use crate::edit::{ChangeSet, ElementChanges};
+use crate::file::beatmap::BeatmapFile;
+use crate::hitsounds::CopyHitsoundsReport;
+#[cfg(feature = "fs")]
+use crate::hitsounds::{copy_hitsounds, CopyHitsoundsOptions};
+#[cfg(feature = "fs")]
+use crate::mapset::{standard_filename, BeatmapSet, Difficulty};
+use crate::Tolerance;
+
+/// What [`cleanup_timing_points`] did to a beatmap.
+#[derive(Clone, Debug, Default)]
+pub struct CleanupReport {
+	/// Speed changes removed because they were useless (see [`remove_useless_speed_changes`]).
+	pub removed_speed_changes: Vec<RemovedSpeedChange>,
+	pub changes: ChangeSet,
+}
+
+/// Cleans up the timing points of a beatmap, like the `cleanup-timing-points` command.
+///
+/// Duplicates are removed, timing points inserted right next to others for hitsounds are merged,
+/// and useless speed changes are removed (see [`remove_useless_speed_changes`]).
+pub fn cleanup_timing_points(beatmap: &mut BeatmapFile) -> CleanupReport {
+	let before = beatmap.timing_points.clone();
+
+	tracing::info!("Removing duplicates...");
+	beatmap.timing_points = remove_duplicates(&beatmap.timing_points);
+
+	tracing::info!("Merging simultaneous timing points...");
+	beatmap.timing_points = merge_adjacent_timing_points(&beatmap.timing_points, Tolerance::DEFAULT.same_time);
+
+	tracing::info!("Removing useless speed changes...");
+	let mode = beatmap.general.as_ref().map_or(0, |general| general.mode);
+	let report = remove_useless_speed_changes(mode, &beatmap.timing_points, &beatmap.hit_objects);
+
+	tracing::info!("Removing duplicates again...");
+	beatmap.timing_points = remove_duplicates(&report.timing_points);
+
+	CleanupReport {
+		removed_speed_changes: report.removed,
+		changes: ChangeSet {
+			timing_points: ElementChanges::diff(&before, &beatmap.timing_points),
+			..ChangeSet::default()
+		},
+	}
+}
+
+/// Copy of a beatmap played at another rate, as a new difficulty named after the rate (like `Hard (1.5x)`).
+///
+/// See [`change_rate`]. The audio file has to be time-stretched separately.
+#[must_use]
+pub fn rate_changed_copy(beatmap: &BeatmapFile, rate: f64, adjust_difficulty: bool) -> BeatmapFile {
+	let mut copy = beatmap.clone();
+	change_rate(&mut copy, rate, adjust_difficulty);
+
+	if let Some(metadata) = &mut copy.metadata {
+		metadata.version = format!("{} ({rate}x)", metadata.version);
+		metadata.beatmap_id = None;
+	}
+
+	copy
+}
+
+/// What [`splat_hitsounds`] changed in a difficulty.
+#[derive(Clone, Debug)]
+pub struct SplatReport {
+	/// Index of the difficulty in the beatmap set.
+	pub difficulty: usize,
+	pub report: CopyHitsoundsReport,
+}
+
+/// Copies the hitsounds of a soundmap to the difficulties of a beatmap set,
+/// see [`copy_hitsounds`](crate::hitsounds::copy_hitsounds).
+///
+/// The soundmap itself (if it is part of the set) and the difficulties whose mode isn't in `modes`
+/// are skipped, and an empty `modes` means every mode. Returns what changed in each difficulty that got hitsounds.
+#[cfg(feature = "fs")]
+pub fn splat_hitsounds(
+	set: &mut BeatmapSet,
+	soundmap: &BeatmapFile,
+	soundmap_filename: Option<&str>,
+	modes: &[u8],
+	options: &CopyHitsoundsOptions,
+) -> Vec<SplatReport> {
+	let mut reports = Vec::new();
+	for (index, difficulty) in set.difficulties.iter_mut().enumerate() {
+		if soundmap_filename == Some(difficulty.filename.as_str()) {
+			continue;
+		}
+
+		let mode = difficulty.beatmap.general.as_ref().map_or(0, |general| general.mode);
+		if !modes.is_empty() && !modes.contains(&mode) {
+			tracing::info!("Skipping {} (mode {mode})", difficulty.filename);
+			continue;
+		}
+
+		tracing::info!("Copying hitsounds to {}...", difficulty.filename);
+		reports.push(SplatReport {
+			difficulty: index,
+			report: copy_hitsounds(&mut difficulty.beatmap, soundmap, options),
+		});
+	}
+
+	reports
+}
+
+/// Nightcore or daycore versions of every difficulty of a beatmap set, see [`apply_rate_preset`].
+///
+/// Each one gets the file name osu! would give it with its new title. The audio files have to be resampled separately.
+#[cfg(feature = "fs")]
+#[must_use]
+pub fn rate_preset_difficulties(set: &BeatmapSet, preset: RatePreset) -> Vec<Difficulty> {
+	(set.difficulties.iter())
+		.map(|difficulty| {
+			tracing::info!("Making {} version of {}...", preset.name(), difficulty.filename);
+			let mut beatmap = difficulty.beatmap.clone();
+			apply_rate_preset(&mut beatmap, preset);
+
+			let filename = (beatmap.metadata.as_ref()).map_or_else(|| difficulty.filename.clone(), standard_filename);
+			Difficulty { filename, beatmap }
+		})
+		.collect()
+}
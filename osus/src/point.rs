@@ -1,6 +1,9 @@
+//! 2D geometry in osu! pixels, for slider paths and positional analysis.
+
 use std::ops::{Add, Div, Mul, Neg, Sub};
 
 #[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Point {
 	pub x: f64,
 	pub y: f64,
@@ -22,10 +25,153 @@ impl Point {
 		self.x.hypot(self.y)
 	}
 
+	#[must_use]
+	pub fn len_squared(self) -> f64 {
+		self.dot(self)
+	}
+
 	#[must_use]
 	pub fn normalized(self) -> Self {
 		self / self.len()
 	}
+
+	/// Z coordinate of the cross product, positive if `rhs` is clockwise from `self` on the playfield (y goes down).
+	#[must_use]
+	pub fn cross(self, rhs: Self) -> f64 {
+		self.x.mul_add(rhs.y, -self.y * rhs.x)
+	}
+
+	/// This vector rotated by 90 degrees, clockwise on the playfield.
+	#[must_use]
+	pub fn perpendicular(self) -> Self {
+		Self::new(-self.y, self.x)
+	}
+
+	/// Angle of this vector in radians from the x axis, between -π and π.
+	#[must_use]
+	pub fn angle(self) -> f64 {
+		self.y.atan2(self.x)
+	}
+
+	#[must_use]
+	pub fn distance(self, other: Self) -> f64 {
+		(other - self).len()
+	}
+
+	/// Linear interpolation from `self` (`t = 0`) to `other` (`t = 1`).
+	#[must_use]
+	pub fn lerp(self, other: Self, t: f64) -> Self {
+		self + (other - self) * t
+	}
+
+	/// Rotates this point around `center` by `angle` radians, clockwise on the playfield.
+	#[must_use]
+	pub fn rotate_around(self, center: Self, angle: f64) -> Self {
+		let (sin, cos) = angle.sin_cos();
+		let offset = self - center;
+		center
+			+ Self::new(
+				offset.x.mul_add(cos, -offset.y * sin),
+				offset.x.mul_add(sin, offset.y * cos),
+			)
+	}
+
+	#[must_use]
+	pub const fn clamp_to_playfield(self) -> Self {
+		Rect::PLAYFIELD.clamp(self)
+	}
+
+	/// This point with its coordinates made whole, or as it is with [`Rounding::Exact`].
+	#[must_use]
+	pub const fn rounded(self, rounding: Rounding) -> Self {
+		match rounding {
+			Rounding::Exact => self,
+			Rounding::Round => Self::new(self.x.round(), self.y.round()),
+			Rounding::Floor => Self::new(self.x.floor(), self.y.floor()),
+		}
+	}
+}
+
+/// How coordinates are made whole, since osu!stable only writes integer positions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rounding {
+	/// Keep the fractional part, which only osu!lazer reads.
+	#[default]
+	Exact,
+	/// Round to the nearest integer, halves away from zero.
+	Round,
+	/// Round down.
+	Floor,
+}
+
+/// Axis-aligned rectangle, from its top-left corner `min` to its bottom-right corner `max`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Rect {
+	pub min: Point,
+	pub max: Point,
+}
+
+impl Rect {
+	/// The osu! playfield, 512 by 384 osu! pixels.
+	pub const PLAYFIELD: Self = Self::new(Point::new(0.0, 0.0), Point::new(512.0, 384.0));
+
+	#[must_use]
+	pub const fn new(min: Point, max: Point) -> Self {
+		Self { min, max }
+	}
+
+	/// Smallest rectangle containing every point, or `None` if there are no points.
+	#[must_use]
+	pub fn from_points(points: impl IntoIterator<Item = Point>) -> Option<Self> {
+		let mut points = points.into_iter();
+		let first = points.next()?;
+		Some(points.fold(Self::new(first, first), |rect, p| {
+			Self::new(
+				Point::new(rect.min.x.min(p.x), rect.min.y.min(p.y)),
+				Point::new(rect.max.x.max(p.x), rect.max.y.max(p.y)),
+			)
+		}))
+	}
+
+	#[must_use]
+	pub fn width(self) -> f64 {
+		self.max.x - self.min.x
+	}
+
+	#[must_use]
+	pub fn height(self) -> f64 {
+		self.max.y - self.min.y
+	}
+
+	#[must_use]
+	pub fn center(self) -> Point {
+		self.min.lerp(self.max, 0.5)
+	}
+
+	/// Whether a point is inside the rectangle, edges included.
+	#[must_use]
+	pub fn contains(self, point: Point) -> bool {
+		(self.min.x..=self.max.x).contains(&point.x) && (self.min.y..=self.max.y).contains(&point.y)
+	}
+
+	/// Distance by which `other` goes out of this rectangle on its furthest side, or 0 if it is inside.
+	#[must_use]
+	pub fn overflow(self, other: Self) -> f64 {
+		(self.min.x - other.min.x)
+			.max(other.max.x - self.max.x)
+			.max(self.min.y - other.min.y)
+			.max(other.max.y - self.max.y)
+			.max(0.0)
+	}
+
+	/// Closest point inside the rectangle.
+	#[must_use]
+	pub const fn clamp(self, point: Point) -> Point {
+		Point::new(
+			point.x.clamp(self.min.x, self.max.x),
+			point.y.clamp(self.min.y, self.max.y),
+		)
+	}
 }
 
 impl Neg for Point {
@@ -79,3 +225,31 @@ impl Div<f64> for Point {
 		}
 	}
 }
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+	use super::*;
+
+	fn coordinates(point: Point) -> (f64, f64) {
+		(point.x, point.y)
+	}
+
+	#[test]
+	fn exact_keeps_fractions() {
+		let point = Point::new(-1.5, 2.5);
+		assert_eq!(coordinates(point.rounded(Rounding::Exact)), (-1.5, 2.5));
+	}
+
+	#[test]
+	fn round_takes_halves_away_from_zero() {
+		assert_eq!(coordinates(Point::new(-1.5, 2.5).rounded(Rounding::Round)), (-2.0, 3.0));
+		assert_eq!(coordinates(Point::new(-1.4, 2.6).rounded(Rounding::Round)), (-1.0, 3.0));
+	}
+
+	#[test]
+	fn floor_goes_down_for_negatives() {
+		assert_eq!(coordinates(Point::new(-1.5, 2.5).rounded(Rounding::Floor)), (-2.0, 2.0));
+		assert_eq!(coordinates(Point::new(-0.1, 0.9).rounded(Rounding::Floor)), (-1.0, 0.0));
+	}
+}
@@ -0,0 +1,31 @@
+//! Progress reporting for long operations, like indexing a whole songs folder.
+//!
+//! The library never prints progress itself: operations that can take a while take a [`ProgressSink`],
+//! which frontends implement to show a progress bar, and which can be [`NoProgress`] when nobody is watching.
+
+/// Receives the progress events of a long operation.
+///
+/// Every method does nothing by default, so that implementors only handle the events they care about.
+/// Methods take `&self` since items can be processed on several threads at once.
+pub trait ProgressSink: Sync {
+	/// The operation started, with `total` items to process if it is known in advance.
+	fn start(&self, message: &str, total: Option<u64>) {
+		let _ = (message, total);
+	}
+
+	/// `amount` more items were processed.
+	fn advance(&self, amount: u64) {
+		let _ = amount;
+	}
+
+	/// The operation is done.
+	fn finish(&self, message: &str) {
+		let _ = message;
+	}
+}
+
+/// Progress sink ignoring every event.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {}
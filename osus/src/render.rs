@@ -0,0 +1,159 @@
+//! SVG previews of the playfield, to see what a pattern looks like without opening the editor.
+
+use std::fmt::Write;
+use std::ops::Range;
+
+use crate::algos::slider_path::SliderPath;
+use crate::file::beatmap::{BeatmapFile, Color, HitObject, Timestamp};
+use crate::timestamp::combo_numbers;
+
+/// Combo colors of the default osu! skin, used when a beatmap has none.
+const DEFAULT_COMBO_COLORS: [Color; 4] = [
+	Color {
+		r: 255,
+		g: 192,
+		b: 0,
+		a: None,
+	},
+	Color {
+		r: 0,
+		g: 202,
+		b: 0,
+		a: None,
+	},
+	Color {
+		r: 18,
+		g: 124,
+		b: 255,
+		a: None,
+	},
+	Color {
+		r: 242,
+		g: 24,
+		b: 57,
+		a: None,
+	},
+];
+
+/// Margin around the playfield in osu! pixels, so that objects on its edges are fully visible.
+const MARGIN: f64 = 64.0;
+
+/// Radius of hit circles in osu! pixels, depending on CS.
+fn circle_radius(circle_size: f32) -> f64 {
+	f64::from(circle_size).mul_add(-4.48, 54.4)
+}
+
+/// Index in the combo colors of each hit object, taking color skips into account.
+fn combo_color_indices(hit_objects: &[HitObject], color_count: usize) -> Vec<usize> {
+	let mut index = 0;
+	let mut started = false;
+
+	(hit_objects.iter())
+		.map(|hit_object| {
+			if let Some(skip) = hit_object.combo_color_skip {
+				if started {
+					index += 1 + usize::from(skip);
+				}
+			}
+			started = true;
+			index % color_count.max(1)
+		})
+		.collect()
+}
+
+fn svg_color(color: Color) -> String {
+	format!("rgb({},{},{})", color.r, color.g, color.b)
+}
+
+/// Renders the hit objects starting in `range` as an SVG image of the playfield.
+///
+/// Circles and slider bodies use the beatmap's combo colors, and have their combo number drawn on them.
+/// Objects further in time are drawn more transparent and below earlier ones, like in osu!.
+/// Spinners are drawn as a large ring in the middle of the playfield.
+#[must_use]
+pub fn render_timeline_svg(beatmap: &BeatmapFile, range: Range<Timestamp>) -> String {
+	let colors = (beatmap.colors.as_ref())
+		.map(|colors| colors.combo_colors.clone())
+		.filter(|colors| !colors.is_empty())
+		.unwrap_or_else(|| DEFAULT_COMBO_COLORS.to_vec());
+
+	let radius = circle_radius(beatmap.difficulty.as_ref().map_or(5.0, |d| d.circle_size));
+	let numbers = combo_numbers(&beatmap.hit_objects);
+	let color_indices = combo_color_indices(&beatmap.hit_objects, colors.len());
+
+	let mut svg = String::new();
+	let (width, height) = (MARGIN.mul_add(2.0, 512.0), MARGIN.mul_add(2.0, 384.0));
+	let _ = writeln!(
+		svg,
+		r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {width} {height}" width="{width}" height="{height}">"#,
+		-MARGIN, -MARGIN
+	);
+	let _ = writeln!(
+		svg,
+		r##"<rect x="{}" y="{}" width="{width}" height="{height}" fill="#1a1a1a"/>"##,
+		-MARGIN, -MARGIN
+	);
+	let _ = writeln!(
+		svg,
+		r##"<rect x="0" y="0" width="512" height="384" fill="none" stroke="#444" stroke-dasharray="4"/>"##
+	);
+
+	let visible: Vec<usize> = (0..beatmap.hit_objects.len())
+		.filter(|&i| range.contains(&beatmap.hit_objects[i].time))
+		.collect();
+
+	// the range can be unbounded, so transparency goes from the first to the last visible object
+	let time_of = |i: Option<&usize>| i.map_or(0.0, |&i| beatmap.hit_objects[i].time);
+	let start = time_of(visible.first());
+	let duration = (time_of(visible.last()) - start).max(1.0);
+
+	// later objects go below earlier ones
+	for &i in visible.iter().rev() {
+		let hit_object = &beatmap.hit_objects[i];
+		let color = svg_color(colors[color_indices[i]]);
+		let opacity = ((hit_object.time - start) / duration).mul_add(-0.6, 1.0);
+		let (x, y) = (f64::from(hit_object.x), f64::from(hit_object.y));
+
+		let _ = writeln!(svg, r#"<g opacity="{opacity:.2}">"#);
+		if hit_object.is_spinner() {
+			let _ = writeln!(
+				svg,
+				r##"<circle cx="256" cy="192" r="160" fill="none" stroke="#ddd" stroke-width="8"/>"##
+			);
+		} else {
+			if let Some(path) = SliderPath::from_hit_object(hit_object) {
+				let points: Vec<String> = (path.points().iter())
+					.map(|p| format!("{:.1},{:.1}", p.x, p.y))
+					.collect();
+				let points = points.join(" ");
+
+				let _ = writeln!(
+					svg,
+					r##"<polyline points="{points}" fill="none" stroke="#fff" stroke-width="{:.1}" stroke-linecap="round" stroke-linejoin="round"/>"##,
+					radius * 2.0
+				);
+				let _ = writeln!(
+					svg,
+					r#"<polyline points="{points}" fill="none" stroke="{color}" stroke-opacity="0.6" stroke-width="{:.1}" stroke-linecap="round" stroke-linejoin="round"/>"#,
+					radius * 1.8
+				);
+			}
+
+			let _ = writeln!(
+				svg,
+				r##"<circle cx="{x}" cy="{y}" r="{:.1}" fill="{color}" stroke="#fff" stroke-width="{:.1}"/>"##,
+				radius * 0.95,
+				radius * 0.1
+			);
+			let _ = writeln!(
+				svg,
+				r##"<text x="{x}" y="{y}" fill="#fff" font-family="sans-serif" font-size="{:.1}" text-anchor="middle" dominant-baseline="central">{}</text>"##,
+				radius, numbers[i]
+			);
+		}
+		let _ = writeln!(svg, "</g>");
+	}
+
+	svg.push_str("</svg>\n");
+	svg
+}
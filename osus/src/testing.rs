@@ -0,0 +1,72 @@
+//! Shared harness to catch asymmetries between the parser and the serializer.
+//!
+//! With the `arbitrary` feature, the beatmap model also implements [`arbitrary::Arbitrary`],
+//! generating values that are always valid to write as a `.osu` file.
+
+#[cfg(feature = "arbitrary")]
+mod generators;
+
+use std::io;
+
+use crate::file::beatmap::errors::BeatmapFileParseError;
+use crate::file::beatmap::BeatmapFile;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RoundtripMismatch {
+	#[error("Could not write beatmap")]
+	Io(
+		#[from]
+		#[source]
+		io::Error,
+	),
+
+	#[error("Could not parse the written beatmap")]
+	Parse(
+		#[from]
+		#[source]
+		BeatmapFileParseError,
+	),
+
+	#[error("Line {line_number} changed after a round trip: expected {expected:?}, found {found:?}")]
+	Line {
+		line_number: usize,
+		expected: String,
+		found: String,
+	},
+}
+
+fn write_to_string(beatmap: &BeatmapFile) -> io::Result<String> {
+	let mut buffer = Vec::new();
+	beatmap.deserialize(&mut buffer)?;
+	String::from_utf8(buffer).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Checks that writing a beatmap, parsing it back and writing it again gives the exact same file.
+///
+/// # Errors
+///
+/// This function will return an error describing the first line that differs,
+/// or the error that happened while writing or parsing the beatmap.
+pub fn roundtrip(beatmap: &BeatmapFile) -> Result<(), RoundtripMismatch> {
+	let written = write_to_string(beatmap)?;
+	let reparsed = BeatmapFile::parse_str(&written)?;
+	let rewritten = write_to_string(&reparsed)?;
+
+	let mut expected_lines = written.lines();
+	let mut found_lines = rewritten.lines();
+	let mut line_number = 0;
+	loop {
+		line_number += 1;
+		match (expected_lines.next(), found_lines.next()) {
+			(None, None) => return Ok(()),
+			(expected, found) if expected != found => {
+				return Err(RoundtripMismatch::Line {
+					line_number,
+					expected: expected.unwrap_or_default().to_owned(),
+					found: found.unwrap_or_default().to_owned(),
+				});
+			}
+			_ => (),
+		}
+	}
+}
@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::file::beatmap::{
+	BeatmapFile, Color, ColorsSection, DifficultySection, EditorSection, Event, EventParams, GeneralSection, HitObject,
+	HitObjectParams, HitObjectType, HitSample, HitSampleSet, HitSound, MetadataSection, OverlayPosition, SampleBank,
+	SliderCurveType, SliderPoint, Timestamp, TimingPoint,
+};
+
+/// Words used to generate text that survives trimming and space-separated lists.
+const WORDS: &[&str] = &[
+	"osu", "yume", "ranked", "kiai", "tech", "stream", "jump", "remix", "tv-size", "cut",
+];
+
+fn word(u: &mut Unstructured) -> Result<String> {
+	Ok((*u.choose(WORDS)?).to_owned())
+}
+
+/// Filename of an event asset, sometimes with spaces or commas that need to be quoted.
+fn asset_filename(u: &mut Unstructured, extension: &str) -> Result<String> {
+	let separator = *u.choose(&["", " ", ", "])?;
+	let name = (0..u.int_in_range(1..=3)?)
+		.map(|_| word(u))
+		.collect::<Result<Vec<_>>>()?
+		.join(separator);
+	Ok(format!("{name}.{extension}"))
+}
+
+fn words(u: &mut Unstructured) -> Result<Vec<String>> {
+	let count = u.int_in_range(0..=4)?;
+	(0..count).map(|_| word(u)).collect()
+}
+
+/// Integer milliseconds, as stable writes them.
+fn timestamp(u: &mut Unstructured) -> Result<Timestamp> {
+	Ok(f64::from(u.int_in_range(0..=600_000)?))
+}
+
+fn volume(u: &mut Unstructured) -> Result<u8> {
+	u.int_in_range(0..=100)
+}
+
+impl<'a> Arbitrary<'a> for SampleBank {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		u.choose(&[Self::Auto, Self::Normal, Self::Soft, Self::Drum]).copied()
+	}
+}
+
+impl<'a> Arbitrary<'a> for HitSampleSet {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		Ok(Self {
+			normal_set: u.arbitrary()?,
+			addition_set: u.arbitrary()?,
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for HitSound {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		let mut hit_sound = Self::NONE;
+		for flag in [Self::NORMAL, Self::WHISTLE, Self::FINISH, Self::CLAP] {
+			if u.arbitrary()? {
+				hit_sound |= flag;
+			}
+		}
+		Ok(hit_sound)
+	}
+}
+
+impl<'a> Arbitrary<'a> for HitSample {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		Ok(Self {
+			normal_set: u.arbitrary()?,
+			addition_set: u.arbitrary()?,
+			index: u.int_in_range(0..=10)?,
+			volume: u32::from(volume(u)?),
+			filename: if u.arbitrary()? {
+				Some(format!("{}.wav", word(u)?))
+			} else {
+				None
+			},
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for SliderPoint {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		Ok(Self {
+			curve_type: *u.choose(&[
+				SliderCurveType::Inherit,
+				SliderCurveType::Inherit,
+				SliderCurveType::Inherit,
+				SliderCurveType::Bezier,
+				SliderCurveType::Linear,
+			])?,
+			x: f32::from(u.int_in_range(0_u16..=512)?),
+			y: f32::from(u.int_in_range(0_u16..=384)?),
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for HitObject {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		let time = timestamp(u)?;
+
+		let object_params = match u.int_in_range(0..=3)? {
+			0 => HitObjectParams::HitCircle,
+			1 => {
+				let slides = u.int_in_range(1..=4)?;
+				let edge_count = slides as usize + 1;
+
+				let point_count = u.int_in_range(1..=5)?;
+				let curve_points = (0..point_count).map(|_| u.arbitrary()).collect::<Result<_>>()?;
+
+				HitObjectParams::Slider {
+					first_curve_type: *u.choose(&[
+						SliderCurveType::Bezier,
+						SliderCurveType::Catmull,
+						SliderCurveType::Linear,
+						SliderCurveType::PerfectCurve,
+					])?,
+					curve_points,
+					slides,
+					length: f64::from(u.int_in_range(10_u16..=1000)?),
+					edge_hitsounds: (0..edge_count).map(|_| u.arbitrary()).collect::<Result<_>>()?,
+					edge_samplesets: (0..edge_count).map(|_| u.arbitrary()).collect::<Result<_>>()?,
+				}
+			}
+			2 => HitObjectParams::Spinner {
+				end_time: time + f64::from(u.int_in_range(100..=10_000)?),
+			},
+			_ => HitObjectParams::Hold {
+				end_time: time + f64::from(u.int_in_range(10..=5_000)?),
+				// tails aren't written to `.osu` files, so a round trip can't keep them
+				tail: None,
+			},
+		};
+
+		let object_type = match object_params {
+			HitObjectParams::HitCircle => HitObjectType::HitCircle,
+			HitObjectParams::Slider { .. } => HitObjectType::Slider,
+			HitObjectParams::Spinner { .. } => HitObjectType::Spinner,
+			HitObjectParams::Hold { .. } => HitObjectType::Hold,
+		};
+
+		Ok(Self {
+			x: f32::from(u.int_in_range(0_u16..=512)?),
+			y: f32::from(u.int_in_range(0_u16..=384)?),
+			time,
+			object_type,
+			combo_color_skip: if u.arbitrary()? {
+				Some(u.int_in_range(0..=7)?)
+			} else {
+				None
+			},
+			hit_sound: u.arbitrary()?,
+			object_params,
+			hit_sample: u.arbitrary()?,
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for TimingPoint {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		let uninherited = u.arbitrary()?;
+
+		Ok(Self {
+			time: timestamp(u)?,
+			beat_length: if uninherited {
+				f64::from(u.int_in_range(100..=2000)?)
+			} else {
+				-f64::from(u.int_in_range(10..=1000)?)
+			},
+			raw_beat_length: None,
+			meter: u.int_in_range(1..=7)?,
+			sample_set: u.arbitrary()?,
+			sample_index: u.int_in_range(0..=10)?,
+			volume: volume(u)?,
+			uninherited,
+			effects: *u.choose(&[0, 1, 8, 9])?,
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for Event {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		let start_time = timestamp(u)?;
+
+		let (event_type, params) = match u.int_in_range(0..=3)? {
+			0 => (
+				"0",
+				EventParams::Background {
+					filename: asset_filename(u, "jpg")?,
+					x_offset: u.int_in_range(-100..=100)?,
+					y_offset: u.int_in_range(-100..=100)?,
+				},
+			),
+			1 => (
+				"Video",
+				EventParams::Video {
+					filename: asset_filename(u, "mp4")?,
+					x_offset: u.int_in_range(-100..=100)?,
+					y_offset: u.int_in_range(-100..=100)?,
+				},
+			),
+			2 => (
+				"2",
+				EventParams::Break {
+					end_time: start_time + f64::from(u.int_in_range(1000..=20_000)?),
+				},
+			),
+			_ => (
+				"5",
+				EventParams::Sample {
+					layer: u.int_in_range(0..=3)?,
+					filename: asset_filename(u, "wav")?,
+					volume: volume(u)?,
+				},
+			),
+		};
+
+		Ok(Self {
+			event_type: event_type.to_owned(),
+			start_time,
+			params,
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for Color {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		Ok(Self {
+			r: u.arbitrary()?,
+			g: u.arbitrary()?,
+			b: u.arbitrary()?,
+			a: u.arbitrary()?,
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for GeneralSection {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		Ok(Self {
+			audio_filename: format!("{}.mp3", word(u)?),
+			audio_lead_in: u.int_in_range(0..=3000)?,
+			preview_time: timestamp(u)?,
+			countdown: u.int_in_range(0..=3)?,
+			sample_set: (*u.choose(&["Normal", "Soft", "Drum"])?).to_owned(),
+			stack_leniency: f64::from(u.int_in_range(0..=10)?) / 10.0,
+			mode: u.int_in_range(0..=3)?,
+			letterbox_in_breaks: u.arbitrary()?,
+			use_skin_sprites: u.arbitrary()?,
+			overlay_position: *u.choose(&[
+				OverlayPosition::NoChange,
+				OverlayPosition::Below,
+				OverlayPosition::Above,
+			])?,
+			epilepsy_warning: u.arbitrary()?,
+			countdown_offset: u.int_in_range(0..=3)?,
+			special_style: u.arbitrary()?,
+			widescreen_storyboard: u.arbitrary()?,
+			samples_match_playback_rate: u.arbitrary()?,
+			..Self::default()
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for EditorSection {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		let bookmark_count = u.int_in_range(0..=4)?;
+
+		Ok(Self {
+			bookmarks: (0..bookmark_count)
+				.map(|_| Ok(f32::from(u.int_in_range(0_u16..=60_000)?)))
+				.collect::<Result<_>>()?,
+			distance_spacing: f64::from(u.int_in_range(1..=30)?) / 10.0,
+			beat_divisor: f64::from(*u.choose(&[1, 2, 3, 4, 6, 8, 12, 16])?),
+			grid_size: *u.choose(&[4, 8, 16, 32])?,
+			timeline_zoom: if u.arbitrary()? {
+				Some(f64::from(u.int_in_range(1..=80)?) / 10.0)
+			} else {
+				None
+			},
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for MetadataSection {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		Ok(Self {
+			title: word(u)?,
+			title_unicode: word(u)?,
+			artist: word(u)?,
+			artist_unicode: word(u)?,
+			creator: word(u)?,
+			version: word(u)?,
+			source: word(u)?,
+			tags: words(u)?,
+			beatmap_id: u.arbitrary()?,
+			beatmap_set_id: u.arbitrary()?,
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for DifficultySection {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		let mut setting = || Ok(f32::from(u.int_in_range(0_u8..=100)?) / 10.0);
+
+		Ok(Self {
+			hp_drain_rate: setting()?,
+			circle_size: setting()?,
+			overall_difficulty: setting()?,
+			approach_rate: setting()?,
+			slider_multiplier: f32::from(u.int_in_range(4_u8..=36)?) / 10.0,
+			slider_tick_rate: f32::from(u.int_in_range(1_u8..=4)?),
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for ColorsSection {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		let combo_color_count = u.int_in_range(0..=8)?;
+
+		Ok(Self {
+			combo_colors: (0..combo_color_count).map(|_| u.arbitrary()).collect::<Result<_>>()?,
+			slider_track_override: u.arbitrary()?,
+			slider_border: u.arbitrary()?,
+		})
+	}
+}
+
+impl<'a> Arbitrary<'a> for BeatmapFile {
+	fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+		let mut events: Vec<Event> = u.arbitrary()?;
+		events.sort_by(|a, b| a.start_time.total_cmp(&b.start_time));
+
+		let mut timing_points: Vec<TimingPoint> = u.arbitrary()?;
+		timing_points.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+		let mut hit_objects: Vec<HitObject> = u.arbitrary()?;
+		hit_objects.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+		Ok(Self {
+			osu_file_format: *u.choose(&[14, 128])?,
+			general: Some(u.arbitrary()?),
+			editor: Some(u.arbitrary()?),
+			metadata: Some(u.arbitrary()?),
+			difficulty: Some(u.arbitrary()?),
+			events,
+			timing_points,
+			colors: Some(u.arbitrary()?),
+			hit_objects,
+			custom_sections: HashMap::new(),
+			trivia: Vec::new(),
+		})
+	}
+}
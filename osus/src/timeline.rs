@@ -0,0 +1,180 @@
+//! Effective timing settings at any point of a beatmap.
+//!
+//! Timing points only store what changes at their time: inherited timing points keep the beat length
+//! of the previous uninherited one, and uninherited ones reset the slider velocity.
+//! [`TimingTimeline`] resolves all of this once, so that each query is a binary search.
+//! [`BeatmapFile::context_at`] finds everything in effect at a single time without building anything.
+
+use crate::file::beatmap::timing_points::{canonical_order, uninherited_at};
+use crate::file::beatmap::{BeatmapFile, SampleBank, Timestamp, TimingPoint};
+use crate::timestamp::combo_number_at;
+
+/// Sample settings in effect at a given time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SampleSettings {
+	pub sample_set: SampleBank,
+	pub sample_index: u32,
+	pub volume: u8,
+}
+
+/// Settings in effect from a timing point to the next one.
+#[derive(Clone, Copy, Debug)]
+struct TimelineSection {
+	time: Timestamp,
+	beat_length: f64,
+	sv_multiplier: f64,
+	samples: SampleSettings,
+	kiai: bool,
+}
+
+/// Timeline of the timing settings of a beatmap.
+#[derive(Clone, Debug, Default)]
+pub struct TimingTimeline {
+	sections: Vec<TimelineSection>,
+}
+
+impl TimingTimeline {
+	/// Builds the timeline of a beatmap's timing points. They don't need to be sorted.
+	#[must_use]
+	pub fn new(timing_points: &[TimingPoint]) -> Self {
+		let mut timing_points: Vec<&TimingPoint> = timing_points.iter().collect();
+		// uninherited timing points go before inherited ones at the same time, like in osu!
+		timing_points.sort_by(|a, b| canonical_order(a, b));
+
+		// before the first uninherited timing point, osu! uses its beat length anyway
+		let mut beat_length = (timing_points.iter())
+			.find(|tp| tp.uninherited)
+			.map_or(f64::NAN, |tp| tp.beat_length);
+
+		let mut sections: Vec<TimelineSection> = Vec::with_capacity(timing_points.len());
+		for tp in timing_points {
+			if tp.uninherited {
+				beat_length = tp.beat_length;
+			}
+
+			let section = TimelineSection {
+				time: tp.time,
+				beat_length,
+				sv_multiplier: tp.sv_multiplier(),
+				samples: SampleSettings {
+					sample_set: tp.sample_set,
+					sample_index: tp.sample_index,
+					volume: tp.volume,
+				},
+				kiai: tp.is_kiai(),
+			};
+
+			// only the last timing point at a given time matters
+			match sections.last_mut() {
+				Some(last) if last.time.total_cmp(&section.time).is_eq() => *last = section,
+				_ => sections.push(section),
+			}
+		}
+
+		Self { sections }
+	}
+
+	/// Section in effect at a given time. Times before the first timing point use the first one.
+	fn section_at(&self, time: Timestamp) -> Option<&TimelineSection> {
+		let index = self.sections.partition_point(|section| section.time <= time);
+		self.sections.get(index.saturating_sub(1))
+	}
+
+	/// Whether there are no timing points at all.
+	#[must_use]
+	pub const fn is_empty(&self) -> bool {
+		self.sections.is_empty()
+	}
+
+	/// Duration of a beat in milliseconds at a given time, or `None` if there is no uninherited timing point.
+	#[must_use]
+	pub fn beat_length_at(&self, time: Timestamp) -> Option<f64> {
+		self.section_at(time)
+			.map(|section| section.beat_length)
+			.filter(|beat_length| !beat_length.is_nan())
+	}
+
+	/// Slider velocity multiplier at a given time.
+	#[must_use]
+	pub fn sv_at(&self, time: Timestamp) -> f64 {
+		self.section_at(time).map_or(1.0, |section| section.sv_multiplier)
+	}
+
+	/// Sample settings at a given time.
+	#[must_use]
+	pub fn sample_settings_at(&self, time: Timestamp) -> SampleSettings {
+		self.section_at(time).map(|section| section.samples).unwrap_or_default()
+	}
+
+	/// Whether kiai time is enabled at a given time.
+	#[must_use]
+	pub fn is_kiai_at(&self, time: Timestamp) -> bool {
+		self.section_at(time).is_some_and(|section| section.kiai)
+	}
+
+	/// Duration of a single slide of a slider in milliseconds, given its length and the beatmap's slider multiplier.
+	#[must_use]
+	pub fn slide_duration_at(&self, time: Timestamp, length: f64, slider_multiplier: f64) -> Option<f64> {
+		let velocity = slider_multiplier * 100.0 * self.sv_at(time);
+		(velocity > 0.0)
+			.then(|| self.beat_length_at(time))
+			.flatten()
+			.map(|beat_length| length / velocity * beat_length)
+	}
+}
+
+/// Everything in effect at a given time of a beatmap, see [`BeatmapFile::context_at`].
+#[derive(Clone, Copy, Debug)]
+pub struct MapContext<'a> {
+	pub time: Timestamp,
+	/// Uninherited timing point in effect, or the first one for times before it, like osu! does.
+	pub uninherited: Option<&'a TimingPoint>,
+	/// Inherited timing point in effect, if there is one after the uninherited one.
+	pub inherited: Option<&'a TimingPoint>,
+	pub sv_multiplier: f64,
+	pub samples: SampleSettings,
+	/// Index of the last hit object starting at or before the time, in the beatmap's hit objects.
+	pub previous: Option<usize>,
+	/// Index of the first hit object starting after the time.
+	pub next: Option<usize>,
+	/// Combo number of the previous hit object, 0 if there is none.
+	pub combo_number: u32,
+}
+
+impl BeatmapFile {
+	/// Everything in effect at a given time: timing points, slider velocity, sample settings,
+	/// combo number and the hit objects around it.
+	///
+	/// Timing points and hit objects are found with binary searches, so they need to be sorted
+	/// (see [`sort_and_fix`](Self::sort_and_fix)). Use a [`TimingTimeline`] for many queries on the timing alone.
+	#[must_use]
+	pub fn context_at(&self, time: Timestamp) -> MapContext<'_> {
+		let timing_points = &self.timing_points;
+		let active = &timing_points[..timing_points.partition_point(|tp| tp.time <= time)];
+
+		let uninherited = uninherited_at(timing_points, time);
+		let inherited = active.last().filter(|tp| !tp.uninherited);
+
+		let samples = (active.last().or_else(|| timing_points.first()))
+			.map(|tp| SampleSettings {
+				sample_set: tp.sample_set,
+				sample_index: tp.sample_index,
+				volume: tp.volume,
+			})
+			.unwrap_or_default();
+
+		let next = self.hit_objects.partition_point(|ho| ho.time <= time);
+		let previous = next.checked_sub(1);
+
+		MapContext {
+			time,
+			uninherited,
+			inherited,
+			sv_multiplier: inherited.map_or(1.0, TimingPoint::sv_multiplier),
+			samples,
+			previous,
+			next: (next < self.hit_objects.len()).then_some(next),
+			combo_number: previous.map_or(0, |index| combo_number_at(&self.hit_objects, index)),
+		}
+	}
+}
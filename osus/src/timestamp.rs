@@ -0,0 +1,267 @@
+//! Timestamps as written by the osu! editor, like `01:23:456 (1,2,3) -`.
+//!
+//! Copying objects in the editor gives such a timestamp, with the time of the first object
+//! and the combo numbers of every selected object (or `time|column` pairs in osu!mania).
+//! This is also the format used in modding discussions on the osu! website.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::file::beatmap::{BeatmapFile, HitObject, Timestamp};
+use crate::{ErrorCategory, Timestamped};
+
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("Invalid timestamp: expected milliseconds or `mm:ss.mmm`, got {0:?}")]
+pub struct InvalidTimestampError(String);
+
+impl InvalidTimestampError {
+	#[must_use]
+	pub const fn category(&self) -> ErrorCategory {
+		ErrorCategory::Parse
+	}
+}
+
+/// Parses a timestamp, either as milliseconds (`83456`) or as minutes and seconds (`01:23.456`).
+///
+/// The osu! editor format (`01:23:456`, optionally followed by the selected objects like `(1,2) -`)
+/// is also accepted, so that timestamps can be copied straight from the editor.
+///
+/// # Errors
+///
+/// This function will return an error if the text is not a valid timestamp.
+pub fn parse_timestamp(s: &str) -> Result<Timestamp, InvalidTimestampError> {
+	let err = || InvalidTimestampError(s.to_string());
+	let timestamp = s.split_whitespace().next().ok_or_else(err)?;
+
+	let parts: Vec<&str> = timestamp.split(':').collect();
+	match parts.as_slice() {
+		[millis] => millis.parse().map_err(|_| err()),
+		[minutes, seconds] => {
+			let minutes: u32 = minutes.parse().map_err(|_| err())?;
+			let seconds: f64 = seconds.parse().map_err(|_| err())?;
+			Ok(f64::from(minutes).mul_add(60_000.0, seconds * 1000.0))
+		}
+		[minutes, seconds, millis] => {
+			let minutes: u32 = minutes.parse().map_err(|_| err())?;
+			let seconds: u32 = seconds.parse().map_err(|_| err())?;
+			let millis: f64 = millis.parse().map_err(|_| err())?;
+			Ok(f64::from(minutes * 60 + seconds).mul_add(1000.0, millis))
+		}
+		_ => Err(err()),
+	}
+}
+
+/// Formats a timestamp like the osu! editor does (`01:23:456`).
+#[must_use]
+pub fn format_timestamp(timestamp: Timestamp) -> String {
+	let sign = if timestamp < 0.0 { "-" } else { "" };
+
+	#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+	let millis = timestamp.abs().round() as u64;
+
+	format!(
+		"{sign}{:02}:{:02}:{:03}",
+		millis / 60_000,
+		millis / 1000 % 60,
+		millis % 1000
+	)
+}
+
+fn is_mania(beatmap: &BeatmapFile) -> bool {
+	beatmap.general.as_ref().is_some_and(|g| g.mode == 3)
+}
+
+/// Whether the hit object at an index starts a new combo.
+fn starts_combo(hit_objects: &[HitObject], index: usize) -> bool {
+	let hit_object = &hit_objects[index];
+	let prev_is_spinner = index > 0 && hit_objects[index - 1].is_spinner();
+
+	// spinners always start a new combo, and so does the object after them
+	hit_object.combo_color_skip.is_some() || hit_object.is_spinner() || prev_is_spinner
+}
+
+/// Combo number displayed on each hit object.
+#[must_use]
+pub fn combo_numbers(hit_objects: &[HitObject]) -> Vec<u32> {
+	let mut numbers = Vec::with_capacity(hit_objects.len());
+
+	let mut number = 0;
+	for index in 0..hit_objects.len() {
+		if starts_combo(hit_objects, index) {
+			number = 1;
+		} else {
+			number += 1;
+		}
+
+		numbers.push(number.max(1));
+	}
+
+	numbers
+}
+
+/// Combo number displayed on the hit object at an index, without going through the objects before its combo.
+///
+/// # Panics
+///
+/// Panics if the index is out of bounds.
+#[must_use]
+pub fn combo_number_at(hit_objects: &[HitObject], index: usize) -> u32 {
+	let combo_start = (0..=index).rev().find(|&i| starts_combo(hit_objects, i)).unwrap_or(0);
+
+	u32::try_from(index - combo_start + 1).unwrap_or(u32::MAX)
+}
+
+/// Reference to a hit object in an editor timestamp.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ObjectReference {
+	/// Combo number of the object.
+	Combo(u32),
+	/// osu!mania note, written as `time|column`.
+	ManiaNote { time: Timestamp, column: u32 },
+}
+
+impl fmt::Display for ObjectReference {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Combo(number) => write!(f, "{number}"),
+			Self::ManiaNote { time, column } => write!(f, "{}|{column}", time.round()),
+		}
+	}
+}
+
+impl FromStr for ObjectReference {
+	type Err = InvalidTimestampError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let err = || InvalidTimestampError(s.to_string());
+		let s = s.trim();
+
+		match s.split_once('|') {
+			Some((time, column)) => Ok(Self::ManiaNote {
+				time: time.parse().map_err(|_| err())?,
+				column: column.parse().map_err(|_| err())?,
+			}),
+			None => Ok(Self::Combo(s.parse().map_err(|_| err())?)),
+		}
+	}
+}
+
+/// A timestamp copied from the osu! editor, with the objects that were selected.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EditorTimestamp {
+	pub time: Timestamp,
+	pub objects: Vec<ObjectReference>,
+}
+
+impl fmt::Display for EditorTimestamp {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", format_timestamp(self.time))?;
+
+		if !self.objects.is_empty() {
+			write!(f, " (")?;
+			for (i, object) in self.objects.iter().enumerate() {
+				if i > 0 {
+					write!(f, ",")?;
+				}
+				write!(f, "{object}")?;
+			}
+			write!(f, ")")?;
+		}
+
+		write!(f, " -")
+	}
+}
+
+impl FromStr for EditorTimestamp {
+	type Err = InvalidTimestampError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.trim();
+		let time = parse_timestamp(s)?;
+
+		let rest = s
+			.split_once(char::is_whitespace)
+			.map_or("", |(_, rest)| rest.trim_start());
+		let objects = match rest.strip_prefix('(').and_then(|rest| rest.split_once(')')) {
+			Some((objects, _)) => (objects.split(',').filter(|o| !o.trim().is_empty()))
+				.map(str::parse)
+				.collect::<Result<_, _>>()?,
+			None => Vec::new(),
+		};
+
+		Ok(Self { time, objects })
+	}
+}
+
+impl EditorTimestamp {
+	/// Creates the timestamp that the osu! editor would give when copying the given hit objects.
+	///
+	/// `indices` are indices in the beatmap's hit objects, which need to be sorted.
+	#[must_use]
+	pub fn from_hit_objects(beatmap: &BeatmapFile, indices: &[usize]) -> Self {
+		let hit_objects = &beatmap.hit_objects;
+		let time = indices
+			.iter()
+			.filter_map(|&i| hit_objects.get(i))
+			.map(|ho| ho.time)
+			.next();
+
+		let objects = if is_mania(beatmap) {
+			let key_count = beatmap.key_count();
+			(indices.iter().filter_map(|&i| hit_objects.get(i)))
+				.map(|ho| ObjectReference::ManiaNote {
+					time: ho.time,
+					column: ho.column(key_count),
+				})
+				.collect()
+		} else {
+			// only the combos of the selected objects are counted, lints reference objects one by one
+			(indices.iter().filter(|&&i| i < hit_objects.len()))
+				.map(|&i| ObjectReference::Combo(combo_number_at(hit_objects, i)))
+				.collect()
+		};
+
+		Self {
+			time: time.unwrap_or_default(),
+			objects,
+		}
+	}
+
+	/// Finds the hit objects this timestamp refers to, as indices in the beatmap's hit objects.
+	///
+	/// References that don't match any hit object are ignored.
+	/// Hit objects need to be sorted.
+	#[must_use]
+	pub fn resolve(&self, beatmap: &BeatmapFile) -> Vec<usize> {
+		let hit_objects = &beatmap.hit_objects;
+		let key_count = beatmap.key_count();
+		let combo_numbers = combo_numbers(hit_objects);
+
+		let mut indices = Vec::new();
+		// combo numbers are matched in order, starting from the object at the timestamp
+		let mut next_index = hit_objects.partition_point(|ho| ho.time < self.time - 2.0);
+
+		for object in &self.objects {
+			match *object {
+				ObjectReference::Combo(number) => {
+					let found = (next_index..hit_objects.len()).find(|&i| combo_numbers[i] == number);
+					if let Some(i) = found {
+						indices.push(i);
+						next_index = i + 1;
+					}
+				}
+				ObjectReference::ManiaNote { time, column } => {
+					let found =
+						(hit_objects.iter()).position(|ho| ho.basically_at(time) && ho.column(key_count) == column);
+					indices.extend(found);
+				}
+			}
+		}
+
+		if self.objects.is_empty() {
+			indices.extend((hit_objects.iter()).position(|ho| ho.basically_at(self.time)));
+		}
+
+		indices
+	}
+}